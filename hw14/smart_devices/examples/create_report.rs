@@ -1,14 +1,18 @@
 use smart_devices::{
     device::{
-        info::{BorrowingDeviceInfoProvider, OwningDeviceInfoProvider},
-        SmartSocket, SmartThermometer,
+        info::{BorrowingDeviceInfoProvider, DeviceRegistry, OwningDeviceInfoProvider},
+        Device, SmartSocket, SmartThermometer,
+    },
+    report::{
+        render::{MarkdownRenderer, ReportRenderer},
+        ReportOptions,
     },
     SmartHouse,
 };
 use std::collections::HashMap;
 
 // Пример использования
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Инициализация устройств
     let socket1 = SmartSocket::new(
         "room1_socket_1",
@@ -30,13 +34,19 @@ fn main() {
     let house_1 = SmartHouse::new(
         "my smart house",
         HashMap::from([
-            ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-            ("room2", vec!["room2_socket_2"]),
+            (
+                "room1",
+                vec![
+                    Box::new(socket1.clone()) as Box<dyn Device>,
+                    Box::new(thermo.clone()),
+                ],
+            ),
+            ("room2", vec![Box::new(socket2.clone()) as Box<dyn Device>]),
         ]),
     );
 
     // Строим отчёт с использованием `OwningDeviceInfoProvider`.
-    let info_provider_1 = OwningDeviceInfoProvider::new(socket1);
+    let info_provider_1 = OwningDeviceInfoProvider::new(socket1.clone());
     let report_1 = house_1.create_report(&info_provider_1);
 
     // Строим отчёт с использованием `BorrowingDeviceInfoProvider`.
@@ -68,16 +78,41 @@ fn main() {
         }
     );
 
+    // Тот же отчёт #1 в мягком режиме: неописанные девайсы помечаются недоступными.
+    let outcome_1 = house_1.create_report_with(&info_provider_1, &ReportOptions::lenient())?;
+
+    println!(
+        r#"
+=== Report #1 (lenient): ===
+
+{}
+
+Missing devices: {:?}
+"#,
+        outcome_1.report, outcome_1.missing
+    );
+
     let mut house_2 = SmartHouse::new(
         "my smart house",
-        HashMap::from([("room1", vec!["room1_thermo_1", "room1_socket_3"])]),
+        HashMap::from([(
+            "room1",
+            vec![
+                Box::new(thermo.clone()) as Box<dyn Device>,
+                Box::new(SmartSocket::new("room1_socket_3", "", false, 0.0)),
+            ],
+        )]),
     );
 
-    house_2.delete_device("room1", "room1_socket_3");
-    house_2.add_room("room2");
-    house_2.add_device("room2", "room2_socket_1");
-    house_2.add_device("room2", "room2_socket_2");
-    house_2.delete_device("room2", "room2_socket_1");
+    house_2.delete_device("room1", "room1_socket_3")?;
+    house_2.add_room("room2")?;
+    house_2.add_device("room2", Box::new(socket1))?;
+    house_2.add_device("room2", Box::new(socket2.clone()))?;
+    house_2.delete_device("room2", "room1_socket_1")?;
+
+    // Ошибки изменения дома говорят, что именно пошло не так.
+    if let Err(err) = house_2.add_room("room2") {
+        println!("Can't add room: {}", err);
+    }
 
     // Строим отчёт с использованием `BorrowingDeviceInfoProvider`.
     let info_provider_3 = BorrowingDeviceInfoProvider::new(&socket2, &thermo);
@@ -95,10 +130,74 @@ fn main() {
         }
     );
 
+    // Управляем розеткой через дом и строим отчёт по девайсам самого дома.
+    if let Some(socket) = house_2.socket_mut("room2", "room2_socket_2") {
+        socket.turn_on();
+    }
+    let report_4 = house_2.create_report(&house_2);
+
+    println!(
+        r#"
+=== Report #4: ===
+
+{}
+"#,
+        match report_4 {
+            Ok(r) => r,
+            Err(err) => format!("{:?}", err),
+        }
+    );
+
+    // Строим отчёт с использованием `DeviceRegistry`, в котором может быть сколько угодно устройств.
+    let mut info_provider_5 = DeviceRegistry::new();
+    info_provider_5.insert_borrowed("room1", &thermo);
+    info_provider_5.insert("room2", Box::new(socket2));
+    let report_5 = house_1.create_report(&info_provider_5);
+
+    println!(
+        r#"
+=== Report #5: ===
+
+{}
+"#,
+        match report_5 {
+            Ok(r) => r,
+            Err(err) => format!("{:?}", err),
+        }
+    );
+
+    // Структурный отчёт по девайсам дома, отрисованный в Markdown.
+    match house_2.report(&house_2) {
+        Ok(report) => println!("{}", MarkdownRenderer.render(&report)),
+        Err(err) => println!("{:?}", err),
+    }
+
+    // Многоэтажный дом: расположения адресуются путями.
+    let mut house_3 = SmartHouse::new_empty("Cottage");
+    house_3.add_room("floor1/hall")?;
+    house_3.add_room("floor2/kitchen")?;
+    house_3.add_device(
+        "floor2/kitchen",
+        Box::new(SmartSocket::new("kettle", "Kitchen socket", true, 220.0)),
+    )?;
+    house_3.add_device("floor1/hall", Box::new(thermo.clone()))?;
+    println!(
+        "Rooms of cottage: {:?}",
+        house_3.rooms().collect::<Vec<_>>()
+    );
+    println!(
+        "{}",
+        house_3
+            .create_report_with(&house_3, &ReportOptions::strict().within("floor2"))?
+            .report
+    );
+
     println!("DEBUG");
     println!("Rooms: {:?}", house_2.rooms().collect::<Vec<String>>());
     println!(
         "Devices of room1: {:?}",
         house_2.devices("room1").collect::<Vec<String>>()
     );
+
+    Ok(())
 }
@@ -0,0 +1,33 @@
+use smart_devices::{dsl, SmartHouse};
+
+const HOUSE: &str = r#"
+house "my smart house"
+
+room room1 {
+    socket room1_socket_1 on 225.5 W description "Smart Plug WiFi Socket EU 16A"
+    thermometer room1_thermo_1 19.2 °C humidity 45 % tags climate
+}
+room room2 {
+    socket room2_socket_2 off 0 W
+    room closet { light lamp on 40 %; sensor door contact }
+}
+"#;
+
+// Дом из текстового описания вместо ручной сборки девайсов
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let house: SmartHouse = HOUSE.parse()?;
+
+    for room in house.locations() {
+        let devices: Vec<String> = house.devices(&room).collect();
+        println!("{room}: {}", devices.join(", "));
+    }
+
+    println!("\n{}", dsl::format_house(&house));
+
+    // Ошибки указывают строку и колонку
+    if let Err(error) = dsl::parse_house("room kitchen {\n    socket kettle on hot\n}") {
+        println!("error: {error}");
+    }
+
+    Ok(())
+}
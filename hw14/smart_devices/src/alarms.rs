@@ -0,0 +1,824 @@
+//! Пороги тревог для показаний девайсов и тревоги, которые они поднимают.
+//!
+//! Тревога поднимается один раз, когда показание пересекает порог, и снимается,
+//! когда оно возвращается за порог с учетом гистерезиса. Поднятые и снятые тревоги
+//! уходят во все подключенные `AlertSink`.
+
+use crate::{
+    clock::{system_clock, Clock},
+    device::{DeviceState, SmartSocket},
+    journal::Operation,
+    location, SmartHouse,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+/// Ошибка набора тревог.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AlarmError {
+    /// Тревога с таким именем уже есть.
+    #[error("alarm \"{0}\" already exists")]
+    DuplicateAlarm(String),
+
+    /// Тревоги с таким именем нет.
+    #[error("alarm \"{0}\" not found")]
+    AlarmNotFound(String),
+
+    /// Гистерезис тревоги отрицательный.
+    #[error("hysteresis of alarm \"{alarm}\" must not be negative, found {hysteresis}")]
+    InvalidHysteresis { alarm: String, hysteresis: f64 },
+}
+
+type Result<T> = std::result::Result<T, AlarmError>;
+
+/// Насколько тревога серьезна
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// Порог, за которым показание девайса считается тревожным
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Threshold {
+    /// Температура термометра выше, °C
+    TemperatureAbove(f64),
+    /// Температура термометра ниже, °C
+    TemperatureBelow(f64),
+    /// Потребляемая мощность розетки выше, Вт
+    PowerAbove(f64),
+    /// Розетка включена дольше
+    OnLongerThan(#[serde(with = "humantime_serde")] Duration),
+}
+
+impl Threshold {
+    /// Значение, которое проверяет порог: °C, Вт или секунды включения
+    fn read(&self, state: &DeviceState, on_for: Option<Duration>) -> Option<f64> {
+        match (self, state) {
+            (
+                Threshold::TemperatureAbove(_) | Threshold::TemperatureBelow(_),
+                DeviceState::Thermometer {
+                    current_temperature,
+                    ..
+                },
+            ) => Some(current_temperature.as_celsius()),
+            (
+                Threshold::PowerAbove(_),
+                DeviceState::Socket {
+                    is_on,
+                    current_power,
+                    ..
+                },
+            ) => Some(if *is_on { *current_power } else { 0.0 }),
+            (Threshold::OnLongerThan(_), DeviceState::Socket { .. }) => {
+                Some(on_for.unwrap_or_default().as_secs_f64())
+            }
+            _ => None,
+        }
+    }
+
+    /// `Some(true)` - порог пересечен, `Some(false)` - значение ушло обратно
+    /// дальше гистерезиса, `None` - между порогом и гистерезисом
+    fn check(&self, value: f64, hysteresis: f64) -> Option<bool> {
+        let (crossed, cleared) = match *self {
+            Threshold::TemperatureAbove(limit) | Threshold::PowerAbove(limit) => {
+                (value > limit, value <= limit - hysteresis)
+            }
+            Threshold::TemperatureBelow(limit) => (value < limit, value >= limit + hysteresis),
+            Threshold::OnLongerThan(limit) => {
+                let limit = limit.as_secs_f64();
+                (value >= limit, value < limit - hysteresis)
+            }
+        };
+
+        if crossed {
+            Some(true)
+        } else if cleared {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Значение вместе с единицами, например `"31.5 °C"`
+    fn format_value(&self, value: f64) -> String {
+        match self {
+            Threshold::TemperatureAbove(_) | Threshold::TemperatureBelow(_) => {
+                format!("{value} °C")
+            }
+            Threshold::PowerAbove(_) => format!("{value} W"),
+            Threshold::OnLongerThan(_) => {
+                let on_for = Duration::from_secs(value.max(0.0) as u64);
+                format!("on for {}", humantime::format_duration(on_for))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Threshold::TemperatureAbove(limit) => write!(f, "temperature above {limit} °C"),
+            Threshold::TemperatureBelow(limit) => write!(f, "temperature below {limit} °C"),
+            Threshold::PowerAbove(limit) => write!(f, "power above {limit} W"),
+            Threshold::OnLongerThan(limit) => {
+                write!(f, "on longer than {}", humantime::format_duration(*limit))
+            }
+        }
+    }
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// Порог тревоги для девайса (путь или уникальное имя).
+/// Для `OnLongerThan` гистерезис задается в секундах.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alarm {
+    pub name: String,
+    pub device: String,
+    pub threshold: Threshold,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hysteresis: f64,
+}
+
+impl Alarm {
+    pub fn new(name: &str, device: &str, threshold: Threshold) -> Self {
+        Self {
+            name: name.to_owned(),
+            device: device.to_owned(),
+            threshold,
+            severity: Severity::default(),
+            hysteresis: 0.0,
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis.abs();
+        self
+    }
+}
+
+/// Тревогу подняли или сняли
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    Raised,
+    Cleared,
+}
+
+/// Запись о поднятой или снятой тревоге
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub alarm: String,
+    pub kind: AlertKind,
+    pub severity: Severity,
+    #[serde(with = "humantime_serde")]
+    pub at: SystemTime,
+    /// Расположение девайса
+    pub room: String,
+    pub device: String,
+    pub threshold: Threshold,
+    /// Показание при проверке: °C, Вт или секунды включения
+    pub value: f64,
+}
+
+impl Alert {
+    /// Полный путь девайса, например `"floor2/kitchen/thermo"`
+    pub fn path(&self) -> String {
+        location::join_path(&self.room, &self.device)
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = self.threshold.format_value(self.value);
+        match self.kind {
+            AlertKind::Raised => write!(
+                f,
+                "{}: alarm \"{}\" raised: {} {}, {}",
+                self.severity,
+                self.alarm,
+                self.path(),
+                value,
+                self.threshold
+            ),
+            AlertKind::Cleared => write!(
+                f,
+                "{}: alarm \"{}\" cleared: {} {}",
+                self.severity,
+                self.alarm,
+                self.path(),
+                value
+            ),
+        }
+    }
+}
+
+/// Получатель тревог: веб-слой, лог, отправка уведомлений.
+/// Вызывается под блокировкой дома, поэтому долгую работу лучше передать в другой поток.
+pub trait AlertSink: Send + Sync {
+    fn send(&self, alert: &Alert);
+}
+
+impl<F> AlertSink for F
+where
+    F: Fn(&Alert) + Send + Sync,
+{
+    fn send(&self, alert: &Alert) {
+        self(alert)
+    }
+}
+
+/// Пишем тревоги в stderr
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl AlertSink for LogSink {
+    fn send(&self, alert: &Alert) {
+        eprintln!("Alert: {alert}");
+    }
+}
+
+/// Копим тревоги в памяти, например для тестов
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    alerts: Mutex<Vec<Alert>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Накопленные тревоги, после вызова список пуст
+    pub fn take(&self) -> Vec<Alert> {
+        std::mem::take(&mut *self.alerts.lock().unwrap())
+    }
+}
+
+impl AlertSink for MemorySink {
+    fn send(&self, alert: &Alert) {
+        self.alerts.lock().unwrap().push(alert.clone());
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    alarm: Alarm,
+    /// Поднятая и еще не снятая тревога
+    raised: Option<Alert>,
+}
+
+impl Entry {
+    /// Проверяем порог, новая тревога - если он пересечен или тревога снята
+    fn check(&mut self, house: &SmartHouse, now: SystemTime) -> Option<Alert> {
+        let reading = house.resolve_device(&self.alarm.device).and_then(|path| {
+            let device = house.device_by_path(&path)?;
+            // Время включения берем у самой розетки, а не с первой проверки
+            let on_since = device
+                .as_any()
+                .downcast_ref::<SmartSocket>()
+                .and_then(SmartSocket::on_since);
+            Some((path, device.state(), on_since))
+        });
+        let Some((path, state, on_since)) = reading else {
+            return self.clear(now);
+        };
+        // Девайс могли переименовать или перенести с тех пор, как тревогу подняли
+        if let (Some(raised), Some((room, device))) =
+            (&mut self.raised, location::split_last(&path))
+        {
+            raised.room = room.to_owned();
+            raised.device = device.to_owned();
+        }
+
+        let on_for = on_since.map(|since| now.duration_since(since).unwrap_or_default());
+        let Some(value) = self.alarm.threshold.read(&state, on_for) else {
+            return self.clear(now);
+        };
+
+        let kind = match (
+            self.alarm.threshold.check(value, self.alarm.hysteresis),
+            &self.raised,
+        ) {
+            (Some(true), None) => AlertKind::Raised,
+            (Some(false), Some(_)) => AlertKind::Cleared,
+            _ => return None,
+        };
+
+        let (room, device) = location::split_last(&path)?;
+        let alert = Alert {
+            alarm: self.alarm.name.clone(),
+            kind,
+            severity: self.alarm.severity,
+            at: now,
+            room: room.to_owned(),
+            device: device.to_owned(),
+            threshold: self.alarm.threshold,
+            value,
+        };
+        self.raised = (kind == AlertKind::Raised).then(|| alert.clone());
+        Some(alert)
+    }
+
+    /// Девайс пропал или больше не дает показаний: поднятая тревога снимается
+    /// с последним показанием
+    fn clear(&mut self, now: SystemTime) -> Option<Alert> {
+        self.raised.take().map(|raised| Alert {
+            kind: AlertKind::Cleared,
+            at: now,
+            ..raised
+        })
+    }
+}
+
+/// Пороги тревог дома, поднятые тревоги и их получатели
+#[derive(Clone)]
+pub struct AlarmMonitor {
+    entries: Vec<Entry>,
+    sinks: Vec<Arc<dyn AlertSink>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AlarmMonitor {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            sinks: Vec::new(),
+            clock: system_clock(),
+        }
+    }
+
+    /// Подменяем часы, по которым отмечается время тревог
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Тревоги в порядке добавления
+    pub fn alarms(&self) -> impl Iterator<Item = &Alarm> {
+        self.entries.iter().map(|e| &e.alarm)
+    }
+
+    pub fn alarm(&self, name: &str) -> Option<&Alarm> {
+        self.alarms().find(|a| a.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Добавляем тревогу в конец, имена тревог уникальны
+    pub fn add_alarm(&mut self, alarm: Alarm) -> Result<()> {
+        self.insert_alarm(self.entries.len(), alarm)
+    }
+
+    /// Ставим тревогу на место `position` (или в конец)
+    pub(crate) fn insert_alarm(&mut self, position: usize, alarm: Alarm) -> Result<()> {
+        if self.alarm(&alarm.name).is_some() {
+            return Err(AlarmError::DuplicateAlarm(alarm.name));
+        }
+        // `with_hysteresis` берет модуль, а тревога из JSON приходит как есть
+        if alarm.hysteresis < 0.0 || alarm.hysteresis.is_nan() {
+            return Err(AlarmError::InvalidHysteresis {
+                alarm: alarm.name,
+                hysteresis: alarm.hysteresis,
+            });
+        }
+
+        let entry = Entry {
+            alarm,
+            raised: None,
+        };
+        self.entries.insert(position.min(self.entries.len()), entry);
+        Ok(())
+    }
+
+    /// Удаляем тревогу, поднятая тревога при этом молча пропадает
+    pub fn remove_alarm(&mut self, name: &str) -> Result<Alarm> {
+        self.take_alarm(name).map(|(_, alarm)| alarm)
+    }
+
+    /// Удаляем тревогу и возвращаем ее вместе с ее местом
+    fn take_alarm(&mut self, name: &str) -> Result<(usize, Alarm)> {
+        let position = self
+            .entries
+            .iter()
+            .position(|e| e.alarm.name == name)
+            .ok_or_else(|| AlarmError::AlarmNotFound(name.to_owned()))?;
+
+        Ok((position, self.entries.remove(position).alarm))
+    }
+
+    /// Ссылки тревог на девайсы, чтобы перенести их при переименовании
+    pub(crate) fn device_references_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.entries.iter_mut().map(|e| &mut e.alarm.device)
+    }
+
+    /// Поднятые и еще не снятые тревоги
+    pub fn active(&self) -> impl Iterator<Item = &Alert> {
+        self.entries.iter().filter_map(|e| e.raised.as_ref())
+    }
+
+    /// Подключаем получателя тревог
+    pub fn add_sink(&mut self, sink: Arc<dyn AlertSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Проверяем пороги по текущему состоянию дома и рассылаем новые тревоги
+    pub fn check(&mut self, house: &SmartHouse) -> Vec<Alert> {
+        let now = self.clock.now();
+        let alerts: Vec<Alert> = self
+            .entries
+            .iter_mut()
+            .filter_map(|e| e.check(house, now))
+            .collect();
+
+        for alert in &alerts {
+            for sink in &self.sinks {
+                sink.send(alert);
+            }
+        }
+        alerts
+    }
+}
+
+impl Default for AlarmMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SmartHouse {
+    /// Добавляем порог тревоги дома, имена тревог уникальны
+    pub fn add_alarm(&mut self, alarm: Alarm) -> Result<()> {
+        self.alarms.add_alarm(alarm.clone())?;
+        self.journal.record(Operation::AddAlarm { alarm });
+        Ok(())
+    }
+
+    /// Удаляем тревогу, поднятая тревога при этом молча пропадает
+    pub fn remove_alarm(&mut self, name: &str) -> Result<Alarm> {
+        let (position, alarm) = self.alarms.take_alarm(name)?;
+        self.journal.record(Operation::RemoveAlarm {
+            position,
+            alarm: alarm.clone(),
+        });
+        Ok(alarm)
+    }
+
+    /// Подключаем получателя тревог дома
+    pub fn add_alert_sink(&mut self, sink: Arc<dyn AlertSink>) {
+        self.alarms.add_sink(sink);
+    }
+}
+
+impl fmt::Debug for AlarmMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlarmMonitor")
+            .field("entries", &self.entries)
+            .field("sinks", &self.sinks.len())
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        device::{SmartSocket, SmartThermometer},
+    };
+
+    fn house(clock: Arc<ManualClock>) -> SmartHouse {
+        let mut house = SmartHouse::new_empty("alarmed house");
+        house.add_room("floor1/kitchen").unwrap();
+        house
+            .add_device(
+                "floor1/kitchen",
+                Box::new(SmartThermometer::new("thermo", "", 22.0)),
+            )
+            .unwrap();
+        house
+            .add_device(
+                "floor1/kitchen",
+                Box::new(SmartSocket::new("iron", "", false, 2200.0)),
+            )
+            .unwrap();
+        house.set_clock(clock);
+        house
+    }
+
+    fn set_temperature(house: &mut SmartHouse, temperature: f64) {
+        house
+            .update_device("floor1/kitchen", "thermo", |d| {
+                d.as_any_mut()
+                    .downcast_mut::<SmartThermometer>()
+                    .unwrap()
+                    .set_temperature(temperature)
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_temperature_alarm() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house(clock.clone());
+        let sink = Arc::new(MemorySink::new());
+        house.add_alert_sink(sink.clone());
+        house
+            .add_alarm(
+                Alarm::new("too hot", "thermo", Threshold::TemperatureAbove(30.0))
+                    .with_severity(Severity::Critical)
+                    .with_hysteresis(2.0),
+            )
+            .unwrap();
+        assert_eq!(
+            Err(AlarmError::DuplicateAlarm("too hot".to_owned())),
+            house.add_alarm(Alarm::new("too hot", "iron", Threshold::PowerAbove(1.0)))
+        );
+
+        assert!(house.check_alarms().is_empty());
+        clock.advance(Duration::from_secs(60));
+        set_temperature(&mut house, 31.5);
+        let alerts = sink.take();
+        assert_eq!(1, alerts.len());
+        assert_eq!(
+            Alert {
+                alarm: "too hot".to_owned(),
+                kind: AlertKind::Raised,
+                severity: Severity::Critical,
+                at: SystemTime::UNIX_EPOCH + Duration::from_secs(60),
+                room: "floor1/kitchen".to_owned(),
+                device: "thermo".to_owned(),
+                threshold: Threshold::TemperatureAbove(30.0),
+                value: 31.5,
+            },
+            alerts[0]
+        );
+        assert_eq!(
+            "critical: alarm \"too hot\" raised: floor1/kitchen/thermo 31.5 °C, temperature above 30 °C",
+            alerts[0].to_string()
+        );
+        assert_eq!(
+            vec![&alerts[0]],
+            house.alarms().active().collect::<Vec<_>>()
+        );
+
+        // Повторно не поднимается, в пределах гистерезиса не снимается
+        set_temperature(&mut house, 33.0);
+        set_temperature(&mut house, 29.0);
+        assert!(sink.take().is_empty());
+
+        set_temperature(&mut house, 27.5);
+        let alerts = sink.take();
+        assert_eq!(1, alerts.len());
+        assert_eq!(AlertKind::Cleared, alerts[0].kind);
+        assert_eq!(
+            "critical: alarm \"too hot\" cleared: floor1/kitchen/thermo 27.5 °C",
+            alerts[0].to_string()
+        );
+        assert_eq!(0, house.alarms().active().count());
+
+        house.remove_alarm("too hot").unwrap();
+        assert_eq!(
+            Err(AlarmError::AlarmNotFound("too hot".to_owned())),
+            house.remove_alarm("too hot").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_socket_alarms() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house(clock.clone());
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let sink_messages = messages.clone();
+        house.add_alert_sink(Arc::new(move |alert: &Alert| {
+            sink_messages.lock().unwrap().push(alert.to_string())
+        }));
+        house
+            .add_alarm(Alarm::new(
+                "iron left on",
+                "floor1/kitchen/iron",
+                Threshold::OnLongerThan(Duration::from_secs(30 * 60)),
+            ))
+            .unwrap();
+        house
+            .add_alarm(
+                Alarm::new("heavy load", "iron", Threshold::PowerAbove(2000.0))
+                    .with_severity(Severity::Info),
+            )
+            .unwrap();
+
+        house
+            .update_device("floor1/kitchen", "iron", |d| {
+                d.as_any_mut()
+                    .downcast_mut::<SmartSocket>()
+                    .unwrap()
+                    .turn_on()
+            })
+            .unwrap();
+        clock.advance(Duration::from_secs(20 * 60));
+        assert!(house.check_alarms().is_empty());
+        clock.advance(Duration::from_secs(10 * 60));
+        let alerts = house.check_alarms();
+        assert_eq!(1, alerts.len());
+        assert_eq!("iron left on", alerts[0].alarm);
+
+        house
+            .update_device("floor1/kitchen", "iron", |d| {
+                d.as_any_mut()
+                    .downcast_mut::<SmartSocket>()
+                    .unwrap()
+                    .turn_off()
+            })
+            .unwrap();
+        assert_eq!(
+            vec![
+                "info: alarm \"heavy load\" raised: floor1/kitchen/iron 2200 W, power above 2000 W",
+                "warning: alarm \"iron left on\" raised: floor1/kitchen/iron on for 30m, on longer than 30m",
+                "warning: alarm \"iron left on\" cleared: floor1/kitchen/iron on for 0s",
+                "info: alarm \"heavy load\" cleared: floor1/kitchen/iron 0 W",
+            ],
+            *messages.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_device_removed() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house(clock.clone());
+        house
+            .add_alarm(Alarm::new(
+                "too hot",
+                "floor1/kitchen/thermo",
+                Threshold::TemperatureAbove(30.0),
+            ))
+            .unwrap();
+        set_temperature(&mut house, 31.0);
+        assert_eq!(1, house.alarms().active().count());
+
+        // Тревога следует за девайсом при переименовании
+        house.rename_room("floor1", "ground").unwrap();
+        assert_eq!(
+            "ground/kitchen/thermo",
+            house.alarms().alarm("too hot").unwrap().device
+        );
+        assert!(house.check_alarms().is_empty());
+
+        clock.advance(Duration::from_secs(60));
+        house.delete_device("ground/kitchen", "thermo").unwrap();
+        let alerts = house.check_alarms();
+        assert_eq!(
+            vec!["warning: alarm \"too hot\" cleared: ground/kitchen/thermo 31 °C"],
+            alerts.iter().map(|a| a.to_string()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(60),
+            alerts[0].at
+        );
+        assert_eq!(0, house.alarms().active().count());
+        assert!(house.check_alarms().is_empty());
+
+        // Вернувшийся девайс проверяется как обычно
+        house.undo().unwrap();
+        assert_eq!(1, house.check_alarms().len());
+    }
+
+    #[test]
+    fn test_below_with_hysteresis() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house(clock);
+        let sink = Arc::new(MemorySink::new());
+        house.add_alert_sink(sink.clone());
+        house
+            .add_alarm(
+                Alarm::new("too cold", "thermo", Threshold::TemperatureBelow(16.0))
+                    .with_hysteresis(1.0),
+            )
+            .unwrap();
+
+        let kinds = |sink: &MemorySink| sink.take().iter().map(|a| a.kind).collect::<Vec<_>>();
+        set_temperature(&mut house, 16.0);
+        assert!(kinds(&sink).is_empty());
+        set_temperature(&mut house, 15.5);
+        assert_eq!(vec![AlertKind::Raised], kinds(&sink));
+
+        // Снимается только на 17 °C и выше
+        set_temperature(&mut house, 16.5);
+        assert!(kinds(&sink).is_empty());
+        set_temperature(&mut house, 17.0);
+        assert_eq!(vec![AlertKind::Cleared], kinds(&sink));
+        set_temperature(&mut house, 16.5);
+        assert!(kinds(&sink).is_empty());
+        set_temperature(&mut house, 15.9);
+        assert_eq!(vec![AlertKind::Raised], kinds(&sink));
+    }
+
+    #[test]
+    fn test_multiple_sinks() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house(clock);
+        let sinks = [Arc::new(MemorySink::new()), Arc::new(MemorySink::new())];
+        for sink in &sinks {
+            house.add_alert_sink(sink.clone());
+        }
+        house
+            .add_alarm(Alarm::new(
+                "too hot",
+                "thermo",
+                Threshold::TemperatureAbove(30.0),
+            ))
+            .unwrap();
+        house
+            .add_alarm(Alarm::new(
+                "heat wave",
+                "thermo",
+                Threshold::TemperatureAbove(25.0),
+            ))
+            .unwrap();
+
+        set_temperature(&mut house, 31.0);
+        for sink in &sinks {
+            assert_eq!(
+                vec!["too hot", "heat wave"],
+                sink.take()
+                    .iter()
+                    .map(|a| a.alarm.as_str())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        // Получатель, подключенный позже, видит только новые тревоги
+        let late = Arc::new(MemorySink::new());
+        house.add_alert_sink(late.clone());
+        set_temperature(&mut house, 20.0);
+        for sink in sinks.iter().chain([&late]) {
+            assert_eq!(2, sink.take().len());
+        }
+    }
+
+    #[test]
+    fn test_on_time_from_socket() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house(clock.clone());
+        house
+            .add_alarm(Alarm::new(
+                "iron left on",
+                "iron",
+                Threshold::OnLongerThan(Duration::from_secs(30 * 60)),
+            ))
+            .unwrap();
+
+        // Включили в обход проверок: время все равно идет с момента включения
+        house
+            .socket_mut("floor1/kitchen", "iron")
+            .unwrap()
+            .turn_on();
+        clock.advance(Duration::from_secs(30 * 60));
+        let alerts = house.check_alarms();
+        assert_eq!(1, alerts.len());
+        assert_eq!(AlertKind::Raised, alerts[0].kind);
+
+        let mut negative = Alarm::new("cold", "thermo", Threshold::TemperatureBelow(10.0));
+        negative.hysteresis = -1.0;
+        assert_eq!(
+            Err(AlarmError::InvalidHysteresis {
+                alarm: "cold".to_owned(),
+                hysteresis: -1.0
+            }),
+            house.add_alarm(negative)
+        );
+    }
+}
@@ -1,24 +1,271 @@
+pub mod blinds;
+pub mod history;
 pub mod info;
+pub mod light;
+pub mod lock;
+pub mod sensor;
+pub mod temperature;
 
-use std::fmt;
+pub use blinds::SmartBlinds;
+pub use light::SmartLight;
+pub use lock::SmartLock;
+pub use sensor::SmartSensor;
 
+use crate::clock::{system_clock, Clock};
+use history::{Reading, ReadingHistory, ReadingStats};
+use light::Color;
+use sensor::SensorKind;
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use temperature::{Temperature, TemperatureUnit};
+
+/// Тип устройства
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    Socket,
+    Thermometer,
+    Light,
+    Lock,
+    Sensor,
+    Blinds,
+}
+
+impl fmt::Display for DeviceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceKind::Socket => write!(f, "Socket"),
+            DeviceKind::Thermometer => write!(f, "Thermometer"),
+            DeviceKind::Light => write!(f, "Light"),
+            DeviceKind::Lock => write!(f, "Lock"),
+            DeviceKind::Sensor => write!(f, "Sensor"),
+            DeviceKind::Blinds => write!(f, "Blinds"),
+        }
+    }
+}
+
+/// Снимок состояния устройства
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeviceState {
+    /// `current_power` - мощность нагрузки, Вт, потребляемая во включенном состоянии
+    Socket {
+        is_on: bool,
+        current_power: f64,
+        #[serde(default = "default_voltage")]
+        voltage: f64,
+        #[serde(default)]
+        energy_kwh: f64,
+    },
+    /// Температура хранится в градусах Цельсия
+    Thermometer {
+        current_temperature: Temperature,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        humidity: Option<f64>,
+    },
+    /// `brightness` - яркость, %, `color` есть только у цветных ламп
+    Light {
+        is_on: bool,
+        brightness: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<Color>,
+    },
+    Lock {
+        is_locked: bool,
+        #[serde(default)]
+        is_jammed: bool,
+    },
+    Sensor {
+        sensor: SensorKind,
+        #[serde(default)]
+        is_triggered: bool,
+        #[serde(
+            default,
+            with = "humantime_serde",
+            skip_serializing_if = "Option::is_none"
+        )]
+        last_triggered: Option<SystemTime>,
+    },
+    /// `position` - процент открытия
+    Blinds { position: u8 },
+}
+
+fn default_voltage() -> f64 {
+    DEFAULT_VOLTAGE
+}
+
+impl DeviceState {
+    pub fn kind(&self) -> DeviceKind {
+        match self {
+            DeviceState::Socket { .. } => DeviceKind::Socket,
+            DeviceState::Thermometer { .. } => DeviceKind::Thermometer,
+            DeviceState::Light { .. } => DeviceKind::Light,
+            DeviceState::Lock { .. } => DeviceKind::Lock,
+            DeviceState::Sensor { .. } => DeviceKind::Sensor,
+            DeviceState::Blinds { .. } => DeviceKind::Blinds,
+        }
+    }
+
+    /// Совпадают ли состояния без учета энергии, накопленной розеткой:
+    /// она растет сама по себе и изменением состояния не считается
+    pub fn same_as(&self, other: &DeviceState) -> bool {
+        let without_energy = |state: &DeviceState| match state {
+            DeviceState::Socket {
+                is_on,
+                current_power,
+                voltage,
+                ..
+            } => DeviceState::Socket {
+                is_on: *is_on,
+                current_power: *current_power,
+                voltage: *voltage,
+                energy_kwh: 0.0,
+            },
+            state => state.clone(),
+        };
+
+        without_energy(self) == without_energy(other)
+    }
+
+    /// Создаем устройство нужного типа с заданным состоянием
+    pub fn into_device(self, name: &str, description: &str) -> Box<dyn Device> {
+        match self {
+            DeviceState::Socket {
+                is_on,
+                current_power,
+                voltage,
+                energy_kwh,
+            } => {
+                let mut socket = SmartSocket::new(name, description, is_on, current_power);
+                socket.set_voltage(voltage);
+                socket.set_energy_kwh(energy_kwh);
+                Box::new(socket)
+            }
+            DeviceState::Thermometer {
+                current_temperature,
+                humidity,
+            } => {
+                let mut thermometer =
+                    SmartThermometer::new(name, description, current_temperature.as_celsius());
+                thermometer.set_humidity(humidity);
+                Box::new(thermometer)
+            }
+            DeviceState::Light {
+                is_on,
+                brightness,
+                color,
+            } => {
+                let mut light = SmartLight::new(name, description, is_on, brightness);
+                light.set_color(color);
+                Box::new(light)
+            }
+            DeviceState::Lock {
+                is_locked,
+                is_jammed,
+            } => {
+                let mut lock = SmartLock::new(name, description, is_locked);
+                if is_jammed {
+                    lock.jam();
+                }
+                Box::new(lock)
+            }
+            DeviceState::Sensor {
+                sensor,
+                is_triggered,
+                last_triggered,
+            } => Box::new(SmartSensor::restore(
+                name,
+                description,
+                sensor,
+                is_triggered,
+                last_triggered,
+            )),
+            DeviceState::Blinds { position } => {
+                Box::new(SmartBlinds::new(name, description, position))
+            }
+        }
+    }
+}
+
+/// Общий интерфейс умных устройств, которыми владеет дом
+pub trait Device: fmt::Display + Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// Переименование, дом сам следит за уникальностью имен
+    fn set_name(&mut self, name: &str);
+
+    fn set_description(&mut self, description: &str);
+
+    fn kind(&self) -> DeviceKind;
+
+    /// Текущее состояние устройства
+    fn state(&self) -> DeviceState;
+
+    /// Нужно для приведения к конкретному типу устройства
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Окно статистики термометра по умолчанию
+pub const DEFAULT_STATS_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Напряжение сети по умолчанию, В
+pub const DEFAULT_VOLTAGE: f64 = 230.0;
+
+/// Умная розетка со счетчиком электроэнергии.
+/// Мощность нагрузки, Вт, потребляется только пока розетка включена;
+/// ток считается по мощности и напряжению, энергия копится по часам розетки.
+#[derive(Debug, Clone)]
 pub struct SmartSocket {
     name: String,
     description: String,
     is_on: bool,
-    current_power: f64,
+    /// Мощность нагрузки, Вт
+    load_power: f64,
+    /// Напряжение сети, В
+    voltage: f64,
+    /// Энергия, накопленная к моменту `metered_at`, Вт*ч
+    energy_wh: f64,
+    metered_at: SystemTime,
+    /// Когда розетку включили, по ее часам
+    on_since: Option<SystemTime>,
+    clock: Arc<dyn Clock>,
 }
 
 impl SmartSocket {
-    pub fn new(name: &str, description: &str, is_on: bool, current_power: f64) -> Self {
+    /// Розетка с нагрузкой `load_power` (Вт) в сети `DEFAULT_VOLTAGE`
+    pub fn new(name: &str, description: &str, is_on: bool, load_power: f64) -> Self {
+        let clock = system_clock();
         Self {
             name: name.to_owned(),
             description: description.to_owned(),
             is_on,
-            current_power,
+            load_power,
+            voltage: DEFAULT_VOLTAGE,
+            energy_wh: 0.0,
+            metered_at: clock.now(),
+            on_since: is_on.then(|| clock.now()),
+            clock,
         }
     }
 
+    /// Подменяем часы счетчика, дальше энергия копится по ним.
+    /// Включенная розетка считается включенной с текущего момента новых часов.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.metered_at = clock.now();
+        self.on_since = self.is_on.then(|| clock.now());
+        self.clock = clock;
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -28,10 +275,16 @@ impl SmartSocket {
     }
 
     pub fn turn_on(&mut self) {
+        self.settle();
+        if !self.is_on {
+            self.on_since = Some(self.clock.now());
+        }
         self.is_on = true
     }
 
     pub fn turn_off(&mut self) {
+        self.settle();
+        self.on_since = None;
         self.is_on = false
     }
 
@@ -39,8 +292,118 @@ impl SmartSocket {
         self.is_on
     }
 
+    /// Когда розетку включили, `None` - выключена
+    pub fn on_since(&self) -> Option<SystemTime> {
+        self.on_since
+    }
+
+    /// Активная мощность, Вт: мощность нагрузки, если розетка включена, иначе 0
     pub fn current_power(&self) -> f64 {
-        self.current_power
+        if self.is_on {
+            self.load_power
+        } else {
+            0.0
+        }
+    }
+
+    /// Мощность нагрузки, Вт, независимо от того, включена ли розетка
+    pub fn load_power(&self) -> f64 {
+        self.load_power
+    }
+
+    pub fn set_load_power(&mut self, load_power: f64) {
+        self.settle();
+        self.load_power = load_power;
+    }
+
+    /// Напряжение, В
+    pub fn voltage(&self) -> f64 {
+        self.voltage
+    }
+
+    pub fn set_voltage(&mut self, voltage: f64) {
+        self.voltage = voltage;
+    }
+
+    /// Ток, А
+    pub fn current(&self) -> f64 {
+        if self.voltage == 0.0 {
+            0.0
+        } else {
+            self.current_power() / self.voltage
+        }
+    }
+
+    /// Потребленная энергия с учетом текущего включения, кВт*ч
+    pub fn energy_kwh(&self) -> f64 {
+        (self.energy_wh + self.pending_energy_wh()) / 1000.0
+    }
+
+    /// Выставляем показания счетчика, например при восстановлении из файла
+    pub fn set_energy_kwh(&mut self, energy_kwh: f64) {
+        self.metered_at = self.clock.now();
+        self.energy_wh = energy_kwh * 1000.0;
+    }
+
+    pub fn reset_energy(&mut self) {
+        self.set_energy_kwh(0.0);
+    }
+
+    /// Энергия с момента последнего учета, Вт*ч
+    fn pending_energy_wh(&self) -> f64 {
+        let hours = self
+            .clock
+            .now()
+            .duration_since(self.metered_at)
+            .unwrap_or_default()
+            .as_secs_f64()
+            / 3600.0;
+        self.current_power() * hours
+    }
+
+    /// Переносим энергию с последнего учета в счетчик, вызываем до смены мощности
+    fn settle(&mut self) {
+        self.energy_wh += self.pending_energy_wh();
+        self.metered_at = self.clock.now();
+    }
+}
+
+impl Device for SmartSocket {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    fn kind(&self) -> DeviceKind {
+        DeviceKind::Socket
+    }
+
+    fn state(&self) -> DeviceState {
+        DeviceState::Socket {
+            is_on: self.is_on,
+            current_power: self.load_power,
+            voltage: self.voltage,
+            energy_kwh: self.energy_kwh(),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
@@ -53,28 +416,62 @@ impl fmt::Display for SmartSocket {
         f.pad("")?;
         write!(
             f,
-            "Current state: {}, {} Volts",
+            "Current state: {}, {} W, {} V, {:.2} A, {:.3} kWh",
             if self.is_on { "on" } else { "off" },
-            self.current_power
+            self.current_power(),
+            self.voltage,
+            self.current(),
+            self.energy_kwh()
         )?;
         Ok(())
     }
 }
+#[derive(Debug, Clone)]
 pub struct SmartThermometer {
     name: String,
     description: String,
-    current_temperature: f64,
+    current_temperature: Temperature,
+    /// Относительная влажность, %, если датчик ее измеряет
+    humidity: Option<f64>,
+    /// Единицы, в которых термометр выводит температуру
+    display_unit: TemperatureUnit,
+    history: ReadingHistory,
+    /// Окно статистики, которую термометр выводит вместе с температурой
+    stats_window: Duration,
+    /// Статистика из отчета для термометра, восстановленного из снимка без истории
+    pub(crate) reported_stats: Option<ReadingStats>,
+    clock: Arc<dyn Clock>,
 }
 
 impl SmartThermometer {
+    /// Термометр с температурой `current_temperature` в градусах Цельсия
     pub fn new(name: &str, description: &str, current_temperature: f64) -> Self {
+        let clock = system_clock();
+        let current_temperature = Temperature::celsius(current_temperature);
+        let mut history = ReadingHistory::default();
+        history.push(clock.now(), current_temperature);
+
         Self {
             name: name.to_owned(),
             description: description.to_owned(),
             current_temperature,
+            humidity: None,
+            display_unit: TemperatureUnit::default(),
+            history,
+            stats_window: DEFAULT_STATS_WINDOW,
+            reported_stats: None,
+            clock,
         }
     }
 
+    /// Подменяем часы, история начинается заново с текущего показания
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.history = ReadingHistory::new(self.history.capacity());
+        self.history.push(clock.now(), self.current_temperature);
+        self.clock = clock;
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -83,12 +480,122 @@ impl SmartThermometer {
         &self.description
     }
 
+    /// Температура в градусах Цельсия
     pub fn current_temperature(&self) -> f64 {
-        self.current_temperature
+        self.current_temperature.as_celsius()
     }
 
+    /// Задаем температуру в градусах Цельсия
     pub fn set_temperature(&mut self, val: f64) {
-        self.current_temperature = val
+        self.set_current_temperature(Temperature::celsius(val))
+    }
+
+    pub fn temperature(&self) -> Temperature {
+        self.current_temperature
+    }
+
+    /// Новое показание, оно же попадает в историю
+    pub fn set_current_temperature(&mut self, temperature: Temperature) {
+        self.current_temperature = temperature;
+        self.history.push(self.clock.now(), temperature);
+    }
+
+    pub fn history(&self) -> &ReadingHistory {
+        &self.history
+    }
+
+    /// Сколько показаний помнить, лишние старые отбрасываются
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history.set_capacity(capacity)
+    }
+
+    /// Показания в интервале `from..=to`
+    pub fn readings_between(&self, from: SystemTime, to: SystemTime) -> Vec<Reading> {
+        self.history.between(from, to).copied().collect()
+    }
+
+    /// Статистика за последние `window` по часам термометра
+    pub fn stats_over(&self, window: Duration) -> Option<ReadingStats> {
+        let now = self.clock.now();
+        let from = now.checked_sub(window).unwrap_or(SystemTime::UNIX_EPOCH);
+        self.history
+            .stats_between(from, now)
+            .map(|stats| ReadingStats { window, ..stats })
+    }
+
+    pub fn stats_window(&self) -> Duration {
+        self.stats_window
+    }
+
+    pub fn set_stats_window(&mut self, window: Duration) {
+        self.stats_window = window
+    }
+
+    /// Статистика для вывода: за окно термометра, если в нем больше одного показания
+    pub fn display_stats(&self) -> Option<ReadingStats> {
+        self.stats_over(self.stats_window)
+            .filter(|stats| stats.count > 1)
+            .or(self.reported_stats)
+    }
+
+    pub fn humidity(&self) -> Option<f64> {
+        self.humidity
+    }
+
+    /// Задаем влажность в процентах, значение приводится к диапазону 0..=100
+    pub fn set_humidity(&mut self, humidity: Option<f64>) {
+        self.humidity = humidity.map(|h| h.clamp(0.0, 100.0))
+    }
+
+    /// Точка росы, если известна влажность
+    pub fn dew_point(&self) -> Option<Temperature> {
+        self.humidity
+            .map(|humidity| self.current_temperature.dew_point(humidity))
+    }
+
+    pub fn display_unit(&self) -> TemperatureUnit {
+        self.display_unit
+    }
+
+    pub fn set_display_unit(&mut self, unit: TemperatureUnit) {
+        self.display_unit = unit
+    }
+}
+
+impl Device for SmartThermometer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    fn kind(&self) -> DeviceKind {
+        DeviceKind::Thermometer
+    }
+
+    fn state(&self) -> DeviceState {
+        DeviceState::Thermometer {
+            current_temperature: self.current_temperature,
+            humidity: self.humidity,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
@@ -101,9 +608,22 @@ impl fmt::Display for SmartThermometer {
         f.pad("")?;
         write!(
             f,
-            "Current temperature: {} Celsus",
-            &self.current_temperature
+            "Current temperature: {}",
+            self.current_temperature.display_in(self.display_unit)
         )?;
+        if let Some(stats) = self.display_stats() {
+            write!(f, " ({})", stats.summary_in(self.display_unit))?;
+        }
+        if let (Some(humidity), Some(dew_point)) = (self.humidity, self.dew_point()) {
+            writeln!(f)?;
+            f.pad("")?;
+            write!(
+                f,
+                "Humidity: {} %, dew point {}",
+                humidity,
+                dew_point.display_in(self.display_unit)
+            )?;
+        }
         Ok(())
     }
 }
@@ -111,6 +631,8 @@ impl fmt::Display for SmartThermometer {
 #[cfg(test)]
 mod tests_smart_socket {
     use super::*;
+    use crate::clock::ManualClock;
+    use std::time::Duration;
 
     #[test]
     fn test_new() {
@@ -137,11 +659,70 @@ mod tests_smart_socket {
         socket.turn_on();
         assert!(socket.is_on());
     }
+
+    #[test]
+    fn test_metering() {
+        let clock = Arc::new(ManualClock::default());
+        let mut socket =
+            SmartSocket::new("socket", "kettle", true, 2000.0).with_clock(clock.clone());
+
+        assert_eq!(2000.0, socket.current_power());
+        assert_eq!(DEFAULT_VOLTAGE, socket.voltage());
+        assert!((socket.current() - 2000.0 / 230.0).abs() < 1e-9);
+
+        clock.advance(Duration::from_secs(30 * 60));
+        assert_eq!(1.0, socket.energy_kwh());
+
+        socket.turn_off();
+        assert_eq!(0.0, socket.current_power());
+        assert_eq!(0.0, socket.current());
+        assert_eq!(2000.0, socket.load_power());
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(1.0, socket.energy_kwh());
+
+        socket.set_load_power(1000.0);
+        socket.set_voltage(220.0);
+        socket.turn_on();
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(2.0, socket.energy_kwh());
+        assert_eq!(
+            "Name: socket\nDescription: kettle\nCurrent state: on, 1000 W, 220 V, 4.55 A, 2.000 kWh",
+            socket.to_string()
+        );
+
+        socket.reset_energy();
+        assert_eq!(0.0, socket.energy_kwh());
+    }
+
+    #[test]
+    fn test_device() {
+        let clock = Arc::new(ManualClock::default());
+        let socket =
+            SmartSocket::new("socket", "description_socket", true, 220.0).with_clock(clock);
+        let device: &dyn Device = &socket;
+
+        assert_eq!(device.name(), "socket");
+        assert_eq!(device.description(), "description_socket");
+        assert_eq!(device.kind(), DeviceKind::Socket);
+        assert_eq!(
+            device.state(),
+            DeviceState::Socket {
+                is_on: true,
+                current_power: 220.0,
+                voltage: DEFAULT_VOLTAGE,
+                energy_kwh: 0.0
+            }
+        );
+        assert!(device.as_any().downcast_ref::<SmartSocket>().is_some());
+        assert!(device.as_any().downcast_ref::<SmartThermometer>().is_none());
+    }
 }
 
 #[cfg(test)]
 mod tests_smart_thermometr {
     use super::*;
+    use crate::clock::ManualClock;
+    use history::Trend;
 
     #[test]
     fn test_new() {
@@ -159,4 +740,83 @@ mod tests_smart_thermometr {
         thermo.set_temperature(20.2);
         assert_eq!(thermo.current_temperature(), 20.2);
     }
+
+    #[test]
+    fn test_device() {
+        let thermo = SmartThermometer::new("thermo", "thermo_description", 32.0);
+        let device: &dyn Device = &thermo;
+
+        assert_eq!(device.name(), "thermo");
+        assert_eq!(device.kind(), DeviceKind::Thermometer);
+        assert_eq!(
+            device.state(),
+            DeviceState::Thermometer {
+                current_temperature: Temperature::celsius(32.0),
+                humidity: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_humidity() {
+        let mut thermo = SmartThermometer::new("thermo", "thermo_description", 25.0);
+        assert_eq!(None, thermo.dew_point());
+        assert_eq!(
+            "Name: thermo\nDescription: thermo_description\nCurrent temperature: 25 °C",
+            thermo.to_string()
+        );
+
+        thermo.set_humidity(Some(120.0));
+        assert_eq!(Some(100.0), thermo.humidity());
+        assert_eq!(
+            "25 °C",
+            thermo
+                .dew_point()
+                .unwrap()
+                .display_in(TemperatureUnit::Celsius)
+        );
+
+        thermo.set_humidity(Some(50.0));
+        thermo.set_display_unit(TemperatureUnit::Fahrenheit);
+        assert_eq!(
+            "Name: thermo\nDescription: thermo_description\nCurrent temperature: 77 °F\nHumidity: 50 %, dew point 56.93 °F",
+            thermo.to_string()
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let clock = Arc::new(ManualClock::default());
+        let mut thermo =
+            SmartThermometer::new("thermo", "thermo_description", 18.0).with_clock(clock.clone());
+
+        for t in [19.0, 20.0, 21.0] {
+            clock.advance(Duration::from_secs(3600));
+            thermo.set_temperature(t);
+        }
+
+        let hour = |h: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(h * 3600);
+        let readings = thermo.readings_between(hour(1), hour(2));
+        assert_eq!(
+            vec![19.0, 20.0],
+            readings
+                .iter()
+                .map(|r| r.temperature.as_celsius())
+                .collect::<Vec<_>>()
+        );
+
+        let stats = thermo.stats_over(Duration::from_secs(2 * 3600)).unwrap();
+        assert_eq!(3, stats.count);
+        assert_eq!(Temperature::celsius(20.0), stats.average);
+        assert_eq!(Trend::Rising, stats.trend);
+
+        assert_eq!(
+            "Name: thermo\nDescription: thermo_description\nCurrent temperature: 21 °C (min 18, max 21 over 24h)",
+            thermo.to_string()
+        );
+
+        thermo.set_history_capacity(1);
+        assert_eq!(1, thermo.history().len());
+        assert_eq!(None, thermo.display_stats());
+    }
 }
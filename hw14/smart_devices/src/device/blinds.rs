@@ -0,0 +1,126 @@
+use super::{Device, DeviceKind, DeviceState};
+use std::{any::Any, fmt};
+
+/// Моторизованные жалюзи, положение - процент открытия: 0 - закрыты, 100 - открыты
+#[derive(Debug, Clone)]
+pub struct SmartBlinds {
+    name: String,
+    description: String,
+    position: u8,
+}
+
+impl SmartBlinds {
+    /// Жалюзи в положении `position` (%, больше 100 обрезается)
+    pub fn new(name: &str, description: &str, position: u8) -> Self {
+        Self {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            position: position.min(100),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn position(&self) -> u8 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: u8) {
+        self.position = position.min(100)
+    }
+
+    pub fn open(&mut self) {
+        self.position = 100
+    }
+
+    pub fn close(&mut self) {
+        self.position = 0
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.position == 0
+    }
+}
+
+impl Device for SmartBlinds {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    fn kind(&self) -> DeviceKind {
+        DeviceKind::Blinds
+    }
+
+    fn state(&self) -> DeviceState {
+        DeviceState::Blinds {
+            position: self.position,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Текстовое положение жалюзи, например `"40 % open"`
+pub(crate) fn blinds_position(position: u8) -> String {
+    match position {
+        0 => "closed".to_owned(),
+        100 => "open".to_owned(),
+        _ => format!("{position} % open"),
+    }
+}
+
+impl fmt::Display for SmartBlinds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("")?;
+        writeln!(f, "Name: {}", &self.name)?;
+        f.pad("")?;
+        writeln!(f, "Description: {}", &self.description)?;
+        f.pad("")?;
+        write!(f, "Current position: {}", blinds_position(self.position))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blinds() {
+        let mut blinds = SmartBlinds::new("blinds", "bedroom window", 40);
+        assert_eq!(
+            "Name: blinds\nDescription: bedroom window\nCurrent position: 40 % open",
+            blinds.to_string()
+        );
+
+        blinds.set_position(250);
+        assert_eq!(100, blinds.position());
+        blinds.close();
+        assert!(blinds.is_closed());
+        assert_eq!(DeviceState::Blinds { position: 0 }, blinds.state());
+    }
+}
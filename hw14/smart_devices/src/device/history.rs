@@ -0,0 +1,243 @@
+use super::temperature::{Temperature, TemperatureUnit};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+/// Сколько показаний термометр помнит по умолчанию
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1024;
+
+/// Изменение быстрее этого, °C в час, считается ростом или падением
+const STEADY_SLOPE: f64 = 0.1;
+
+/// Показание с отметкой времени
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    #[serde(with = "humantime_serde")]
+    pub at: SystemTime,
+    pub temperature: Temperature,
+}
+
+/// Направление изменения температуры
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+impl fmt::Display for Trend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trend::Rising => write!(f, "rising"),
+            Trend::Falling => write!(f, "falling"),
+            Trend::Steady => write!(f, "steady"),
+        }
+    }
+}
+
+/// Статистика показаний за окно времени
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReadingStats {
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    pub count: usize,
+    pub min: Temperature,
+    pub max: Temperature,
+    pub average: Temperature,
+    /// Наклон линейной регрессии, °C в час
+    pub slope: f64,
+    pub trend: Trend,
+}
+
+impl ReadingStats {
+    /// Краткая сводка, например `"min 17.9, max 21.4 over 24h"`
+    pub fn summary_in(&self, unit: TemperatureUnit) -> String {
+        let value = |t: Temperature| (t.value_in(unit) * 100.0).round() / 100.0;
+        format!(
+            "min {}, max {} over {}",
+            value(self.min),
+            value(self.max),
+            format_window(self.window)
+        )
+    }
+}
+
+/// Окно в самых крупных целых единицах: `"24h"`, `"90m"`, `"45s"`
+fn format_window(window: Duration) -> String {
+    let secs = window.as_secs();
+    match secs {
+        0 => humantime::format_duration(window).to_string(),
+        _ if secs.is_multiple_of(3600) => format!("{}h", secs / 3600),
+        _ if secs.is_multiple_of(60) => format!("{}m", secs / 60),
+        _ => format!("{secs}s"),
+    }
+}
+
+/// Ограниченный буфер показаний, самые старые вытесняются новыми
+#[derive(Debug, Clone)]
+pub struct ReadingHistory {
+    capacity: usize,
+    readings: VecDeque<Reading>,
+}
+
+impl ReadingHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            readings: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Меняем размер буфера, лишние старые показания отбрасываются
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.readings.len() > self.capacity {
+            self.readings.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    pub fn push(&mut self, at: SystemTime, temperature: Temperature) {
+        if self.readings.len() == self.capacity {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(Reading { at, temperature });
+    }
+
+    /// Все показания от старых к новым
+    pub fn iter(&self) -> impl Iterator<Item = &Reading> {
+        self.readings.iter()
+    }
+
+    pub fn latest(&self) -> Option<&Reading> {
+        self.readings.back()
+    }
+
+    /// Показания в интервале `from..=to`
+    pub fn between(&self, from: SystemTime, to: SystemTime) -> impl Iterator<Item = &Reading> {
+        self.readings
+            .iter()
+            .filter(move |r| from <= r.at && r.at <= to)
+    }
+
+    /// Статистика показаний в интервале `from..=to`, `None`, если показаний нет
+    pub fn stats_between(&self, from: SystemTime, to: SystemTime) -> Option<ReadingStats> {
+        let readings: Vec<&Reading> = self.between(from, to).collect();
+        let first = readings.first()?;
+
+        let celsius = |r: &&Reading| r.temperature.as_celsius();
+        let min = readings.iter().map(celsius).fold(f64::INFINITY, f64::min);
+        let max = readings
+            .iter()
+            .map(celsius)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let count = readings.len();
+        let average = readings.iter().map(celsius).sum::<f64>() / count as f64;
+
+        // Наклон по методу наименьших квадратов, время в часах от первого показания
+        let hours = |r: &&Reading| {
+            r.at.duration_since(first.at)
+                .unwrap_or_default()
+                .as_secs_f64()
+                / 3600.0
+        };
+        let mean_hours = readings.iter().map(hours).sum::<f64>() / count as f64;
+        let (covariance, variance) = readings.iter().fold((0.0, 0.0), |(cov, var), r| {
+            let dx = hours(r) - mean_hours;
+            (cov + dx * (celsius(r) - average), var + dx * dx)
+        });
+        let slope = if variance > 0.0 {
+            covariance / variance
+        } else {
+            0.0
+        };
+        let trend = if slope > STEADY_SLOPE {
+            Trend::Rising
+        } else if slope < -STEADY_SLOPE {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        };
+
+        Some(ReadingStats {
+            window: to.duration_since(from).unwrap_or_default(),
+            count,
+            min: Temperature::celsius(min),
+            max: Temperature::celsius(max),
+            average: Temperature::celsius(average),
+            slope,
+            trend,
+        })
+    }
+}
+
+impl Default for ReadingHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(hours * 3600)
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut history = ReadingHistory::new(3);
+        for hour in 0..5 {
+            history.push(at(hour), Temperature::celsius(hour as f64));
+        }
+
+        assert_eq!(3, history.len());
+        assert_eq!(at(2), history.iter().next().unwrap().at);
+
+        history.set_capacity(1);
+        assert_eq!(at(4), history.latest().unwrap().at);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut history = ReadingHistory::default();
+        for (hour, t) in [(0, 18.0), (1, 19.0), (2, 20.0), (3, 21.0), (10, 5.0)] {
+            history.push(at(hour), Temperature::celsius(t));
+        }
+
+        assert_eq!(2, history.between(at(1), at(2)).count());
+
+        let stats = history.stats_between(at(0), at(3)).unwrap();
+        assert_eq!(4, stats.count);
+        assert_eq!(Temperature::celsius(18.0), stats.min);
+        assert_eq!(Temperature::celsius(21.0), stats.max);
+        assert_eq!(Temperature::celsius(19.5), stats.average);
+        assert!((stats.slope - 1.0).abs() < 1e-9);
+        assert_eq!(Trend::Rising, stats.trend);
+        assert_eq!(
+            "min 18, max 21 over 3h",
+            stats.summary_in(TemperatureUnit::Celsius)
+        );
+
+        let stats = history.stats_between(at(3), at(10)).unwrap();
+        assert_eq!(Trend::Falling, stats.trend);
+
+        assert!(history.stats_between(at(4), at(9)).is_none());
+    }
+}
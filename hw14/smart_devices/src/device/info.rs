@@ -1,10 +1,36 @@
-use super::{SmartSocket, SmartThermometer};
+use super::{Device, SmartSocket, SmartThermometer};
+use std::collections::HashMap;
 
 pub trait DeviceInfoProvider {
     // todo: метод, возвращающий состояние устройства по имени комнаты и имени устройства
     fn info(&self, location_name: &str, device_name: &str) -> Option<String>;
 }
 
+/// Описание устройства в формате, общем для всех поставщиков
+pub fn device_info(location_name: &str, device: &dyn Device) -> String {
+    format!(
+        r#"Location: {}
+Device/{}: 
+{:<2}"#,
+        location_name,
+        device.kind(),
+        device,
+    )
+}
+
+/// Описание устройства, которое поставщик не смог описать
+pub fn unavailable_device_info(location_name: &str, device: &dyn Device) -> String {
+    format!(
+        r#"Location: {}
+Device/{}: 
+  Name: {}
+  Current state: unavailable"#,
+        location_name,
+        device.kind(),
+        device.name(),
+    )
+}
+
 // Пользовательские поставщики информации об устройствах.
 // Могут как хранить устройства, так и заимствывать.
 pub struct OwningDeviceInfoProvider {
@@ -23,12 +49,7 @@ impl DeviceInfoProvider for OwningDeviceInfoProvider {
             return None;
         }
 
-        Some(format!(
-            r#"Location: {}
-Device/Socket: 
-{:<2}"#,
-            location_name, self.socket,
-        ))
+        Some(device_info(location_name, &self.socket))
     }
 }
 
@@ -46,27 +67,102 @@ impl<'a, 'b> BorrowingDeviceInfoProvider<'a, 'b> {
 impl<'a, 'b> DeviceInfoProvider for BorrowingDeviceInfoProvider<'a, 'b> {
     fn info(&self, location_name: &str, device_name: &str) -> Option<String> {
         if self.socket.name() == device_name {
-            return Some(format!(
-                r#"Location: {}
-Device/Socket: 
-{:<2}"#,
-                location_name, self.socket
-            ));
+            return Some(device_info(location_name, self.socket));
         }
 
         if self.thermo.name() == device_name {
-            return Some(format!(
-                r#"Location: {}
-Device/Thermometer: 
-{:<2}"#,
-                location_name, self.thermo
-            ));
+            return Some(device_info(location_name, self.thermo));
         }
 
         None
     }
 }
 
+enum RegistryEntry<'a> {
+    Owned(Box<dyn Device>),
+    Borrowed(&'a dyn Device),
+}
+
+impl RegistryEntry<'_> {
+    fn device(&self) -> &dyn Device {
+        match self {
+            RegistryEntry::Owned(device) => device.as_ref(),
+            RegistryEntry::Borrowed(device) => *device,
+        }
+    }
+}
+
+/// Реестр устройств любого типа, сгруппированных по комнатам.
+/// Устройства могут как храниться в реестре, так и заимствоваться.
+#[derive(Default)]
+pub struct DeviceRegistry<'a> {
+    rooms: HashMap<String, HashMap<String, RegistryEntry<'a>>>,
+}
+
+impl<'a> DeviceRegistry<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляем устройство во владение реестра, заменяя одноименное в той же комнате
+    pub fn insert(&mut self, location_name: &str, device: Box<dyn Device>) {
+        self.insert_entry(location_name, RegistryEntry::Owned(device));
+    }
+
+    /// Добавляем заимствованное устройство, заменяя одноименное в той же комнате
+    pub fn insert_borrowed(&mut self, location_name: &str, device: &'a dyn Device) {
+        self.insert_entry(location_name, RegistryEntry::Borrowed(device));
+    }
+
+    fn insert_entry(&mut self, location_name: &str, entry: RegistryEntry<'a>) {
+        self.rooms
+            .entry(location_name.to_owned())
+            .or_default()
+            .insert(entry.device().name().to_owned(), entry);
+    }
+
+    /// Удаляем устройство, возвращаем true, если оно было в реестре
+    pub fn remove(&mut self, location_name: &str, device_name: &str) -> bool {
+        let Some(devices) = self.rooms.get_mut(location_name) else {
+            return false;
+        };
+
+        let removed = devices.remove(device_name).is_some();
+        if devices.is_empty() {
+            self.rooms.remove(location_name);
+        }
+
+        removed
+    }
+
+    pub fn get(&self, location_name: &str, device_name: &str) -> Option<&dyn Device> {
+        self.rooms
+            .get(location_name)?
+            .get(device_name)
+            .map(|entry| entry.device())
+    }
+
+    pub fn contains(&self, location_name: &str, device_name: &str) -> bool {
+        self.get(location_name, device_name).is_some()
+    }
+
+    /// Количество устройств во всех комнатах
+    pub fn len(&self) -> usize {
+        self.rooms.values().map(|devices| devices.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+}
+
+impl DeviceInfoProvider for DeviceRegistry<'_> {
+    fn info(&self, location_name: &str, device_name: &str) -> Option<String> {
+        self.get(location_name, device_name)
+            .map(|device| device_info(location_name, device))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,7 +182,7 @@ mod tests {
 Device/Socket: 
   Name: test_socket_name
   Description: test socket description
-  Current state: on, 220.2 Volts"#,
+  Current state: on, 220.2 W, 230 V, 0.96 A, 0.000 kWh"#,
             info1
         );
 
@@ -110,7 +206,7 @@ Device/Socket:
 Device/Socket: 
   Name: test_socket_name
   Description: test socket description
-  Current state: on, 220.2 Volts"#,
+  Current state: on, 220.2 W, 230 V, 0.96 A, 0.000 kWh"#,
             info1
         );
 
@@ -123,11 +219,55 @@ Device/Socket:
 Device/Thermometer: 
   Name: test_thermo_name
   Description: test thermo description
-  Current temperature: 13 Celsus"#,
+  Current temperature: 13 °C"#,
             info2
         );
 
         let info3 = info_provider.info("test_location_name", "unknown_device_name");
         assert_eq!(None, info3);
     }
+
+    #[test]
+    fn test_registry_provider() {
+        let socket = SmartSocket::new("test_socket_name", "test socket description", true, 220.2);
+        let thermo = SmartThermometer::new("test_thermo_name", "test thermo description", 13.0);
+
+        let mut registry = DeviceRegistry::new();
+        registry.insert_borrowed("room1", &socket);
+        registry.insert("room2", Box::new(thermo));
+        registry.insert(
+            "room2",
+            Box::new(SmartSocket::new("room2_socket", "", false, 0.0)),
+        );
+
+        assert_eq!(3, registry.len());
+        assert!(registry.contains("room1", "test_socket_name"));
+        assert!(!registry.contains("room2", "test_socket_name"));
+
+        assert_eq!(
+            r#"Location: room1
+Device/Socket: 
+  Name: test_socket_name
+  Description: test socket description
+  Current state: on, 220.2 W, 230 V, 0.96 A, 0.000 kWh"#,
+            registry.info("room1", "test_socket_name").unwrap()
+        );
+
+        assert_eq!(
+            r#"Location: room2
+Device/Thermometer: 
+  Name: test_thermo_name
+  Description: test thermo description
+  Current temperature: 13 °C"#,
+            registry.info("room2", "test_thermo_name").unwrap()
+        );
+
+        assert_eq!(None, registry.info("room1", "test_thermo_name"));
+
+        assert!(registry.remove("room2", "test_thermo_name"));
+        assert!(!registry.remove("room2", "test_thermo_name"));
+        assert!(registry.remove("room2", "room2_socket"));
+        assert!(registry.remove("room1", "test_socket_name"));
+        assert!(registry.is_empty());
+    }
 }
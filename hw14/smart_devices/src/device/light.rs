@@ -0,0 +1,225 @@
+use super::{Device, DeviceKind, DeviceState};
+use serde::{Deserialize, Serialize};
+use std::{any::Any, fmt, str::FromStr};
+use thiserror::Error;
+
+/// Цвет RGB, в текстовом виде `#rrggbb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid color: {0}, expected #rrggbb")]
+pub struct InvalidColor(pub String);
+
+impl FromStr for Color {
+    type Err = InvalidColor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| InvalidColor(s.to_owned()))
+        };
+        if hex.len() != 6 {
+            return Err(InvalidColor(s.to_owned()));
+        }
+
+        Ok(Self::rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = InvalidColor;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+/// Диммируемая лампа, цветная, если задан цвет
+#[derive(Debug, Clone)]
+pub struct SmartLight {
+    name: String,
+    description: String,
+    is_on: bool,
+    /// Яркость, %
+    brightness: u8,
+    color: Option<Color>,
+}
+
+impl SmartLight {
+    /// Белая лампа с яркостью `brightness` (%, больше 100 обрезается)
+    pub fn new(name: &str, description: &str, is_on: bool, brightness: u8) -> Self {
+        Self {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            is_on,
+            brightness: brightness.min(100),
+            color: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn turn_on(&mut self) {
+        self.is_on = true
+    }
+
+    pub fn turn_off(&mut self) {
+        self.is_on = false
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness.min(100)
+    }
+
+    /// Цвет цветной лампы, `None` для обычной
+    pub fn color(&self) -> Option<Color> {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Option<Color>) {
+        self.color = color
+    }
+}
+
+impl Device for SmartLight {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    fn kind(&self) -> DeviceKind {
+        DeviceKind::Light
+    }
+
+    fn state(&self) -> DeviceState {
+        DeviceState::Light {
+            is_on: self.is_on,
+            brightness: self.brightness,
+            color: self.color,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for SmartLight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("")?;
+        writeln!(f, "Name: {}", &self.name)?;
+        f.pad("")?;
+        writeln!(f, "Description: {}", &self.description)?;
+        f.pad("")?;
+        write!(
+            f,
+            "Current state: {}, brightness {} %",
+            if self.is_on { "on" } else { "off" },
+            self.brightness
+        )?;
+        if let Some(color) = self.color {
+            write!(f, ", color {color}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        assert_eq!(Ok(Color::rgb(255, 136, 0)), "#ff8800".parse());
+        assert_eq!(Ok(Color::rgb(0, 0, 16)), "000010".parse());
+        assert_eq!(
+            Err(InvalidColor("#ff88".to_owned())),
+            "#ff88".parse::<Color>()
+        );
+        assert!("#gg0000".parse::<Color>().is_err());
+        assert_eq!("#ffffff", Color::WHITE.to_string());
+    }
+
+    #[test]
+    fn test_light() {
+        let mut light = SmartLight::new("lamp", "ceiling", false, 150);
+        assert_eq!(100, light.brightness());
+        assert_eq!(
+            "Name: lamp\nDescription: ceiling\nCurrent state: off, brightness 100 %",
+            light.to_string()
+        );
+
+        light.turn_on();
+        light.set_brightness(40);
+        light.set_color(Some(Color::rgb(255, 136, 0)));
+        assert_eq!(
+            "Name: lamp\nDescription: ceiling\nCurrent state: on, brightness 40 %, color #ff8800",
+            light.to_string()
+        );
+        assert_eq!(
+            DeviceState::Light {
+                is_on: true,
+                brightness: 40,
+                color: Some(Color::rgb(255, 136, 0))
+            },
+            light.state()
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use super::{Device, DeviceKind, DeviceState};
+use std::{any::Any, fmt};
+use thiserror::Error;
+
+/// Механизм замка заклинило, пока его не починят, он не открывается и не закрывается
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("lock \"{0}\" is jammed")]
+pub struct LockJammed(pub String);
+
+/// Дверной замок
+#[derive(Debug, Clone)]
+pub struct SmartLock {
+    name: String,
+    description: String,
+    is_locked: bool,
+    is_jammed: bool,
+}
+
+impl SmartLock {
+    pub fn new(name: &str, description: &str, is_locked: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            is_locked,
+            is_jammed: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn lock(&mut self) -> Result<(), LockJammed> {
+        self.set_locked(true)
+    }
+
+    pub fn unlock(&mut self) -> Result<(), LockJammed> {
+        self.set_locked(false)
+    }
+
+    fn set_locked(&mut self, is_locked: bool) -> Result<(), LockJammed> {
+        if self.is_jammed {
+            return Err(LockJammed(self.name.clone()));
+        }
+
+        self.is_locked = is_locked;
+        Ok(())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.is_locked
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.is_jammed
+    }
+
+    /// Замок сообщил, что механизм заклинило
+    pub fn jam(&mut self) {
+        self.is_jammed = true
+    }
+
+    /// Механизм починили
+    pub fn clear_jam(&mut self) {
+        self.is_jammed = false
+    }
+}
+
+impl Device for SmartLock {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    fn kind(&self) -> DeviceKind {
+        DeviceKind::Lock
+    }
+
+    fn state(&self) -> DeviceState {
+        DeviceState::Lock {
+            is_locked: self.is_locked,
+            is_jammed: self.is_jammed,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for SmartLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("")?;
+        writeln!(f, "Name: {}", &self.name)?;
+        f.pad("")?;
+        writeln!(f, "Description: {}", &self.description)?;
+        f.pad("")?;
+        write!(
+            f,
+            "Current state: {}",
+            if self.is_locked { "locked" } else { "unlocked" }
+        )?;
+        if self.is_jammed {
+            write!(f, ", jammed")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock() {
+        let mut lock = SmartLock::new("front", "front door", false);
+        lock.lock().unwrap();
+        assert!(lock.is_locked());
+
+        lock.jam();
+        assert_eq!(Err(LockJammed("front".to_owned())), lock.unlock());
+        assert!(lock.is_locked());
+        assert_eq!(
+            "Name: front\nDescription: front door\nCurrent state: locked, jammed",
+            lock.to_string()
+        );
+
+        lock.clear_jam();
+        lock.unlock().unwrap();
+        assert_eq!(
+            DeviceState::Lock {
+                is_locked: false,
+                is_jammed: false
+            },
+            lock.state()
+        );
+    }
+}
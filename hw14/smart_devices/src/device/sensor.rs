@@ -0,0 +1,205 @@
+use super::{Device, DeviceKind, DeviceState};
+use crate::clock::{system_clock, Clock};
+use serde::{Deserialize, Serialize};
+use std::{any::Any, fmt, sync::Arc, time::SystemTime};
+
+/// Что регистрирует датчик
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    /// Датчик движения, срабатывает на движение
+    Motion,
+    /// Датчик открытия двери или окна, срабатывает на открытие
+    Contact,
+}
+
+impl fmt::Display for SensorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SensorKind::Motion => write!(f, "motion"),
+            SensorKind::Contact => write!(f, "contact"),
+        }
+    }
+}
+
+/// Датчик движения или открытия, помнит время последнего срабатывания
+#[derive(Debug, Clone)]
+pub struct SmartSensor {
+    name: String,
+    description: String,
+    sensor: SensorKind,
+    is_triggered: bool,
+    last_triggered: Option<SystemTime>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SmartSensor {
+    pub fn new(name: &str, description: &str, sensor: SensorKind) -> Self {
+        Self {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            sensor,
+            is_triggered: false,
+            last_triggered: None,
+            clock: system_clock(),
+        }
+    }
+
+    /// Подменяем часы, по которым отмечается срабатывание
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Восстанавливаем датчик из сохраненного состояния
+    pub(crate) fn restore(
+        name: &str,
+        description: &str,
+        sensor: SensorKind,
+        is_triggered: bool,
+        last_triggered: Option<SystemTime>,
+    ) -> Self {
+        Self {
+            is_triggered,
+            last_triggered,
+            ..Self::new(name, description, sensor)
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn sensor(&self) -> SensorKind {
+        self.sensor
+    }
+
+    /// Датчик зафиксировал движение или открытие
+    pub fn trigger(&mut self) {
+        self.is_triggered = true;
+        self.last_triggered = Some(self.clock.now());
+    }
+
+    /// Движение прекратилось или дверь закрыли
+    pub fn clear(&mut self) {
+        self.is_triggered = false
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.is_triggered
+    }
+
+    pub fn last_triggered(&self) -> Option<SystemTime> {
+        self.last_triggered
+    }
+}
+
+impl Device for SmartSensor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    fn kind(&self) -> DeviceKind {
+        DeviceKind::Sensor
+    }
+
+    fn state(&self) -> DeviceState {
+        DeviceState::Sensor {
+            sensor: self.sensor,
+            is_triggered: self.is_triggered,
+            last_triggered: self.last_triggered,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Текстовое состояние датчика, например `"motion detected"` или `"closed"`
+pub(crate) fn sensor_state(sensor: SensorKind, is_triggered: bool) -> &'static str {
+    match (sensor, is_triggered) {
+        (SensorKind::Motion, true) => "motion detected",
+        (SensorKind::Motion, false) => "no motion",
+        (SensorKind::Contact, true) => "open",
+        (SensorKind::Contact, false) => "closed",
+    }
+}
+
+/// Время срабатывания в RFC 3339
+pub(crate) fn format_triggered(last_triggered: Option<SystemTime>) -> String {
+    last_triggered
+        .map(|t| humantime::format_rfc3339_seconds(t).to_string())
+        .unwrap_or_else(|| "never".to_owned())
+}
+
+impl fmt::Display for SmartSensor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("")?;
+        writeln!(f, "Name: {}", &self.name)?;
+        f.pad("")?;
+        writeln!(f, "Description: {}", &self.description)?;
+        f.pad("")?;
+        write!(
+            f,
+            "Current state: {} sensor, {}, last triggered {}",
+            self.sensor,
+            sensor_state(self.sensor, self.is_triggered),
+            format_triggered(self.last_triggered)
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sensor() {
+        let clock = Arc::new(ManualClock::new(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        ));
+        let mut sensor =
+            SmartSensor::new("hall", "hall motion", SensorKind::Motion).with_clock(clock.clone());
+        assert_eq!(
+            "Name: hall\nDescription: hall motion\nCurrent state: motion sensor, no motion, last triggered never",
+            sensor.to_string()
+        );
+
+        sensor.trigger();
+        clock.advance(Duration::from_secs(60));
+        assert!(sensor.is_triggered());
+        sensor.clear();
+        assert_eq!(
+            "Name: hall\nDescription: hall motion\nCurrent state: motion sensor, no motion, last triggered 2023-11-14T22:13:20Z",
+            sensor.to_string()
+        );
+        assert_eq!(
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            sensor.last_triggered()
+        );
+    }
+}
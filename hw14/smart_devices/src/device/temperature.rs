@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Единица измерения температуры
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl fmt::Display for TemperatureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemperatureUnit::Celsius => write!(f, "°C"),
+            TemperatureUnit::Fahrenheit => write!(f, "°F"),
+            TemperatureUnit::Kelvin => write!(f, "K"),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("unknown temperature unit: {0}")]
+pub struct UnknownTemperatureUnit(pub String);
+
+impl FromStr for TemperatureUnit {
+    type Err = UnknownTemperatureUnit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnit::Celsius),
+            "f" | "fahrenheit" => Ok(TemperatureUnit::Fahrenheit),
+            "k" | "kelvin" => Ok(TemperatureUnit::Kelvin),
+            _ => Err(UnknownTemperatureUnit(s.to_owned())),
+        }
+    }
+}
+
+/// Температура. Хранится в градусах Цельсия, в них же сериализуется.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Temperature(f64);
+
+impl Temperature {
+    pub fn celsius(value: f64) -> Self {
+        Self(value)
+    }
+
+    pub fn fahrenheit(value: f64) -> Self {
+        Self((value - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn kelvin(value: f64) -> Self {
+        Self(value - 273.15)
+    }
+
+    /// Температура из значения в заданных единицах
+    pub fn new(value: f64, unit: TemperatureUnit) -> Self {
+        match unit {
+            TemperatureUnit::Celsius => Self::celsius(value),
+            TemperatureUnit::Fahrenheit => Self::fahrenheit(value),
+            TemperatureUnit::Kelvin => Self::kelvin(value),
+        }
+    }
+
+    pub fn as_celsius(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_fahrenheit(self) -> f64 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+
+    pub fn as_kelvin(self) -> f64 {
+        self.0 + 273.15
+    }
+
+    /// Значение в заданных единицах
+    pub fn value_in(self, unit: TemperatureUnit) -> f64 {
+        match unit {
+            TemperatureUnit::Celsius => self.as_celsius(),
+            TemperatureUnit::Fahrenheit => self.as_fahrenheit(),
+            TemperatureUnit::Kelvin => self.as_kelvin(),
+        }
+    }
+
+    /// Форматирование в заданных единицах, например `"66.2 °F"`
+    pub fn display_in(self, unit: TemperatureUnit) -> String {
+        // Округляем, чтобы не тащить хвосты вроде 66.19999999999999 после пересчета
+        let value = (self.value_in(unit) * 100.0).round() / 100.0;
+        format!("{value} {unit}")
+    }
+
+    /// Точка росы по формуле Магнуса, `humidity` - относительная влажность в процентах
+    pub fn dew_point(self, humidity: f64) -> Self {
+        const B: f64 = 17.62;
+        const C: f64 = 243.12;
+
+        let t = self.0;
+        let gamma = (humidity.clamp(f64::MIN_POSITIVE, 100.0) / 100.0).ln() + B * t / (C + t);
+        Self(C * gamma / (B - gamma))
+    }
+}
+
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display_in(TemperatureUnit::Celsius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!((expected - actual).abs() < 1e-9, "{expected} != {actual}");
+    }
+
+    #[test]
+    fn test_conversion() {
+        let t = Temperature::celsius(100.0);
+        assert_close(212.0, t.as_fahrenheit());
+        assert_close(373.15, t.as_kelvin());
+
+        assert_close(-40.0, Temperature::fahrenheit(-40.0).as_celsius());
+        assert_close(0.0, Temperature::kelvin(273.15).as_celsius());
+        assert_close(
+            20.0,
+            Temperature::new(68.0, TemperatureUnit::Fahrenheit).as_celsius(),
+        );
+
+        assert_eq!(
+            "66.2 °F",
+            Temperature::celsius(19.0).display_in(TemperatureUnit::Fahrenheit)
+        );
+        assert_eq!("19 °C", Temperature::celsius(19.0).to_string());
+    }
+
+    #[test]
+    fn test_dew_point() {
+        let t = Temperature::celsius(25.0);
+        assert_close(25.0, t.dew_point(100.0).as_celsius());
+        assert!((t.dew_point(50.0).as_celsius() - 13.85).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_unit() {
+        assert_eq!(Ok(TemperatureUnit::Fahrenheit), "F".parse());
+        assert_eq!(Ok(TemperatureUnit::Kelvin), "kelvin".parse());
+        assert_eq!(
+            Err(UnknownTemperatureUnit("rankine".to_owned())),
+            "rankine".parse::<TemperatureUnit>()
+        );
+    }
+}
@@ -0,0 +1,714 @@
+//! Структурная разница между двумя домами и ее применение к третьему.
+//!
+//! `a.diff(&b)` перечисляет изменения, которые превращают `a` в `b`: комнаты
+//! добавлены, удалены или переименованы, девайсы добавлены, удалены, перенесены
+//! или изменены. Разница печатается построчно через `Display` и сериализуется в JSON.
+//! `apply_diff` применяет ее к другому дому и сообщает о конфликтах, а `merge`
+//! переносит на дом изменения между двумя снимками.
+
+use crate::{
+    dsl, journal,
+    location::{self, Location},
+    storage::DeviceRecord,
+    SmartHouse,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+/// Одно изменение дома. Пути - на момент применения изменения,
+/// т.е. после переименований, которые идут раньше.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    RenameRoom {
+        room: String,
+        new_name: String,
+    },
+    AddRoom {
+        room: String,
+    },
+    /// Расположение удаляется вместе с вложенными, девайсы из него
+    /// удаляются отдельными изменениями раньше
+    RemoveRoom {
+        room: String,
+    },
+    MoveDevice {
+        from: String,
+        to: String,
+        device: String,
+    },
+    /// Состояние, описание или теги девайса до и после
+    ChangeDevice {
+        room: String,
+        before: DeviceRecord,
+        after: DeviceRecord,
+    },
+    AddDevice {
+        room: String,
+        device: DeviceRecord,
+    },
+    RemoveDevice {
+        room: String,
+        device: DeviceRecord,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::RenameRoom { room, new_name } => {
+                let (parent, _) = location::split_last(room).unwrap_or_default();
+                let renamed = location::join_path(parent, new_name);
+                write!(f, "~ room {room} -> {renamed}")
+            }
+            Change::AddRoom { room } => write!(f, "+ room {room}"),
+            Change::RemoveRoom { room } => write!(f, "- room {room}"),
+            Change::MoveDevice { from, to, device } => {
+                write!(f, "> device {device}: {from} -> {to}")
+            }
+            Change::ChangeDevice {
+                room,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ device {}: {} -> {}",
+                location::join_path(room, &after.name),
+                dsl::format_device(before),
+                dsl::format_device(after)
+            ),
+            Change::AddDevice { room, device } => write!(
+                f,
+                "+ device {}: {}",
+                location::join_path(room, &device.name),
+                dsl::format_device(device)
+            ),
+            Change::RemoveDevice { room, device } => write!(
+                f,
+                "- device {}: {}",
+                location::join_path(room, &device.name),
+                dsl::format_device(device)
+            ),
+        }
+    }
+}
+
+/// Изменения в порядке применения: переименования и новые комнаты,
+/// затем девайсы и в конце удаление комнат
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HouseDiff {
+    pub changes: Vec<Change>,
+}
+
+impl HouseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter()
+    }
+}
+
+impl fmt::Display for HouseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Изменение, которое не удалось применить, и причина
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conflict {
+    pub change: Change,
+    pub reason: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.change, self.reason)
+    }
+}
+
+/// Итог применения разницы. Изменения, которые в доме уже есть,
+/// попадают в `skipped`, остальные - в `applied` или `conflicts`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub applied: Vec<Change>,
+    pub skipped: Vec<Change>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeReport {
+    /// Все изменения применились без конфликтов
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Результат применения одного изменения
+enum Outcome {
+    Applied,
+    Skipped,
+    Conflict(String),
+}
+
+impl<E: fmt::Display> From<Result<(), E>> for Outcome {
+    fn from(result: Result<(), E>) -> Self {
+        match result {
+            Ok(()) => Outcome::Applied,
+            Err(error) => Outcome::Conflict(error.to_string()),
+        }
+    }
+}
+
+/// Девайсы одинаковые, если не считать энергии, накопленной розеткой
+fn same_device(a: &DeviceRecord, b: &DeviceRecord) -> bool {
+    a.name == b.name
+        && a.description == b.description
+        && a.tags == b.tags
+        && a.state.same_as(&b.state)
+}
+
+/// Есть ли девайсы в расположении или вложенных в него
+fn has_devices(location: &Location) -> bool {
+    !location.devices.is_empty() || location.children.iter().any(has_devices)
+}
+
+/// Имена девайсов и вложенных расположений, по ним узнаем переименованную комнату
+fn contents(location: &Location) -> (BTreeSet<&str>, BTreeSet<&str>) {
+    let devices = location.devices.iter().map(|d| d.name()).collect();
+    let children = location.children.iter().map(|l| l.name.as_str()).collect();
+    (devices, children)
+}
+
+/// Путь после переименований расположений
+fn renamed(path: &str, renames: &[(String, String)]) -> String {
+    let mut path = path.to_owned();
+    for (old, new) in renames {
+        if location::is_within(&path, old) {
+            path = format!("{new}{}", &path[old.len()..]);
+        }
+    }
+    path
+}
+
+impl SmartHouse {
+    /// Изменения, которые превращают этот дом в `other`.
+    /// Комната считается переименованной, если новая комната с тем же родителем
+    /// содержит хотя бы один ее девайс или вложенное расположение.
+    pub fn diff(&self, other: &SmartHouse) -> HouseDiff {
+        let old_rooms = self.walk();
+        let new_rooms = other.walk();
+        let old_paths: HashSet<&str> = old_rooms.iter().map(|(p, _)| p.as_str()).collect();
+        let new_paths: HashSet<&str> = new_rooms.iter().map(|(p, _)| p.as_str()).collect();
+
+        let mut changes = Vec::new();
+        let mut renames = Vec::new();
+        let mut matched = HashSet::new();
+        let mut removed = Vec::new();
+        for (path, location) in &old_rooms {
+            let path = renamed(path, &renames);
+            if new_paths.contains(path.as_str()) {
+                matched.insert(path);
+                continue;
+            }
+            if removed
+                .iter()
+                .any(|r: &String| location::is_within(&path, r))
+            {
+                continue;
+            }
+
+            let (parent, _) = location::split_last(&path).unwrap_or_default();
+            let content = contents(location);
+            let candidate = new_rooms.iter().find(|(new_path, new_location)| {
+                !old_paths.contains(new_path.as_str())
+                    && !matched.contains(new_path)
+                    && location::split_last(new_path).is_some_and(|(p, _)| p == parent)
+                    && {
+                        let (devices, children) = contents(new_location);
+                        !devices.is_disjoint(&content.0) || !children.is_disjoint(&content.1)
+                    }
+            });
+            match candidate {
+                Some((new_path, new_location)) => {
+                    changes.push(Change::RenameRoom {
+                        room: path.clone(),
+                        new_name: new_location.name.clone(),
+                    });
+                    matched.insert(new_path.clone());
+                    renames.push((path, new_path.clone()));
+                }
+                None => removed.push(path),
+            }
+        }
+
+        for (path, _) in &new_rooms {
+            if !matched.contains(path) {
+                changes.push(Change::AddRoom { room: path.clone() });
+            }
+        }
+
+        // Девайсы по комнатам, старые - уже по новым путям комнат
+        let records = |rooms: &[(String, &Location)], translate: bool| {
+            let mut records = Vec::new();
+            for (path, location) in rooms {
+                let path = if translate {
+                    renamed(path, &renames)
+                } else {
+                    path.clone()
+                };
+                for device in &location.devices {
+                    let tags = location.tags.get(device.name());
+                    records.push((path.clone(), DeviceRecord::of(device.as_ref(), tags)));
+                }
+            }
+            records
+        };
+        let old_devices = records(&old_rooms, true);
+        let new_devices = records(&new_rooms, false);
+        let find = |devices: &[(String, DeviceRecord)], room: &str, name: &str| {
+            devices.iter().any(|(r, d)| r == room && d.name == name)
+        };
+
+        let gone: Vec<&(String, DeviceRecord)> = old_devices
+            .iter()
+            .filter(|(room, d)| !find(&new_devices, room, &d.name))
+            .collect();
+        let appeared: Vec<&(String, DeviceRecord)> = new_devices
+            .iter()
+            .filter(|(room, d)| !find(&old_devices, room, &d.name))
+            .collect();
+        let unique = |devices: &[&(String, DeviceRecord)], name: &str| {
+            devices.iter().filter(|(_, d)| d.name == name).count() == 1
+        };
+
+        // Девайс перенесен, если имя пропало и появилось ровно по разу
+        let mut moved = HashMap::new();
+        for (from, before) in &gone {
+            if !unique(&gone, &before.name) || !unique(&appeared, &before.name) {
+                continue;
+            }
+            if let Some((to, _)) = appeared.iter().find(|(_, d)| d.name == before.name) {
+                changes.push(Change::MoveDevice {
+                    from: from.clone(),
+                    to: to.clone(),
+                    device: before.name.clone(),
+                });
+                moved.insert(before.name.clone(), before);
+            }
+        }
+
+        for (room, after) in &new_devices {
+            let before = old_devices
+                .iter()
+                .find(|(r, d)| r == room && d.name == after.name)
+                .map(|(_, d)| d)
+                .or_else(|| moved.get(&after.name).copied());
+            if let Some(before) = before.filter(|before| !same_device(before, after)) {
+                changes.push(Change::ChangeDevice {
+                    room: room.clone(),
+                    before: before.clone(),
+                    after: after.clone(),
+                });
+            }
+        }
+
+        for (room, device) in &appeared {
+            if !moved.contains_key(&device.name) {
+                changes.push(Change::AddDevice {
+                    room: room.clone(),
+                    device: device.clone(),
+                });
+            }
+        }
+        for (room, device) in &gone {
+            if !moved.contains_key(&device.name) {
+                changes.push(Change::RemoveDevice {
+                    room: room.clone(),
+                    device: device.clone(),
+                });
+            }
+        }
+        for room in removed {
+            changes.push(Change::RemoveRoom { room });
+        }
+
+        HouseDiff { changes }
+    }
+
+    /// Применяем разницу по порядку. Изменение, которое противоречит дому
+    /// (например, девайс здесь тоже изменили), пропускается и попадает в конфликты.
+    /// Все примененные изменения отменяются одной операцией журнала.
+    pub fn apply_diff(&mut self, diff: &HouseDiff) -> MergeReport {
+        self.journaled("apply diff".to_owned(), |house| {
+            let mut report = MergeReport::default();
+            for change in &diff.changes {
+                match house.apply_change(change) {
+                    Outcome::Applied => report.applied.push(change.clone()),
+                    Outcome::Skipped => report.skipped.push(change.clone()),
+                    Outcome::Conflict(reason) => report.conflicts.push(Conflict {
+                        change: change.clone(),
+                        reason,
+                    }),
+                }
+            }
+            report
+        })
+    }
+
+    /// Трехстороннее слияние: переносим на этот дом изменения,
+    /// которые превратили `base` в `other`
+    pub fn merge(&mut self, base: &SmartHouse, other: &SmartHouse) -> MergeReport {
+        self.apply_diff(&base.diff(other))
+    }
+
+    /// Девайс комнаты в виде записи вместе с тегами
+    fn device_record(&self, room: &str, device: &str) -> Option<DeviceRecord> {
+        let location = self.room(room).ok()?;
+        let device = location.devices.iter().find(|d| d.name() == device)?;
+        Some(DeviceRecord::of(
+            device.as_ref(),
+            location.tags.get(device.name()),
+        ))
+    }
+
+    fn apply_change(&mut self, change: &Change) -> Outcome {
+        match change {
+            Change::RenameRoom { room, new_name } => {
+                let (parent, _) = location::split_last(room).unwrap_or_default();
+                if !self.has_room(room) && self.has_room(&location::join_path(parent, new_name)) {
+                    return Outcome::Skipped;
+                }
+                self.rename_room(room, new_name).into()
+            }
+            Change::AddRoom { room } => {
+                if self.has_room(room) {
+                    return Outcome::Skipped;
+                }
+                self.add_room(room).into()
+            }
+            Change::RemoveRoom { room } => match self.room(room) {
+                Err(_) => Outcome::Skipped,
+                Ok(location) if has_devices(location) => {
+                    Outcome::Conflict(format!("room \"{room}\" is not empty"))
+                }
+                Ok(_) => self.delete_room(room).into(),
+            },
+            Change::MoveDevice { from, to, device } => {
+                if self.device(from, device).is_none() && self.device(to, device).is_some() {
+                    return Outcome::Skipped;
+                }
+                self.move_device(from, to, device).into()
+            }
+            Change::ChangeDevice {
+                room,
+                before,
+                after,
+            } => match self.device_record(room, &after.name) {
+                None => self.room_devices_mut(room, &after.name).map(|_| ()).into(),
+                Some(current) if same_device(&current, after) => Outcome::Skipped,
+                Some(current) if !same_device(&current, before) => Outcome::Conflict(format!(
+                    "device \"{}\" was changed here too",
+                    location::join_path(room, &after.name)
+                )),
+                Some(current) => self.replace_device(room, &current, after).into(),
+            },
+            Change::AddDevice { room, device } => match self.device_record(room, &device.name) {
+                Some(current) if same_device(&current, device) => Outcome::Skipped,
+                _ => device.clone().add_to(self, room).into(),
+            },
+            Change::RemoveDevice { room, device } => match self.device_record(room, &device.name) {
+                None => Outcome::Skipped,
+                Some(current) if !same_device(&current, device) => Outcome::Conflict(format!(
+                    "device \"{}\" was changed here",
+                    location::join_path(room, &device.name)
+                )),
+                Some(_) => self.delete_device(room, &device.name).map(|_| ()).into(),
+            },
+        }
+    }
+
+    /// Приводим девайс к записи `after`. Состояние и описание меняются на месте,
+    /// а девайс другого вида заменяется новым.
+    fn replace_device(
+        &mut self,
+        room: &str,
+        current: &DeviceRecord,
+        after: &DeviceRecord,
+    ) -> crate::Result<()> {
+        let path = location::join_path(room, &after.name);
+        let same_kind =
+            std::mem::discriminant(&current.state) == std::mem::discriminant(&after.state);
+        if same_kind {
+            self.change_device(&path, |device| journal::set_state(device, &after.state))?;
+            self.describe_device(room, &after.name, &after.description)?;
+            for tag in current.tags.difference(&after.tags) {
+                self.untag_device(room, &after.name, tag)?;
+            }
+            for tag in after.tags.difference(&current.tags) {
+                self.tag_device(room, &after.name, tag)?;
+            }
+            return Ok(());
+        }
+
+        let position = self
+            .devices(room)
+            .position(|name| name == after.name)
+            .unwrap_or_default();
+        self.delete_device(room, &after.name)?;
+        after.clone().add_to(self, room)?;
+        self.move_device_to(room, &after.name, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::ManualClock, journal::Operation};
+    use std::{sync::Arc, time::SystemTime};
+
+    /// Дом из описания с остановленными часами, чтобы розетки не копили энергию
+    fn house(text: &str) -> SmartHouse {
+        let mut house = dsl::parse_house(text).unwrap();
+        house.set_clock(Arc::new(ManualClock::new(SystemTime::UNIX_EPOCH)));
+        house
+    }
+
+    const BASE: &str = r#"
+room kitchen {
+    socket kettle on 2200 W
+    thermometer t1 21 °C
+}
+room hall {
+    light lamp on 60 %
+    room closet { sensor door contact }
+}
+room garage { lock gate locked }
+"#;
+
+    #[test]
+    fn test_diff() {
+        let base = house(BASE);
+        let other = house(
+            r#"
+room kitchen {
+    socket kettle off 0 W
+    light lamp on 60 % tags main
+}
+room lobby {
+    room closet { sensor door contact }
+}
+room office { thermometer t1 21 °C; blinds b 30 % }
+"#,
+        );
+
+        let diff = base.diff(&other);
+        assert_eq!(
+            "~ room hall -> lobby
++ room office
+> device t1: kitchen -> office
+> device lamp: lobby -> kitchen
+~ device kitchen/kettle: socket kettle on 2200 W -> socket kettle off 0 W
+~ device kitchen/lamp: light lamp on 60 % -> light lamp on 60 % tags main
++ device office/b: blinds b 30 %
+- device garage/gate: lock gate locked
+- room garage
+",
+            diff.to_string()
+        );
+        assert!(other.diff(&other).is_empty());
+
+        let json = serde_json::to_string(&diff).unwrap();
+        assert!(json.contains(r#""change":"rename_room""#), "{json}");
+        assert_eq!(diff, serde_json::from_str(&json).unwrap());
+
+        let mut copy = house(BASE);
+        let report = copy.apply_diff(&diff);
+        assert!(report.is_clean(), "{:?}", report.conflicts);
+        assert_eq!(diff.changes.len(), report.applied.len());
+        assert!(copy.diff(&other).is_empty(), "{}", copy.diff(&other));
+
+        // Повторное применение ничего не меняет, а отмена возвращает исходный дом
+        assert_eq!(diff.changes.len(), copy.apply_diff(&diff).skipped.len());
+        copy.undo().unwrap();
+        assert!(copy.diff(&base).is_empty(), "{}", copy.diff(&base));
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let base = house(BASE);
+        let mut theirs = house(BASE);
+        theirs.delete_device("garage", "gate").unwrap();
+        theirs.delete_room("garage").unwrap();
+        theirs.socket_mut("kitchen", "kettle").unwrap().turn_off();
+        theirs.add_room("attic").unwrap();
+
+        let mut ours = house(BASE);
+        ours.update_device("garage", "gate", |d| {
+            d.as_any_mut()
+                .downcast_mut::<crate::device::SmartLock>()
+                .unwrap()
+                .unlock()
+                .unwrap();
+        })
+        .unwrap();
+        ours.add_room("attic").unwrap();
+
+        let report = ours.merge(&base, &theirs);
+        assert_eq!(
+            vec![
+                "- device garage/gate: lock gate locked: device \"garage/gate\" was changed here",
+                "- room garage: room \"garage\" is not empty",
+            ],
+            report
+                .conflicts
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Change::AddRoom {
+                room: "attic".to_owned()
+            }],
+            report.skipped
+        );
+        assert!(!ours.socket("kitchen", "kettle").unwrap().is_on());
+        assert!(ours.has_room("garage"));
+    }
+
+    #[test]
+    fn test_nested_renames() {
+        let base = house(BASE);
+        let other = house(
+            r#"
+room kitchen {
+    socket kettle on 2200 W
+    thermometer t1 21 °C
+}
+room lobby {
+    light lamp on 60 %
+    room wardrobe { sensor door contact }
+}
+room garage { lock gate locked }
+"#,
+        );
+
+        let diff = base.diff(&other);
+        assert_eq!(
+            "~ room hall -> lobby\n~ room lobby/closet -> lobby/wardrobe\n",
+            diff.to_string()
+        );
+
+        let mut copy = house(BASE);
+        assert!(copy.apply_diff(&diff).is_clean());
+        assert!(copy.diff(&other).is_empty(), "{}", copy.diff(&other));
+        assert!(copy.device("lobby/wardrobe", "door").is_some());
+    }
+
+    #[test]
+    fn test_same_names_in_rooms() {
+        let rooms = r#"
+room kitchen { light lamp on 60 % }
+room hall { light lamp off 10 %; socket s off 0 W }
+room garage {}
+"#;
+        let base = house(rooms);
+
+        // Одна из двух ламп перенесена, другая изменена на месте
+        let other = house(
+            r#"
+room kitchen { light lamp off 60 % }
+room hall { socket s off 0 W }
+room garage { light lamp off 10 % }
+"#,
+        );
+        let diff = base.diff(&other);
+        assert_eq!(
+            "> device lamp: hall -> garage
+~ device kitchen/lamp: light lamp on 60 % -> light lamp off 60 %
+",
+            diff.to_string()
+        );
+        let mut copy = house(rooms);
+        assert!(copy.apply_diff(&diff).is_clean());
+        assert!(copy.diff(&other).is_empty(), "{}", copy.diff(&other));
+
+        // Пропали обе лампы, а появилась одна: непонятно, какую перенесли
+        let other = house(
+            r#"
+room kitchen {}
+room hall { socket s off 0 W }
+room garage { light lamp off 10 % }
+"#,
+        );
+        let diff = base.diff(&other);
+        assert_eq!(
+            "+ device garage/lamp: light lamp off 10 %
+- device kitchen/lamp: light lamp on 60 %
+- device hall/lamp: light lamp off 10 %
+",
+            diff.to_string()
+        );
+        let mut copy = house(rooms);
+        assert!(copy.apply_diff(&diff).is_clean());
+        assert!(copy.diff(&other).is_empty(), "{}", copy.diff(&other));
+    }
+
+    #[test]
+    fn test_move_with_changes() {
+        let base = house(BASE);
+        let other = house(
+            r#"
+room kitchen {
+    socket kettle on 2200 W description "new kettle"
+    thermometer t1 21 °C
+    lock gate unlocked
+}
+room hall {
+    light lamp on 60 %
+    room closet { sensor door contact }
+}
+room garage {}
+"#,
+        );
+
+        let diff = base.diff(&other);
+        assert_eq!(
+            "> device gate: garage -> kitchen
+~ device kitchen/kettle: socket kettle on 2200 W -> socket kettle on 2200 W description \"new kettle\"
+~ device kitchen/gate: lock gate locked -> lock gate unlocked
+",
+            diff.to_string()
+        );
+
+        let mut copy = house(BASE);
+        copy.journal_mut().clear();
+        assert!(copy.apply_diff(&diff).is_clean());
+        assert!(copy.diff(&other).is_empty(), "{}", copy.diff(&other));
+
+        // Описание меняется на месте: девайс не пересоздается и не уезжает в конец комнаты
+        assert_eq!(
+            vec!["kettle", "t1", "gate"],
+            copy.devices("kitchen").collect::<Vec<_>>()
+        );
+        let Some(Operation::Batch { operations, .. }) = copy.journal().operations().last() else {
+            panic!("expected a batch");
+        };
+        assert!(operations
+            .iter()
+            .any(|op| matches!(op, Operation::DescribeDevice { .. })));
+        assert!(!operations
+            .iter()
+            .any(|op| matches!(op, Operation::DeleteDevice { .. })));
+
+        copy.undo().unwrap();
+        assert!(copy.diff(&base).is_empty(), "{}", copy.diff(&base));
+    }
+}
@@ -0,0 +1,902 @@
+//! Текстовый формат описания дома: комнаты, вложенные расположения и девайсы.
+//!
+//! ```text
+//! # комментарий
+//! house "my smart house"
+//!
+//! room kitchen {
+//!     socket kettle on 2200 W description "Electric kettle" tags heating
+//!     thermometer t1 21.5 °C humidity 40 %
+//!     room pantry { light lamp off 60 %; sensor door contact }
+//! }
+//! room hall {
+//!     lock "front door" locked
+//!     blinds curtains 50 %
+//! }
+//! ```
+//!
+//! Девайсы:
+//! - `socket NAME [on|off] POWER W [voltage V V]`
+//! - `thermometer NAME TEMPERATURE °C|°F|K [humidity H %]`
+//! - `light NAME [on|off] BRIGHTNESS % [color "#rrggbb"]`
+//! - `lock NAME locked|unlocked [jammed]`
+//! - `sensor NAME motion|contact [triggered]`
+//! - `blinds NAME POSITION %`
+//!
+//! За любым девайсом могут идти `description "..."` и `tags a, b`.
+//! Инструкции разделяются переводом строки или `;`, единицы пишутся слитно
+//! с числом или через пробел. Имена с пробелами и знаками `{};,#"` берутся
+//! в двойные кавычки, внутри кавычек `\"`, `\\`, `\n` и `\t`.
+//! Термометр записывается в тех единицах, в которых объявлен.
+//! Счетчики энергии розеток и время срабатывания датчиков в описание не входят.
+
+use crate::{
+    device::{
+        light::Color,
+        sensor::SensorKind,
+        temperature::{Temperature, TemperatureUnit},
+        DeviceState, DEFAULT_VOLTAGE,
+    },
+    location::{self, Location},
+    quoting,
+    storage::DeviceRecord,
+    tags, SmartHouse,
+};
+use std::{collections::BTreeSet, fmt::Write, str::FromStr};
+use thiserror::Error;
+
+/// Имя дома, если в описании нет `house`
+pub const DEFAULT_HOUSE_NAME: &str = "smart house";
+
+/// Ошибка в описании дома с позицией, с которой она началась
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("line {line}, column {column}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// Разбираем описание дома
+pub fn parse_house(text: &str) -> Result<SmartHouse> {
+    let tokens = tokenize(text)?;
+    Parser::new(tokens).house()
+}
+
+/// Записываем комнаты и девайсы дома в текстовом формате.
+/// `parse_house` читает результат обратно в такой же дом.
+pub fn format_house(house: &SmartHouse) -> String {
+    let mut out = format!("house {}\n", quote(house.name()));
+    for location in &house.locations {
+        out.push('\n');
+        format_location(&mut out, location, 0);
+    }
+    out
+}
+
+impl FromStr for SmartHouse {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_house(s)
+    }
+}
+
+fn format_location(out: &mut String, location: &Location, depth: usize) {
+    let indent = "    ".repeat(depth);
+    if location.devices.is_empty() && location.children.is_empty() {
+        let _ = writeln!(out, "{indent}room {} {{}}", quote(&location.name));
+        return;
+    }
+
+    let _ = writeln!(out, "{indent}room {} {{", quote(&location.name));
+    for device in &location.devices {
+        let record = DeviceRecord::of(device.as_ref(), location.tags.get(device.name()));
+        let _ = writeln!(out, "{indent}    {}", format_device(&record));
+    }
+    for child in &location.children {
+        format_location(out, child, depth + 1);
+    }
+    let _ = writeln!(out, "{indent}}}");
+}
+
+/// Девайс одной инструкцией, например `socket kettle on 2200 W`
+pub(crate) fn format_device(device: &DeviceRecord) -> String {
+    let switch = |is_on: bool| if is_on { "on" } else { "off" };
+    let name = quote(&device.name);
+    let mut out = match &device.state {
+        DeviceState::Socket {
+            is_on,
+            current_power,
+            voltage,
+            ..
+        } => {
+            let mut out = format!("socket {name} {} {current_power} W", switch(*is_on));
+            if *voltage != DEFAULT_VOLTAGE {
+                let _ = write!(out, " voltage {voltage} V");
+            }
+            out
+        }
+        DeviceState::Thermometer {
+            current_temperature,
+            humidity,
+        } => {
+            // Печатаем в тех же единицах, в которых термометр объявлен
+            let mut out = format!(
+                "thermometer {name} {}",
+                current_temperature.display_in(device.unit)
+            );
+            if let Some(humidity) = humidity {
+                let _ = write!(out, " humidity {humidity} %");
+            }
+            out
+        }
+        DeviceState::Light {
+            is_on,
+            brightness,
+            color,
+        } => {
+            let mut out = format!("light {name} {} {brightness} %", switch(*is_on));
+            if let Some(color) = color {
+                let _ = write!(out, " color \"{color}\"");
+            }
+            out
+        }
+        DeviceState::Lock {
+            is_locked,
+            is_jammed,
+        } => {
+            let state = if *is_locked { "locked" } else { "unlocked" };
+            let mut out = format!("lock {name} {state}");
+            if *is_jammed {
+                out.push_str(" jammed");
+            }
+            out
+        }
+        DeviceState::Sensor {
+            sensor,
+            is_triggered,
+            ..
+        } => {
+            let mut out = format!("sensor {name} {sensor}");
+            if *is_triggered {
+                out.push_str(" triggered");
+            }
+            out
+        }
+        DeviceState::Blinds { position } => format!("blinds {name} {position} %"),
+    };
+
+    if !device.description.is_empty() {
+        let _ = write!(out, " description {}", quote(&device.description));
+    }
+    if !device.tags.is_empty() {
+        let tags: Vec<String> = device.tags.iter().map(|t| quote(t)).collect();
+        let _ = write!(out, " tags {}", tags.join(", "));
+    }
+    out
+}
+
+/// Имя в кавычках, если без них оно не прочитается обратно
+fn quote(name: &str) -> String {
+    quoting::quote(name, QUOTED_SYMBOLS)
+}
+
+/// Знаки, из-за которых имя берется в кавычки
+const QUOTED_SYMBOLS: &str = "{},;";
+
+/// Символы, которые сами по себе являются словами
+const SYMBOLS: &str = "{},";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Word,
+    Quoted,
+    Symbol,
+    /// Конец инструкции: перевод строки или `;`
+    End,
+    /// Конец текста
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    /// Как токен показывается в сообщениях об ошибках
+    fn describe(&self) -> String {
+        match self.kind {
+            TokenKind::End if self.text == ";" => "\";\"".to_owned(),
+            TokenKind::End => "end of line".to_owned(),
+            TokenKind::Eof => "end of text".to_owned(),
+            _ => format!("\"{}\"", self.text),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+    // Слово копится вместе с позицией его начала
+    let mut word: Option<(String, usize, usize)> = None;
+
+    let flush = |word: &mut Option<(String, usize, usize)>, tokens: &mut Vec<Token>| {
+        if let Some((text, line, column)) = word.take() {
+            tokens.push(Token {
+                kind: TokenKind::Word,
+                text,
+                line,
+                column,
+            });
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        let (start_line, start_column) = (line, column);
+        column += 1;
+        match c {
+            '#' if word.is_none() => while chars.next_if(|&c| c != '\n').is_some() {},
+            '"' => {
+                flush(&mut word, &mut tokens);
+                let Some((quoted, count)) = quoting::unquote(&mut chars) else {
+                    return Err(ParseError {
+                        line: start_line,
+                        column: start_column,
+                        message: "unterminated quoted name".to_owned(),
+                    });
+                };
+                column += count;
+                tokens.push(Token {
+                    kind: TokenKind::Quoted,
+                    text: quoted,
+                    line: start_line,
+                    column: start_column,
+                });
+            }
+            '\n' | ';' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(Token {
+                    kind: TokenKind::End,
+                    text: c.to_string(),
+                    line: start_line,
+                    column: start_column,
+                });
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                }
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut tokens),
+            c if SYMBOLS.contains(c) => {
+                flush(&mut word, &mut tokens);
+                tokens.push(Token {
+                    kind: TokenKind::Symbol,
+                    text: c.to_string(),
+                    line: start_line,
+                    column: start_column,
+                });
+            }
+            c => match &mut word {
+                Some((text, ..)) => text.push(c),
+                None => word = Some((c.to_string(), start_line, start_column)),
+            },
+        }
+    }
+    flush(&mut word, &mut tokens);
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        text: String::new(),
+        line,
+        column,
+    });
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    house: SmartHouse,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            house: SmartHouse::new_empty(DEFAULT_HOUSE_NAME),
+        }
+    }
+
+    fn error_at<T>(token: &Token, message: String) -> Result<T> {
+        Err(ParseError {
+            line: token.line,
+            column: token.column,
+            message,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        // Последний токен всегда `Eof`, дальше него не идем
+        &self.tokens[self.position.min(self.tokens.len() - 1)]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Следующее слово - ключевое слово `keyword`
+    fn is_keyword(&self, keyword: &str) -> bool {
+        let token = self.peek();
+        token.kind == TokenKind::Word && token.text.eq_ignore_ascii_case(keyword)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        let token = self.peek();
+        token.kind == TokenKind::Symbol && token.text == symbol
+    }
+
+    /// Инструкция закончилась: дальше конец строки, `}` или конец текста
+    fn at_statement_end(&self) -> bool {
+        matches!(self.peek().kind, TokenKind::End | TokenKind::Eof) || self.is_symbol("}")
+    }
+
+    fn skip_ends(&mut self) {
+        while self.peek().kind == TokenKind::End {
+            self.advance();
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        let token = self.advance();
+        if token.kind != TokenKind::Symbol || token.text != symbol {
+            return Self::error_at(
+                &token,
+                format!("expected \"{symbol}\", found {}", token.describe()),
+            );
+        }
+        Ok(())
+    }
+
+    fn expect_statement_end(&mut self) -> Result<()> {
+        if !self.at_statement_end() {
+            let token = self.peek();
+            return Self::error_at(token, format!("unexpected {}", token.describe()));
+        }
+        Ok(())
+    }
+
+    /// Имя - слово или строка в кавычках
+    fn name(&mut self) -> Result<Token> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::Word | TokenKind::Quoted => Ok(token),
+            _ => Self::error_at(&token, format!("expected name, found {}", token.describe())),
+        }
+    }
+
+    fn house(mut self) -> Result<SmartHouse> {
+        self.skip_ends();
+        if self.is_keyword("house") {
+            self.advance();
+            let name = self.name()?;
+            self.house = SmartHouse::new_empty(&name.text);
+            self.expect_statement_end()?;
+        }
+
+        loop {
+            self.skip_ends();
+            let token = self.advance();
+            match token.kind {
+                TokenKind::Eof => break,
+                TokenKind::Word if token.text.eq_ignore_ascii_case("room") => self.room("")?,
+                TokenKind::Word
+                    if DEVICE_KINDS.contains(&token.text.to_ascii_lowercase().as_str()) =>
+                {
+                    return Self::error_at(&token, "device outside of a room".to_owned())
+                }
+                _ => {
+                    return Self::error_at(
+                        &token,
+                        format!("expected \"room\", found {}", token.describe()),
+                    )
+                }
+            }
+        }
+
+        // Описание - не изменение, отменять его нечего
+        self.house.journal_mut().clear();
+        Ok(self.house)
+    }
+
+    /// Комната после слова `room` со всем содержимым
+    fn room(&mut self, parent: &str) -> Result<()> {
+        let name = self.name()?;
+        if name.text.contains('/') || name.text.trim().is_empty() {
+            return Self::error_at(&name, format!("invalid room name \"{}\"", name.text));
+        }
+        let path = location::join_path(parent, &name.text);
+        if let Err(error) = self.house.add_room(&path) {
+            return Self::error_at(&name, error.to_string());
+        }
+
+        self.expect_symbol("{")?;
+        loop {
+            self.skip_ends();
+            if self.is_symbol("}") {
+                self.advance();
+                return self.expect_statement_end();
+            }
+
+            let token = self.advance();
+            let kind = token.text.to_ascii_lowercase();
+            match token.kind {
+                TokenKind::Word if kind == "room" => self.room(&path)?,
+                TokenKind::Word if DEVICE_KINDS.contains(&kind.as_str()) => {
+                    self.device(&kind, &path)?
+                }
+                TokenKind::Eof => {
+                    return Self::error_at(&token, format!("room \"{path}\" is not closed"))
+                }
+                _ => {
+                    return Self::error_at(
+                        &token,
+                        format!("expected \"room\" or device, found {}", token.describe()),
+                    )
+                }
+            }
+        }
+    }
+
+    /// Девайс после слова с его видом
+    fn device(&mut self, kind: &str, room: &str) -> Result<()> {
+        let name = self.name()?;
+        let mut unit = TemperatureUnit::default();
+        let state = match kind {
+            "socket" => DeviceState::Socket {
+                is_on: self.switch()?,
+                current_power: self.non_negative(&["w"])?,
+                voltage: DEFAULT_VOLTAGE,
+                energy_kwh: 0.0,
+            },
+            "thermometer" => {
+                let (temperature, declared) = self.temperature()?;
+                unit = declared;
+                DeviceState::Thermometer {
+                    current_temperature: temperature,
+                    humidity: None,
+                }
+            }
+            "light" => DeviceState::Light {
+                is_on: self.switch()?,
+                brightness: self.percent()?,
+                color: None,
+            },
+            "lock" => DeviceState::Lock {
+                is_locked: self.one_of(&["unlocked", "locked"])? == 1,
+                is_jammed: false,
+            },
+            "sensor" => DeviceState::Sensor {
+                sensor: match self.one_of(&["motion", "contact"])? {
+                    0 => SensorKind::Motion,
+                    _ => SensorKind::Contact,
+                },
+                is_triggered: false,
+                last_triggered: None,
+            },
+            _ => DeviceState::Blinds {
+                position: self.percent()?,
+            },
+        };
+
+        let mut device = DeviceRecord {
+            name: name.text.clone(),
+            description: String::new(),
+            tags: BTreeSet::new(),
+            unit,
+            state,
+        };
+        while !self.at_statement_end() {
+            self.option(&mut device)?;
+        }
+
+        if let Err(error) = device.add_to(&mut self.house, room) {
+            return Self::error_at(&name, error.to_string());
+        }
+        Ok(())
+    }
+
+    /// Необязательная часть инструкции девайса
+    fn option(&mut self, device: &mut DeviceRecord) -> Result<()> {
+        let token = self.advance();
+        let keyword = token.text.to_ascii_lowercase();
+        if token.kind != TokenKind::Word {
+            return Self::error_at(&token, format!("unexpected {}", token.describe()));
+        }
+
+        match (keyword.as_str(), &mut device.state) {
+            ("description", _) => device.description = self.name()?.text,
+            ("tags", _) => loop {
+                let tag = self.name()?;
+                if let Err(error) = tags::validate_tag(&tag.text) {
+                    return Self::error_at(&tag, error.to_string());
+                }
+                device.tags.insert(tag.text);
+                if !self.is_symbol(",") {
+                    break;
+                }
+                self.advance();
+            },
+            ("voltage", DeviceState::Socket { voltage, .. }) => {
+                *voltage = self.non_negative(&["v"])?
+            }
+            ("humidity", DeviceState::Thermometer { humidity, .. }) => {
+                let token = self.peek().clone();
+                let value = self.quantity(&["%"])?;
+                if !(0.0..=100.0).contains(&value) {
+                    return Self::error_at(
+                        &token,
+                        format!("expected humidity from 0 to 100 %, found {value}"),
+                    );
+                }
+                *humidity = Some(value)
+            }
+            ("color", DeviceState::Light { color, .. }) => {
+                let value = self.name()?;
+                match value.text.parse::<Color>() {
+                    Ok(value) => *color = Some(value),
+                    Err(error) => return Self::error_at(&value, error.to_string()),
+                }
+            }
+            ("jammed", DeviceState::Lock { is_jammed, .. }) => *is_jammed = true,
+            ("triggered", DeviceState::Sensor { is_triggered, .. }) => *is_triggered = true,
+            _ => return Self::error_at(&token, format!("unexpected {}", token.describe())),
+        }
+        Ok(())
+    }
+
+    /// Необязательные `on` или `off`, по умолчанию выключено
+    fn switch(&mut self) -> Result<bool> {
+        if self.is_keyword("on") || self.is_keyword("off") {
+            return Ok(self.advance().text.eq_ignore_ascii_case("on"));
+        }
+        Ok(false)
+    }
+
+    /// Одно из слов `words`, возвращаем его номер
+    fn one_of(&mut self, words: &[&str]) -> Result<usize> {
+        let token = self.advance();
+        if token.kind == TokenKind::Word {
+            if let Some(index) = words
+                .iter()
+                .position(|w| token.text.eq_ignore_ascii_case(w))
+            {
+                return Ok(index);
+            }
+        }
+
+        let expected: Vec<String> = words.iter().map(|w| format!("\"{w}\"")).collect();
+        Self::error_at(
+            &token,
+            format!(
+                "expected {}, found {}",
+                expected.join(" or "),
+                token.describe()
+            ),
+        )
+    }
+
+    /// Число с единицей, слитно (`220W`) или через пробел.
+    /// Единица необязательна, без нее возвращаем пустую строку.
+    fn number_with_unit(&mut self, is_unit: impl Fn(&str) -> bool) -> Result<(f64, Token, String)> {
+        let token = self.advance();
+        let split = token
+            .text
+            .find(|c: char| !(c.is_ascii_digit() || "+-.".contains(c)))
+            .unwrap_or(token.text.len());
+        let (number, unit) = token.text.split_at(split);
+        let value = match (token.kind, number.parse::<f64>()) {
+            (TokenKind::Word, Ok(value)) if value.is_finite() => value,
+            _ => {
+                return Self::error_at(
+                    &token,
+                    format!("expected number, found {}", token.describe()),
+                )
+            }
+        };
+
+        let mut unit = unit.to_owned();
+        if unit.is_empty() && self.peek().kind == TokenKind::Word && is_unit(&self.peek().text) {
+            unit = self.advance().text;
+        }
+        Ok((value, token, unit))
+    }
+
+    fn quantity(&mut self, units: &[&str]) -> Result<f64> {
+        let is_unit = |unit: &str| units.iter().any(|u| unit.eq_ignore_ascii_case(u));
+        let (value, token, unit) = self.number_with_unit(is_unit)?;
+        if !unit.is_empty() && !is_unit(&unit) {
+            return Self::error_at(
+                &token,
+                format!(
+                    "expected value in {}, found \"{unit}\"",
+                    units[0].to_uppercase()
+                ),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Неотрицательная величина: мощность, напряжение
+    fn non_negative(&mut self, units: &[&str]) -> Result<f64> {
+        let token = self.peek().clone();
+        let value = self.quantity(units)?;
+        if value < 0.0 {
+            return Self::error_at(
+                &token,
+                format!("expected non-negative value, found {value}"),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Проценты от 0 до 100
+    fn percent(&mut self) -> Result<u8> {
+        let token = self.peek().clone();
+        let value = self.quantity(&["%"])?;
+        if !(0.0..=100.0).contains(&value) || value.fract() != 0.0 {
+            return Self::error_at(
+                &token,
+                format!("expected whole percent from 0 to 100, found {value}"),
+            );
+        }
+        Ok(value as u8)
+    }
+
+    /// Температура, по умолчанию в °C, не ниже абсолютного нуля, и единицы, в которых она записана
+    fn temperature(&mut self) -> Result<(Temperature, TemperatureUnit)> {
+        let is_unit = |unit: &str| {
+            unit.trim_start_matches('°')
+                .parse::<TemperatureUnit>()
+                .is_ok()
+        };
+        let (value, token, unit) = self.number_with_unit(is_unit)?;
+        let unit = match unit.trim_start_matches('°') {
+            "" => TemperatureUnit::Celsius,
+            unit => match unit.parse::<TemperatureUnit>() {
+                Ok(unit) => unit,
+                Err(error) => return Self::error_at(&token, error.to_string()),
+            },
+        };
+
+        let temperature = Temperature::new(value, unit);
+        // Сравниваем с округлением, чтобы -459.67 °F не оказались ниже нуля после пересчета
+        if (temperature.as_kelvin() * 100.0).round() < 0.0 {
+            return Self::error_at(
+                &token,
+                format!("temperature {value} {unit} is below absolute zero"),
+            );
+        }
+        Ok((temperature, unit))
+    }
+}
+
+/// Слова, с которых начинаются инструкции девайсов
+const DEVICE_KINDS: [&str; 6] = ["socket", "thermometer", "light", "lock", "sensor", "blinds"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r##"
+# кухня
+house "my house"
+
+room kitchen {
+    socket kettle on 2200W description "Electric \"fast\" kettle" tags heating, main_load
+    thermometer t1 70 °F humidity 40%; room pantry { light lamp 60 % color "#ff8800" description "line one\nline\ttwo" }
+}
+room hall {
+    lock "front door" locked jammed
+    sensor door contact triggered
+    blinds curtains 50 %
+    socket fan off 15 W voltage 110 V
+}
+room empty {}
+"##;
+
+        let house = parse_house(text).unwrap();
+        assert_eq!("my house", house.name());
+        assert_eq!(
+            vec!["kitchen", "kitchen/pantry", "hall", "empty"],
+            house.locations().collect::<Vec<_>>()
+        );
+        assert!(!house.journal().can_undo());
+
+        let kettle = house.socket("kitchen", "kettle").unwrap();
+        assert!(kettle.is_on());
+        assert_eq!(2200.0, kettle.current_power());
+        assert_eq!("Electric \"fast\" kettle", kettle.description());
+        assert_eq!(
+            vec!["heating", "main_load"],
+            house
+                .device_tags("kitchen", "kettle")
+                .unwrap()
+                .collect::<Vec<_>>()
+        );
+        let lamp = house.device("kitchen/pantry", "lamp").unwrap();
+        assert_eq!("line one\nline\ttwo", lamp.description());
+        let t1 = house.thermometer("kitchen", "t1").unwrap();
+        assert!((t1.current_temperature() - 21.1).abs() < 0.1);
+
+        let formatted = format_house(&house);
+        assert!(formatted
+            .contains("    room pantry {\n        light lamp off 60 % color \"#ff8800\" description \"line one\\nline\\ttwo\"\n    }"));
+        assert!(formatted.contains("room empty {}"));
+        assert!(formatted.contains("socket fan off 15 W voltage 110 V"));
+        assert!(formatted.contains("thermometer t1 70 °F humidity 40 %"));
+
+        let parsed: SmartHouse = formatted.parse().unwrap();
+        assert_eq!(formatted, format_house(&parsed));
+    }
+
+    #[test]
+    fn test_canonical_text() {
+        // Текст в том виде, в каком его пишет `format_house`, возвращается как есть
+        let text = r##"house "my house"
+
+room kitchen {
+    socket kettle on 2200 W voltage 110 V description "Electric kettle" tags heating, main_load
+    thermometer t1 70 °F humidity 40 %
+    thermometer t2 21.5 °C
+    thermometer freezer 255.37 K
+    room pantry {
+        light lamp off 60 % color "#ff8800"
+        sensor door contact triggered
+    }
+}
+
+room hall {
+    lock "front door" locked jammed
+    blinds curtains 50 %
+}
+
+room empty {}
+"##;
+        assert_eq!(text, format_house(&parse_house(text).unwrap()));
+    }
+
+    #[test]
+    fn test_comments_and_separators() {
+        let text = "# заголовок\nhouse home # имя\n\nroom a { socket s1 on 1 W; socket s2 off 2 W # конец\n  light \"#1\" 10 %;; }\n# room b {}\n";
+        let house = parse_house(text).unwrap();
+        assert_eq!("home", house.name());
+        assert_eq!(vec!["a"], house.locations().collect::<Vec<_>>());
+        assert_eq!(
+            vec!["s1", "s2", "#1"],
+            house.devices("a").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_quoted_and_keyword_names() {
+        let text = r#"house "a#b"
+
+room "{x}" {
+    socket on off 1 W
+    light socket on 10 % tags tags
+    lock description locked description description
+    sensor "a;b" motion
+    blinds "c,d # e" 0 %
+    room room {}
+}
+"#;
+        let house = parse_house(text).unwrap();
+        assert_eq!("a#b", house.name());
+        assert_eq!(
+            vec!["on", "socket", "description", "a;b", "c,d # e"],
+            house.devices("{x}").collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "description",
+            house.device("{x}", "description").unwrap().description()
+        );
+        assert!(house.has_room("{x}/room"));
+        assert_eq!(text, format_house(&house));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| {
+            let error = parse_house(text).err().unwrap();
+            (error.line, error.column, error.message)
+        };
+
+        assert_eq!(
+            (2, 20, "expected number, found \"hot\"".to_owned()),
+            error("room kitchen {\n    thermometer t1 hot\n}")
+        );
+        assert_eq!(
+            (1, 1, "device outside of a room".to_owned()),
+            error("socket s1 on 10 W")
+        );
+        assert_eq!(
+            (2, 1, "room \"kitchen\" is not closed".to_owned()),
+            error("room kitchen {\n")
+        );
+        assert_eq!(
+            (3, 10, "unterminated quoted name".to_owned()),
+            error("room a {\n}\nroom b { \"c")
+        );
+        assert_eq!(
+            (1, 23, "expected value in W, found \"V\"".to_owned()),
+            error("room a { socket s1 on 10V }")
+        );
+        assert_eq!(
+            (1, 26, "unexpected \"humidity\"".to_owned()),
+            error("room a { socket s1 on 10 humidity 5 % }")
+        );
+        assert_eq!(
+            (
+                1,
+                19,
+                "expected whole percent from 0 to 100, found 120".to_owned()
+            ),
+            error("room a { blinds b 120 % }")
+        );
+
+        assert_eq!(
+            (1, 23, "expected non-negative value, found -10".to_owned()),
+            error("room a { socket s1 on -10 W }")
+        );
+        assert_eq!(
+            (1, 34, "expected non-negative value, found -5".to_owned()),
+            error("room a { socket s1 on 10 voltage -5V }")
+        );
+        assert_eq!(
+            (
+                1,
+                24,
+                "temperature -500 °F is below absolute zero".to_owned()
+            ),
+            error("room a { thermometer t -500 °F }")
+        );
+        assert!(parse_house("room a { thermometer t -459.67 °F; thermometer k 0 K }").is_ok());
+        assert_eq!(
+            (
+                1,
+                36,
+                "expected humidity from 0 to 100 %, found 120".to_owned()
+            ),
+            error("room a { thermometer t 20 humidity 120% }")
+        );
+
+        assert_eq!(
+            (1, 35, "invalid tag \"bad tag\"".to_owned()),
+            error("room a { socket s on 1 W tags ok, \"bad tag\" }")
+        );
+        assert_eq!(
+            (1, 31, "expected name, found \"}\"".to_owned()),
+            error("room a { socket s on 1 W tags }")
+        );
+
+        // Повтор имени девайса показывается на самом имени
+        let (line, column, message) = error("room a {\n  socket s1 on 1 W\n  light s1 10 %\n}");
+        assert_eq!((3, 9), (line, column));
+        assert!(message.contains("s1"), "{message}");
+    }
+}
@@ -0,0 +1,605 @@
+//! Шина событий дома и девайсов: подписчики узнают об изменениях сразу,
+//! а не опрашивают дом.
+//!
+//! Подписаться можно двумя способами:
+//!
+//! - `EventBus::subscribe` - обработчик вызывается синхронно в момент публикации;
+//! - `EventBus::subscribe_queue` - события копятся в очереди `Subscription`,
+//!   из которой их читают блокирующим `recv` из потока или через `recv_async().await`.
+
+use crate::{
+    device::{Device, DeviceKind, DeviceState},
+    location,
+    power::Overload,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, Weak},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// Сколько событий хранит очередь подписки, при переполнении выбрасываются старые
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Событие дома или девайса. Пути комнат - полные, например `"floor2/kitchen"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    RoomAdded {
+        room: String,
+    },
+    /// Расположение удалено вместе с вложенными и их девайсами
+    RoomRemoved {
+        room: String,
+    },
+    RoomRenamed {
+        room: String,
+        new_name: String,
+    },
+    DeviceAdded {
+        room: String,
+        device: String,
+        kind: DeviceKind,
+    },
+    DeviceRemoved {
+        room: String,
+        device: String,
+        kind: DeviceKind,
+    },
+    DeviceMoved {
+        from: String,
+        to: String,
+        device: String,
+        kind: DeviceKind,
+    },
+    DeviceRenamed {
+        room: String,
+        device: String,
+        new_name: String,
+        kind: DeviceKind,
+    },
+    /// `room` нет у девайса, который работает сам по себе, например по tcp
+    DeviceStateChanged {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
+        device: String,
+        old: DeviceState,
+        new: DeviceState,
+    },
+    /// Включение розетки `device` в `room` превысило бюджет мощности,
+    /// а политика бюджета это пропустила
+    PowerOverload {
+        room: String,
+        device: String,
+        overload: Overload,
+    },
+}
+
+impl Event {
+    /// Расположения, которых касается событие
+    pub fn rooms(&self) -> Vec<&str> {
+        match self {
+            Event::RoomAdded { room }
+            | Event::RoomRemoved { room }
+            | Event::RoomRenamed { room, .. }
+            | Event::DeviceAdded { room, .. }
+            | Event::DeviceRemoved { room, .. }
+            | Event::DeviceRenamed { room, .. }
+            | Event::PowerOverload { room, .. } => vec![room],
+            Event::DeviceMoved { from, to, .. } => vec![from, to],
+            Event::DeviceStateChanged { room, .. } => room.as_deref().into_iter().collect(),
+        }
+    }
+
+    /// Имя девайса, если событие про девайс
+    pub fn device(&self) -> Option<&str> {
+        match self {
+            Event::RoomAdded { .. } | Event::RoomRemoved { .. } | Event::RoomRenamed { .. } => None,
+            Event::DeviceAdded { device, .. }
+            | Event::DeviceRemoved { device, .. }
+            | Event::DeviceMoved { device, .. }
+            | Event::DeviceRenamed { device, .. }
+            | Event::DeviceStateChanged { device, .. }
+            | Event::PowerOverload { device, .. } => Some(device),
+        }
+    }
+
+    pub fn kind(&self) -> Option<DeviceKind> {
+        match self {
+            Event::RoomAdded { .. } | Event::RoomRemoved { .. } | Event::RoomRenamed { .. } => None,
+            Event::DeviceAdded { kind, .. }
+            | Event::DeviceRemoved { kind, .. }
+            | Event::DeviceMoved { kind, .. }
+            | Event::DeviceRenamed { kind, .. } => Some(*kind),
+            Event::DeviceStateChanged { new, .. } => Some(new.kind()),
+            Event::PowerOverload { .. } => Some(DeviceKind::Socket),
+        }
+    }
+}
+
+/// Отбор событий. Пустой фильтр пропускает все события.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    /// Расположение вместе с вложенными
+    pub room: Option<String>,
+    /// Имя девайса или полный путь `"kitchen/kettle"`
+    pub device: Option<String>,
+    pub kind: Option<DeviceKind>,
+}
+
+impl EventFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn room(mut self, room: &str) -> Self {
+        self.room = Some(room.to_owned());
+        self
+    }
+
+    pub fn device(mut self, device: &str) -> Self {
+        self.device = Some(device.to_owned());
+        self
+    }
+
+    pub fn kind(mut self, kind: DeviceKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        let rooms = event.rooms();
+        let room_matches = |root: &String| rooms.iter().any(|r| location::is_within(r, root));
+        let device_matches = |device: &String| {
+            event.device().is_some_and(|name| {
+                name == device
+                    || location::split_last(device).is_some_and(|(room, name_in_path)| {
+                        name == name_in_path
+                            && rooms.iter().any(|r| {
+                                location::normalize_path(r) == location::normalize_path(room)
+                            })
+                    })
+            })
+        };
+
+        self.room.as_ref().is_none_or(room_matches)
+            && self.device.as_ref().is_none_or(device_matches)
+            && self.kind.is_none_or(|kind| event.kind() == Some(kind))
+    }
+}
+
+/// Номер подписки, по которому от нее можно отписаться
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Handler = Arc<dyn Fn(&Event) + Send + Sync>;
+
+enum Target {
+    Handler(Handler),
+    Queue(Weak<Queue>),
+}
+
+struct Subscriber {
+    id: SubscriptionId,
+    filter: EventFilter,
+    target: Target,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    list: Vec<Subscriber>,
+}
+
+#[derive(Default)]
+struct BusInner {
+    subscribers: Mutex<Subscribers>,
+}
+
+impl Drop for BusInner {
+    /// Шины больше нет - очереди подписок закрываются
+    fn drop(&mut self) {
+        let subscribers = self
+            .subscribers
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner());
+        for subscriber in &subscribers.list {
+            if let Target::Queue(queue) = &subscriber.target {
+                if let Some(queue) = queue.upgrade() {
+                    queue.close();
+                }
+            }
+        }
+    }
+}
+
+/// Шина событий. Клоны разделяют одних и тех же подписчиков,
+/// так что шину можно раздать дому, серверам и веб-слою.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    inner: Arc<BusInner>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&self, filter: EventFilter, target: Target) -> SubscriptionId {
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        subscribers.next_id += 1;
+        let id = SubscriptionId(subscribers.next_id);
+        subscribers.list.push(Subscriber { id, filter, target });
+        id
+    }
+
+    /// Синхронный подписчик: вызывается в потоке, который публикует событие,
+    /// поэтому должен отрабатывать быстро
+    pub fn subscribe<F>(&self, filter: EventFilter, handler: F) -> SubscriptionId
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        self.add(filter, Target::Handler(Arc::new(handler)))
+    }
+
+    /// Подписка с очередью событий, отписывается сама, когда ее отпускают
+    pub fn subscribe_queue(&self, filter: EventFilter) -> Subscription {
+        self.subscribe_queue_with_capacity(filter, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    pub fn subscribe_queue_with_capacity(
+        &self,
+        filter: EventFilter,
+        capacity: usize,
+    ) -> Subscription {
+        let queue = Arc::new(Queue::new(capacity.max(1)));
+        let id = self.add(filter, Target::Queue(Arc::downgrade(&queue)));
+        Subscription {
+            id,
+            queue,
+            bus: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// `false` - такой подписки уже нет
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        let len = subscribers.list.len();
+        subscribers.list.retain(|s| s.id != id);
+        subscribers.list.len() != len
+    }
+
+    /// Публикуем `DeviceStateChanged`, если состояние девайса отличается от `old`.
+    /// Энергия, накопленная розеткой, растет сама по себе и изменением не считается.
+    pub fn publish_state_change(&self, room: Option<&str>, device: &dyn Device, old: DeviceState) {
+        self.publish_change(room, device.name(), old, device.state());
+    }
+
+    /// То же по имени девайса и его состояниям. Удобно, когда девайс под блокировкой:
+    /// ее надо отпустить до публикации, иначе обработчик, читающий девайс, зависнет.
+    pub fn publish_change(
+        &self,
+        room: Option<&str>,
+        device: &str,
+        old: DeviceState,
+        new: DeviceState,
+    ) {
+        if !old.same_as(&new) {
+            self.publish(Event::DeviceStateChanged {
+                room: room.map(location::normalize_path),
+                device: device.to_owned(),
+                old,
+                new,
+            });
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.inner.subscribers.lock().unwrap().list.len()
+    }
+
+    /// Раздаем событие подходящим подписчикам. Обработчики вызываются без
+    /// блокировки шины, так что внутри них можно подписываться и публиковать.
+    pub fn publish(&self, event: Event) {
+        let mut handlers = Vec::new();
+        {
+            let mut subscribers = self.inner.subscribers.lock().unwrap();
+            subscribers
+                .list
+                .retain(|subscriber| match &subscriber.target {
+                    Target::Handler(handler) => {
+                        if subscriber.filter.matches(&event) {
+                            handlers.push(Arc::clone(handler));
+                        }
+                        true
+                    }
+                    Target::Queue(queue) => match queue.upgrade() {
+                        Some(queue) => {
+                            if subscriber.filter.matches(&event) {
+                                queue.push(event.clone());
+                            }
+                            true
+                        }
+                        None => false,
+                    },
+                });
+        }
+
+        for handler in handlers {
+            handler(&event);
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<Event>,
+    missed: u64,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+struct Queue {
+    capacity: usize,
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+            ready: Condvar::new(),
+        }
+    }
+
+    fn push(&self, event: Event) {
+        let mut state = self.state.lock().unwrap();
+        if state.events.len() == self.capacity {
+            state.events.pop_front();
+            state.missed += 1;
+        }
+        state.events.push_back(event);
+        self.wake(state);
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        self.wake(state);
+    }
+
+    fn wake(&self, mut state: std::sync::MutexGuard<QueueState>) {
+        let waker = state.waker.take();
+        drop(state);
+        self.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Очередь событий подписки
+pub struct Subscription {
+    id: SubscriptionId,
+    queue: Arc<Queue>,
+    bus: Weak<BusInner>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> SubscriptionId {
+        self.id
+    }
+
+    /// Следующее событие без ожидания
+    pub fn try_recv(&self) -> Option<Event> {
+        self.queue.state.lock().unwrap().events.pop_front()
+    }
+
+    /// Ждем следующее событие, `None` - шины больше нет
+    pub fn recv(&self) -> Option<Event> {
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.queue.ready.wait(state).unwrap();
+        }
+    }
+
+    /// Ждем следующее событие не дольше `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+            let now = Instant::now();
+            if state.closed || now >= deadline {
+                return None;
+            }
+            state = self
+                .queue
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Все накопившиеся события
+    pub fn drain(&self) -> Vec<Event> {
+        self.queue.state.lock().unwrap().events.drain(..).collect()
+    }
+
+    /// Следующее событие для async кода: `while let Some(event) = sub.recv_async().await`
+    pub fn recv_async(&mut self) -> Next<'_> {
+        Next { subscription: self }
+    }
+
+    /// Сколько событий выброшено из-за переполнения очереди
+    pub fn missed(&self) -> u64 {
+        self.queue.state.lock().unwrap().missed
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(bus) = self.bus.upgrade() {
+            EventBus { inner: bus }.unsubscribe(self.id);
+        }
+    }
+}
+
+/// Future следующего события подписки
+pub struct Next<'a> {
+    subscription: &'a mut Subscription,
+}
+
+impl Future for Next<'_> {
+    type Output = Option<Event>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.subscription.queue.state.lock().unwrap();
+        if let Some(event) = state.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::AtomicUsize, sync::atomic::Ordering, thread};
+
+    fn socket_changed(room: &str, device: &str, is_on: bool) -> Event {
+        let state = |is_on| DeviceState::Socket {
+            is_on,
+            current_power: 100.0,
+            voltage: 230.0,
+            energy_kwh: 0.0,
+        };
+        Event::DeviceStateChanged {
+            room: Some(room.to_owned()),
+            device: device.to_owned(),
+            old: state(!is_on),
+            new: state(is_on),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let event = socket_changed("floor2/kitchen", "kettle", true);
+        assert!(EventFilter::all().matches(&event));
+        assert!(EventFilter::all().room("floor2").matches(&event));
+        assert!(!EventFilter::all().room("floor1").matches(&event));
+        assert!(EventFilter::all().device("kettle").matches(&event));
+        assert!(EventFilter::all()
+            .device("floor2/kitchen/kettle")
+            .matches(&event));
+        assert!(!EventFilter::all().device("hall/kettle").matches(&event));
+        assert!(EventFilter::all().kind(DeviceKind::Socket).matches(&event));
+        assert!(!EventFilter::all()
+            .kind(DeviceKind::Socket)
+            .matches(&Event::RoomAdded {
+                room: "floor2".to_owned()
+            }));
+
+        let moved = Event::DeviceMoved {
+            from: "hall".to_owned(),
+            to: "floor2/kitchen".to_owned(),
+            device: "kettle".to_owned(),
+            kind: DeviceKind::Socket,
+        };
+        assert!(EventFilter::all().room("hall").matches(&moved));
+        assert!(EventFilter::all().room("floor2").matches(&moved));
+    }
+
+    #[test]
+    fn test_subscribers() {
+        let bus = EventBus::new();
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        let id = bus.subscribe(EventFilter::all().room("hall"), move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let queue = bus.subscribe_queue_with_capacity(EventFilter::all(), 2);
+
+        bus.publish(socket_changed("hall", "lamp", true));
+        bus.publish(socket_changed("kitchen", "kettle", true));
+        bus.publish(socket_changed("hall", "lamp", false));
+        assert_eq!(2, count.load(Ordering::SeqCst));
+        assert_eq!(1, queue.missed());
+        assert_eq!(
+            vec![
+                socket_changed("kitchen", "kettle", true),
+                socket_changed("hall", "lamp", false)
+            ],
+            queue.drain()
+        );
+
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        assert_eq!(1, bus.subscriber_count());
+        drop(queue);
+        assert_eq!(0, bus.subscriber_count());
+    }
+
+    #[test]
+    fn test_queue_across_threads() {
+        let bus = EventBus::new();
+        let mut queue = bus.subscribe_queue(EventFilter::all());
+        assert_eq!(None, queue.recv_timeout(Duration::from_millis(10)));
+
+        let publisher = bus.clone();
+        thread::spawn(move || {
+            publisher.publish(Event::RoomAdded {
+                room: "hall".to_owned(),
+            })
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            Some(Event::RoomAdded {
+                room: "hall".to_owned()
+            }),
+            queue.recv()
+        );
+
+        // Future будится публикацией и завершается, когда шины больше нет
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let mut next = queue.recv_async();
+        assert_eq!(Poll::Pending, Pin::new(&mut next).poll(&mut cx));
+        bus.publish(Event::RoomRemoved {
+            room: "hall".to_owned(),
+        });
+        assert_eq!(
+            Poll::Ready(Some(Event::RoomRemoved {
+                room: "hall".to_owned()
+            })),
+            Pin::new(&mut next).poll(&mut cx)
+        );
+        drop(bus);
+        assert_eq!(
+            Poll::Ready(None),
+            Pin::new(&mut queue.recv_async()).poll(&mut cx)
+        );
+        assert_eq!(None, queue.recv());
+    }
+}
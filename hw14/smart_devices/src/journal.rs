@@ -0,0 +1,1127 @@
+//! Журнал изменений дома: каждая операция хранит достаточно, чтобы ее
+//! отменить (`undo`), повторить (`redo`) или проиграть на другом доме (`replay`).
+
+use crate::{
+    alarms::{Alarm, AlarmError},
+    device::{
+        temperature::TemperatureUnit, Device, DeviceState, SmartBlinds, SmartLight, SmartLock,
+        SmartSensor, SmartSocket, SmartThermometer,
+    },
+    location,
+    power::PowerBudget,
+    rules::{Rule, RuleError},
+    scene::{Scene, SceneError},
+    schedule::{ScheduleError, ScheduledJob},
+    storage::{DeviceRecord, RoomRecord},
+    DeviceNamePolicy, SmartHouse, SmartHouseError,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt};
+use thiserror::Error;
+
+/// Столько последних операций журнал хранит по умолчанию
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 100;
+
+/// Ошибка отмены, повтора или проигрывания операции.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum JournalError {
+    /// Операцию нельзя выполнить на доме в его нынешнем виде.
+    #[error(transparent)]
+    House(#[from] SmartHouseError),
+
+    /// Операцию со сценой нельзя выполнить.
+    #[error(transparent)]
+    Scene(#[from] SceneError),
+
+    /// Операцию с правилом нельзя выполнить.
+    #[error(transparent)]
+    Rule(#[from] RuleError),
+
+    /// Операцию с расписанием нельзя выполнить.
+    #[error(transparent)]
+    Schedule(#[from] ScheduleError),
+
+    /// Операцию с тревогой нельзя выполнить.
+    #[error(transparent)]
+    Alarm(#[from] AlarmError),
+
+    /// Девайс по пути уже другого вида, его состояние не восстановить.
+    #[error("device \"{0}\" does not match the journal")]
+    DeviceMismatch(String),
+}
+
+type Result<T> = std::result::Result<T, JournalError>;
+
+/// Обратимое изменение дома. Пути комнат и девайсов - на момент операции.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// `created` - первое расположение пути, которого до операции не было
+    AddRoom {
+        room: String,
+        created: String,
+    },
+    /// `position` - место среди соседей, `record` - комната со всем поддеревом
+    DeleteRoom {
+        room: String,
+        position: usize,
+        record: RoomRecord,
+    },
+    RenameRoom {
+        room: String,
+        new_name: String,
+    },
+    /// Порядок всех расположений дома до и после перестановки
+    ReorderRooms {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    AddDevice {
+        room: String,
+        device: DeviceRecord,
+    },
+    DeleteDevice {
+        room: String,
+        position: usize,
+        device: DeviceRecord,
+    },
+    /// `position` - место девайса в комнате `from`
+    MoveDevice {
+        from: String,
+        to: String,
+        device: String,
+        position: usize,
+    },
+    RenameDevice {
+        room: String,
+        device: String,
+        new_name: String,
+    },
+    DescribeDevice {
+        room: String,
+        device: String,
+        old: String,
+        new: String,
+    },
+    ReorderDevices {
+        room: String,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    TagDevice {
+        room: String,
+        device: String,
+        tag: String,
+    },
+    UntagDevice {
+        room: String,
+        device: String,
+        tag: String,
+    },
+    /// Состояние девайса по пути `device` до и после изменения
+    ChangeDevice {
+        device: String,
+        old: DeviceState,
+        new: DeviceState,
+    },
+    SetTemperatureUnit {
+        old: TemperatureUnit,
+        new: TemperatureUnit,
+    },
+    SetDeviceNamePolicy {
+        old: DeviceNamePolicy,
+        new: DeviceNamePolicy,
+    },
+    /// Бюджет мощности расположения, без `room` - всего дома
+    SetPowerBudget {
+        room: Option<String>,
+        old: Option<PowerBudget>,
+        new: Option<PowerBudget>,
+    },
+    SetLoadPriority {
+        device: String,
+        old: Option<i32>,
+        new: Option<i32>,
+    },
+    AddScene {
+        scene: Scene,
+    },
+    DeleteScene {
+        position: usize,
+        scene: Scene,
+    },
+    AddRule {
+        rule: Rule,
+    },
+    /// `position` - место правила среди правил дома
+    RemoveRule {
+        position: usize,
+        rule: Rule,
+    },
+    AddJob {
+        job: ScheduledJob,
+    },
+    RemoveJob {
+        position: usize,
+        job: ScheduledJob,
+    },
+    AddAlarm {
+        alarm: Alarm,
+    },
+    RemoveAlarm {
+        position: usize,
+        alarm: Alarm,
+    },
+    /// Несколько операций, которые отменяются и повторяются вместе,
+    /// например применение сцены
+    Batch {
+        name: String,
+        operations: Vec<Operation>,
+    },
+}
+
+impl Operation {
+    /// Выполняем операцию на доме
+    fn apply(&self, house: &mut SmartHouse) -> Result<()> {
+        match self {
+            Operation::AddRoom { room, .. } => house.add_room(room)?,
+            Operation::DeleteRoom { room, .. } => house.delete_room(room)?,
+            Operation::RenameRoom { room, new_name } => house.rename_room(room, new_name)?,
+            Operation::ReorderRooms { after, .. } => reorder_rooms(house, after),
+            Operation::AddDevice { room, device } => device.clone().add_to(house, room)?,
+            Operation::DeleteDevice { room, device, .. } => {
+                house.delete_device(room, &device.name)?;
+            }
+            Operation::MoveDevice {
+                from, to, device, ..
+            } => house.move_device(from, to, device)?,
+            Operation::RenameDevice {
+                room,
+                device,
+                new_name,
+            } => house.rename_device(room, device, new_name)?,
+            Operation::DescribeDevice {
+                room, device, new, ..
+            } => house.describe_device(room, device, new)?,
+            Operation::ReorderDevices { room, after, .. } => reorder_devices(house, room, after)?,
+            Operation::TagDevice { room, device, tag } => {
+                house.tag_device(room, device, tag)?;
+            }
+            Operation::UntagDevice { room, device, tag } => {
+                house.untag_device(room, device, tag)?;
+            }
+            Operation::ChangeDevice { device, new, .. } => restore_state(house, device, new)?,
+            Operation::SetTemperatureUnit { new, .. } => house.set_temperature_unit(*new),
+            Operation::SetDeviceNamePolicy { new, .. } => house.set_device_name_policy(*new)?,
+            Operation::SetPowerBudget { room, new, .. } => {
+                house.set_power_budget(room.as_deref(), *new)?
+            }
+            Operation::SetLoadPriority { device, new, .. } => {
+                house.set_load_priority(device, *new)?
+            }
+            Operation::AddScene { scene } => house.add_scene(scene.clone())?,
+            Operation::DeleteScene { scene, .. } => {
+                house.delete_scene(&scene.name)?;
+            }
+            Operation::AddRule { rule } => house.add_rule(rule.clone())?,
+            Operation::RemoveRule { rule, .. } => {
+                house.remove_rule(&rule.name)?;
+            }
+            Operation::AddJob { job } => house.add_job(job.clone())?,
+            Operation::RemoveJob { job, .. } => {
+                house.remove_job(&job.name)?;
+            }
+            Operation::AddAlarm { alarm } => house.add_alarm(alarm.clone())?,
+            Operation::RemoveAlarm { alarm, .. } => {
+                house.remove_alarm(&alarm.name)?;
+            }
+            Operation::Batch { operations, .. } => {
+                for (i, operation) in operations.iter().enumerate() {
+                    if let Err(error) = operation.apply(house) {
+                        // Группа выполняется целиком или не выполняется вовсе
+                        for operation in operations[..i].iter().rev() {
+                            let _ = operation.revert(house);
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Возвращаем дом в состояние до операции
+    fn revert(&self, house: &mut SmartHouse) -> Result<()> {
+        match self {
+            Operation::AddRoom { created, .. } => house.delete_room(created)?,
+            Operation::DeleteRoom {
+                room,
+                position,
+                record,
+            } => {
+                let (parent, _) = location::split_last(room)
+                    .ok_or_else(|| SmartHouseError::InvalidName(room.clone()))?;
+                record.clone().add_to(house, parent)?;
+                house.move_room_to(room, *position)?;
+            }
+            Operation::RenameRoom { room, new_name } => {
+                let (parent, name) = location::split_last(room)
+                    .ok_or_else(|| SmartHouseError::InvalidName(room.clone()))?;
+                house.rename_room(&location::join_path(parent, new_name), name)?;
+            }
+            Operation::ReorderRooms { before, .. } => reorder_rooms(house, before),
+            Operation::AddDevice { room, device } => {
+                house.delete_device(room, &device.name)?;
+            }
+            Operation::DeleteDevice {
+                room,
+                position,
+                device,
+            } => {
+                device.clone().add_to(house, room)?;
+                house.move_device_to(room, &device.name, *position)?;
+            }
+            Operation::MoveDevice {
+                from,
+                to,
+                device,
+                position,
+            } => {
+                house.move_device(to, from, device)?;
+                house.move_device_to(from, device, *position)?;
+            }
+            Operation::RenameDevice {
+                room,
+                device,
+                new_name,
+            } => house.rename_device(room, new_name, device)?,
+            Operation::DescribeDevice {
+                room, device, old, ..
+            } => house.describe_device(room, device, old)?,
+            Operation::ReorderDevices { room, before, .. } => reorder_devices(house, room, before)?,
+            Operation::TagDevice { room, device, tag } => {
+                house.untag_device(room, device, tag)?;
+            }
+            Operation::UntagDevice { room, device, tag } => {
+                house.tag_device(room, device, tag)?;
+            }
+            Operation::ChangeDevice { device, old, .. } => restore_state(house, device, old)?,
+            Operation::SetTemperatureUnit { old, .. } => house.set_temperature_unit(*old),
+            Operation::SetDeviceNamePolicy { old, .. } => house.set_device_name_policy(*old)?,
+            Operation::SetPowerBudget { room, old, .. } => {
+                house.set_power_budget(room.as_deref(), *old)?
+            }
+            Operation::SetLoadPriority { device, old, .. } => {
+                house.set_load_priority(device, *old)?
+            }
+            Operation::AddScene { scene } => {
+                house.delete_scene(&scene.name)?;
+            }
+            Operation::DeleteScene { position, scene } => {
+                house.add_scene(scene.clone())?;
+                let scene = house.scenes.pop().expect("scene was just added");
+                let position = (*position).min(house.scenes.len());
+                house.scenes.insert(position, scene);
+            }
+            Operation::AddRule { rule } => {
+                house.remove_rule(&rule.name)?;
+            }
+            Operation::RemoveRule { position, rule } => {
+                house.rules.insert_rule(*position, rule.clone())?
+            }
+            Operation::AddJob { job } => {
+                house.remove_job(&job.name)?;
+            }
+            Operation::RemoveJob { position, job } => {
+                house.schedule.insert_job(*position, job.clone())?
+            }
+            Operation::AddAlarm { alarm } => {
+                house.remove_alarm(&alarm.name)?;
+            }
+            Operation::RemoveAlarm { position, alarm } => {
+                house.alarms.insert_alarm(*position, alarm.clone())?
+            }
+            Operation::Batch { operations, .. } => {
+                for (i, operation) in operations.iter().enumerate().rev() {
+                    if let Err(error) = operation.revert(house) {
+                        // Группа отменяется целиком: уже отмененное выполняем снова
+                        for operation in &operations[i + 1..] {
+                            let _ = operation.apply(house);
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::AddRoom { room, .. } => write!(f, "add room \"{room}\""),
+            Operation::DeleteRoom { room, .. } => write!(f, "delete room \"{room}\""),
+            Operation::RenameRoom { room, new_name } => {
+                write!(f, "rename room \"{room}\" to \"{new_name}\"")
+            }
+            Operation::ReorderRooms { .. } => write!(f, "reorder rooms"),
+            Operation::AddDevice { room, device } => {
+                write!(
+                    f,
+                    "add device \"{}\"",
+                    location::join_path(room, &device.name)
+                )
+            }
+            Operation::DeleteDevice { room, device, .. } => {
+                write!(
+                    f,
+                    "delete device \"{}\"",
+                    location::join_path(room, &device.name)
+                )
+            }
+            Operation::MoveDevice {
+                from, to, device, ..
+            } => write!(f, "move device \"{device}\" from \"{from}\" to \"{to}\""),
+            Operation::RenameDevice {
+                room,
+                device,
+                new_name,
+            } => write!(
+                f,
+                "rename device \"{}\" to \"{new_name}\"",
+                location::join_path(room, device)
+            ),
+            Operation::ReorderDevices { room, .. } => write!(f, "reorder devices in \"{room}\""),
+            Operation::TagDevice { room, device, tag } => {
+                write!(
+                    f,
+                    "tag \"{}\" with {tag}",
+                    location::join_path(room, device)
+                )
+            }
+            Operation::UntagDevice { room, device, tag } => {
+                write!(
+                    f,
+                    "untag {tag} from \"{}\"",
+                    location::join_path(room, device)
+                )
+            }
+            Operation::DescribeDevice { room, device, .. } => write!(
+                f,
+                "change description of \"{}\"",
+                location::join_path(room, device)
+            ),
+            Operation::ChangeDevice { device, .. } => write!(f, "change device \"{device}\""),
+            Operation::SetTemperatureUnit { new, .. } => write!(f, "set temperature unit {new}"),
+            Operation::SetDeviceNamePolicy { new, .. } => {
+                write!(f, "set device name policy {new:?}")
+            }
+            Operation::SetPowerBudget { room, new, .. } => {
+                match room {
+                    Some(room) => write!(f, "set power budget of \"{room}\"")?,
+                    None => write!(f, "set power budget of the house")?,
+                }
+                match new {
+                    Some(budget) => write!(f, " to {budget}"),
+                    None => write!(f, " to none"),
+                }
+            }
+            Operation::SetLoadPriority { device, new, .. } => match new {
+                Some(priority) => write!(f, "set priority of \"{device}\" to {priority}"),
+                None => write!(f, "reset priority of \"{device}\""),
+            },
+            Operation::AddScene { scene } => write!(f, "add scene \"{}\"", scene.name),
+            Operation::DeleteScene { scene, .. } => write!(f, "delete scene \"{}\"", scene.name),
+            Operation::AddRule { rule } => write!(f, "add rule \"{}\"", rule.name),
+            Operation::RemoveRule { rule, .. } => write!(f, "remove rule \"{}\"", rule.name),
+            Operation::AddJob { job } => write!(f, "add job \"{}\"", job.name),
+            Operation::RemoveJob { job, .. } => write!(f, "remove job \"{}\"", job.name),
+            Operation::AddAlarm { alarm } => write!(f, "add alarm \"{}\"", alarm.name),
+            Operation::RemoveAlarm { alarm, .. } => {
+                write!(f, "remove alarm \"{}\"", alarm.name)
+            }
+            Operation::Batch { name, .. } => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Ставим расположения на каждом уровне в порядке `order`, незнакомые - в конец
+fn reorder_rooms(house: &mut SmartHouse, order: &[String]) {
+    let position = |path: &str| order.iter().position(|p| p == path).unwrap_or(usize::MAX);
+    house.sort_rooms_by(|a, b| position(a).cmp(&position(b)));
+}
+
+fn reorder_devices(house: &mut SmartHouse, room: &str, order: &[String]) -> Result<()> {
+    let position = |name: &str| order.iter().position(|n| n == name).unwrap_or(usize::MAX);
+    house.sort_devices_by(room, |a, b| position(a.name()).cmp(&position(b.name())))?;
+    Ok(())
+}
+
+/// Возвращаем девайсу состояние из журнала. Энергию розетки не трогаем:
+/// счетчик показывает, сколько потреблено на самом деле.
+fn restore_state(house: &mut SmartHouse, path: &str, state: &DeviceState) -> Result<()> {
+    let restored = house.change_device(path, |device| set_state(device, state))?;
+
+    if restored {
+        Ok(())
+    } else {
+        Err(JournalError::DeviceMismatch(path.to_owned()))
+    }
+}
+
+/// Возвращаем девайсу состояние, `false` - девайс другого вида
+pub(crate) fn set_state(device: &mut dyn Device, state: &DeviceState) -> bool {
+    let device = device.as_any_mut();
+    match state {
+        DeviceState::Socket {
+            is_on,
+            current_power,
+            voltage,
+            ..
+        } => {
+            let Some(socket) = device.downcast_mut::<SmartSocket>() else {
+                return false;
+            };
+            if *is_on {
+                socket.turn_on();
+            } else {
+                socket.turn_off();
+            }
+            socket.set_load_power(*current_power);
+            socket.set_voltage(*voltage);
+        }
+        DeviceState::Thermometer {
+            current_temperature,
+            humidity,
+        } => {
+            let Some(thermometer) = device.downcast_mut::<SmartThermometer>() else {
+                return false;
+            };
+            thermometer.set_current_temperature(*current_temperature);
+            thermometer.set_humidity(*humidity);
+        }
+        DeviceState::Light {
+            is_on,
+            brightness,
+            color,
+        } => {
+            let Some(light) = device.downcast_mut::<SmartLight>() else {
+                return false;
+            };
+            if *is_on {
+                light.turn_on();
+            } else {
+                light.turn_off();
+            }
+            light.set_brightness(*brightness);
+            light.set_color(*color);
+        }
+        DeviceState::Lock {
+            is_locked,
+            is_jammed,
+        } => {
+            let Some(lock) = device.downcast_mut::<SmartLock>() else {
+                return false;
+            };
+            lock.clear_jam();
+            let _ = if *is_locked {
+                lock.lock()
+            } else {
+                lock.unlock()
+            };
+            if *is_jammed {
+                lock.jam();
+            }
+        }
+        DeviceState::Sensor { is_triggered, .. } => {
+            let Some(sensor) = device.downcast_mut::<SmartSensor>() else {
+                return false;
+            };
+            if *is_triggered {
+                sensor.trigger();
+            } else {
+                sensor.clear();
+            }
+        }
+        DeviceState::Blinds { position } => {
+            let Some(blinds) = device.downcast_mut::<SmartBlinds>() else {
+                return false;
+            };
+            blinds.set_position(*position);
+        }
+    }
+
+    true
+}
+
+/// История изменений дома. Хранит не больше `capacity` последних операций,
+/// более старые отбрасываются и считаются в `dropped`.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    done: VecDeque<Operation>,
+    /// Отмененные операции, последняя отмененная - в конце
+    undone: Vec<Operation>,
+    capacity: usize,
+    dropped: usize,
+    /// Операции открытой группы и глубина вложенности групп
+    batch: Vec<Operation>,
+    batch_depth: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOURNAL_CAPACITY)
+    }
+}
+
+impl Journal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+            capacity,
+            dropped: 0,
+            batch: Vec::new(),
+            batch_depth: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Меняем емкость, лишние старые операции отбрасываются
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    /// Выполненные операции от старой к новой
+    pub fn operations(&self) -> impl Iterator<Item = &Operation> {
+        self.done.iter()
+    }
+
+    /// Отмененные операции, первой идет та, которую повторит `redo`
+    pub fn undone(&self) -> impl Iterator<Item = &Operation> {
+        self.undone.iter().rev()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Сколько операций отброшено из-за емкости. Пока ноль, проигрывание
+    /// журнала на пустом доме восстанавливает дом целиком.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+        self.dropped = 0;
+    }
+
+    /// Новая операция отменяет возможность повтора отмененных
+    pub(crate) fn record(&mut self, operation: Operation) {
+        if self.batch_depth > 0 {
+            self.batch.push(operation);
+            return;
+        }
+
+        self.undone.clear();
+        self.done.push_back(operation);
+        self.trim();
+    }
+
+    /// Дальнейшие операции до `end_batch` попадут в журнал одной
+    pub(crate) fn begin_batch(&mut self) {
+        self.batch_depth += 1;
+    }
+
+    pub(crate) fn end_batch(&mut self, name: String) {
+        self.batch_depth -= 1;
+        if self.batch_depth > 0 || self.batch.is_empty() {
+            return;
+        }
+
+        let mut operations = std::mem::take(&mut self.batch);
+        if operations.len() == 1 {
+            self.record(operations.remove(0));
+        } else {
+            self.record(Operation::Batch { name, operations });
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.done.len() > self.capacity {
+            self.done.pop_front();
+            self.dropped += 1;
+        }
+    }
+}
+
+impl SmartHouse {
+    /// Журнал изменений дома
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    pub fn journal_mut(&mut self) -> &mut Journal {
+        &mut self.journal
+    }
+
+    /// Отменяем последнюю операцию, `None` - отменять нечего.
+    /// Если отменить не удалось, операция остается в журнале.
+    pub fn undo(&mut self) -> Result<Option<Operation>> {
+        let Some(operation) = self.journal.done.pop_back() else {
+            return Ok(None);
+        };
+
+        match self.unjournaled(|house| operation.revert(house)) {
+            Ok(()) => {
+                self.journal.undone.push(operation.clone());
+                Ok(Some(operation))
+            }
+            Err(error) => {
+                self.journal.done.push_back(operation);
+                Err(error)
+            }
+        }
+    }
+
+    /// Повторяем последнюю отмененную операцию, `None` - повторять нечего
+    pub fn redo(&mut self) -> Result<Option<Operation>> {
+        let Some(operation) = self.journal.undone.pop() else {
+            return Ok(None);
+        };
+
+        match self.unjournaled(|house| operation.apply(house)) {
+            Ok(()) => {
+                self.journal.done.push_back(operation.clone());
+                self.journal.trim();
+                Ok(Some(operation))
+            }
+            Err(error) => {
+                self.journal.undone.push(operation);
+                Err(error)
+            }
+        }
+    }
+
+    /// Проигрываем операции по порядку, например журнал другого дома на пустом доме.
+    /// Операции попадают в журнал этого дома, так что их можно отменить.
+    pub fn replay<'a, I>(&mut self, operations: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a Operation>,
+    {
+        for operation in operations {
+            self.journaled(operation.to_string(), |house| operation.apply(house))?;
+        }
+
+        Ok(())
+    }
+
+    /// Изменения внутри `change` записываются в журнал одной операцией
+    pub(crate) fn journaled<F, R>(&mut self, name: String, change: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        self.journal.begin_batch();
+        let result = self.without_reactions(change);
+        self.journal.end_batch(name);
+        self.settle();
+        result
+    }
+
+    /// Как `journaled`, но если `change` вернул ошибку, его изменения
+    /// отменяются и в журнал не попадают
+    pub(crate) fn atomic<F, T, E>(&mut self, name: String, change: F) -> std::result::Result<T, E>
+    where
+        F: FnOnce(&mut Self) -> std::result::Result<T, E>,
+    {
+        self.journal.begin_batch();
+        let start = self.journal.batch.len();
+        let pending = self.reactions_pending;
+        let result = self.without_reactions(change);
+        if result.is_err() {
+            let operations = self.journal.batch.split_off(start);
+            self.unjournaled(|house| {
+                for operation in operations.iter().rev() {
+                    let _ = operation.revert(house);
+                }
+            });
+            self.reactions_pending = pending;
+        }
+        self.journal.end_batch(name);
+        self.settle();
+        result
+    }
+
+    /// Изменения внутри `change` в журнал не попадают и правила с тревогами
+    /// не проверяют: отмена не должна запускать автоматизацию
+    fn unjournaled<F, R>(&mut self, change: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        let journal = std::mem::replace(&mut self.journal, Journal::new(0));
+        let pending = self.reactions_pending;
+        let result = self.without_reactions(change);
+        self.reactions_pending = pending;
+        self.journal = journal;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rules::Action, scene::TargetState, tags::TagQuery};
+
+    fn house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("journaled house");
+        house.add_room("kitchen").unwrap();
+        house.add_room("floor2/bedroom").unwrap();
+        house
+            .add_device(
+                "kitchen",
+                Box::new(SmartSocket::new("kettle", "", false, 2000.0)),
+            )
+            .unwrap();
+        house
+            .add_device("kitchen", Box::new(SmartLight::new("lamp", "", true, 80)))
+            .unwrap();
+        house.tag_device("kitchen", "kettle", "heating").unwrap();
+        house
+            .add_device(
+                "floor2/bedroom",
+                Box::new(SmartSocket::new("heater", "", true, 800.0)),
+            )
+            .unwrap();
+        house
+            .tag_device("floor2/bedroom", "heater", "heating")
+            .unwrap();
+        house
+    }
+
+    /// Расположения с девайсами, их тегами и состояниями
+    type Snapshot = Vec<(String, Vec<(String, Vec<String>, DeviceState)>)>;
+
+    fn snapshot(house: &SmartHouse) -> Snapshot {
+        house
+            .locations()
+            .map(|room| {
+                let devices = house
+                    .room_devices(&room)
+                    .map(|d| {
+                        let tags = house.device_tags(&room, d.name()).unwrap();
+                        let state = match d.state() {
+                            DeviceState::Socket {
+                                is_on,
+                                current_power,
+                                voltage,
+                                ..
+                            } => DeviceState::Socket {
+                                is_on,
+                                current_power,
+                                voltage,
+                                energy_kwh: 0.0,
+                            },
+                            state => state,
+                        };
+                        (
+                            d.name().to_owned(),
+                            tags.map(str::to_owned).collect(),
+                            state,
+                        )
+                    })
+                    .collect();
+                (room, devices)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut house = house();
+        let before = snapshot(&house);
+
+        house.delete_room("kitchen").unwrap();
+        assert!(!house.has_room("kitchen"));
+        assert_eq!(
+            Ok(Some("delete room \"kitchen\"".to_owned())),
+            house.undo().map(|op| op.map(|op| op.to_string()))
+        );
+        assert_eq!(before, snapshot(&house));
+        assert!(house.journal().can_redo());
+
+        house.redo().unwrap();
+        assert!(!house.has_room("kitchen"));
+        house.undo().unwrap();
+
+        house.rename_room("floor2", "upstairs").unwrap();
+        house
+            .move_device("kitchen", "upstairs/bedroom", "kettle")
+            .unwrap();
+        house.move_room_to("upstairs", 0).unwrap();
+        assert!(!house.journal().can_redo());
+        for _ in 0..3 {
+            house.undo().unwrap();
+        }
+        assert_eq!(before, snapshot(&house));
+
+        // Добавление вложенной комнаты отменяется вместе с созданными предками
+        house.add_room("garage/workshop").unwrap();
+        house.undo().unwrap();
+        assert!(!house.has_room("garage"));
+    }
+
+    #[test]
+    fn test_state_changes() {
+        let mut house = house();
+        let before = snapshot(&house);
+
+        house
+            .add_scene(
+                Scene::new("cozy")
+                    .with_target("kitchen/kettle", TargetState::Socket { is_on: true })
+                    .with_target(
+                        "kitchen/lamp",
+                        TargetState::Light {
+                            is_on: true,
+                            brightness: 30,
+                            color: None,
+                        },
+                    ),
+            )
+            .unwrap();
+        house.apply_scene("cozy").unwrap();
+        assert!(house.socket("kitchen", "kettle").unwrap().is_on());
+
+        // Сцена отменяется одним шагом
+        assert_eq!(
+            "apply scene \"cozy\"",
+            house.undo().unwrap().unwrap().to_string()
+        );
+        assert!(!house.socket("kitchen", "kettle").unwrap().is_on());
+
+        house.apply_tagged(&TagQuery::tagged("heating"), Action::TurnOff);
+        house.undo().unwrap();
+        house.undo().unwrap();
+        assert_eq!(before, snapshot(&house));
+        assert_eq!(None, house.scene("cozy"));
+
+        // Девайс подменили другим видом - состояние не восстановить
+        house
+            .update_device("kitchen", "lamp", |d| {
+                d.as_any_mut()
+                    .downcast_mut::<SmartLight>()
+                    .unwrap()
+                    .turn_off()
+            })
+            .unwrap();
+        house.delete_device("kitchen", "lamp").unwrap();
+        house
+            .add_device("kitchen", Box::new(SmartLock::new("lamp", "", true)))
+            .unwrap();
+        house.journal_mut().done.retain(|op| {
+            !matches!(
+                op,
+                Operation::AddDevice { .. } | Operation::DeleteDevice { .. }
+            )
+        });
+        assert_eq!(
+            Err(JournalError::DeviceMismatch("kitchen/lamp".to_owned())),
+            house.undo()
+        );
+        assert!(house.journal().can_undo());
+    }
+
+    #[test]
+    fn test_batch_undo_is_atomic() {
+        let mut house = house();
+        house
+            .add_scene(
+                Scene::new("cozy")
+                    .with_target("kitchen/kettle", TargetState::Socket { is_on: true })
+                    .with_target(
+                        "kitchen/lamp",
+                        TargetState::Light {
+                            is_on: true,
+                            brightness: 30,
+                            color: None,
+                        },
+                    ),
+            )
+            .unwrap();
+        house.apply_scene("cozy").unwrap();
+        let applied = snapshot(&house);
+
+        // Чайник подменили замком: лампу отменить можно, чайник - нет
+        house.delete_device("kitchen", "kettle").unwrap();
+        house
+            .add_device("kitchen", Box::new(SmartLock::new("kettle", "", true)))
+            .unwrap();
+        house.move_device_to("kitchen", "kettle", 0).unwrap();
+        house.journal_mut().done.retain(|op| {
+            !matches!(
+                op,
+                Operation::AddDevice { .. }
+                    | Operation::DeleteDevice { .. }
+                    | Operation::ReorderDevices { .. }
+            )
+        });
+        let replaced = snapshot(&house);
+        assert_ne!(applied, replaced);
+
+        assert_eq!(
+            Err(JournalError::DeviceMismatch("kitchen/kettle".to_owned())),
+            house.undo()
+        );
+        assert_eq!(replaced, snapshot(&house));
+        assert_eq!(
+            Some(30),
+            house
+                .device("kitchen", "lamp")
+                .and_then(|d| d.as_any().downcast_ref::<SmartLight>())
+                .map(|light| light.brightness())
+        );
+        assert!(house.journal().can_undo());
+    }
+
+    #[test]
+    fn test_rules_schedule_and_alarms() {
+        use crate::{
+            alarms::Threshold,
+            rules::{Condition, Metric},
+            schedule::Switch,
+        };
+
+        let mut house = house();
+        house.journal_mut().clear();
+        let rule = |name: &str| {
+            Rule::new(
+                name,
+                Condition::above("kettle", Metric::Power, 3000.0),
+                Action::TurnOff("kettle".to_owned()),
+            )
+        };
+        house.add_rule(rule("first")).unwrap();
+        house.add_rule(rule("second")).unwrap();
+        house
+            .add_job(ScheduledJob::repeat("night", "heater", Switch::Off, "0 23 * * *").unwrap())
+            .unwrap();
+        house
+            .add_alarm(Alarm::new(
+                "heavy load",
+                "kettle",
+                Threshold::PowerAbove(2500.0),
+            ))
+            .unwrap();
+        house.remove_rule("first").unwrap();
+        house.remove_job("night").unwrap();
+        house.remove_alarm("heavy load").unwrap();
+        assert_eq!(
+            vec![
+                "add rule \"first\"",
+                "add rule \"second\"",
+                "add job \"night\"",
+                "add alarm \"heavy load\"",
+                "remove rule \"first\"",
+                "remove job \"night\"",
+                "remove alarm \"heavy load\""
+            ],
+            house
+                .journal()
+                .operations()
+                .map(|op| op.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        // Удаленное правило возвращается на свое место
+        for _ in 0..3 {
+            house.undo().unwrap();
+        }
+        assert_eq!(
+            vec!["first", "second"],
+            house.rules().rules().map(|r| &r.name).collect::<Vec<_>>()
+        );
+        assert!(house.schedule().job("night").is_some());
+        assert!(house.alarms().alarm("heavy load").is_some());
+
+        while house.undo().unwrap().is_some() {}
+        assert!(house.rules().is_empty());
+        assert!(house.schedule().is_empty());
+        assert!(house.alarms().is_empty());
+
+        house.redo().unwrap();
+        assert_eq!(
+            Err(RuleError::DuplicateRule("first".to_owned())),
+            house.add_rule(rule("first"))
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut house = house();
+        house.delete_device("kitchen", "lamp").unwrap();
+        house
+            .rename_device("floor2/bedroom", "heater", "radiator")
+            .unwrap();
+        house.sort_rooms_by(|a, b| b.cmp(a));
+        house.set_temperature_unit(TemperatureUnit::Fahrenheit);
+        house
+            .update_device("kitchen", "kettle", |d| {
+                d.as_any_mut()
+                    .downcast_mut::<SmartSocket>()
+                    .unwrap()
+                    .turn_on()
+            })
+            .unwrap();
+
+        // Журнал переживает сериализацию и восстанавливает дом с нуля
+        let json =
+            serde_json::to_string(&house.journal().operations().collect::<Vec<_>>()).unwrap();
+        let operations: Vec<Operation> = serde_json::from_str(&json).unwrap();
+        let mut replayed = SmartHouse::new_empty("replayed");
+        replayed.replay(&operations).unwrap();
+        assert_eq!(snapshot(&house), snapshot(&replayed));
+        assert_eq!(TemperatureUnit::Fahrenheit, replayed.temperature_unit());
+        assert_eq!(
+            house.journal().operations().count(),
+            replayed.journal().operations().count()
+        );
+
+        assert!(replayed.replay(&operations[..1]).is_err());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut house = SmartHouse::new_empty("small journal");
+        house.journal_mut().set_capacity(2);
+        for room in ["a", "b", "c"] {
+            house.add_room(room).unwrap();
+        }
+        assert_eq!(1, house.journal().dropped());
+        assert_eq!(
+            vec!["add room \"b\"", "add room \"c\""],
+            house
+                .journal()
+                .operations()
+                .map(|op| op.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        house.undo().unwrap();
+        house.undo().unwrap();
+        assert_eq!(Ok(None), house.undo());
+        assert_eq!(vec!["a"], house.rooms().collect::<Vec<_>>());
+    }
+}
@@ -1,345 +1,2163 @@
+pub mod alarms;
+pub mod clock;
 pub mod device;
+pub mod diff;
+pub mod dsl;
+pub mod events;
+pub mod journal;
+pub mod location;
+pub mod power;
+pub mod query;
+mod quoting;
+pub mod report;
+pub mod rules;
+pub mod scene;
+pub mod schedule;
+pub mod simulation;
+pub mod storage;
+pub mod tags;
 
-use device::info::DeviceInfoProvider;
-use std::{collections::HashMap, ops::ControlFlow};
+use alarms::{AlarmMonitor, Alert};
+use clock::Clock;
+use device::{
+    info::{device_info, unavailable_device_info, DeviceInfoProvider},
+    temperature::TemperatureUnit,
+    Device, SmartSensor, SmartSocket, SmartThermometer,
+};
+use events::{Event, EventBus};
+use journal::{Journal, Operation};
+use location::Location;
+use power::{Overload, PowerBudgets};
+use report::{
+    DeviceEntry, MissingDevice, Report, ReportMode, ReportOptions, ReportOutcome, RoomReport,
+};
+use rules::{Firing, RuleEngine};
+use scene::Scene;
+use schedule::{ScheduledRun, Scheduler};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use thiserror::Error;
 
-#[derive(Error, Debug, PartialEq)]
+/// Ошибка работы с домом.
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum SmartHouseError {
-    #[error("create report error")]
-    ReportError,
+    /// В доме нет такой комнаты.
+    #[error("room \"{0}\" not found")]
+    RoomNotFound(String),
+
+    /// В комнате нет такого девайса.
+    #[error("device \"{device}\" not found in room \"{room}\"")]
+    DeviceNotFound { room: String, device: String },
+
+    /// Комната с таким именем уже есть.
+    #[error("room \"{0}\" already exists")]
+    DuplicateRoom(String),
+
+    /// Девайс с таким именем уже есть в комнате.
+    #[error("device \"{device}\" already exists in room \"{room}\"")]
+    DuplicateDevice { room: String, device: String },
+
+    /// Ни один девайс комнаты не удалось описать, `device` - первый из них.
+    #[error(
+        "create report error in room \"{room}\"{}",
+        .device.as_ref().map(|d| format!(", device \"{d}\"")).unwrap_or_default()
+    )]
+    ReportError {
+        room: String,
+        device: Option<String>,
+    },
+
+    /// Имя не годится для комнаты или девайса: пустое или содержит разделитель пути.
+    #[error("invalid name \"{0}\"")]
+    InvalidName(String),
+
+    /// Тег пустой или содержит что-то кроме букв, цифр, `-` и `_`.
+    #[error("invalid tag \"{0}\"")]
+    InvalidTag(String),
+
+    /// Девайс не розетка.
+    #[error("device \"{0}\" is not a socket")]
+    NotASocket(String),
+
+    /// Предел мощности должен быть неотрицательным числом.
+    #[error("invalid power limit {0}")]
+    InvalidPowerLimit(f64),
+
+    /// Розетку нельзя включить: бюджет расположения (или дома, если `room` нет) превышен.
+    #[error(
+        "power budget of {} exceeded: {load} W of {limit} W",
+        .room.as_ref().map(|r| format!("room \"{r}\"")).unwrap_or_else(|| "the house".to_owned())
+    )]
+    PowerBudgetExceeded {
+        room: Option<String>,
+        load: f64,
+        limit: f64,
+    },
 }
 
 type Result<T> = std::result::Result<T, SmartHouseError>;
 
+/// Девайс в отчете и его описание, `None` - поставщик его не описал
+type ReportedDevice<'a> = (&'a dyn Device, Option<String>);
+
+/// Политика уникальности имен девайсов
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceNamePolicy {
+    /// Имя уникально в пределах комнаты
+    #[default]
+    PerRoom,
+    /// Имя уникально во всем доме, так что девайс можно найти по одному имени
+    HouseWide,
+}
+
+/// Умный дом: дерево расположений (этажи, зоны, комнаты) с девайсами.
+/// Расположения адресуются путями вида `"floor2/kitchen"`, комнатами считаются
+/// расположения без вложенных. Для плоского дома путь совпадает с именем комнаты.
 pub struct SmartHouse {
     name: String,
-    room_names: Vec<String>,
-    devices: HashMap<String, Vec<String>>,
+    locations: Vec<Location>,
+    device_names: DeviceNamePolicy,
+    temperature_unit: TemperatureUnit,
+    rules: RuleEngine,
+    schedule: Scheduler,
+    scenes: Vec<Scene>,
+    events: EventBus,
+    journal: Journal,
+    power: PowerBudgets,
+    alarms: AlarmMonitor,
+    /// Вложенность изменений, после которых правила и тревоги проверяются один раз в конце
+    held_reactions: usize,
+    /// Девайсы менялись, пока проверка правил и тревог была отложена
+    reactions_pending: bool,
 }
 
 impl SmartHouse {
     pub fn new_empty(name: &str) -> Self {
         Self {
             name: name.to_owned(),
-            room_names: Vec::new(),
-            devices: HashMap::new(),
+            locations: Vec::new(),
+            device_names: DeviceNamePolicy::default(),
+            temperature_unit: TemperatureUnit::default(),
+            rules: RuleEngine::default(),
+            schedule: Scheduler::default(),
+            scenes: Vec::new(),
+            events: EventBus::default(),
+            journal: Journal::default(),
+            power: PowerBudgets::default(),
+            alarms: AlarmMonitor::default(),
+            held_reactions: 0,
+            reactions_pending: false,
         }
     }
     /// Конструктор дома
-    pub fn new(name: &str, devices: HashMap<&str, Vec<&str>>) -> Self {
-        let room_names = devices.keys().map(|n| (*n).to_owned()).collect();
-
-        let house_devices = devices
-            .iter()
-            .map(|(room_name, room_devices)| {
-                (
-                    (*room_name).to_owned(),
-                    (*room_devices).iter().map(|d| (*d).to_owned()).collect(),
-                )
-            })
-            .collect();
-
-        Self {
-            name: name.to_owned(),
-            room_names,
-            devices: house_devices,
+    /// Порядок комнат в `HashMap` не определен, поэтому комнаты сортируются по пути
+    pub fn new(name: &str, devices: HashMap<&str, Vec<Box<dyn Device>>>) -> Self {
+        let mut house = Self::new_empty(name);
+        let mut devices: Vec<_> = devices.into_iter().collect();
+        devices.sort_by(|a, b| a.0.cmp(b.0));
+        for (room, room_devices) in devices {
+            if let Ok(location) = house.ensure_location(room) {
+                location.devices.extend(room_devices);
+            }
         }
+
+        house
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    /// Перечисляем комнаты
+    fn room(&self, room: &str) -> Result<&Location> {
+        location::find(&self.locations, room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))
+    }
+
+    fn room_mut(&mut self, room: &str) -> Result<&mut Location> {
+        location::find_mut(&mut self.locations, room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))
+    }
+
+    /// Находим расположение, создавая недостающие по пути
+    fn ensure_location(&mut self, path: &str) -> Result<&mut Location> {
+        let mut locations = &mut self.locations;
+        let mut segments = location::split_path(path).peekable();
+        if segments.peek().is_none() {
+            return Err(SmartHouseError::RoomNotFound(path.to_owned()));
+        }
+
+        while let Some(segment) = segments.next() {
+            let position = match locations.iter().position(|l| l.name == segment) {
+                Some(position) => position,
+                None => {
+                    locations.push(Location::new(segment));
+                    locations.len() - 1
+                }
+            };
+
+            if segments.peek().is_none() {
+                return Ok(&mut locations[position]);
+            }
+            locations = &mut locations[position].children;
+        }
+
+        unreachable!("path has at least one segment")
+    }
+
+    /// Все расположения в порядке обхода дерева вместе с путями
+    fn walk(&self) -> Vec<(String, &Location)> {
+        let mut out = Vec::new();
+        location::walk(&self.locations, "", &mut out);
+        out
+    }
+
+    pub fn has_room(&self, room: &str) -> bool {
+        self.room(room).is_ok()
+    }
+
+    /// Перечисляем комнаты (расположения без вложенных) в их текущем порядке
     pub fn rooms(&self) -> impl Iterator<Item = String> {
-        self.room_names.clone().into_iter()
+        self.walk()
+            .into_iter()
+            .filter(|(_, l)| l.is_leaf())
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Перечисляем пути всех расположений: этажей, зон и комнат
+    pub fn locations(&self) -> impl Iterator<Item = String> {
+        self.walk()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Перечисляем пути вложенных расположений первого уровня
+    pub fn sub_locations(&self, path: &str) -> Result<impl Iterator<Item = String>> {
+        let path = location::normalize_path(path);
+        Ok(self
+            .room(&path)?
+            .children
+            .iter()
+            .map(|l| location::join_path(&path, &l.name))
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    /// Добавляем новую комнату (или любое расположение) в конец,
+    /// недостающие расположения по пути создаются
+    pub fn add_room(&mut self, room: &str) -> Result<()> {
+        if self.has_room(room) {
+            return Err(SmartHouseError::DuplicateRoom(room.to_owned()));
+        }
+
+        let mut created = String::new();
+        for segment in location::split_path(room) {
+            created = location::join_path(&created, segment);
+            if !self.has_room(&created) {
+                break;
+            }
+        }
+
+        self.ensure_location(room)?;
+        self.events.publish(Event::RoomAdded {
+            room: location::normalize_path(room),
+        });
+        self.journal.record(Operation::AddRoom {
+            room: location::normalize_path(room),
+            created,
+        });
+        Ok(())
+    }
+
+    /// Удаляем сущ-щее расположение вместе с вложенными и их девайсами
+    pub fn delete_room(&mut self, room: &str) -> Result<()> {
+        let name = format!("delete room \"{}\"", location::normalize_path(room));
+        self.journaled(name, |house| {
+            house.drop_power_within(room)?;
+            house.remove_room(room)
+        })
+    }
+
+    fn remove_room(&mut self, room: &str) -> Result<()> {
+        let (_, name) = location::split_last(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let siblings = location::siblings_mut(&mut self.locations, room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let position = siblings
+            .iter()
+            .position(|l| l.name == name)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+
+        let record = storage::RoomRecord::from_location(&siblings.remove(position));
+        self.events.publish(Event::RoomRemoved {
+            room: location::normalize_path(room),
+        });
+        self.journal.record(Operation::DeleteRoom {
+            room: location::normalize_path(room),
+            position,
+            record,
+        });
+        Ok(())
+    }
+
+    /// Переставляем расположение среди соседей на позицию `index`
+    /// (или в конец, если индекс больше числа соседей)
+    pub fn move_room_to(&mut self, room: &str, index: usize) -> Result<()> {
+        let (_, name) = location::split_last(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let siblings = location::siblings_mut(&mut self.locations, room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let position = siblings
+            .iter()
+            .position(|l| l.name == name)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+
+        let before = self.locations().collect();
+        let siblings = location::siblings_mut(&mut self.locations, room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let room = siblings.remove(position);
+        let index = index.min(siblings.len());
+        siblings.insert(index, room);
+        self.record_room_order(before);
+        Ok(())
     }
 
-    /// Добавляем новую комнату, если еще не сущ-т
-    pub fn add_room(&mut self, room: &str) {
-        let room = room.to_owned();
-        if !self.room_names.contains(&room) {
-            self.room_names.push(room.clone());
-            self.devices.insert(room, Vec::new());
+    /// Сортируем расположения на каждом уровне дерева, сравниваются пути
+    pub fn sort_rooms_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&str, &str) -> Ordering,
+    {
+        fn sort<F: FnMut(&str, &str) -> Ordering>(
+            locations: &mut [Location],
+            parent: &str,
+            compare: &mut F,
+        ) {
+            locations.sort_by(|a, b| {
+                compare(
+                    &location::join_path(parent, &a.name),
+                    &location::join_path(parent, &b.name),
+                )
+            });
+            for l in locations {
+                let path = location::join_path(parent, &l.name);
+                sort(&mut l.children, &path, compare);
+            }
         }
+
+        let before = self.locations().collect();
+        sort(&mut self.locations, "", &mut compare);
+        self.record_room_order(before);
     }
 
-    /// Удаляем сущ-щую комнату
-    pub fn delete_room(&mut self, room: &str) {
-        self.room_names.retain(|r| r != room);
-        self.devices.remove(room);
+    /// Записываем перестановку расположений, если порядок изменился
+    fn record_room_order(&mut self, before: Vec<String>) {
+        let after: Vec<String> = self.locations().collect();
+        if before != after {
+            self.journal
+                .record(Operation::ReorderRooms { before, after });
+        }
     }
 
     /// Перечисляем девайсы комнаты
     pub fn devices(&self, room: &str) -> impl Iterator<Item = String> {
-        self.devices
-            .get(room)
-            .cloned()
-            .unwrap_or_default()
+        self.room_devices(room)
+            .map(|d| d.name().to_owned())
+            .collect::<Vec<_>>()
             .into_iter()
     }
 
-    /// Добавляем девайс в сущ-шую комнату
-    /// Если комнаты не сущ-т, то и девайс не добавится
-    pub fn add_device(&mut self, room: &str, device: &str) {
-        self.devices
-            .entry(room.to_owned())
-            .and_modify(|devices| devices.push(device.to_owned()));
+    /// Перечисляем сами девайсы комнаты
+    pub fn room_devices(&self, room: &str) -> impl Iterator<Item = &dyn Device> {
+        self.room(room)
+            .ok()
+            .into_iter()
+            .flat_map(|r| r.devices.iter().map(|d| d.as_ref()))
     }
 
-    /// Удаляем девайс из комнаты, если девайс и комнтата сущ-т
-    pub fn delete_device(&mut self, room: &str, device: &str) {
-        self.devices
-            .entry(room.to_owned())
-            .and_modify(|devices| devices.retain(|d| d != device));
+    /// Единицы, в которых отчеты показывают температуру
+    pub fn temperature_unit(&self) -> TemperatureUnit {
+        self.temperature_unit
     }
 
-    fn create_room_report<I: DeviceInfoProvider>(
-        &self,
-        room_name: &str,
-        room_devices: &[String],
-        info_provider: &I,
-    ) -> Result<String> {
-        let device_reports = room_devices
-            .iter()
-            .flat_map(move |device_name| info_provider.info(room_name, device_name))
-            .fold(Vec::<String>::new(), |mut acc, s| {
-                acc.push(s);
-                acc
+    pub fn set_temperature_unit(&mut self, unit: TemperatureUnit) {
+        if self.temperature_unit != unit {
+            self.journal.record(Operation::SetTemperatureUnit {
+                old: self.temperature_unit,
+                new: unit,
             });
-
-        if device_reports.is_empty() {
-            return Err(SmartHouseError::ReportError);
         }
+        self.temperature_unit = unit;
+    }
 
-        Ok(device_reports.join("\n"))
+    pub fn device_name_policy(&self) -> DeviceNamePolicy {
+        self.device_names
     }
 
-    pub fn create_report<I: DeviceInfoProvider>(&self, info_provider: &I) -> Result<String> {
-        let room_devices_reports = match self
-            .devices
-            .iter()
-            .map(|(room_name, room_devices)| {
-                self.create_room_report(room_name, room_devices, info_provider)
-            })
-            .try_fold(Vec::<String>::new(), |mut acc, x| match x {
-                Ok(s) => {
-                    acc.push(s);
-                    ControlFlow::Continue(acc)
+    /// Меняем политику уникальности имен девайсов.
+    /// Включить уникальность во всем доме нельзя, пока в нем есть одноименные девайсы.
+    pub fn set_device_name_policy(&mut self, policy: DeviceNamePolicy) -> Result<()> {
+        if policy == DeviceNamePolicy::HouseWide {
+            let mut seen = HashSet::new();
+            for (path, location) in self.walk() {
+                for device in &location.devices {
+                    if !seen.insert(device.name()) {
+                        return Err(SmartHouseError::DuplicateDevice {
+                            room: path,
+                            device: device.name().to_owned(),
+                        });
+                    }
                 }
-                Err(err) => ControlFlow::Break(err),
-            }) {
-            ControlFlow::Break(err) => return Err(err),
-            ControlFlow::Continue(v) => v,
+            }
+        }
+
+        if self.device_names != policy {
+            self.journal.record(Operation::SetDeviceNamePolicy {
+                old: self.device_names,
+                new: policy,
+            });
+        }
+        self.device_names = policy;
+        Ok(())
+    }
+
+    /// Пути расположений, в которых есть девайс с таким именем
+    pub fn device_rooms(&self, device: &str) -> impl Iterator<Item = String> {
+        self.walk()
+            .into_iter()
+            .filter(|(_, l)| l.devices.iter().any(|d| d.name() == device))
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Проверяем, что имя девайса годится для пути и не занято согласно политике уникальности
+    fn check_device_name(&self, room: &str, device: &str) -> Result<()> {
+        if device.is_empty() || device.contains(location::PATH_SEPARATOR) {
+            return Err(SmartHouseError::InvalidName(device.to_owned()));
+        }
+
+        let taken_in = match self.device_names {
+            DeviceNamePolicy::PerRoom => self.device(room, device).map(|_| room.to_owned()),
+            DeviceNamePolicy::HouseWide => self.device_rooms(device).next(),
         };
 
-        Ok(room_devices_reports.join("\n"))
+        match taken_in {
+            Some(room) => Err(SmartHouseError::DuplicateDevice {
+                room,
+                device: device.to_owned(),
+            }),
+            None => Ok(()),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Добавляем девайс в конец сущ-шей комнаты
+    pub fn add_device(&mut self, room: &str, device: Box<dyn Device>) -> Result<()> {
+        self.room(room)?;
+        self.check_device_name(room, device.name())?;
+        let room = location::normalize_path(room);
+        let path = location::join_path(&room, device.name());
+        let event = Event::DeviceAdded {
+            room: room.clone(),
+            device: device.name().to_owned(),
+            kind: device.kind(),
+        };
+        let operation = Operation::AddDevice {
+            room: room.clone(),
+            device: storage::DeviceRecord::of(device.as_ref(), None),
+        };
+        self.room_mut(&room)?.devices.push(device);
 
-    struct TestOkInfoProvider {}
+        // Включенная розетка подчиняется бюджетам, как при включении.
+        // Выключенные ради нее розетки отменяются вместе с добавлением.
+        self.journaled(format!("add device \"{path}\""), |house| {
+            if let Err(error) = house.admit_power(&path) {
+                house.room_mut(&room)?.devices.pop();
+                return Err(error);
+            }
+            house.events.publish(event);
+            house.journal.record(operation);
+            Ok(())
+        })
+    }
 
-    impl DeviceInfoProvider for TestOkInfoProvider {
-        fn info(&self, location_name: &str, device_name: &str) -> Option<String> {
-            Some(format!(
-                "location: {}, device: {}",
-                location_name, device_name
-            ))
+    /// Переносим девайс в конец другой комнаты
+    pub fn move_device(&mut self, from: &str, to: &str, device: &str) -> Result<()> {
+        self.room(to)?;
+        self.room_devices_mut(from, device)?;
+        if location::normalize_path(from) == location::normalize_path(to) {
+            return Ok(());
+        }
+        if self.device(to, device).is_some() {
+            return Err(SmartHouseError::DuplicateDevice {
+                room: to.to_owned(),
+                device: device.to_owned(),
+            });
         }
+
+        let (from, to) = (location::normalize_path(from), location::normalize_path(to));
+        let position = self.relocate_device(&from, &to, device, None)?;
+        let event = Event::DeviceMoved {
+            from: from.clone(),
+            to: to.clone(),
+            device: device.to_owned(),
+            kind: self
+                .device(&to, device)
+                .expect("device was just moved")
+                .kind(),
+        };
+        let operation = Operation::MoveDevice {
+            from: from.clone(),
+            to: to.clone(),
+            device: device.to_owned(),
+            position,
+        };
+
+        // На новом месте розетка подчиняется бюджетам, как при включении
+        let path = location::join_path(&to, device);
+        self.journaled(operation.to_string(), |house| {
+            if let Err(error) = house.admit_power(&path) {
+                house.relocate_device(&to, &from, device, Some(position))?;
+                return Err(error);
+            }
+            house.events.publish(event);
+            house.journal.record(operation);
+            Ok(())
+        })
     }
 
-    struct TestFailInfoProvider {}
+    /// Перекладываем девайс с тегами в комнату `to` на место `position` (или в конец)
+    /// и переносим ссылки на него. Возвращаем его прежнее место в `from`.
+    fn relocate_device(
+        &mut self,
+        from: &str,
+        to: &str,
+        device: &str,
+        position: Option<usize>,
+    ) -> Result<usize> {
+        let (devices, old_position) = self.room_devices_mut(from, device)?;
+        let device = devices.remove(old_position);
+        let tags = self.room_mut(from)?.tags.remove(device.name());
+        let name = device.name().to_owned();
+        let room = self.room_mut(to)?;
+        if let Some(tags) = tags {
+            room.tags.insert(name.clone(), tags);
+        }
+        let position = position
+            .unwrap_or(room.devices.len())
+            .min(room.devices.len());
+        room.devices.insert(position, device);
+        self.rekey_devices(&[(
+            location::join_path(from, &name),
+            location::join_path(to, &name),
+        )]);
+        Ok(old_position)
+    }
 
-    impl DeviceInfoProvider for TestFailInfoProvider {
-        fn info(&self, _location_name: &str, _device_name: &str) -> Option<String> {
-            None
+    /// Переименовываем девайс, не меняя его места в комнате
+    pub fn rename_device(&mut self, room: &str, device: &str, new_name: &str) -> Result<()> {
+        self.room_devices_mut(room, device)?;
+        if device == new_name {
+            return Ok(());
+        }
+        self.check_device_name(room, new_name)?;
+
+        let (devices, position) = self.room_devices_mut(room, device)?;
+        devices[position].set_name(new_name);
+        let kind = devices[position].kind();
+        let location = self.room_mut(room)?;
+        if let Some(tags) = location.tags.remove(device) {
+            location.tags.insert(new_name.to_owned(), tags);
         }
+        let room_path = location::normalize_path(room);
+        self.rekey_devices(&[(
+            location::join_path(&room_path, device),
+            location::join_path(&room_path, new_name),
+        )]);
+        self.events.publish(Event::DeviceRenamed {
+            room: location::normalize_path(room),
+            device: device.to_owned(),
+            new_name: new_name.to_owned(),
+            kind,
+        });
+        self.journal.record(Operation::RenameDevice {
+            room: location::normalize_path(room),
+            device: device.to_owned(),
+            new_name: new_name.to_owned(),
+        });
+        Ok(())
     }
 
-    #[test]
-    fn test_create_report_ok() {
-        let house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Меняем описание девайса
+    pub fn describe_device(&mut self, room: &str, device: &str, description: &str) -> Result<()> {
+        let (devices, position) = self.room_devices_mut(room, device)?;
+        let old = devices[position].description().to_owned();
+        if old == description {
+            return Ok(());
+        }
 
-        let info_provider = TestOkInfoProvider {};
+        devices[position].set_description(description);
+        self.journal.record(Operation::DescribeDevice {
+            room: location::normalize_path(room),
+            device: device.to_owned(),
+            old,
+            new: description.to_owned(),
+        });
+        Ok(())
+    }
 
-        let report = house.create_report(&info_provider).unwrap();
-        let mut report_lines = report.split('\n').collect::<Vec<&str>>();
-        report_lines.sort();
-        let report = report_lines.join("\n");
+    /// Переименовываем расположение (последний сегмент пути) вместе со всем поддеревом
+    pub fn rename_room(&mut self, room: &str, new_name: &str) -> Result<()> {
+        if new_name.is_empty() || new_name.contains(location::PATH_SEPARATOR) {
+            return Err(SmartHouseError::InvalidName(new_name.to_owned()));
+        }
 
-        assert_eq!(
-            r#"location: room1, device: room1_socket_1
-location: room1, device: room1_thermo_1
-location: room2, device: room2_socket_2"#,
-            report
-        )
+        let (parent, name) = location::split_last(room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let siblings = location::siblings_mut(&mut self.locations, room)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        let position = siblings
+            .iter()
+            .position(|l| l.name == name)
+            .ok_or_else(|| SmartHouseError::RoomNotFound(room.to_owned()))?;
+        if name == new_name {
+            return Ok(());
+        }
+        if siblings.iter().any(|l| l.name == new_name) {
+            return Err(SmartHouseError::DuplicateRoom(location::join_path(
+                parent, new_name,
+            )));
+        }
+
+        siblings[position].name = new_name.to_owned();
+        let old_room = location::normalize_path(room);
+        let new_room = location::join_path(parent, new_name);
+        let moved: Vec<(String, String)> = self
+            .walk()
+            .into_iter()
+            .filter(|(path, _)| location::is_within(path, &new_room))
+            .flat_map(|(path, l)| {
+                let old_path = format!("{old_room}{}", &path[new_room.len()..]);
+                l.devices
+                    .iter()
+                    .map(move |d| {
+                        (
+                            location::join_path(&old_path, d.name()),
+                            location::join_path(&path, d.name()),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        self.rekey_devices(&moved);
+        self.power.rekey_rooms(&old_room, &new_room);
+        self.events.publish(Event::RoomRenamed {
+            room: location::normalize_path(room),
+            new_name: new_name.to_owned(),
+        });
+        self.journal.record(Operation::RenameRoom {
+            room: location::normalize_path(room),
+            new_name: new_name.to_owned(),
+        });
+        Ok(())
     }
 
-    #[test]
-    fn test_create_report_fail() {
-        let house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Девайсы переехали со старых путей на новые: переносим ссылки на них
+    /// в правилах, расписании, сценах и тревогах и приоритеты розеток. Ссылка по имени
+    /// меняется, только если девайса со старым именем в доме больше нет.
+    fn rekey_devices(&mut self, moved: &[(String, String)]) {
+        for (old, new) in moved {
+            self.power.rekey_device(old, new);
+            let (_, old_name) = location::split_last(old).unwrap_or_default();
+            let (_, new_name) = location::split_last(new).unwrap_or_default();
+            let by_name = old_name != new_name && self.device_rooms(old_name).next().is_none();
+            let rekey = |reference: &mut String| {
+                if location::normalize_path(reference) == *old {
+                    *reference = new.clone();
+                } else if by_name && reference == old_name {
+                    *reference = new_name.to_owned();
+                }
+            };
 
-        let info_provider = TestFailInfoProvider {};
+            self.rules.device_references_mut().for_each(rekey);
+            self.schedule.device_references_mut().for_each(rekey);
+            self.alarms.device_references_mut().for_each(rekey);
+            self.scenes
+                .iter_mut()
+                .flat_map(|scene| scene.targets.iter_mut().map(|t| &mut t.device))
+                .for_each(rekey);
+        }
+    }
 
-        let report = house.create_report(&info_provider);
-        assert_eq!(Err(SmartHouseError::ReportError), report)
+    fn room_devices_mut(
+        &mut self,
+        room: &str,
+        device: &str,
+    ) -> Result<(&mut Vec<Box<dyn Device>>, usize)> {
+        let room_name = room;
+        let room = self.room_mut(room_name)?;
+        let position = room
+            .devices
+            .iter()
+            .position(|d| d.name() == device)
+            .ok_or_else(|| SmartHouseError::DeviceNotFound {
+                room: room_name.to_owned(),
+                device: device.to_owned(),
+            })?;
+
+        Ok((&mut room.devices, position))
     }
 
-    #[test]
-    fn test_get_rooms() {
-        let house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Удаляем девайс из комнаты и возвращаем его
+    pub fn delete_device(&mut self, room: &str, device: &str) -> Result<Box<dyn Device>> {
+        let path = location::join_path(&location::normalize_path(room), device);
+        self.journaled(format!("delete device \"{path}\""), |house| {
+            if house.socket(room, device).is_some() {
+                house.set_load_priority(&path, None)?;
+            }
+            house.remove_device(room, device)
+        })
+    }
+
+    fn remove_device(&mut self, room: &str, device: &str) -> Result<Box<dyn Device>> {
+        let (devices, position) = self.room_devices_mut(room, device)?;
+        let device = devices.remove(position);
+        let tags = self.room_mut(room)?.tags.remove(device.name());
+        self.events.publish(Event::DeviceRemoved {
+            room: location::normalize_path(room),
+            device: device.name().to_owned(),
+            kind: device.kind(),
+        });
+        self.journal.record(Operation::DeleteDevice {
+            room: location::normalize_path(room),
+            position,
+            device: storage::DeviceRecord::of(device.as_ref(), tags.as_ref()),
+        });
+        Ok(device)
+    }
 
-        let mut rooms: Vec<String> = house.rooms().collect();
-        rooms.sort();
+    /// Переставляем девайс внутри комнаты на позицию `index` (или в конец)
+    pub fn move_device_to(&mut self, room: &str, device: &str, index: usize) -> Result<()> {
+        let before = self.devices(room).collect();
+        let (devices, position) = self.room_devices_mut(room, device)?;
 
-        assert_eq!(vec!["room1", "room2"], rooms);
+        let device = devices.remove(position);
+        let index = index.min(devices.len());
+        devices.insert(index, device);
+        self.record_device_order(room, before);
+        Ok(())
     }
 
-    #[test]
-    fn test_add_room() {
-        let mut house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Сортируем девайсы комнаты
+    pub fn sort_devices_by<F>(&mut self, room: &str, mut compare: F) -> Result<()>
+    where
+        F: FnMut(&dyn Device, &dyn Device) -> Ordering,
+    {
+        let before = self.devices(room).collect();
+        let location = self.room_mut(room)?;
+        location
+            .devices
+            .sort_by(|a, b| compare(a.as_ref(), b.as_ref()));
+        self.record_device_order(room, before);
+        Ok(())
+    }
 
-        house.add_room("room3");
+    /// Записываем перестановку девайсов комнаты, если порядок изменился
+    fn record_device_order(&mut self, room: &str, before: Vec<String>) {
+        let after: Vec<String> = self.devices(room).collect();
+        if before != after {
+            self.journal.record(Operation::ReorderDevices {
+                room: location::normalize_path(room),
+                before,
+                after,
+            });
+        }
+    }
 
-        let mut rooms: Vec<String> = house.rooms().collect();
-        rooms.sort();
+    /// Девайс по полному пути, например `"floor2/kitchen/socket1"`
+    pub fn device_by_path(&self, path: &str) -> Option<&dyn Device> {
+        let (room, device) = location::split_last(path)?;
+        self.device(room, device)
+    }
 
-        assert_eq!(vec!["room1", "room2", "room3"], rooms);
+    pub fn device_by_path_mut(&mut self, path: &str) -> Option<&mut (dyn Device + 'static)> {
+        let (room, device) = location::split_last(path)?;
+        self.device_mut(room, device)
     }
 
-    #[test]
-    fn test_delete_room() {
-        let mut house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Путь девайса по пути или по имени, если девайс с таким именем в доме один
+    pub fn resolve_device(&self, device: &str) -> Option<String> {
+        if self.device_by_path(device).is_some() {
+            return Some(device.to_owned());
+        }
 
-        house.delete_room("room1");
+        let mut rooms = self.device_rooms(device);
+        match (rooms.next(), rooms.next()) {
+            (Some(room), None) => Some(location::join_path(&room, device)),
+            _ => None,
+        }
+    }
 
-        let rooms: Vec<String> = house.rooms().collect();
-        assert_eq!(vec!["room2"], rooms);
+    /// Девайс комнаты по имени
+    pub fn device(&self, room: &str, device: &str) -> Option<&dyn Device> {
+        self.room(room)
+            .ok()?
+            .devices
+            .iter()
+            .find(|d| d.name() == device)
+            .map(|d| d.as_ref())
     }
 
-    #[test]
-    fn test_get_devices() {
-        let house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    pub fn device_mut(&mut self, room: &str, device: &str) -> Option<&mut (dyn Device + 'static)> {
+        self.room_mut(room)
+            .ok()?
+            .devices
+            .iter_mut()
+            .find(|d| d.name() == device)
+            .map(|d| d.as_mut())
+    }
 
-        let mut devices: Vec<String> = house.devices("room1").collect();
-        devices.sort();
+    /// Розетка комнаты по имени, если девайс с таким именем - розетка
+    pub fn socket(&self, room: &str, device: &str) -> Option<&SmartSocket> {
+        self.device(room, device)?.as_any().downcast_ref()
+    }
 
-        assert_eq!(vec!["room1_socket_1", "room1_thermo_1"], devices);
+    pub fn socket_mut(&mut self, room: &str, device: &str) -> Option<&mut SmartSocket> {
+        self.device_mut(room, device)?.as_any_mut().downcast_mut()
     }
 
-    #[test]
-    fn test_add_device() {
-        let mut house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                ("room1", vec!["room1_socket_1", "room1_thermo_1"]),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Термометр комнаты по имени, если девайс с таким именем - термометр
+    pub fn thermometer(&self, room: &str, device: &str) -> Option<&SmartThermometer> {
+        self.device(room, device)?.as_any().downcast_ref()
+    }
 
-        house.add_device("room1", "room1_socket_3");
-        let mut devices: Vec<String> = house.devices("room1").collect();
-        devices.sort();
+    pub fn thermometer_mut(&mut self, room: &str, device: &str) -> Option<&mut SmartThermometer> {
+        self.device_mut(room, device)?.as_any_mut().downcast_mut()
+    }
 
-        assert_eq!(
-            vec!["room1_socket_1", "room1_socket_3", "room1_thermo_1",],
-            devices
-        );
+    /// Меняем состояние девайса и сразу проверяем правила автоматизации и тревоги,
+    /// в ответе - сработавшие правила
+    pub fn update_device<F>(&mut self, room: &str, device: &str, update: F) -> Result<Vec<Firing>>
+    where
+        F: FnOnce(&mut dyn Device),
+    {
+        self.room_devices_mut(room, device)?;
+        let path = location::join_path(room, device);
+        self.without_reactions(|house| house.change_device(&path, update))?;
+        Ok(self.react().0)
+    }
 
-        house.add_device("room3", "room3_socket_1");
+    /// Меняем девайс по пути и публикуем `DeviceStateChanged`, если состояние изменилось.
+    /// Если розетка стала потреблять больше, проверяются бюджеты мощности: при отказе
+    /// девайс возвращается в прежнее состояние. После изменения проверяются правила
+    /// и тревоги, внутри пакета журнала - один раз в его конце. Изменения через
+    /// `device_mut` и похожие методы в шину не попадают, бюджеты и правила не проверяют.
+    pub fn change_device<F, R>(&mut self, path: &str, change: F) -> Result<R>
+    where
+        F: FnOnce(&mut dyn Device) -> R,
+    {
+        self.change_device_checked(path, change)
+            .map(|(result, _)| result)
+    }
 
-        let mut rooms: Vec<String> = house.rooms().collect();
-        rooms.sort();
-        assert_eq!(vec!["room1", "room2"], rooms);
+    /// То же, что `change_device`, но еще возвращаем превышения бюджетов,
+    /// которые пропустила их политика
+    fn change_device_checked<F, R>(&mut self, path: &str, change: F) -> Result<(R, Vec<Overload>)>
+    where
+        F: FnOnce(&mut dyn Device) -> R,
+    {
+        let (room, name) = location::split_last(path)
+            .ok_or_else(|| SmartHouseError::InvalidName(path.to_owned()))?;
+        let not_found = || SmartHouseError::DeviceNotFound {
+            room: room.to_owned(),
+            device: name.to_owned(),
+        };
+        let device = self.device_by_path_mut(path).ok_or_else(not_found)?;
+        let old = device.state();
+        let result = change(device);
+        let new = device.state();
 
-        let devices: Vec<String> = house.devices("room3").collect();
-        assert_eq!(Vec::<String>::new(), devices);
+        // Выключенные ради бюджета розетки отменяются вместе с изменением
+        let name = format!("change device \"{}\"", location::normalize_path(path));
+        self.journaled(name, |house| {
+            let mut overloads = Vec::new();
+            if power::draw(&new) > power::draw(&old) {
+                match house.check_power(path) {
+                    Ok((found, shed)) => {
+                        house.enforce_power(path, &found, &shed)?;
+                        overloads = found;
+                    }
+                    Err(error) => {
+                        let device = house.device_by_path_mut(path).ok_or_else(not_found)?;
+                        journal::set_state(device, &old);
+                        return Err(error);
+                    }
+                }
+            }
+
+            let device = house.device_by_path(path).ok_or_else(not_found)?;
+            house
+                .events
+                .publish_state_change(Some(room), device, old.clone());
+            if !old.same_as(&new) {
+                house.journal.record(Operation::ChangeDevice {
+                    device: location::normalize_path(path),
+                    old,
+                    new,
+                });
+            }
+            house.reactions_pending = true;
+            Ok((result, overloads))
+        })
     }
 
-    #[test]
-    fn test_add_device_to_empty() {
-        let mut house = SmartHouse::new_empty("my smart house");
+    /// Шина событий дома, ее клоны можно раздать подписчикам
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
 
-        house.add_room("room1");
-        house.add_device("room1", "device1");
-        let mut devices: Vec<String> = house.devices("room1").collect();
-        devices.sort();
+    /// Правила автоматизации дома
+    pub fn rules(&self) -> &RuleEngine {
+        &self.rules
+    }
 
-        assert_eq!(vec!["device1"], devices);
+    /// Проверяем правила по текущему состоянию и выполняем действия сработавших.
+    /// Изменения от действий правила повторно не запускают.
+    pub fn evaluate_rules(&mut self) -> Vec<Firing> {
+        let mut rules = std::mem::take(&mut self.rules);
+        let firings = self.without_reactions(|house| rules.evaluate(house));
+        self.rules = rules;
+        firings
     }
 
-    #[test]
-    fn test_delete_device() {
-        let mut house = SmartHouse::new(
-            "my smart house",
-            HashMap::from([
-                (
-                    "room1",
-                    vec!["room1_socket_1", "room1_thermo_1", "room1_socket_3"],
-                ),
-                ("room2", vec!["room2_socket_2"]),
-            ]),
-        );
+    /// Правила и тревоги внутри `change` не проверяются, даже если девайсы менялись
+    pub(crate) fn without_reactions<F, R>(&mut self, change: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        self.held_reactions += 1;
+        let result = change(self);
+        self.held_reactions -= 1;
+        result
+    }
+
+    /// Проверяем правила и тревоги, если девайсы менялись и проверка не отложена
+    pub(crate) fn settle(&mut self) {
+        if self.held_reactions == 0 && self.reactions_pending {
+            self.react();
+        }
+    }
 
-        house.delete_device("room1", "room1_socket_1");
-        house.delete_device("room2", "room2_socket_2");
-        house.delete_device("room3", "some_unexisting_device");
+    /// Проверяем правила, затем тревоги - уже с изменениями от действий правил
+    pub(crate) fn react(&mut self) -> (Vec<Firing>, Vec<Alert>) {
+        let firings = self.evaluate_rules();
+        let alerts = self.check_alarms();
+        self.reactions_pending = false;
+        (firings, alerts)
+    }
 
-        let mut devices: Vec<String> = house.devices("room1").collect();
-        devices.sort();
-        assert_eq!(vec!["room1_socket_3", "room1_thermo_1",], devices);
+    /// Какие правила сработали бы сейчас, без изменения дома
+    pub fn dry_run_rules(&self) -> Vec<Firing> {
+        self.rules.dry_run(self)
+    }
 
-        let mut devices: Vec<String> = house.devices("room2").collect();
-        devices.sort();
-        assert_eq!(Vec::<String>::new(), devices);
+    /// Расписания розеток дома
+    pub fn schedule(&self) -> &Scheduler {
+        &self.schedule
+    }
 
-        let mut devices: Vec<String> = house.devices("room3").collect();
-        devices.sort();
-        assert_eq!(Vec::<String>::new(), devices);
+    /// Выполняем действия расписаний, время которых наступило с прошлой проверки
+    pub fn run_schedule(&mut self) -> Vec<ScheduledRun> {
+        let mut schedule = std::mem::take(&mut self.schedule);
+        let runs = schedule.run_due(self);
+        self.schedule = schedule;
+        runs
+    }
 
-        let mut rooms: Vec<String> = house.rooms().collect();
-        rooms.sort();
-        assert_eq!(vec!["room1", "room2"], rooms);
+    /// Пороги тревог дома и получатели тревог
+    pub fn alarms(&self) -> &AlarmMonitor {
+        &self.alarms
+    }
+
+    /// Проверяем пороги тревог и рассылаем поднятые и снятые тревоги получателям
+    pub fn check_alarms(&mut self) -> Vec<Alert> {
+        let mut alarms = std::mem::take(&mut self.alarms);
+        let alerts = alarms.check(self);
+        self.alarms = alarms;
+        alerts
+    }
+
+    /// Подменяем часы розеток, термометров, датчиков, правил, расписаний и тревог дома.
+    /// Счетчики розеток продолжают копить энергию по новым часам,
+    /// история термометров начинается заново.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.rules = std::mem::take(&mut self.rules).with_clock(clock.clone());
+        self.schedule = std::mem::take(&mut self.schedule).with_clock(clock.clone());
+        self.alarms = std::mem::take(&mut self.alarms).with_clock(clock.clone());
+
+        let paths: Vec<String> = self.walk().into_iter().map(|(path, _)| path).collect();
+        for path in paths {
+            let Ok(location) = self.room_mut(&path) else {
+                continue;
+            };
+            for device in &mut location.devices {
+                let any = device.as_any_mut();
+                if let Some(socket) = any.downcast_mut::<SmartSocket>() {
+                    *socket = socket.clone().with_clock(clock.clone());
+                } else if let Some(thermometer) = any.downcast_mut::<SmartThermometer>() {
+                    *thermometer = thermometer.clone().with_clock(clock.clone());
+                } else if let Some(sensor) = any.downcast_mut::<SmartSensor>() {
+                    *sensor = sensor.clone().with_clock(clock.clone());
+                }
+            }
+        }
+    }
+
+    /// Расположения, попадающие в отчет: комнаты и расположения с девайсами
+    fn report_locations(&self, root: Option<&str>) -> Result<Vec<(String, &Location)>> {
+        if let Some(root) = root {
+            self.room(root)?;
+        }
+
+        Ok(self
+            .walk()
+            .into_iter()
+            .filter(|(path, _)| root.is_none_or(|root| location::is_within(path, root)))
+            .filter(|(_, l)| l.is_leaf() || !l.devices.is_empty())
+            .collect())
+    }
+
+    /// Девайсы комнаты в отчете, общие для текстового и структурного отчета.
+    /// Неописанные девайсы попадают в `missing`, а в отчет - только в мягком режиме. `None` - комнату пропускаем:
+    /// в ней нет девайсов с тегами из настроек.
+    fn report_devices<'a, I: DeviceInfoProvider>(
+        &self,
+        room_name: &str,
+        room: &'a Location,
+        info_provider: &I,
+        options: &ReportOptions,
+        missing: &mut Vec<MissingDevice>,
+    ) -> Result<Option<Vec<ReportedDevice<'a>>>> {
+        let devices: Vec<&dyn Device> = match &options.tags {
+            Some(query) => query.filter(room).collect(),
+            None => room.devices.iter().map(|d| d.as_ref()).collect(),
+        };
+        if options.tags.is_some() && devices.is_empty() {
+            return Ok(None);
+        }
+
+        let mut described = Vec::new();
+        for device in &devices {
+            let info = info_provider.info(room_name, device.name());
+            if info.is_none() {
+                missing.push(MissingDevice {
+                    room: room_name.to_owned(),
+                    device: device.name().to_owned(),
+                });
+                if options.mode == ReportMode::Strict {
+                    continue;
+                }
+            }
+            described.push((*device, info));
+        }
+
+        if options.mode == ReportMode::Strict && described.is_empty() {
+            return Err(SmartHouseError::ReportError {
+                room: room_name.to_owned(),
+                device: devices.first().map(|d| d.name().to_owned()),
+            });
+        }
+
+        Ok(Some(described))
+    }
+
+    pub fn create_report<I: DeviceInfoProvider>(&self, info_provider: &I) -> Result<String> {
+        self.create_report_with(info_provider, &ReportOptions::default())
+            .map(|outcome| outcome.report)
+    }
+
+    /// Отчет с настройками. В строгом режиме комната, ни один девайс которой не описан,
+    /// ломает весь отчет, в мягком - такие девайсы помечаются недоступными.
+    /// В обоих режимах возвращается список неописанных девайсов.
+    pub fn create_report_with<I: DeviceInfoProvider>(
+        &self,
+        info_provider: &I,
+        options: &ReportOptions,
+    ) -> Result<ReportOutcome> {
+        let mut missing = Vec::new();
+        let mut device_reports = Vec::new();
+        for (path, room) in self.report_locations(options.location.as_deref())? {
+            let Some(devices) =
+                self.report_devices(&path, room, info_provider, options, &mut missing)?
+            else {
+                continue;
+            };
+            device_reports.extend(devices.into_iter().map(|(device, info)| {
+                info.unwrap_or_else(|| unavailable_device_info(&path, device))
+            }));
+        }
+
+        Ok(ReportOutcome {
+            report: device_reports.join("\n"),
+            missing,
+        })
+    }
+
+    /// Структурный отчет, который можно отрисовать любым `ReportRenderer`
+    pub fn report<I: DeviceInfoProvider>(&self, info_provider: &I) -> Result<Report> {
+        self.report_of(None, info_provider)
+    }
+
+    /// Структурный отчет по поддереву расположений
+    pub fn report_of<I: DeviceInfoProvider>(
+        &self,
+        location: Option<&str>,
+        info_provider: &I,
+    ) -> Result<Report> {
+        let options = ReportOptions {
+            location: location.map(str::to_owned),
+            ..ReportOptions::strict()
+        };
+        self.report_with(info_provider, &options)
+    }
+
+    /// Структурный отчет с настройками. Комнаты, в которых не описан ни один девайс,
+    /// в строгом режиме ломают отчет, а в мягком неописанные девайсы помечаются
+    /// недоступными. В обоих режимах неописанные девайсы попадают в `missing`.
+    pub fn report_with<I: DeviceInfoProvider>(
+        &self,
+        info_provider: &I,
+        options: &ReportOptions,
+    ) -> Result<Report> {
+        let mut rooms = Vec::new();
+        let mut missing = Vec::new();
+        for (path, room) in self.report_locations(options.location.as_deref())? {
+            let Some(devices) =
+                self.report_devices(&path, room, info_provider, options, &mut missing)?
+            else {
+                continue;
+            };
+            // Записи строятся по девайсам самого дома, поставщик только решает,
+            // какие из них описаны
+            let devices = devices
+                .into_iter()
+                .map(|(device, info)| match info {
+                    Some(_) => DeviceEntry::from_device(device),
+                    None => DeviceEntry::unavailable(device),
+                })
+                .collect();
+            rooms.push(RoomReport {
+                name: path,
+                devices,
+            });
+        }
+
+        Ok(Report {
+            house: self.name.clone(),
+            temperature_unit: self.temperature_unit,
+            rooms,
+            missing,
+        })
+    }
+}
+
+/// Дом сам является поставщиком информации о своих девайсах
+impl DeviceInfoProvider for SmartHouse {
+    fn info(&self, location_name: &str, device_name: &str) -> Option<String> {
+        self.device(location_name, device_name)
+            .map(|device| device_info(location_name, device))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use device::{info::OwningDeviceInfoProvider, temperature::Temperature};
+
+    fn socket(name: &str) -> Box<dyn Device> {
+        Box::new(SmartSocket::new(name, "test socket", true, 220.0))
+    }
+
+    fn thermo(name: &str) -> Box<dyn Device> {
+        Box::new(SmartThermometer::new(name, "test thermometer", 20.0))
+    }
+
+    struct TestOkInfoProvider {}
+
+    impl DeviceInfoProvider for TestOkInfoProvider {
+        fn info(&self, location_name: &str, device_name: &str) -> Option<String> {
+            Some(format!(
+                "location: {}, device: {}",
+                location_name, device_name
+            ))
+        }
+    }
+
+    struct TestFailInfoProvider {}
+
+    impl DeviceInfoProvider for TestFailInfoProvider {
+        fn info(&self, _location_name: &str, _device_name: &str) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_create_report_ok() {
+        let house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        let info_provider = TestOkInfoProvider {};
+
+        let report = house.create_report(&info_provider).unwrap();
+
+        assert_eq!(
+            r#"location: room1, device: room1_socket_1
+location: room1, device: room1_thermo_1
+location: room2, device: room2_socket_2"#,
+            report
+        )
+    }
+
+    #[test]
+    fn test_create_report_fail() {
+        let house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        let info_provider = TestFailInfoProvider {};
+
+        let report = house.create_report(&info_provider);
+        assert_eq!(
+            Err(SmartHouseError::ReportError {
+                room: "room1".to_owned(),
+                device: Some("room1_socket_1".to_owned())
+            }),
+            report
+        )
+    }
+
+    #[test]
+    fn test_get_rooms() {
+        let house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        let rooms: Vec<String> = house.rooms().collect();
+
+        assert_eq!(vec!["room1", "room2"], rooms);
+    }
+
+    #[test]
+    fn test_add_room() {
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        house.add_room("room3").unwrap();
+        assert_eq!(
+            Err(SmartHouseError::DuplicateRoom("room3".to_owned())),
+            house.add_room("room3")
+        );
+
+        let rooms: Vec<String> = house.rooms().collect();
+
+        assert_eq!(vec!["room1", "room2", "room3"], rooms);
+    }
+
+    #[test]
+    fn test_delete_room() {
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        house.delete_room("room1").unwrap();
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("room1".to_owned())),
+            house.delete_room("room1")
+        );
+
+        let rooms: Vec<String> = house.rooms().collect();
+        assert_eq!(vec!["room2"], rooms);
+    }
+
+    #[test]
+    fn test_get_devices() {
+        let house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        let devices: Vec<String> = house.devices("room1").collect();
+
+        assert_eq!(vec!["room1_socket_1", "room1_thermo_1"], devices);
+    }
+
+    #[test]
+    fn test_add_device() {
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        house.add_device("room1", socket("room1_socket_3")).unwrap();
+        let devices: Vec<String> = house.devices("room1").collect();
+
+        assert_eq!(
+            vec!["room1_socket_1", "room1_thermo_1", "room1_socket_3"],
+            devices
+        );
+
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("room3".to_owned())),
+            house.add_device("room3", socket("room3_socket_1"))
+        );
+        assert_eq!(
+            Err(SmartHouseError::DuplicateDevice {
+                room: "room1".to_owned(),
+                device: "room1_socket_3".to_owned()
+            }),
+            house.add_device("room1", thermo("room1_socket_3"))
+        );
+
+        let rooms: Vec<String> = house.rooms().collect();
+        assert_eq!(vec!["room1", "room2"], rooms);
+
+        let devices: Vec<String> = house.devices("room3").collect();
+        assert_eq!(Vec::<String>::new(), devices);
+    }
+
+    #[test]
+    fn test_add_device_to_empty() {
+        let mut house = SmartHouse::new_empty("my smart house");
+
+        house.add_room("room1").unwrap();
+        house.add_device("room1", socket("device1")).unwrap();
+        let devices: Vec<String> = house.devices("room1").collect();
+
+        assert_eq!(vec!["device1"], devices);
+    }
+
+    #[test]
+    fn test_delete_device() {
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![
+                        socket("room1_socket_1"),
+                        thermo("room1_thermo_1"),
+                        socket("room1_socket_3"),
+                    ],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+            ]),
+        );
+
+        let deleted = house.delete_device("room1", "room1_socket_1").unwrap();
+        assert_eq!("room1_socket_1", deleted.name());
+        house.delete_device("room2", "room2_socket_2").unwrap();
+        assert_eq!(
+            Some(SmartHouseError::RoomNotFound("room3".to_owned())),
+            house.delete_device("room3", "some_unexisting_device").err()
+        );
+        assert_eq!(
+            Some(SmartHouseError::DeviceNotFound {
+                room: "room1".to_owned(),
+                device: "room1_socket_1".to_owned()
+            }),
+            house.delete_device("room1", "room1_socket_1").err()
+        );
+
+        let devices: Vec<String> = house.devices("room1").collect();
+        assert_eq!(vec!["room1_thermo_1", "room1_socket_3"], devices);
+
+        let devices: Vec<String> = house.devices("room2").collect();
+        assert_eq!(Vec::<String>::new(), devices);
+
+        let devices: Vec<String> = house.devices("room3").collect();
+        assert_eq!(Vec::<String>::new(), devices);
+
+        let rooms: Vec<String> = house.rooms().collect();
+        assert_eq!(vec!["room1", "room2"], rooms);
+    }
+
+    #[test]
+    fn test_typed_lookup() {
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([(
+                "room1",
+                vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+            )]),
+        );
+
+        assert!(house.socket("room1", "room1_socket_1").is_some());
+        assert!(house.socket("room1", "room1_thermo_1").is_none());
+        assert!(house.socket("room2", "room1_socket_1").is_none());
+        assert!(house.thermometer("room1", "room1_thermo_1").is_some());
+        assert!(house.thermometer("room1", "room1_socket_1").is_none());
+
+        house
+            .socket_mut("room1", "room1_socket_1")
+            .unwrap()
+            .turn_off();
+        assert!(!house.socket("room1", "room1_socket_1").unwrap().is_on());
+
+        house
+            .thermometer_mut("room1", "room1_thermo_1")
+            .unwrap()
+            .set_temperature(25.5);
+        assert_eq!(
+            25.5,
+            house
+                .thermometer("room1", "room1_thermo_1")
+                .unwrap()
+                .current_temperature()
+        );
+    }
+
+    #[test]
+    fn test_house_as_info_provider() {
+        let house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([("room1", vec![socket("room1_socket_1")])]),
+        );
+
+        assert_eq!(
+            r#"Location: room1
+Device/Socket: 
+  Name: room1_socket_1
+  Description: test socket
+  Current state: on, 220 W, 230 V, 0.96 A, 0.000 kWh"#,
+            house.create_report(&house).unwrap()
+        );
+        assert_eq!(None, house.info("room1", "unknown_device"));
+    }
+
+    #[test]
+    fn test_report() {
+        let mut house = SmartHouse::new_empty("my smart house");
+        house.add_room("room2").unwrap();
+        house.add_room("room1").unwrap();
+        house.add_device("room1", socket("room1_socket_1")).unwrap();
+        house.add_device("room2", thermo("room2_thermo_1")).unwrap();
+
+        let report = house.report(&house).unwrap();
+        assert_eq!("my smart house", report.house);
+        assert_eq!(
+            vec!["room2", "room1"],
+            report.rooms.iter().map(|r| &r.name).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(true), report.rooms[1].devices[0].is_on());
+        assert_eq!(
+            Some(Temperature::celsius(20.0)),
+            report.rooms[0].devices[0].temperature()
+        );
+
+        // Поставщику достаточно описывать девайсы, состояние берется из дома
+        let names = |report: &Report| {
+            report
+                .rooms
+                .iter()
+                .flat_map(|r| r.devices.iter().map(|d| d.name.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&report),
+            names(&house.report(&TestOkInfoProvider {}).unwrap())
+        );
+        assert_eq!(
+            Err(SmartHouseError::ReportError {
+                room: "room2".to_owned(),
+                device: Some("room2_thermo_1".to_owned())
+            }),
+            house.report(&TestFailInfoProvider {})
+        );
+    }
+
+    #[test]
+    fn test_room_order() {
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                ("room2", vec![socket("room2_socket_1")]),
+                ("room3", vec![socket("room3_socket_1")]),
+                ("room1", vec![socket("room1_socket_1")]),
+            ]),
+        );
+        assert_eq!(
+            vec!["room1", "room2", "room3"],
+            house.rooms().collect::<Vec<_>>()
+        );
+
+        house.move_room_to("room3", 0).unwrap();
+        house.move_room_to("room1", 100).unwrap();
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("room4".to_owned())),
+            house.move_room_to("room4", 0)
+        );
+        assert_eq!(
+            vec!["room3", "room2", "room1"],
+            house.rooms().collect::<Vec<_>>()
+        );
+
+        let report = house.create_report(&TestOkInfoProvider {}).unwrap();
+        assert_eq!(
+            r#"location: room3, device: room3_socket_1
+location: room2, device: room2_socket_1
+location: room1, device: room1_socket_1"#,
+            report
+        );
+
+        house.sort_rooms_by(|a, b| a.cmp(b));
+        assert_eq!(
+            vec!["room1", "room2", "room3"],
+            house.rooms().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_device_order() {
+        let mut house = SmartHouse::new_empty("my smart house");
+        house.add_room("room1").unwrap();
+        house.add_device("room1", thermo("b")).unwrap();
+        house.add_device("room1", socket("c")).unwrap();
+        house.add_device("room1", socket("a")).unwrap();
+
+        house.move_device_to("room1", "a", 0).unwrap();
+        assert_eq!(
+            Err(SmartHouseError::DeviceNotFound {
+                room: "room1".to_owned(),
+                device: "d".to_owned()
+            }),
+            house.move_device_to("room1", "d", 0)
+        );
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("room2".to_owned())),
+            house.move_device_to("room2", "a", 0)
+        );
+        assert_eq!(
+            vec!["a", "b", "c"],
+            house.devices("room1").collect::<Vec<_>>()
+        );
+
+        house
+            .sort_devices_by("room1", |a, b| b.name().cmp(a.name()))
+            .unwrap();
+        assert_eq!(
+            vec!["c", "b", "a"],
+            house.devices("room1").collect::<Vec<_>>()
+        );
+
+        house
+            .sort_devices_by("room1", |a, b| {
+                a.kind().to_string().cmp(&b.kind().to_string())
+            })
+            .unwrap();
+        assert_eq!(
+            vec!["c", "a", "b"],
+            house.devices("room1").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_create_report_lenient() {
+        let described = SmartSocket::new("room1_socket_1", "test socket", false, 0.0);
+        let house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "room1",
+                    vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+                ),
+                ("room2", vec![socket("room2_socket_2")]),
+                ("room3", vec![]),
+            ]),
+        );
+
+        let info_provider = OwningDeviceInfoProvider::new(described);
+
+        let outcome = house
+            .create_report_with(&info_provider, &ReportOptions::lenient())
+            .unwrap();
+        assert_eq!(
+            r#"Location: room1
+Device/Socket: 
+  Name: room1_socket_1
+  Description: test socket
+  Current state: off, 0 W, 230 V, 0.00 A, 0.000 kWh
+Location: room1
+Device/Thermometer: 
+  Name: room1_thermo_1
+  Current state: unavailable
+Location: room2
+Device/Socket: 
+  Name: room2_socket_2
+  Current state: unavailable"#,
+            outcome.report
+        );
+        assert_eq!(
+            vec![
+                MissingDevice {
+                    room: "room1".to_owned(),
+                    device: "room1_thermo_1".to_owned()
+                },
+                MissingDevice {
+                    room: "room2".to_owned(),
+                    device: "room2_socket_2".to_owned()
+                },
+            ],
+            outcome.missing
+        );
+
+        assert_eq!(
+            Err(SmartHouseError::ReportError {
+                room: "room2".to_owned(),
+                device: Some("room2_socket_2".to_owned())
+            }),
+            house.create_report_with(&info_provider, &ReportOptions::strict())
+        );
+    }
+
+    fn nested_house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("my smart house");
+        house.add_room("floor1/hall").unwrap();
+        house.add_room("floor2/kitchen").unwrap();
+        house.add_room("floor2/bedroom").unwrap();
+        house.add_device("floor1/hall", socket("socket1")).unwrap();
+        house
+            .add_device("floor2/kitchen", socket("socket1"))
+            .unwrap();
+        house
+            .add_device("floor2/bedroom", thermo("thermo1"))
+            .unwrap();
+        house.add_device("floor2", thermo("stairs")).unwrap();
+        house
+    }
+
+    #[test]
+    fn test_nested_locations() {
+        let mut house = nested_house();
+
+        assert_eq!(
+            vec!["floor1/hall", "floor2/kitchen", "floor2/bedroom"],
+            house.rooms().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                "floor1",
+                "floor1/hall",
+                "floor2",
+                "floor2/kitchen",
+                "floor2/bedroom"
+            ],
+            house.locations().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["floor2/kitchen", "floor2/bedroom"],
+            house.sub_locations("floor2").unwrap().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["socket1"],
+            house.devices("floor2/kitchen").collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(SmartHouseError::DuplicateRoom("floor2".to_owned())),
+            house.add_room("floor2")
+        );
+
+        assert_eq!(
+            "socket1",
+            house
+                .device_by_path("floor2/kitchen/socket1")
+                .unwrap()
+                .name()
+        );
+        assert!(house.device_by_path("floor2/hall/socket1").is_none());
+        house
+            .device_by_path_mut("/floor2/kitchen/socket1")
+            .and_then(|d| d.as_any_mut().downcast_mut::<SmartSocket>())
+            .unwrap()
+            .turn_off();
+        assert!(!house.socket("floor2/kitchen", "socket1").unwrap().is_on());
+        assert!(house.socket("floor1/hall", "socket1").unwrap().is_on());
+
+        house.move_room_to("floor2/bedroom", 0).unwrap();
+        house.sort_rooms_by(|a, b| b.cmp(a));
+        assert_eq!(
+            vec!["floor2/kitchen", "floor2/bedroom", "floor1/hall"],
+            house.rooms().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_delete_subtree() {
+        let mut house = nested_house();
+
+        house.delete_room("floor2").unwrap();
+        assert_eq!(vec!["floor1/hall"], house.rooms().collect::<Vec<_>>());
+        assert!(house.device_by_path("floor2/kitchen/socket1").is_none());
+
+        house.delete_room("floor1/hall").unwrap();
+        assert_eq!(vec!["floor1"], house.rooms().collect::<Vec<_>>());
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("floor1/hall".to_owned())),
+            house.delete_room("floor1/hall")
+        );
+    }
+
+    #[test]
+    fn test_subtree_report() {
+        let house = nested_house();
+
+        let report = house.report_of(Some("floor2"), &house).unwrap();
+        assert_eq!(
+            vec!["floor2", "floor2/kitchen", "floor2/bedroom"],
+            report
+                .rooms
+                .iter()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let outcome = house
+            .create_report_with(&house, &ReportOptions::strict().within("floor2/kitchen"))
+            .unwrap();
+        assert!(outcome.report.starts_with("Location: floor2/kitchen\n"));
+        assert_eq!(1, outcome.report.matches("Location:").count());
+
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("floor3".to_owned())),
+            house.create_report_with(&house, &ReportOptions::strict().within("floor3"))
+        );
+    }
+
+    #[test]
+    fn test_move_device() {
+        let mut house = nested_house();
+
+        house
+            .move_device("floor2/kitchen", "floor2/bedroom", "socket1")
+            .unwrap();
+        assert_eq!(
+            vec!["thermo1", "socket1"],
+            house.devices("floor2/bedroom").collect::<Vec<_>>()
+        );
+        assert!(house.devices("floor2/kitchen").next().is_none());
+
+        house
+            .add_device("floor2/kitchen", socket("socket1"))
+            .unwrap();
+        assert_eq!(
+            Err(SmartHouseError::DuplicateDevice {
+                room: "floor2/bedroom".to_owned(),
+                device: "socket1".to_owned()
+            }),
+            house.move_device("floor2/kitchen", "floor2/bedroom", "socket1")
+        );
+        assert_eq!(
+            Err(SmartHouseError::DeviceNotFound {
+                room: "floor1/hall".to_owned(),
+                device: "thermo1".to_owned()
+            }),
+            house.move_device("floor1/hall", "floor2/kitchen", "thermo1")
+        );
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("floor3".to_owned())),
+            house.move_device("floor1/hall", "floor3", "socket1")
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut house = nested_house();
+
+        house.rename_room("floor2", "attic").unwrap();
+        assert_eq!(
+            vec!["floor1/hall", "attic/kitchen", "attic/bedroom"],
+            house.rooms().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(SmartHouseError::DuplicateRoom("attic/kitchen".to_owned())),
+            house.rename_room("attic/bedroom", "kitchen")
+        );
+        assert_eq!(
+            Err(SmartHouseError::InvalidName("a/b".to_owned())),
+            house.rename_room("attic/bedroom", "a/b")
+        );
+        assert_eq!(
+            Err(SmartHouseError::RoomNotFound("floor2".to_owned())),
+            house.rename_room("floor2", "floor3")
+        );
+
+        house.add_device("attic/kitchen", thermo("thermo")).unwrap();
+        assert_eq!(
+            Err(SmartHouseError::InvalidName("a/b".to_owned())),
+            house.add_device("attic/kitchen", thermo("a/b"))
+        );
+        assert_eq!(
+            Err(SmartHouseError::InvalidName("a/b".to_owned())),
+            house.rename_device("attic/kitchen", "thermo", "a/b")
+        );
+        house
+            .rename_device("attic/kitchen", "socket1", "kettle")
+            .unwrap();
+        assert_eq!(
+            vec!["kettle", "thermo"],
+            house.devices("attic/kitchen").collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(SmartHouseError::DuplicateDevice {
+                room: "attic/kitchen".to_owned(),
+                device: "thermo".to_owned()
+            }),
+            house.rename_device("attic/kitchen", "kettle", "thermo")
+        );
+    }
+
+    #[test]
+    fn test_rename_keeps_references() {
+        use rules::{Action, Condition, Metric, Rule};
+        use scene::{Scene, TargetState};
+        use schedule::Switch;
+
+        let mut house = nested_house();
+        house
+            .add_rule(Rule::new(
+                "heat",
+                Condition::below("thermo1", Metric::Temperature, 18.0),
+                Action::TurnOn("floor2/kitchen/socket1".to_owned()),
+            ))
+            .unwrap();
+        house
+            .add_timer(
+                "off",
+                "floor2/kitchen/socket1",
+                Switch::Off,
+                std::time::Duration::from_secs(60),
+            )
+            .unwrap();
+        house
+            .add_scene(
+                Scene::new("night")
+                    .with_target("floor1/hall/socket1", TargetState::Socket { is_on: false }),
+            )
+            .unwrap();
+
+        let references = |house: &SmartHouse| {
+            let rule = house.rules().rules().next().unwrap();
+            vec![
+                rule.when.device().to_owned(),
+                rule.then[0].device().to_owned(),
+                house.schedule().jobs().next().unwrap().device.clone(),
+                house.scenes().next().unwrap().targets[0].device.clone(),
+            ]
+        };
+        let before = references(&house);
+
+        house.rename_room("floor2", "attic").unwrap();
+        house.rename_room("floor1", "ground").unwrap();
+        house
+            .move_device("attic/kitchen", "attic/bedroom", "socket1")
+            .unwrap();
+        house
+            .rename_device("attic/bedroom", "socket1", "kettle")
+            .unwrap();
+        house
+            .rename_device("attic/bedroom", "thermo1", "thermo")
+            .unwrap();
+        assert_eq!(
+            vec![
+                "thermo",
+                "attic/bedroom/kettle",
+                "attic/bedroom/kettle",
+                "ground/hall/socket1"
+            ],
+            references(&house)
+        );
+
+        for _ in 0..5 {
+            house.undo().unwrap();
+        }
+        assert_eq!(before, references(&house));
+    }
+
+    #[test]
+    fn test_device_name_policy() {
+        let mut house = nested_house();
+
+        assert_eq!(
+            Err(SmartHouseError::DuplicateDevice {
+                room: "floor2/kitchen".to_owned(),
+                device: "socket1".to_owned()
+            }),
+            house.set_device_name_policy(DeviceNamePolicy::HouseWide)
+        );
+        assert_eq!(DeviceNamePolicy::PerRoom, house.device_name_policy());
+
+        house
+            .rename_device("floor2/kitchen", "socket1", "socket2")
+            .unwrap();
+        house
+            .set_device_name_policy(DeviceNamePolicy::HouseWide)
+            .unwrap();
+        assert_eq!(
+            vec!["floor2/kitchen"],
+            house.device_rooms("socket2").collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            Err(SmartHouseError::DuplicateDevice {
+                room: "floor2/kitchen".to_owned(),
+                device: "socket2".to_owned()
+            }),
+            house.add_device("floor1/hall", socket("socket2"))
+        );
+        assert_eq!(
+            Err(SmartHouseError::DuplicateDevice {
+                room: "floor2/bedroom".to_owned(),
+                device: "thermo1".to_owned()
+            }),
+            house.rename_device("floor1/hall", "socket1", "thermo1")
+        );
+        house
+            .move_device("floor2/kitchen", "floor1/hall", "socket2")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_report_other_device_kinds() {
+        use device::{
+            info::DeviceRegistry, light::Color, sensor::SensorKind, SmartBlinds, SmartLight,
+            SmartLock, SmartSensor,
+        };
+        use report::render::{CsvRenderer, ReportRenderer};
+
+        let mut light = SmartLight::new("light", "ceiling", true, 80);
+        light.set_color(Some(Color::rgb(255, 136, 0)));
+        let mut lock = SmartLock::new("lock", "front door", true);
+        lock.jam();
+
+        let mut house = SmartHouse::new_empty("my smart house");
+        house.add_room("hall").unwrap();
+        house.add_device("hall", Box::new(light)).unwrap();
+        house.add_device("hall", Box::new(lock)).unwrap();
+        house
+            .add_device(
+                "hall",
+                Box::new(SmartSensor::new("motion", "hall", SensorKind::Motion)),
+            )
+            .unwrap();
+        house
+            .add_device("hall", Box::new(SmartBlinds::new("blinds", "window", 25)))
+            .unwrap();
+
+        let mut registry = DeviceRegistry::new();
+        registry.insert(
+            "hall",
+            Box::new(SmartLock::new("lock", "front door", false)),
+        );
+        assert_eq!(
+            r#"Location: hall
+Device/Lock: 
+  Name: lock
+  Description: front door
+  Current state: unlocked"#,
+            house.create_report(&registry).unwrap()
+        );
+
+        assert_eq!(
+            r#"Location: hall
+Device/Light: 
+  Name: light
+  Description: ceiling
+  Current state: on, brightness 80 %, color #ff8800
+Location: hall
+Device/Lock: 
+  Name: lock
+  Description: front door
+  Current state: locked, jammed
+Location: hall
+Device/Sensor: 
+  Name: motion
+  Description: hall
+  Current state: motion sensor, no motion, last triggered never
+Location: hall
+Device/Blinds: 
+  Name: blinds
+  Description: window
+  Current position: 25 % open"#,
+            house.create_report(&house).unwrap()
+        );
+
+        let csv = CsvRenderer.render(&house.report(&house).unwrap());
+        assert!(csv.contains("hall,light,Light,ceiling,\"on, 80 %, #ff8800\","));
+        assert!(csv.contains("hall,lock,Lock,front door,\"locked, jammed\","));
+        assert!(csv.contains("hall,blinds,Blinds,window,25 % open,"));
+    }
+
+    #[test]
+    fn test_update_device_runs_rules() {
+        use rules::{Action, Condition, Metric, Rule};
+
+        let mut house = SmartHouse::new(
+            "my smart house",
+            HashMap::from([(
+                "room1",
+                vec![socket("room1_socket_1"), thermo("room1_thermo_1")],
+            )]),
+        );
+        house
+            .add_rule(Rule::new(
+                "overheat",
+                Condition::above("room1_thermo_1", Metric::Temperature, 25.0),
+                Action::TurnOff("room1_socket_1".to_owned()),
+            ))
+            .unwrap();
+
+        let firings = house
+            .update_device("room1", "room1_thermo_1", |device| {
+                if let Some(thermo) = device.as_any_mut().downcast_mut::<SmartThermometer>() {
+                    thermo.set_temperature(26.0);
+                }
+            })
+            .unwrap();
+        assert_eq!(
+            vec!["overheat: turn off room1_socket_1"],
+            firings.iter().map(|f| f.to_string()).collect::<Vec<_>>()
+        );
+        assert!(!house.socket("room1", "room1_socket_1").unwrap().is_on());
+        assert!(house.dry_run_rules().is_empty());
+
+        assert_eq!(
+            Err(SmartHouseError::DeviceNotFound {
+                room: "room1".to_owned(),
+                device: "nope".to_owned()
+            }),
+            house.update_device("room1", "nope", |_| {})
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        use device::DeviceKind;
+        use events::EventFilter;
+
+        let mut house = SmartHouse::new_empty("my smart house");
+        let all = house.events().subscribe_queue(EventFilter::all());
+        let sockets = house
+            .events()
+            .subscribe_queue(EventFilter::all().room("floor2").kind(DeviceKind::Socket));
+
+        house.add_room("floor2/kitchen").unwrap();
+        house.add_room("hall").unwrap();
+        house.add_device("hall", socket("kettle")).unwrap();
+        house.add_device("hall", thermo("thermo")).unwrap();
+        house
+            .move_device("hall", "floor2/kitchen", "kettle")
+            .unwrap();
+        house
+            .update_device("floor2/kitchen", "kettle", |device| {
+                if let Some(socket) = device.as_any_mut().downcast_mut::<SmartSocket>() {
+                    socket.turn_off();
+                }
+            })
+            .unwrap();
+        // Состояние не изменилось - события нет
+        house
+            .update_device("floor2/kitchen", "kettle", |_| {})
+            .unwrap();
+        house
+            .rename_device("floor2/kitchen", "kettle", "teapot")
+            .unwrap();
+        house.delete_room("floor2").unwrap();
+
+        let names: Vec<String> = all
+            .drain()
+            .iter()
+            .map(|e| {
+                serde_json::to_value(e).unwrap()["event"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "room_added",
+                "room_added",
+                "device_added",
+                "device_added",
+                "device_moved",
+                "device_state_changed",
+                "device_renamed",
+                "room_removed",
+            ],
+            names
+        );
+
+        let events = sockets.drain();
+        assert_eq!(3, events.len());
+        assert!(matches!(
+            &events[1],
+            Event::DeviceStateChanged { room: Some(room), device, .. }
+                if room == "floor2/kitchen" && device == "kettle"
+        ));
     }
 }
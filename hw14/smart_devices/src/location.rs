@@ -0,0 +1,135 @@
+use crate::device::Device;
+use std::collections::{BTreeSet, HashMap};
+
+/// Разделитель сегментов пути, например `"floor2/kitchen/socket1"`
+pub const PATH_SEPARATOR: char = '/';
+
+/// Сегменты пути без пустых частей, так что `"/floor2//kitchen/"` == `"floor2/kitchen"`
+pub fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split(PATH_SEPARATOR).filter(|s| !s.is_empty())
+}
+
+/// Канонический вид пути
+pub fn normalize_path(path: &str) -> String {
+    split_path(path).collect::<Vec<_>>().join("/")
+}
+
+pub fn join_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{parent}{PATH_SEPARATOR}{name}")
+    }
+}
+
+/// Делим путь на родителя и последний сегмент
+pub fn split_last(path: &str) -> Option<(&str, &str)> {
+    let path = path.trim_matches(PATH_SEPARATOR);
+    if path.is_empty() {
+        return None;
+    }
+
+    Some(path.rsplit_once(PATH_SEPARATOR).unwrap_or(("", path)))
+}
+
+/// Лежит ли `path` внутри поддерева `root` (или совпадает с ним)
+pub fn is_within(path: &str, root: &str) -> bool {
+    let mut path = split_path(path);
+    split_path(root).all(|segment| path.next() == Some(segment))
+}
+
+/// Узел дерева расположений: этаж, зона или комната
+pub(crate) struct Location {
+    pub(crate) name: String,
+    pub(crate) devices: Vec<Box<dyn Device>>,
+    /// Теги девайсов по имени, девайсы без тегов не хранятся
+    pub(crate) tags: HashMap<String, BTreeSet<String>>,
+    pub(crate) children: Vec<Location>,
+}
+
+impl Location {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            devices: Vec::new(),
+            tags: HashMap::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Комната - это расположение без вложенных расположений
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+pub(crate) fn find<'a>(locations: &'a [Location], path: &str) -> Option<&'a Location> {
+    let mut segments = split_path(path);
+    let first = segments.next()?;
+    let mut location = locations.iter().find(|l| l.name == first)?;
+    for segment in segments {
+        location = location.children.iter().find(|l| l.name == segment)?;
+    }
+
+    Some(location)
+}
+
+pub(crate) fn find_mut<'a>(locations: &'a mut [Location], path: &str) -> Option<&'a mut Location> {
+    let mut segments = split_path(path);
+    let first = segments.next()?;
+    let mut location = locations.iter_mut().find(|l| l.name == first)?;
+    for segment in segments {
+        location = location.children.iter_mut().find(|l| l.name == segment)?;
+    }
+
+    Some(location)
+}
+
+/// Список, в котором лежит расположение `path` вместе с соседями
+pub(crate) fn siblings_mut<'a>(
+    locations: &'a mut Vec<Location>,
+    path: &str,
+) -> Option<&'a mut Vec<Location>> {
+    let (parent, _) = split_last(path)?;
+    if parent.is_empty() {
+        return Some(locations);
+    }
+
+    find_mut(locations, parent).map(|l| &mut l.children)
+}
+
+/// Обход дерева в глубину, родитель идет раньше детей
+pub(crate) fn walk<'a>(
+    locations: &'a [Location],
+    parent: &str,
+    out: &mut Vec<(String, &'a Location)>,
+) {
+    for location in locations {
+        let path = join_path(parent, &location.name);
+        out.push((path.clone(), location));
+        walk(&location.children, &path, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        assert_eq!("floor2/kitchen", normalize_path("/floor2//kitchen/"));
+        assert_eq!("floor2/kitchen", join_path("floor2", "kitchen"));
+        assert_eq!("kitchen", join_path("", "kitchen"));
+        assert_eq!(
+            Some(("floor2/kitchen", "socket1")),
+            split_last("floor2/kitchen/socket1")
+        );
+        assert_eq!(Some(("", "kitchen")), split_last("kitchen"));
+        assert_eq!(None, split_last("/"));
+
+        assert!(is_within("floor2/kitchen", "floor2"));
+        assert!(is_within("floor2", "floor2"));
+        assert!(!is_within("floor22/kitchen", "floor2"));
+        assert!(!is_within("floor2", "floor2/kitchen"));
+    }
+}
@@ -0,0 +1,759 @@
+//! Бюджеты мощности: сколько ватт могут одновременно потреблять розетки
+//! расположения (вместе с вложенными) или всего дома. Бюджет проверяется,
+//! когда розетка начинает потреблять больше: включается или растет нагрузка.
+
+use crate::{
+    device::{DeviceState, SmartSocket},
+    events::Event,
+    journal::Operation,
+    location, SmartHouse, SmartHouseError,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+type Result<T> = std::result::Result<T, SmartHouseError>;
+
+/// Что делать, если включение розетки превышает бюджет
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPolicy {
+    /// Не включать розетку
+    #[default]
+    Refuse,
+    /// Выключить другие розетки с меньшим или равным приоритетом
+    Shed,
+    /// Включить и только предупредить
+    Warn,
+}
+
+impl fmt::Display for BudgetPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BudgetPolicy::Refuse => write!(f, "refuse"),
+            BudgetPolicy::Shed => write!(f, "shed"),
+            BudgetPolicy::Warn => write!(f, "warn"),
+        }
+    }
+}
+
+/// Предельная мощность, Вт, и политика при ее превышении
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerBudget {
+    pub limit: f64,
+    #[serde(default)]
+    pub policy: BudgetPolicy,
+}
+
+impl PowerBudget {
+    pub fn new(limit: f64, policy: BudgetPolicy) -> Self {
+        Self { limit, policy }
+    }
+}
+
+impl fmt::Display for PowerBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} W, {}", self.limit, self.policy)
+    }
+}
+
+/// Бюджеты дома и расположений и приоритеты розеток.
+/// Расположения и розетки адресуются путями, как в правилах и сценах.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerBudgets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    house: Option<PowerBudget>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    rooms: BTreeMap<String, PowerBudget>,
+    /// Приоритет по пути розетки, по умолчанию 0
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    priorities: BTreeMap<String, i32>,
+}
+
+impl PowerBudgets {
+    pub fn is_empty(&self) -> bool {
+        self.house.is_none() && self.rooms.is_empty() && self.priorities.is_empty()
+    }
+
+    /// Бюджет всего дома
+    pub fn house(&self) -> Option<PowerBudget> {
+        self.house
+    }
+
+    pub fn room(&self, room: &str) -> Option<PowerBudget> {
+        self.rooms.get(&location::normalize_path(room)).copied()
+    }
+
+    /// Бюджеты расположений по пути
+    pub fn rooms(&self) -> impl Iterator<Item = (&str, PowerBudget)> {
+        self.rooms
+            .iter()
+            .map(|(room, budget)| (room.as_str(), *budget))
+    }
+
+    pub fn priority(&self, device: &str) -> i32 {
+        self.priorities
+            .get(&location::normalize_path(device))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn priorities(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.priorities
+            .iter()
+            .map(|(device, p)| (device.as_str(), *p))
+    }
+
+    /// Расположение `old` переименовали в `new`: переносим бюджеты его поддерева
+    pub(crate) fn rekey_rooms(&mut self, old: &str, new: &str) {
+        let moved: Vec<String> = self
+            .rooms
+            .keys()
+            .filter(|room| location::is_within(room, old))
+            .cloned()
+            .collect();
+        for room in moved {
+            let budget = self.rooms.remove(&room).expect("key was just found");
+            self.rooms
+                .insert(format!("{new}{}", &room[old.len()..]), budget);
+        }
+    }
+
+    /// Розетка переехала с пути `old` на `new`: переносим ее приоритет
+    pub(crate) fn rekey_device(&mut self, old: &str, new: &str) {
+        if let Some(priority) = self.priorities.remove(old) {
+            self.priorities.insert(new.to_owned(), priority);
+        }
+    }
+
+    /// Оставляем бюджеты только существующих расположений
+    /// и приоритеты только существующих розеток
+    pub(crate) fn retain_known(&mut self, house: &SmartHouse) {
+        self.rooms.retain(|room, _| house.room(room).is_ok());
+        self.priorities.retain(|device, _| {
+            house
+                .device_by_path(device)
+                .is_some_and(|d| d.as_any().is::<SmartSocket>())
+        });
+    }
+
+    /// Бюджеты, которые касаются девайса в расположении `room`:
+    /// сначала самые вложенные расположения, последним - дом
+    fn covering(&self, room: &str) -> Vec<(Option<&str>, PowerBudget)> {
+        let mut budgets: Vec<_> = self
+            .rooms
+            .iter()
+            .filter(|(root, _)| location::is_within(room, root))
+            .map(|(root, budget)| (Some(root.as_str()), *budget))
+            .collect();
+        budgets.sort_by_key(|(root, _)| std::cmp::Reverse(root.map(str::len)));
+        budgets.extend(self.house.map(|budget| (None, budget)));
+        budgets
+    }
+}
+
+/// Превышение бюджета, которое пропустили (`Warn`) или разрешили, выключив
+/// другие розетки (`Shed`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Overload {
+    /// Расположение, чей бюджет превышен, `None` - бюджет дома
+    pub budget: Option<String>,
+    /// Нагрузка с включенной розеткой, Вт
+    pub load: f64,
+    pub limit: f64,
+    pub policy: BudgetPolicy,
+    /// Пути выключенных розеток
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shed: Vec<String>,
+}
+
+impl fmt::Display for Overload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.budget {
+            Some(room) => write!(f, "room \"{room}\"")?,
+            None => write!(f, "house")?,
+        }
+        write!(f, " draws {} W of {} W", self.load, self.limit)?;
+        if !self.shed.is_empty() {
+            write!(f, ", turned off {}", self.shed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Мощность, которую розетка потребляет в этом состоянии, Вт
+pub(crate) fn draw(state: &DeviceState) -> f64 {
+    match state {
+        DeviceState::Socket {
+            is_on: true,
+            current_power,
+            ..
+        } => *current_power,
+        _ => 0.0,
+    }
+}
+
+impl SmartHouse {
+    pub fn power_budgets(&self) -> &PowerBudgets {
+        &self.power
+    }
+
+    /// Задаем или снимаем (`None`) бюджет расположения, а без `room` - всего дома
+    pub fn set_power_budget(
+        &mut self,
+        room: Option<&str>,
+        budget: Option<PowerBudget>,
+    ) -> Result<()> {
+        if let Some(budget) = budget {
+            if !budget.limit.is_finite() || budget.limit < 0.0 {
+                return Err(SmartHouseError::InvalidPowerLimit(budget.limit));
+            }
+        }
+
+        let room = match room {
+            Some(room) => {
+                self.room(room)?;
+                Some(location::normalize_path(room))
+            }
+            None => None,
+        };
+        let old = match &room {
+            Some(room) => match budget {
+                Some(budget) => self.power.rooms.insert(room.clone(), budget),
+                None => self.power.rooms.remove(room),
+            },
+            None => std::mem::replace(&mut self.power.house, budget),
+        };
+
+        if old != budget {
+            self.journal.record(Operation::SetPowerBudget {
+                room,
+                old,
+                new: budget,
+            });
+        }
+        Ok(())
+    }
+
+    /// Задаем или снимаем (`None`) приоритет розетки: при нехватке
+    /// мощности первыми выключаются розетки с меньшим приоритетом
+    pub fn set_load_priority(&mut self, device: &str, priority: Option<i32>) -> Result<()> {
+        let (room, name) = location::split_last(device)
+            .ok_or_else(|| SmartHouseError::InvalidName(device.to_owned()))?;
+        if self.socket(room, name).is_none() {
+            self.room_devices_mut(room, name)?;
+            return Err(SmartHouseError::NotASocket(device.to_owned()));
+        }
+
+        let device = location::normalize_path(device);
+        let old = match priority {
+            Some(priority) => self.power.priorities.insert(device.clone(), priority),
+            None => self.power.priorities.remove(&device),
+        };
+        if old != priority {
+            self.journal.record(Operation::SetLoadPriority {
+                device,
+                old,
+                new: priority,
+            });
+        }
+        Ok(())
+    }
+
+    /// Снимаем бюджеты поддерева `room` и приоритеты его розеток перед его удалением.
+    /// Снятие попадает в журнал, так что отмена удаления вернет и их.
+    pub(crate) fn drop_power_within(&mut self, room: &str) -> Result<()> {
+        let room = location::normalize_path(room);
+        let budgets: Vec<String> = self
+            .power
+            .rooms
+            .keys()
+            .filter(|budget| location::is_within(budget, &room))
+            .cloned()
+            .collect();
+        for budget in budgets {
+            self.set_power_budget(Some(&budget), None)?;
+        }
+
+        let priorities: Vec<String> = self
+            .power
+            .priorities
+            .keys()
+            .filter(|device| location::is_within(device, &room))
+            .cloned()
+            .collect();
+        for device in priorities {
+            self.set_load_priority(&device, None)?;
+        }
+        Ok(())
+    }
+
+    /// Сколько сейчас потребляют розетки расположения, а без `room` - всего дома, Вт
+    pub fn power_load(&self, room: Option<&str>) -> Result<f64> {
+        if let Some(room) = room {
+            self.room(room)?;
+        }
+
+        Ok(self.load_within(room))
+    }
+
+    /// Бюджет удаленного расположения ничего не весит, поэтому без проверки пути
+    fn load_within(&self, room: Option<&str>) -> f64 {
+        self.walk()
+            .into_iter()
+            .filter(|(path, _)| room.is_none_or(|room| location::is_within(path, room)))
+            .flat_map(|(_, location)| location.devices.iter())
+            .map(|device| draw(&device.state()))
+            .sum()
+    }
+
+    /// Включаем или выключаем розетку по пути или уникальному имени.
+    /// В ответе - превышения бюджетов, которые политика пропустила.
+    pub fn switch_socket(&mut self, device: &str, on: bool) -> Result<Vec<Overload>> {
+        let path = self
+            .resolve_device(device)
+            .ok_or_else(|| SmartHouseError::DeviceNotFound {
+                room: location::split_last(device)
+                    .map(|(room, _)| room.to_owned())
+                    .unwrap_or_default(),
+                device: device.to_owned(),
+            })?;
+        if self
+            .device_by_path(&path)
+            .is_none_or(|d| !d.as_any().is::<SmartSocket>())
+        {
+            return Err(SmartHouseError::NotASocket(path));
+        }
+
+        let (_, overloads) = self.change_device_checked(&path, |device| {
+            if let Some(socket) = device.as_any_mut().downcast_mut::<SmartSocket>() {
+                if on {
+                    socket.turn_on();
+                } else {
+                    socket.turn_off();
+                }
+            }
+        })?;
+        Ok(overloads)
+    }
+
+    /// Проверяем бюджеты после того, как розетка `path` стала потреблять больше.
+    /// Возвращаем превышения и розетки, которые надо выключить, либо ошибку,
+    /// если розетку включать нельзя. Сам дом не меняется.
+    pub(crate) fn check_power(&self, path: &str) -> Result<(Vec<Overload>, Vec<String>)> {
+        let Some((room, _)) = location::split_last(path) else {
+            return Ok(Default::default());
+        };
+        let priority = self.power.priority(path);
+        let mut overloads = Vec::new();
+        let mut shed: Vec<(String, f64)> = Vec::new();
+
+        for (budget_room, budget) in self.power.covering(room) {
+            let in_scope =
+                |device_room: &str| budget_room.is_none_or(|r| location::is_within(device_room, r));
+            let already_shed: f64 = shed
+                .iter()
+                .filter(|(p, _)| location::split_last(p).is_some_and(|(r, _)| in_scope(r)))
+                .map(|(_, draw)| draw)
+                .sum();
+            let mut load = self.load_within(budget_room) - already_shed;
+            if load <= budget.limit {
+                continue;
+            }
+
+            let exceeded = SmartHouseError::PowerBudgetExceeded {
+                room: budget_room.map(str::to_owned),
+                load,
+                limit: budget.limit,
+            };
+            let overload_load = load;
+            let mut shed_here = Vec::new();
+            match budget.policy {
+                BudgetPolicy::Refuse => return Err(exceeded),
+                BudgetPolicy::Warn => {}
+                BudgetPolicy::Shed => {
+                    let mut candidates: Vec<(String, f64, i32)> = self
+                        .walk()
+                        .into_iter()
+                        .filter(|(device_room, _)| in_scope(device_room))
+                        .flat_map(|(device_room, location)| {
+                            location.devices.iter().map(move |d| {
+                                (
+                                    location::join_path(&device_room, d.name()),
+                                    draw(&d.state()),
+                                )
+                            })
+                        })
+                        .filter(|(p, draw)| {
+                            *draw > 0.0 && p != path && !shed.iter().any(|(s, _)| s == p)
+                        })
+                        .map(|(p, draw)| {
+                            let priority = self.power.priority(&p);
+                            (p, draw, priority)
+                        })
+                        .filter(|(_, _, p)| *p <= priority)
+                        .collect();
+                    candidates.sort_by_key(|(_, _, priority)| *priority);
+
+                    for (candidate, draw, _) in candidates {
+                        if load <= budget.limit {
+                            break;
+                        }
+                        load -= draw;
+                        shed_here.push(candidate.clone());
+                        shed.push((candidate, draw));
+                    }
+                    if load > budget.limit {
+                        return Err(exceeded);
+                    }
+                }
+            }
+
+            overloads.push(Overload {
+                budget: budget_room.map(str::to_owned),
+                load: overload_load,
+                limit: budget.limit,
+                policy: budget.policy,
+                shed: shed_here,
+            });
+        }
+
+        Ok((overloads, shed.into_iter().map(|(p, _)| p).collect()))
+    }
+
+    /// Проверяем бюджеты так, будто розетки `draws` (путь и новая мощность, Вт)
+    /// переключились разом. Политика `Shed` может выключить только другие розетки.
+    /// Сам дом не меняется.
+    pub(crate) fn check_power_together(&self, draws: &[(String, f64)]) -> Result<()> {
+        let old_draw = |path: &str| {
+            self.device_by_path(path)
+                .map(|device| draw(&device.state()))
+                .unwrap_or_default()
+        };
+        let rising: Vec<&str> = draws
+            .iter()
+            .filter(|(path, new)| *new > old_draw(path))
+            .map(|(path, _)| path.as_str())
+            .collect();
+
+        let mut budgets: Vec<(Option<&str>, PowerBudget)> = Vec::new();
+        for path in &rising {
+            let (room, _) = location::split_last(path).unwrap_or_default();
+            for budget in self.power.covering(room) {
+                if !budgets.contains(&budget) {
+                    budgets.push(budget);
+                }
+            }
+        }
+
+        for (budget_room, budget) in budgets {
+            let in_scope = |path: &str| {
+                location::split_last(path).is_some_and(|(room, _)| {
+                    budget_room.is_none_or(|r| location::is_within(room, r))
+                })
+            };
+            let load = self.load_within(budget_room)
+                + draws
+                    .iter()
+                    .filter(|(path, _)| in_scope(path))
+                    .map(|(path, new)| new - old_draw(path))
+                    .sum::<f64>();
+            if load <= budget.limit {
+                continue;
+            }
+
+            let exceeded = SmartHouseError::PowerBudgetExceeded {
+                room: budget_room.map(str::to_owned),
+                load,
+                limit: budget.limit,
+            };
+            match budget.policy {
+                BudgetPolicy::Refuse => return Err(exceeded),
+                BudgetPolicy::Warn => {}
+                BudgetPolicy::Shed => {
+                    let priority = rising
+                        .iter()
+                        .filter(|path| in_scope(path))
+                        .map(|path| self.power.priority(path))
+                        .max()
+                        .unwrap_or_default();
+                    let sheddable: f64 = self
+                        .walk()
+                        .into_iter()
+                        .flat_map(|(room, location)| {
+                            location.devices.iter().map(move |d| {
+                                (location::join_path(&room, d.name()), draw(&d.state()))
+                            })
+                        })
+                        .filter(|(path, draw)| {
+                            *draw > 0.0
+                                && in_scope(path)
+                                && !draws.iter().any(|(changed, _)| changed == path)
+                                && self.power.priority(path) <= priority
+                        })
+                        .map(|(_, draw)| draw)
+                        .sum();
+                    if load - sheddable > budget.limit {
+                        return Err(exceeded);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Розетка `path` появилась в расположении уже включенной (добавили или
+    /// перенесли): проверяем бюджеты и выполняем их политику, как при включении
+    pub(crate) fn admit_power(&mut self, path: &str) -> Result<()> {
+        if self
+            .device_by_path(path)
+            .is_none_or(|device| draw(&device.state()) <= 0.0)
+        {
+            return Ok(());
+        }
+
+        let (overloads, shed) = self.check_power(path)?;
+        self.enforce_power(path, &overloads, &shed)
+    }
+
+    /// Выключаем розетки, отобранные `check_power`, и сообщаем о превышениях
+    pub(crate) fn enforce_power(
+        &mut self,
+        path: &str,
+        overloads: &[Overload],
+        shed: &[String],
+    ) -> Result<()> {
+        for socket in shed {
+            self.change_device(socket, |device| {
+                if let Some(socket) = device.as_any_mut().downcast_mut::<SmartSocket>() {
+                    socket.turn_off();
+                }
+            })?;
+        }
+
+        let (room, device) = location::split_last(path).unwrap_or_default();
+        for overload in overloads {
+            self.events.publish(Event::PowerOverload {
+                room: room.to_owned(),
+                device: device.to_owned(),
+                overload: overload.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::{DeviceKind, SmartThermometer},
+        events::EventFilter,
+        rules::{Action, RuleError},
+        tags::TagQuery,
+    };
+
+    fn house(policy: BudgetPolicy) -> SmartHouse {
+        let mut house = SmartHouse::new_empty("powered house");
+        house.add_room("floor1/kitchen").unwrap();
+        house.add_room("floor1/hall").unwrap();
+        for (room, socket, power, on) in [
+            ("floor1/kitchen", "kettle", 1500.0, true),
+            ("floor1/kitchen", "oven", 2000.0, false),
+            ("floor1/kitchen", "fridge", 200.0, true),
+            ("floor1/hall", "heater", 1000.0, false),
+        ] {
+            house
+                .add_device(room, Box::new(SmartSocket::new(socket, "", on, power)))
+                .unwrap();
+        }
+        house
+            .set_power_budget(
+                Some("floor1/kitchen"),
+                Some(PowerBudget::new(3000.0, policy)),
+            )
+            .unwrap();
+        house.journal_mut().clear();
+        house
+    }
+
+    #[test]
+    fn test_refuse() {
+        let mut house = house(BudgetPolicy::Refuse);
+        assert_eq!(1700.0, house.power_load(Some("floor1")).unwrap());
+
+        assert_eq!(
+            Err(SmartHouseError::PowerBudgetExceeded {
+                room: Some("floor1/kitchen".to_owned()),
+                load: 3700.0,
+                limit: 3000.0,
+            }),
+            house.switch_socket("oven", true)
+        );
+        assert!(!house.socket("floor1/kitchen", "oven").unwrap().is_on());
+        assert!(!house.journal().can_undo());
+
+        // Правила упираются в тот же бюджет
+        let results = house.apply_tagged(
+            &TagQuery::default().of_kind(DeviceKind::Socket),
+            Action::TurnOn,
+        );
+        assert!(matches!(
+            results[1].result,
+            Err(RuleError::House(
+                SmartHouseError::PowerBudgetExceeded { .. }
+            ))
+        ));
+        assert!(house.socket("floor1/hall", "heater").unwrap().is_on());
+
+        // Бюджет дома проверяется после бюджета комнаты
+        house
+            .set_power_budget(None, Some(PowerBudget::new(2000.0, BudgetPolicy::Refuse)))
+            .unwrap();
+        house.switch_socket("heater", false).unwrap();
+        assert!(matches!(
+            house.switch_socket("heater", true),
+            Err(SmartHouseError::PowerBudgetExceeded { room: None, .. })
+        ));
+        assert_eq!(
+            Err(SmartHouseError::InvalidPowerLimit(-1.0)),
+            house.set_power_budget(None, Some(PowerBudget::new(-1.0, BudgetPolicy::Warn)))
+        );
+    }
+
+    #[test]
+    fn test_shed() {
+        let mut house = house(BudgetPolicy::Shed);
+        house
+            .set_load_priority("floor1/kitchen/fridge", Some(10))
+            .unwrap();
+        house
+            .add_device(
+                "floor1/hall",
+                Box::new(SmartThermometer::new("thermo", "", 20.0)),
+            )
+            .unwrap();
+        assert_eq!(
+            Err(SmartHouseError::NotASocket("floor1/hall/thermo".to_owned())),
+            house.set_load_priority("floor1/hall/thermo", Some(1))
+        );
+
+        let overloads = house.switch_socket("oven", true).unwrap();
+        assert_eq!(
+            vec![Overload {
+                budget: Some("floor1/kitchen".to_owned()),
+                load: 3700.0,
+                limit: 3000.0,
+                policy: BudgetPolicy::Shed,
+                shed: vec!["floor1/kitchen/kettle".to_owned()],
+            }],
+            overloads
+        );
+        assert!(!house.socket("floor1/kitchen", "kettle").unwrap().is_on());
+        assert!(house.socket("floor1/kitchen", "fridge").unwrap().is_on());
+
+        // Включение отменяется вместе с выключенными розетками
+        house.undo().unwrap();
+        assert!(house.socket("floor1/kitchen", "kettle").unwrap().is_on());
+        assert!(!house.socket("floor1/kitchen", "oven").unwrap().is_on());
+
+        // Выключать можно только розетки с приоритетом не выше включаемой
+        house
+            .set_load_priority("floor1/kitchen/kettle", Some(5))
+            .unwrap();
+        assert!(house.switch_socket("oven", true).is_err());
+    }
+
+    #[test]
+    fn test_paths_follow_rooms_and_sockets() {
+        let mut house = house(BudgetPolicy::Refuse);
+        house
+            .set_load_priority("floor1/kitchen/kettle", Some(5))
+            .unwrap();
+        let budget = Some(PowerBudget::new(3000.0, BudgetPolicy::Refuse));
+
+        house.rename_room("floor1", "ground").unwrap();
+        house
+            .move_device("ground/kitchen", "ground/hall", "kettle")
+            .unwrap();
+        house
+            .rename_device("ground/hall", "kettle", "boiler")
+            .unwrap();
+        assert_eq!(budget, house.power_budgets().room("ground/kitchen"));
+        assert_eq!(
+            vec![("ground/hall/boiler", 5)],
+            house.power_budgets().priorities().collect::<Vec<_>>()
+        );
+
+        // Удаление снимает бюджеты и приоритеты, отмена возвращает
+        house.delete_device("ground/hall", "boiler").unwrap();
+        house.delete_room("ground/kitchen").unwrap();
+        assert!(house.power_budgets().is_empty());
+        house.undo().unwrap();
+        house.undo().unwrap();
+        assert_eq!(budget, house.power_budgets().room("ground/kitchen"));
+        assert_eq!(5, house.power_budgets().priority("ground/hall/boiler"));
+
+        for _ in 0..3 {
+            house.undo().unwrap();
+        }
+        assert_eq!(budget, house.power_budgets().room("floor1/kitchen"));
+        assert_eq!(5, house.power_budgets().priority("floor1/kitchen/kettle"));
+    }
+
+    #[test]
+    fn test_add_and_move() {
+        let iron = |on: bool| Box::new(SmartSocket::new("iron", "", on, 1500.0));
+
+        let mut refusing = house(BudgetPolicy::Refuse);
+        let exceeded = Err(SmartHouseError::PowerBudgetExceeded {
+            room: Some("floor1/kitchen".to_owned()),
+            load: 3200.0,
+            limit: 3000.0,
+        });
+        assert_eq!(exceeded, refusing.add_device("floor1/kitchen", iron(true)));
+        assert!(refusing.device("floor1/kitchen", "iron").is_none());
+        assert!(!refusing.journal().can_undo());
+
+        refusing.add_device("floor1/hall", iron(true)).unwrap();
+        assert_eq!(
+            exceeded,
+            refusing.move_device("floor1/hall", "floor1/kitchen", "iron")
+        );
+        assert_eq!(
+            vec!["heater", "iron"],
+            refusing.devices("floor1/hall").collect::<Vec<_>>()
+        );
+        assert_eq!(1, refusing.journal().operations().count());
+
+        // Ради добавленной розетки политика выключает другие, отмена их включает
+        let mut house = house(BudgetPolicy::Shed);
+        house.add_device("floor1/kitchen", iron(true)).unwrap();
+        assert!(!house.socket("floor1/kitchen", "kettle").unwrap().is_on());
+        house.undo().unwrap();
+        assert!(house.device("floor1/kitchen", "iron").is_none());
+        assert!(house.socket("floor1/kitchen", "kettle").unwrap().is_on());
+    }
+
+    #[test]
+    fn test_warn() {
+        let mut house = house(BudgetPolicy::Warn);
+        let events = house
+            .events()
+            .subscribe_queue(EventFilter::all().device("floor1/kitchen/oven"));
+
+        let overloads = house.switch_socket("floor1/kitchen/oven", true).unwrap();
+        assert_eq!(1, overloads.len());
+        assert_eq!(
+            "room \"floor1/kitchen\" draws 3700 W of 3000 W",
+            overloads[0].to_string()
+        );
+        assert!(house.socket("floor1/kitchen", "oven").unwrap().is_on());
+
+        let events = events.drain();
+        assert_eq!(2, events.len());
+        assert!(
+            matches!(&events[0], Event::PowerOverload { overload, .. } if overload == &overloads[0])
+        );
+    }
+}
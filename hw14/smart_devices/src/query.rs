@@ -0,0 +1,341 @@
+//! Поиск девайсов по всему дому: по виду, расположению, имени, состоянию и тегам.
+
+use crate::{
+    device::{temperature::Temperature, Device, DeviceKind, DeviceState},
+    location,
+    tags::TagQuery,
+    SmartHouse,
+};
+use std::collections::BTreeSet;
+
+/// Условия поиска девайсов, все заданные условия должны выполняться.
+/// Пустой запрос находит все девайсы дома.
+///
+/// ```
+/// # use smart_devices::{query::DeviceQuery, device::DeviceKind};
+/// let query = DeviceQuery::new()
+///     .of_kind(DeviceKind::Socket)
+///     .in_room("floor2")
+///     .named("heat*")
+///     .on(true);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceQuery {
+    pub kind: Option<DeviceKind>,
+    /// Расположение вместе со всеми вложенными
+    pub room: Option<String>,
+    /// Шаблон имени: `*` - любая строка, `?` - любой символ
+    pub name: Option<String>,
+    /// Включен ли девайс. Подходят только розетки и лампы.
+    pub is_on: Option<bool>,
+    /// Границы температуры включительно. Подходят только термометры.
+    pub min_temperature: Option<Temperature>,
+    pub max_temperature: Option<Temperature>,
+    /// Девайс должен иметь все эти теги и не иметь ни одного из `excluded_tags`
+    pub tags: BTreeSet<String>,
+    pub excluded_tags: BTreeSet<String>,
+}
+
+impl DeviceQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn of_kind(mut self, kind: DeviceKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn in_room(mut self, room: &str) -> Self {
+        self.room = Some(location::normalize_path(room));
+        self
+    }
+
+    pub fn named(mut self, pattern: &str) -> Self {
+        self.name = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn on(mut self, is_on: bool) -> Self {
+        self.is_on = Some(is_on);
+        self
+    }
+
+    /// Температура от `min` до `max` включительно
+    pub fn temperature_between(mut self, min: Temperature, max: Temperature) -> Self {
+        self.min_temperature = Some(min);
+        self.max_temperature = Some(max);
+        self
+    }
+
+    pub fn tagged(mut self, tag: &str) -> Self {
+        self.tags.insert(tag.to_owned());
+        self
+    }
+
+    pub fn without_tag(mut self, tag: &str) -> Self {
+        self.excluded_tags.insert(tag.to_owned());
+        self
+    }
+
+    /// Подходит ли девайс из расположения `room` с тегами `tags`
+    pub fn matches(
+        &self,
+        room: &str,
+        device: &dyn Device,
+        tags: Option<&BTreeSet<String>>,
+    ) -> bool {
+        let has = |tag: &String| tags.is_some_and(|tags| tags.contains(tag));
+        self.kind.is_none_or(|kind| device.kind() == kind)
+            && self
+                .room
+                .as_ref()
+                .is_none_or(|root| location::is_within(room, root))
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|pattern| matches_pattern(pattern, device.name()))
+            && self.tags.iter().all(has)
+            && !self.excluded_tags.iter().any(has)
+            && self.matches_state(&device.state())
+    }
+
+    fn matches_state(&self, state: &DeviceState) -> bool {
+        if let Some(expected) = self.is_on {
+            let is_on = match state {
+                DeviceState::Socket { is_on, .. } | DeviceState::Light { is_on, .. } => *is_on,
+                _ => return false,
+            };
+            if is_on != expected {
+                return false;
+            }
+        }
+
+        if self.min_temperature.is_some() || self.max_temperature.is_some() {
+            let DeviceState::Thermometer {
+                current_temperature,
+                ..
+            } = state
+            else {
+                return false;
+            };
+            if self
+                .min_temperature
+                .is_some_and(|min| *current_temperature < min)
+                || self
+                    .max_temperature
+                    .is_some_and(|max| *current_temperature > max)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl From<&TagQuery> for DeviceQuery {
+    fn from(query: &TagQuery) -> Self {
+        Self {
+            kind: query.kind,
+            tags: query.tags.clone(),
+            excluded_tags: query.excluded.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Сопоставление имени с шаблоном, где `*` - любая строка, `?` - любой символ
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Жадно идем по имени, запоминая последнюю `*`, чтобы откатиться к ней
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Найденный девайс вместе с его расположением и тегами
+#[derive(Clone)]
+pub struct DeviceMatch<'a> {
+    pub room: String,
+    pub device: &'a dyn Device,
+    pub tags: Option<&'a BTreeSet<String>>,
+}
+
+impl DeviceMatch<'_> {
+    /// Полный путь девайса, например `"floor2/kitchen/socket1"`
+    pub fn path(&self) -> String {
+        location::join_path(&self.room, self.device.name())
+    }
+
+    /// Теги девайса по алфавиту
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.into_iter().flatten().map(String::as_str)
+    }
+}
+
+impl SmartHouse {
+    /// Девайсы дома, подходящие запросу, в порядке обхода дома
+    pub fn find_devices(&self, query: &DeviceQuery) -> Vec<DeviceMatch<'_>> {
+        self.walk()
+            .into_iter()
+            .flat_map(|(room, location)| {
+                location.devices.iter().filter_map(move |device| {
+                    let tags = location.tags.get(device.name());
+                    query
+                        .matches(&room, device.as_ref(), tags)
+                        .then(|| DeviceMatch {
+                            room: room.clone(),
+                            device: device.as_ref(),
+                            tags,
+                        })
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{SmartLight, SmartSocket, SmartThermometer};
+
+    fn house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("searchable house");
+        house.add_room("kitchen").unwrap();
+        house.add_room("floor2/bedroom").unwrap();
+        house.add_room("floor2/bath").unwrap();
+        let devices: [(&str, Box<dyn Device>); 6] = [
+            (
+                "kitchen",
+                Box::new(SmartSocket::new("heater", "", true, 1500.0)),
+            ),
+            (
+                "kitchen",
+                Box::new(SmartThermometer::new("thermo", "", 23.5)),
+            ),
+            (
+                "floor2/bedroom",
+                Box::new(SmartSocket::new("heated floor", "", false, 800.0)),
+            ),
+            (
+                "floor2/bedroom",
+                Box::new(SmartLight::new("lamp", "", true, 40)),
+            ),
+            (
+                "floor2/bedroom",
+                Box::new(SmartThermometer::new("thermo", "", 18.0)),
+            ),
+            (
+                "floor2/bath",
+                Box::new(SmartThermometer::new("thermo", "", 26.0)),
+            ),
+        ];
+        for (room, device) in devices {
+            house.add_device(room, device).unwrap();
+        }
+        house.tag_device("kitchen", "heater", "heating").unwrap();
+        house
+            .tag_device("floor2/bedroom", "heated floor", "heating")
+            .unwrap();
+        house
+    }
+
+    fn paths(house: &SmartHouse, query: &DeviceQuery) -> Vec<String> {
+        house.find_devices(query).iter().map(|m| m.path()).collect()
+    }
+
+    #[test]
+    fn test_pattern() {
+        assert!(matches_pattern("heat*", "heater"));
+        assert!(matches_pattern("*floor", "heated floor"));
+        assert!(matches_pattern("th?rmo", "thermo"));
+        assert!(matches_pattern("*e*e*", "heated floor"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("heat", "heater"));
+        assert!(!matches_pattern("*x*", "heater"));
+        assert!(!matches_pattern("?", ""));
+    }
+
+    #[test]
+    fn test_find_devices() {
+        let house = house();
+        assert_eq!(6, house.find_devices(&DeviceQuery::new()).len());
+        assert_eq!(
+            vec!["kitchen/heater", "floor2/bedroom/heated floor"],
+            paths(&house, &DeviceQuery::new().named("heat*"))
+        );
+        assert_eq!(
+            vec!["kitchen/heater", "floor2/bedroom/lamp"],
+            paths(&house, &DeviceQuery::new().on(true))
+        );
+        assert_eq!(
+            vec!["floor2/bedroom/heated floor"],
+            paths(
+                &house,
+                &DeviceQuery::new()
+                    .of_kind(DeviceKind::Socket)
+                    .in_room("floor2")
+            )
+        );
+        assert_eq!(
+            vec!["floor2/bedroom/thermo", "floor2/bath/thermo"],
+            paths(
+                &house,
+                &DeviceQuery::new().in_room("/floor2/").named("thermo")
+            )
+        );
+        assert_eq!(
+            vec!["kitchen/heater"],
+            paths(&house, &DeviceQuery::new().tagged("heating").on(true))
+        );
+        assert!(house
+            .find_devices(&DeviceQuery::new().without_tag("heating").on(false))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_temperature() {
+        let house = house();
+        let found = house.find_devices(
+            &DeviceQuery::new()
+                .temperature_between(Temperature::celsius(20.0), Temperature::fahrenheit(80.0)),
+        );
+        assert_eq!(
+            vec!["kitchen/thermo", "floor2/bath/thermo"],
+            found.iter().map(|m| m.path()).collect::<Vec<_>>()
+        );
+        assert_eq!("floor2/bath", found[1].room);
+        assert_eq!(DeviceKind::Thermometer, found[1].device.kind());
+
+        let mut query = DeviceQuery::new();
+        query.max_temperature = Some(Temperature::celsius(18.0));
+        assert_eq!(vec!["floor2/bedroom/thermo"], paths(&house, &query));
+
+        let found = house.find_devices(&DeviceQuery::new().tagged("heating"));
+        assert_eq!(vec!["heating"], found[0].tags().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,60 @@
+//! Имена в кавычках, общие для текстовых форматов правил и описания дома
+
+use std::iter::Peekable;
+
+/// Символы, которые внутри кавычек пишутся через `\`, и буква после `\`
+const ESCAPES: [(char, char); 4] = [('\\', '\\'), ('"', '"'), ('\n', 'n'), ('\t', 't')];
+
+/// Имя как есть или в кавычках, если без них оно не прочитается обратно:
+/// пустое, с пробелами, со знаками формата `symbols` или с `"`, `#`, `\`
+pub(crate) fn quote(name: &str, symbols: &str) -> String {
+    let plain = !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || symbols.contains(c) || "\"#\\".contains(c));
+    if plain {
+        return name.to_owned();
+    }
+
+    let mut quoted = String::from('"');
+    for c in name.chars() {
+        match ESCAPES.iter().find(|(raw, _)| *raw == c) {
+            Some((_, letter)) => {
+                quoted.push('\\');
+                quoted.push(*letter);
+            }
+            None => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Читаем имя после открывающей кавычки до закрывающей и снимаем экранирование.
+/// Возвращаем имя и число прочитанных символов, `None` - если кавычка
+/// не закрыта до конца строки.
+pub(crate) fn unquote(chars: &mut Peekable<impl Iterator<Item = char>>) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut count = 0;
+    loop {
+        let c = chars.next_if(|&c| c != '\n')?;
+        count += 1;
+        match c {
+            '"' => return Some((name, count)),
+            '\\' => {
+                let escape = chars
+                    .peek()
+                    .and_then(|next| ESCAPES.iter().find(|(_, letter)| letter == next));
+                match escape {
+                    Some(&(raw, _)) => {
+                        chars.next();
+                        count += 1;
+                        name.push(raw);
+                    }
+                    None => name.push('\\'),
+                }
+            }
+            c => name.push(c),
+        }
+    }
+}
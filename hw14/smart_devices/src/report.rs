@@ -0,0 +1,405 @@
+pub mod render;
+
+use crate::device::{
+    blinds::blinds_position,
+    history::ReadingStats,
+    sensor::{format_triggered, sensor_state},
+    temperature::{Temperature, TemperatureUnit},
+    Device, DeviceKind, DeviceState, SmartThermometer,
+};
+use crate::tags::TagQuery;
+use serde::{Deserialize, Serialize};
+
+/// Режим построения отчета
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportMode {
+    /// Комната без единого описанного девайса - ошибка
+    #[default]
+    Strict,
+    /// Неописанные девайсы помечаются как недоступные
+    Lenient,
+}
+
+/// Настройки построения отчета
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    pub mode: ReportMode,
+    /// Путь поддерева расположений, по которому строится отчет; `None` - весь дом
+    pub location: Option<String>,
+    /// Только девайсы, подходящие запросу по тегам; комнаты без таких девайсов пропускаются
+    pub tags: Option<TagQuery>,
+}
+
+impl ReportOptions {
+    pub fn strict() -> Self {
+        Self {
+            mode: ReportMode::Strict,
+            ..Default::default()
+        }
+    }
+
+    pub fn lenient() -> Self {
+        Self {
+            mode: ReportMode::Lenient,
+            ..Default::default()
+        }
+    }
+
+    /// Ограничиваем отчет поддеревом расположений
+    pub fn within(mut self, location: &str) -> Self {
+        self.location = Some(location.to_owned());
+        self
+    }
+
+    /// Ограничиваем отчет девайсами с тегами
+    pub fn tagged(mut self, query: TagQuery) -> Self {
+        self.tags = Some(query);
+        self
+    }
+}
+
+/// Девайс, который поставщик не смог описать
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MissingDevice {
+    pub room: String,
+    pub device: String,
+}
+
+/// Текстовый отчет и список девайсов, которые не попали в него
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportOutcome {
+    pub report: String,
+    pub missing: Vec<MissingDevice>,
+}
+
+fn on_off(is_on: bool) -> &'static str {
+    if is_on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Структурный отчет по дому
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub house: String,
+    /// Единицы, в которых отрисовывается температура.
+    /// Сами значения в отчете всегда в градусах Цельсия.
+    #[serde(default)]
+    pub temperature_unit: TemperatureUnit,
+    pub rooms: Vec<RoomReport>,
+    /// Девайсы, которые поставщик не смог описать
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<MissingDevice>,
+}
+
+/// Отчет по комнате
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomReport {
+    pub name: String,
+    pub devices: Vec<DeviceEntry>,
+}
+
+/// Запись об устройстве с типизированным состоянием
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceEntry {
+    pub name: String,
+    pub description: String,
+    #[serde(flatten)]
+    pub state: DeviceState,
+    /// Статистика показаний термометра за его окно
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ReadingStats>,
+    /// Поставщик не описал девайс, в мягком отчете его состояние не показывается
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unavailable: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl DeviceEntry {
+    pub fn from_device(device: &dyn Device) -> Self {
+        Self {
+            name: device.name().to_owned(),
+            description: device.description().to_owned(),
+            state: device.state(),
+            stats: device
+                .as_any()
+                .downcast_ref::<SmartThermometer>()
+                .and_then(SmartThermometer::display_stats),
+            unavailable: false,
+        }
+    }
+
+    /// Запись о неописанном девайсе
+    pub fn unavailable(device: &dyn Device) -> Self {
+        Self {
+            stats: None,
+            unavailable: true,
+            ..Self::from_device(device)
+        }
+    }
+
+    pub fn kind(&self) -> DeviceKind {
+        self.state.kind()
+    }
+
+    /// Включено ли устройство, если у него есть такое состояние
+    pub fn is_on(&self) -> Option<bool> {
+        match self.state {
+            DeviceState::Socket { is_on, .. } | DeviceState::Light { is_on, .. } => Some(is_on),
+            _ => None,
+        }
+    }
+
+    /// Краткое текстовое состояние для таблиц отчета, например `"on"` или `"locked, jammed"`
+    pub fn status(&self) -> Option<String> {
+        match self.state {
+            DeviceState::Socket { is_on, .. } => Some(on_off(is_on).to_owned()),
+            DeviceState::Thermometer { .. } => None,
+            DeviceState::Light {
+                is_on,
+                brightness,
+                color,
+            } => Some(match color {
+                Some(color) => format!("{}, {brightness} %, {color}", on_off(is_on)),
+                None => format!("{}, {brightness} %", on_off(is_on)),
+            }),
+            DeviceState::Lock {
+                is_locked,
+                is_jammed,
+            } => Some(format!(
+                "{}{}",
+                if is_locked { "locked" } else { "unlocked" },
+                if is_jammed { ", jammed" } else { "" }
+            )),
+            DeviceState::Sensor {
+                sensor,
+                is_triggered,
+                last_triggered,
+            } => Some(format!(
+                "{}, last triggered {}",
+                sensor_state(sensor, is_triggered),
+                format_triggered(last_triggered)
+            )),
+            DeviceState::Blinds { position } => Some(blinds_position(position)),
+        }
+    }
+
+    /// Активная мощность, Вт: у выключенной розетки 0
+    pub fn power(&self) -> Option<f64> {
+        match self.state {
+            DeviceState::Socket {
+                is_on,
+                current_power,
+                ..
+            } => Some(if is_on { current_power } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    /// Напряжение, В
+    pub fn voltage(&self) -> Option<f64> {
+        match self.state {
+            DeviceState::Socket { voltage, .. } => Some(voltage),
+            _ => None,
+        }
+    }
+
+    /// Ток, А
+    pub fn current(&self) -> Option<f64> {
+        let voltage = self.voltage()?;
+        let power = self.power()?;
+        Some(if voltage == 0.0 { 0.0 } else { power / voltage })
+    }
+
+    /// Потребленная энергия, кВт*ч
+    pub fn energy(&self) -> Option<f64> {
+        match self.state {
+            DeviceState::Socket { energy_kwh, .. } => Some(energy_kwh),
+            _ => None,
+        }
+    }
+
+    pub fn temperature(&self) -> Option<Temperature> {
+        match self.state {
+            DeviceState::Thermometer {
+                current_temperature,
+                ..
+            } => Some(current_temperature),
+            _ => None,
+        }
+    }
+
+    /// Относительная влажность, %
+    pub fn humidity(&self) -> Option<f64> {
+        match self.state {
+            DeviceState::Thermometer { humidity, .. } => humidity,
+            _ => None,
+        }
+    }
+
+    pub fn dew_point(&self) -> Option<Temperature> {
+        Some(self.temperature()?.dew_point(self.humidity()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        render::{MarkdownRenderer, ReportRenderer, TextRenderer},
+        *,
+    };
+    use crate::{
+        device::{info::DeviceInfoProvider, SmartLight, SmartLock, SmartSocket},
+        SmartHouse, SmartHouseError,
+    };
+
+    /// Поставщик, который описывает только девайсы с заданными именами
+    struct NamesProvider(Vec<&'static str>);
+
+    impl DeviceInfoProvider for NamesProvider {
+        fn info(&self, location_name: &str, device_name: &str) -> Option<String> {
+            self.0
+                .contains(&device_name)
+                .then(|| format!("{location_name}/{device_name}"))
+        }
+    }
+
+    fn house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("my smart house");
+        house.add_room("kitchen").unwrap();
+        house.add_room("floor2/bedroom").unwrap();
+        house
+            .add_device(
+                "kitchen",
+                Box::new(SmartSocket::new("kettle", "electric kettle", true, 2300.0)),
+            )
+            .unwrap();
+        let mut thermo = SmartThermometer::new("thermo", "", 20.0);
+        thermo.set_humidity(Some(50.0));
+        house.add_device("kitchen", Box::new(thermo)).unwrap();
+        house
+            .add_device(
+                "floor2/bedroom",
+                Box::new(SmartLight::new("lamp", "", true, 40)),
+            )
+            .unwrap();
+        house
+            .add_device("floor2/bedroom", Box::new(SmartLock::new("door", "", true)))
+            .unwrap();
+        house.tag_device("floor2/bedroom", "lamp", "light").unwrap();
+        house
+    }
+
+    #[test]
+    fn test_model_from_house() {
+        let house = house();
+        let provider = NamesProvider(vec!["kettle", "thermo", "lamp", "door"]);
+        let report = house.report(&provider).unwrap();
+
+        assert_eq!("my smart house", report.house);
+        assert_eq!(
+            vec!["kitchen", "floor2/bedroom"],
+            report.rooms.iter().map(|r| &r.name).collect::<Vec<_>>()
+        );
+
+        let kettle = &report.rooms[0].devices[0];
+        assert_eq!(DeviceKind::Socket, kettle.kind());
+        assert_eq!("electric kettle", kettle.description);
+        assert_eq!(Some(true), kettle.is_on());
+        assert_eq!(Some(2300.0), kettle.power());
+        assert_eq!(Some(10.0), kettle.current());
+        assert_eq!(Some("on".to_owned()), kettle.status());
+
+        let thermo = &report.rooms[0].devices[1];
+        assert_eq!(Some(Temperature::celsius(20.0)), thermo.temperature());
+        assert_eq!(Some(50.0), thermo.humidity());
+        assert!((thermo.dew_point().unwrap().as_celsius() - 9.3).abs() < 0.1);
+        assert_eq!(None, thermo.power());
+
+        let bedroom = &report.rooms[1].devices;
+        assert_eq!(Some("on, 40 %".to_owned()), bedroom[0].status());
+        assert_eq!(Some("locked".to_owned()), bedroom[1].status());
+    }
+
+    #[test]
+    fn test_model_options() {
+        let house = house();
+
+        // Описан только чайник: спальня без описанных девайсов ломает строгий отчет
+        let provider = NamesProvider(vec!["kettle"]);
+        assert_eq!(
+            Err(SmartHouseError::ReportError {
+                room: "floor2/bedroom".to_owned(),
+                device: Some("lamp".to_owned())
+            }),
+            house.report(&provider)
+        );
+        let report = house.report_of(Some("kitchen"), &provider).unwrap();
+        assert_eq!(1, report.rooms.len());
+        assert_eq!(
+            vec!["kettle"],
+            report.rooms[0]
+                .devices
+                .iter()
+                .map(|d| &d.name)
+                .collect::<Vec<_>>()
+        );
+
+        let options = ReportOptions::strict().tagged(TagQuery::tagged("light"));
+        let report = house.report_with(&house, &options).unwrap();
+        assert_eq!(1, report.rooms.len());
+        assert_eq!("lamp", report.rooms[0].devices[0].name);
+    }
+
+    #[test]
+    fn test_model_lenient() {
+        let house = house();
+        let provider = NamesProvider(vec!["kettle"]);
+        let missing = |room: &str, device: &str| MissingDevice {
+            room: room.to_owned(),
+            device: device.to_owned(),
+        };
+
+        // Мягкий отчет не теряет комнат: неописанные девайсы помечены недоступными
+        let report = house
+            .report_with(&provider, &ReportOptions::lenient())
+            .unwrap();
+        assert_eq!(
+            vec!["kitchen", "floor2/bedroom"],
+            report.rooms.iter().map(|r| &r.name).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![false, true],
+            report.rooms[0]
+                .devices
+                .iter()
+                .map(|d| d.unavailable)
+                .collect::<Vec<_>>()
+        );
+        assert!(report.rooms[1].devices.iter().all(|d| d.unavailable));
+        assert_eq!(
+            vec![
+                missing("kitchen", "thermo"),
+                missing("floor2/bedroom", "lamp"),
+                missing("floor2/bedroom", "door")
+            ],
+            report.missing
+        );
+
+        let text = TextRenderer.render(&report);
+        assert_eq!(3, text.matches("Current state: unavailable").count());
+        assert!(MarkdownRenderer
+            .render(&report)
+            .contains("| lamp | Light |  | unavailable |"));
+
+        // Строгий отчет по кухне тоже сообщает о неописанных девайсах
+        let report = house.report_of(Some("kitchen"), &provider).unwrap();
+        assert_eq!(1, report.rooms[0].devices.len());
+        assert_eq!(vec![missing("kitchen", "thermo")], report.missing);
+    }
+}
@@ -0,0 +1,421 @@
+use super::{DeviceEntry, Report};
+use crate::device::{
+    info::{device_info, unavailable_device_info},
+    temperature::{Temperature, TemperatureUnit},
+    SmartThermometer,
+};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Представление отчета в конкретном формате
+pub trait ReportRenderer {
+    fn render(&self, report: &Report) -> String;
+}
+
+/// Текстовый отчет в привычном формате `create_report`
+pub struct TextRenderer;
+
+pub struct JsonRenderer;
+
+/// Отчет в виде таблиц Markdown, по таблице на комнату
+pub struct MarkdownRenderer;
+
+/// Отчет в виде таблиц HTML, по таблице на комнату
+pub struct HtmlRenderer;
+
+/// Отчет одной таблицей CSV для электронных таблиц
+pub struct CsvRenderer;
+
+/// Поддерживаемые форматы отчета
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Markdown,
+    Html,
+    Csv,
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("unknown report format: {0}")]
+pub struct UnknownReportFormat(pub String);
+
+impl FromStr for ReportFormat {
+    type Err = UnknownReportFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(UnknownReportFormat(s.to_owned())),
+        }
+    }
+}
+
+impl ReportFormat {
+    pub fn renderer(self) -> Box<dyn ReportRenderer> {
+        match self {
+            ReportFormat::Text => Box::new(TextRenderer),
+            ReportFormat::Json => Box::new(JsonRenderer),
+            ReportFormat::Markdown => Box::new(MarkdownRenderer),
+            ReportFormat::Html => Box::new(HtmlRenderer),
+            ReportFormat::Csv => Box::new(CsvRenderer),
+        }
+    }
+}
+
+impl ReportRenderer for TextRenderer {
+    fn render(&self, report: &Report) -> String {
+        report
+            .rooms
+            .iter()
+            .flat_map(|room| {
+                room.devices.iter().map(move |entry| {
+                    let mut device = entry
+                        .state
+                        .clone()
+                        .into_device(&entry.name, &entry.description);
+                    if entry.unavailable {
+                        return unavailable_device_info(&room.name, device.as_ref());
+                    }
+                    if let Some(thermometer) =
+                        device.as_any_mut().downcast_mut::<SmartThermometer>()
+                    {
+                        thermometer.set_display_unit(report.temperature_unit);
+                        thermometer.reported_stats = entry.stats;
+                    }
+                    device_info(&room.name, device.as_ref())
+                })
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl ReportRenderer for JsonRenderer {
+    fn render(&self, report: &Report) -> String {
+        serde_json::to_string_pretty(report).expect("report is always serializable")
+    }
+}
+
+/// Заголовки таблицы, температура подписывается единицами отчета
+fn columns(unit: TemperatureUnit) -> [String; 12] {
+    [
+        "Device".to_owned(),
+        "Kind".to_owned(),
+        "Description".to_owned(),
+        "State".to_owned(),
+        "Power, W".to_owned(),
+        "Voltage, V".to_owned(),
+        "Current, A".to_owned(),
+        "Energy, kWh".to_owned(),
+        format!("Temperature, {unit}"),
+        "Humidity, %".to_owned(),
+        format!("Dew point, {unit}"),
+        format!("Statistics, {unit}"),
+    ]
+}
+
+fn row(entry: &DeviceEntry, unit: TemperatureUnit) -> [String; 12] {
+    if entry.unavailable {
+        let mut cells: [String; 12] = Default::default();
+        cells[0] = entry.name.clone();
+        cells[1] = entry.kind().to_string();
+        cells[2] = entry.description.clone();
+        cells[3] = "unavailable".to_owned();
+        return cells;
+    }
+
+    let temperature = |t: Temperature| ((t.value_in(unit) * 100.0).round() / 100.0).to_string();
+
+    [
+        entry.name.clone(),
+        entry.kind().to_string(),
+        entry.description.clone(),
+        entry.status().unwrap_or_default(),
+        entry.power().map(|p| p.to_string()).unwrap_or_default(),
+        entry.voltage().map(|v| v.to_string()).unwrap_or_default(),
+        entry
+            .current()
+            .map(|a| format!("{a:.2}"))
+            .unwrap_or_default(),
+        entry
+            .energy()
+            .map(|e| format!("{e:.3}"))
+            .unwrap_or_default(),
+        entry.temperature().map(temperature).unwrap_or_default(),
+        entry.humidity().map(|h| h.to_string()).unwrap_or_default(),
+        entry.dew_point().map(temperature).unwrap_or_default(),
+        entry
+            .stats
+            .map(|stats| format!("{}, {}", stats.summary_in(unit), stats.trend))
+            .unwrap_or_default(),
+    ]
+}
+
+impl ReportRenderer for MarkdownRenderer {
+    fn render(&self, report: &Report) -> String {
+        let escape = |s: &str| s.replace('|', "\\|").replace('\n', " ");
+        let line = |cells: &[String]| format!("| {} |\n", cells.join(" | "));
+
+        let mut out = format!("# {}\n", escape(&report.house));
+        for room in &report.rooms {
+            out += &format!("\n## {}\n\n", escape(&room.name));
+            out += &line(&columns(report.temperature_unit).map(|c| escape(&c)));
+            out += &line(&columns(report.temperature_unit).map(|_| "---".to_owned()));
+            for entry in &room.devices {
+                out += &line(&row(entry, report.temperature_unit).map(|cell| escape(&cell)));
+            }
+        }
+
+        out
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+impl ReportRenderer for HtmlRenderer {
+    fn render(&self, report: &Report) -> String {
+        let line = |tag: &str, cells: &[String]| {
+            let cells: String = cells
+                .iter()
+                .map(|cell| format!("<{tag}>{}</{tag}>", escape_html(cell)))
+                .collect();
+            format!("<tr>{cells}</tr>\n")
+        };
+
+        let mut out = format!("<h1>{}</h1>\n", escape_html(&report.house));
+        for room in &report.rooms {
+            out += &format!("<h2>{}</h2>\n<table>\n", escape_html(&room.name));
+            out += &line("th", &columns(report.temperature_unit));
+            for entry in &room.devices {
+                out += &line("td", &row(entry, report.temperature_unit));
+            }
+            out += "</table>\n";
+        }
+
+        out
+    }
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+impl ReportRenderer for CsvRenderer {
+    fn render(&self, report: &Report) -> String {
+        let line = |room: &str, cells: &[String]| {
+            let cells: Vec<String> = std::iter::once(room)
+                .chain(cells.iter().map(|c| c.as_str()))
+                .map(escape_csv)
+                .collect();
+            format!("{}\n", cells.join(","))
+        };
+
+        let mut out = line("Room", &columns(report.temperature_unit));
+        for room in &report.rooms {
+            for entry in &room.devices {
+                out += &line(&room.name, &row(entry, report.temperature_unit));
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::{
+            history::{ReadingStats, Trend},
+            DeviceState,
+        },
+        report::RoomReport,
+    };
+    use std::time::Duration;
+
+    fn test_report() -> Report {
+        Report {
+            house: "my house".to_owned(),
+            temperature_unit: TemperatureUnit::Celsius,
+            rooms: vec![
+                RoomReport {
+                    name: "room1".to_owned(),
+                    devices: vec![
+                        DeviceEntry {
+                            name: "socket".to_owned(),
+                            description: "kettle, 2kW".to_owned(),
+                            state: DeviceState::Socket {
+                                is_on: true,
+                                current_power: 220.5,
+                                voltage: 225.0,
+                                energy_kwh: 1.5,
+                            },
+                            stats: None,
+                            unavailable: false,
+                        },
+                        DeviceEntry {
+                            name: "thermo".to_owned(),
+                            description: "<wall>".to_owned(),
+                            state: DeviceState::Thermometer {
+                                current_temperature: Temperature::celsius(19.2),
+                                humidity: Some(50.0),
+                            },
+                            stats: Some(ReadingStats {
+                                window: Duration::from_secs(24 * 60 * 60),
+                                count: 10,
+                                min: Temperature::celsius(17.9),
+                                max: Temperature::celsius(21.4),
+                                average: Temperature::celsius(19.5),
+                                slope: 0.2,
+                                trend: Trend::Rising,
+                            }),
+                            unavailable: false,
+                        },
+                    ],
+                },
+                RoomReport {
+                    name: "room2".to_owned(),
+                    devices: vec![DeviceEntry {
+                        name: "lamp|1".to_owned(),
+                        description: "".to_owned(),
+                        state: DeviceState::Socket {
+                            is_on: false,
+                            current_power: 1000.0,
+                            voltage: 230.0,
+                            energy_kwh: 0.0,
+                        },
+                        stats: None,
+                        unavailable: false,
+                    }],
+                },
+            ],
+            missing: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        let report = test_report();
+        assert_eq!(
+            r#"Location: room1
+Device/Socket: 
+  Name: socket
+  Description: kettle, 2kW
+  Current state: on, 220.5 W, 225 V, 0.98 A, 1.500 kWh
+Location: room1
+Device/Thermometer: 
+  Name: thermo
+  Description: <wall>
+  Current temperature: 19.2 °C (min 17.9, max 21.4 over 24h)
+  Humidity: 50 %, dew point 8.52 °C
+Location: room2
+Device/Socket: 
+  Name: lamp|1
+  Description: 
+  Current state: off, 0 W, 230 V, 0.00 A, 0.000 kWh"#,
+            TextRenderer.render(&report)
+        );
+
+        let report = Report {
+            temperature_unit: TemperatureUnit::Fahrenheit,
+            ..test_report()
+        };
+        let text = TextRenderer.render(&report);
+        assert!(
+            text.contains("Current temperature: 66.56 °F (min 64.22, max 70.52 over 24h)\n  Humidity: 50 %, dew point 47.33 °F")
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let report = test_report();
+        let json = JsonRenderer.render(&report);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!("socket", value["rooms"][0]["devices"][0]["kind"]);
+        assert_eq!(220.5, value["rooms"][0]["devices"][0]["current_power"]);
+        assert_eq!(225.0, value["rooms"][0]["devices"][0]["voltage"]);
+        assert_eq!(1.5, value["rooms"][0]["devices"][0]["energy_kwh"]);
+        assert_eq!(report, serde_json::from_str::<Report>(&json).unwrap());
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            r#"# my house
+
+## room1
+
+| Device | Kind | Description | State | Power, W | Voltage, V | Current, A | Energy, kWh | Temperature, °C | Humidity, % | Dew point, °C | Statistics, °C |
+| --- | --- | --- | --- | --- | --- | --- | --- | --- | --- | --- | --- |
+| socket | Socket | kettle, 2kW | on | 220.5 | 225 | 0.98 | 1.500 |  |  |  |  |
+| thermo | Thermometer | <wall> |  |  |  |  |  | 19.2 | 50 | 8.52 | min 17.9, max 21.4 over 24h, rising |
+
+## room2
+
+| Device | Kind | Description | State | Power, W | Voltage, V | Current, A | Energy, kWh | Temperature, °C | Humidity, % | Dew point, °C | Statistics, °C |
+| --- | --- | --- | --- | --- | --- | --- | --- | --- | --- | --- | --- |
+| lamp\|1 | Socket |  | off | 0 | 230 | 0.00 | 0.000 |  |  |  |  |
+"#,
+            MarkdownRenderer.render(&test_report())
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let html = HtmlRenderer.render(&test_report());
+
+        assert!(html.starts_with("<h1>my house</h1>\n<h2>room1</h2>\n<table>\n<tr><th>Device</th>"));
+        assert!(html.contains("<tr><td>thermo</td><td>Thermometer</td><td>&lt;wall&gt;</td><td></td><td></td><td></td><td></td><td></td><td>19.2</td><td>50</td><td>8.52</td><td>min 17.9, max 21.4 over 24h, rising</td></tr>"));
+        assert_eq!(2, html.matches("</table>").count());
+    }
+
+    #[test]
+    fn test_temperature_unit() {
+        let report = Report {
+            temperature_unit: TemperatureUnit::Kelvin,
+            ..test_report()
+        };
+        let csv = CsvRenderer.render(&report);
+
+        assert!(csv.contains(r#""Temperature, K","Humidity, %","Dew point, K","Statistics, K""#));
+        assert!(csv.contains("room1,thermo,Thermometer,<wall>,,,,,,292.35,50,281.67,\"min 291.05, max 294.55 over 24h, rising\""));
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            r#"Room,Device,Kind,Description,State,"Power, W","Voltage, V","Current, A","Energy, kWh","Temperature, °C","Humidity, %","Dew point, °C","Statistics, °C"
+room1,socket,Socket,"kettle, 2kW",on,220.5,225,0.98,1.500,,,,
+room1,thermo,Thermometer,<wall>,,,,,,19.2,50,8.52,"min 17.9, max 21.4 over 24h, rising"
+room2,lamp|1,Socket,,off,0,230,0.00,0.000,,,,
+"#,
+            CsvRenderer.render(&test_report())
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Ok(ReportFormat::Markdown), "md".parse());
+        assert_eq!(Ok(ReportFormat::Csv), "CSV".parse());
+        assert_eq!(
+            Err(UnknownReportFormat("pdf".to_owned())),
+            "pdf".parse::<ReportFormat>()
+        );
+    }
+}
@@ -0,0 +1,842 @@
+pub mod text;
+
+use crate::{
+    clock::{system_clock, Clock},
+    device::{
+        lock::LockJammed, Device, DeviceState, SmartBlinds, SmartLight, SmartLock, SmartSocket,
+    },
+    journal::Operation,
+    SmartHouse, SmartHouseError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+/// Ошибка правил автоматизации.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RuleError {
+    /// Правило с таким именем уже есть.
+    #[error("rule \"{0}\" already exists")]
+    DuplicateRule(String),
+
+    /// Правила с таким именем нет.
+    #[error("rule \"{0}\" not found")]
+    RuleNotFound(String),
+
+    /// Девайс не найден ни по пути, ни по уникальному имени.
+    #[error("device \"{0}\" not found")]
+    DeviceNotFound(String),
+
+    /// Девайс не умеет выполнять действие.
+    #[error("device \"{device}\" can't {action}")]
+    UnsupportedAction { device: String, action: String },
+
+    /// Замок заклинило.
+    #[error(transparent)]
+    LockJammed(#[from] LockJammed),
+
+    /// Ошибка дома.
+    #[error(transparent)]
+    House(#[from] SmartHouseError),
+
+    /// Ошибка в тексте правила.
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+
+    /// Гистерезис правила отрицательный.
+    #[error("hysteresis of rule \"{rule}\" must not be negative, found {hysteresis}")]
+    InvalidHysteresis { rule: String, hysteresis: f64 },
+}
+
+type Result<T> = std::result::Result<T, RuleError>;
+
+/// Величина, за которой следит условие
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Температура термометра, °C
+    Temperature,
+    /// Влажность термометра, %
+    Humidity,
+    /// Потребляемая мощность розетки, W (0, если выключена)
+    Power,
+    /// Накопленная энергия розетки, kWh
+    Energy,
+    /// Яркость лампы, %
+    Brightness,
+    /// Открытие жалюзи, %
+    Position,
+}
+
+impl Metric {
+    /// Значение величины в состоянии девайса, `None`, если у девайса ее нет
+    pub fn read(self, state: &DeviceState) -> Option<f64> {
+        match (self, state) {
+            (
+                Metric::Temperature,
+                DeviceState::Thermometer {
+                    current_temperature,
+                    ..
+                },
+            ) => Some(current_temperature.as_celsius()),
+            (Metric::Humidity, DeviceState::Thermometer { humidity, .. }) => *humidity,
+            (
+                Metric::Power,
+                DeviceState::Socket {
+                    is_on,
+                    current_power,
+                    ..
+                },
+            ) => Some(if *is_on { *current_power } else { 0.0 }),
+            (Metric::Energy, DeviceState::Socket { energy_kwh, .. }) => Some(*energy_kwh),
+            (Metric::Brightness, DeviceState::Light { brightness, .. }) => {
+                Some(f64::from(*brightness))
+            }
+            (Metric::Position, DeviceState::Blinds { position }) => Some(f64::from(*position)),
+            _ => None,
+        }
+    }
+
+    /// Единица, в которой задается порог
+    pub fn unit(self) -> &'static str {
+        match self {
+            Metric::Temperature => "°C",
+            Metric::Humidity | Metric::Brightness | Metric::Position => "%",
+            Metric::Power => "W",
+            Metric::Energy => "kWh",
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Metric::Temperature => write!(f, "temperature"),
+            Metric::Humidity => write!(f, "humidity"),
+            Metric::Power => write!(f, "power"),
+            Metric::Energy => write!(f, "energy"),
+            Metric::Brightness => write!(f, "brightness"),
+            Metric::Position => write!(f, "position"),
+        }
+    }
+}
+
+/// Условие срабатывания правила.
+/// Девайс задается путем (`"room1/room1_thermo_1"`) или именем, уникальным в доме.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// Величина больше порога
+    Above {
+        device: String,
+        metric: Metric,
+        value: f64,
+    },
+    /// Величина меньше порога
+    Below {
+        device: String,
+        metric: Metric,
+        value: f64,
+    },
+    /// Датчик сработал
+    Triggered { device: String },
+}
+
+impl Condition {
+    pub fn above(device: &str, metric: Metric, value: f64) -> Self {
+        Condition::Above {
+            device: device.to_owned(),
+            metric,
+            value,
+        }
+    }
+
+    pub fn below(device: &str, metric: Metric, value: f64) -> Self {
+        Condition::Below {
+            device: device.to_owned(),
+            metric,
+            value,
+        }
+    }
+
+    pub fn triggered(device: &str) -> Self {
+        Condition::Triggered {
+            device: device.to_owned(),
+        }
+    }
+
+    pub fn device(&self) -> &str {
+        match self {
+            Condition::Above { device, .. }
+            | Condition::Below { device, .. }
+            | Condition::Triggered { device } => device,
+        }
+    }
+
+    fn device_mut(&mut self) -> &mut String {
+        match self {
+            Condition::Above { device, .. }
+            | Condition::Below { device, .. }
+            | Condition::Triggered { device } => device,
+        }
+    }
+
+    /// Сравниваем состояние с условием: `Some(true)` - выполнено,
+    /// `Some(false)` - вышло за гистерезис и правило можно взвести снова,
+    /// `None` - между порогом и гистерезисом или величины нет
+    fn check(&self, state: &DeviceState, hysteresis: f64) -> Option<bool> {
+        match self {
+            Condition::Above { metric, value, .. } => {
+                let current = metric.read(state)?;
+                if current > *value {
+                    Some(true)
+                } else if current <= value - hysteresis {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            Condition::Below { metric, value, .. } => {
+                let current = metric.read(state)?;
+                if current < *value {
+                    Some(true)
+                } else if current >= value + hysteresis {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            Condition::Triggered { .. } => match state {
+                DeviceState::Sensor { is_triggered, .. } => Some(*is_triggered),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Действие правила над девайсом (путь или уникальное имя)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Включить розетку или лампу
+    TurnOn(String),
+    /// Выключить розетку или лампу
+    TurnOff(String),
+    Lock(String),
+    Unlock(String),
+    /// Открыть жалюзи полностью
+    Open(String),
+    /// Закрыть жалюзи
+    Close(String),
+}
+
+impl Action {
+    pub fn device(&self) -> &str {
+        match self {
+            Action::TurnOn(device)
+            | Action::TurnOff(device)
+            | Action::Lock(device)
+            | Action::Unlock(device)
+            | Action::Open(device)
+            | Action::Close(device) => device,
+        }
+    }
+
+    fn device_mut(&mut self) -> &mut String {
+        match self {
+            Action::TurnOn(device)
+            | Action::TurnOff(device)
+            | Action::Lock(device)
+            | Action::Unlock(device)
+            | Action::Open(device)
+            | Action::Close(device) => device,
+        }
+    }
+
+    /// Название действия без девайса, например `"turn off"`
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Action::TurnOn(_) => "turn on",
+            Action::TurnOff(_) => "turn off",
+            Action::Lock(_) => "lock",
+            Action::Unlock(_) => "unlock",
+            Action::Open(_) => "open",
+            Action::Close(_) => "close",
+        }
+    }
+
+    fn unsupported(&self) -> RuleError {
+        RuleError::UnsupportedAction {
+            device: self.device().to_owned(),
+            action: self.verb().to_owned(),
+        }
+    }
+
+    /// Проверяем, что действие применимо, ничего не меняя
+    pub(crate) fn check(&self, house: &SmartHouse) -> Result<()> {
+        let device = house
+            .resolve_device(self.device())
+            .and_then(|path| house.device_by_path(&path))
+            .ok_or_else(|| RuleError::DeviceNotFound(self.device().to_owned()))?;
+        let device = device.as_any();
+
+        let supported = match self {
+            Action::TurnOn(_) | Action::TurnOff(_) => {
+                device.is::<SmartSocket>() || device.is::<SmartLight>()
+            }
+            Action::Lock(_) | Action::Unlock(_) => match device.downcast_ref::<SmartLock>() {
+                Some(lock) if lock.is_jammed() => {
+                    return Err(LockJammed(lock.name().to_owned()).into())
+                }
+                Some(_) => true,
+                None => false,
+            },
+            Action::Open(_) | Action::Close(_) => device.is::<SmartBlinds>(),
+        };
+
+        if supported {
+            Ok(())
+        } else {
+            Err(self.unsupported())
+        }
+    }
+
+    pub(crate) fn apply(&self, house: &mut SmartHouse) -> Result<()> {
+        let path = house
+            .resolve_device(self.device())
+            .ok_or_else(|| RuleError::DeviceNotFound(self.device().to_owned()))?;
+        house.change_device(&path, |device| self.apply_to(device))?
+    }
+
+    fn apply_to(&self, device: &mut dyn Device) -> Result<()> {
+        let device = device.as_any_mut();
+
+        match self {
+            Action::TurnOn(_) | Action::TurnOff(_) => {
+                let on = matches!(self, Action::TurnOn(_));
+                if let Some(socket) = device.downcast_mut::<SmartSocket>() {
+                    if on {
+                        socket.turn_on()
+                    } else {
+                        socket.turn_off()
+                    }
+                } else if let Some(light) = device.downcast_mut::<SmartLight>() {
+                    if on {
+                        light.turn_on()
+                    } else {
+                        light.turn_off()
+                    }
+                } else {
+                    return Err(self.unsupported());
+                }
+            }
+            Action::Lock(_) | Action::Unlock(_) => {
+                let lock = device
+                    .downcast_mut::<SmartLock>()
+                    .ok_or_else(|| self.unsupported())?;
+                if matches!(self, Action::Lock(_)) {
+                    lock.lock()?
+                } else {
+                    lock.unlock()?
+                }
+            }
+            Action::Open(_) | Action::Close(_) => {
+                let blinds = device
+                    .downcast_mut::<SmartBlinds>()
+                    .ok_or_else(|| self.unsupported())?;
+                if matches!(self, Action::Open(_)) {
+                    blinds.open()
+                } else {
+                    blinds.close()
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// Правило автоматизации: когда выполняется условие, выполняем действия.
+/// Сработав, правило ждет, пока величина не уйдет за порог на `hysteresis`,
+/// и не срабатывает чаще, чем раз в `cooldown`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    pub then: Vec<Action>,
+    /// В единицах величины условия
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hysteresis: f64,
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Duration::is_zero"
+    )]
+    pub cooldown: Duration,
+}
+
+impl Rule {
+    pub fn new(name: &str, when: Condition, then: Action) -> Self {
+        Self {
+            name: name.to_owned(),
+            when,
+            then: vec![then],
+            hysteresis: 0.0,
+            cooldown: Duration::ZERO,
+        }
+    }
+
+    /// Добавляем еще одно действие
+    pub fn with_action(mut self, action: Action) -> Self {
+        self.then.push(action);
+        self
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis.abs();
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+/// Действие сработавшего правила и его результат.
+/// При пробном прогоне результат показывает, удалось бы ли выполнить действие.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Firing {
+    pub rule: String,
+    pub action: Action,
+    pub result: Result<()>,
+}
+
+impl fmt::Display for Firing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, text::format_action(&self.action))?;
+        if let Err(error) = &self.result {
+            write!(f, " ({error})")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    rule: Rule,
+    /// Правило взведено и сработает, как только выполнится условие
+    armed: bool,
+    last_fired: Option<SystemTime>,
+}
+
+impl Entry {
+    /// Правило должно сработать сейчас
+    fn is_due(&self, house: &SmartHouse, now: SystemTime) -> bool {
+        let cooled_down = self
+            .last_fired
+            .is_none_or(|at| now.duration_since(at).unwrap_or_default() >= self.rule.cooldown);
+
+        self.armed && cooled_down && self.check(house) == Some(true)
+    }
+
+    fn check(&self, house: &SmartHouse) -> Option<bool> {
+        let device = house.resolve_device(self.rule.when.device())?;
+        let state = house.device_by_path(&device)?.state();
+        self.rule.when.check(&state, self.rule.hysteresis)
+    }
+}
+
+/// Набор правил автоматизации и их состояние между проверками
+#[derive(Debug, Clone)]
+pub struct RuleEngine {
+    entries: Vec<Entry>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            clock: system_clock(),
+        }
+    }
+
+    /// Подменяем часы, по которым отсчитывается `cooldown`
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Ссылки правил на девайсы, чтобы перенести их при переименовании
+    pub(crate) fn device_references_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.entries.iter_mut().flat_map(|e| {
+            std::iter::once(e.rule.when.device_mut())
+                .chain(e.rule.then.iter_mut().map(Action::device_mut))
+        })
+    }
+
+    /// Правила в порядке добавления
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.entries.iter().map(|e| &e.rule)
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.rules().find(|r| r.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Добавляем правило в конец, имена правил уникальны
+    pub fn add_rule(&mut self, rule: Rule) -> Result<()> {
+        self.insert_rule(self.entries.len(), rule)
+    }
+
+    /// Ставим правило на место `position` (или в конец)
+    pub(crate) fn insert_rule(&mut self, position: usize, rule: Rule) -> Result<()> {
+        if self.rule(&rule.name).is_some() {
+            return Err(RuleError::DuplicateRule(rule.name));
+        }
+        // `with_hysteresis` берет модуль, а правило из JSON приходит как есть
+        if rule.hysteresis < 0.0 || rule.hysteresis.is_nan() {
+            return Err(RuleError::InvalidHysteresis {
+                rule: rule.name,
+                hysteresis: rule.hysteresis,
+            });
+        }
+
+        let entry = Entry {
+            rule,
+            armed: true,
+            last_fired: None,
+        };
+        self.entries.insert(position.min(self.entries.len()), entry);
+        Ok(())
+    }
+
+    pub fn remove_rule(&mut self, name: &str) -> Result<Rule> {
+        self.take_rule(name).map(|(_, rule)| rule)
+    }
+
+    /// Удаляем правило и возвращаем его вместе с его местом
+    fn take_rule(&mut self, name: &str) -> Result<(usize, Rule)> {
+        let position = self
+            .entries
+            .iter()
+            .position(|e| e.rule.name == name)
+            .ok_or_else(|| RuleError::RuleNotFound(name.to_owned()))?;
+
+        Ok((position, self.entries.remove(position).rule))
+    }
+
+    /// Проверяем правила и выполняем действия сработавших.
+    /// Условия проверяются по состоянию до выполнения действий,
+    /// так что правила в одном проходе друг друга не запускают.
+    pub fn evaluate(&mut self, house: &mut SmartHouse) -> Vec<Firing> {
+        let now = self.clock.now();
+        let due: Vec<bool> = self.entries.iter().map(|e| e.is_due(house, now)).collect();
+
+        let mut firings = Vec::new();
+        for (entry, due) in self.entries.iter_mut().zip(due) {
+            if due {
+                entry.armed = false;
+                entry.last_fired = Some(now);
+                for action in &entry.rule.then {
+                    firings.push(Firing {
+                        rule: entry.rule.name.clone(),
+                        action: action.clone(),
+                        result: action.apply(house),
+                    });
+                }
+            } else if entry.check(house) == Some(false) {
+                entry.armed = true;
+            }
+        }
+
+        firings
+    }
+
+    /// Пробный прогон: какие правила сработали бы сейчас и удались бы их действия.
+    /// Ни дом, ни состояние правил не меняются.
+    pub fn dry_run(&self, house: &SmartHouse) -> Vec<Firing> {
+        let now = self.clock.now();
+        self.entries
+            .iter()
+            .filter(|e| e.is_due(house, now))
+            .flat_map(|e| {
+                e.rule.then.iter().map(|action| Firing {
+                    rule: e.rule.name.clone(),
+                    action: action.clone(),
+                    result: action.check(house),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for RuleEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SmartHouse {
+    /// Добавляем правило автоматизации дома, имена правил уникальны
+    pub fn add_rule(&mut self, rule: Rule) -> Result<()> {
+        self.rules.add_rule(rule.clone())?;
+        self.journal.record(Operation::AddRule { rule });
+        Ok(())
+    }
+
+    /// Добавляем несколько правил одной операцией журнала.
+    /// Если хоть одно не подходит, не добавляется ни одно.
+    pub fn add_rules(&mut self, rules: impl IntoIterator<Item = Rule>) -> Result<()> {
+        self.atomic("add rules".to_owned(), |house| {
+            rules.into_iter().try_for_each(|rule| house.add_rule(rule))
+        })
+    }
+
+    pub fn remove_rule(&mut self, name: &str) -> Result<Rule> {
+        let (position, rule) = self.rules.take_rule(name)?;
+        self.journal.record(Operation::RemoveRule {
+            position,
+            rule: rule.clone(),
+        });
+        Ok(rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        device::{Device, SmartThermometer},
+    };
+    use std::collections::HashMap;
+
+    fn house() -> SmartHouse {
+        SmartHouse::new(
+            "my smart house",
+            HashMap::from([(
+                "room1",
+                vec![
+                    Box::new(SmartSocket::new("room1_socket_1", "heater", true, 2000.0))
+                        as Box<dyn Device>,
+                    Box::new(SmartThermometer::new("room1_thermo_1", "wall", 20.0)),
+                    Box::new(SmartLock::new("door", "front door", false)),
+                ],
+            )]),
+        )
+    }
+
+    fn set_temperature(house: &mut SmartHouse, value: f64) {
+        house
+            .thermometer_mut("room1", "room1_thermo_1")
+            .unwrap()
+            .set_temperature(value);
+    }
+
+    fn is_on(house: &SmartHouse) -> bool {
+        house.socket("room1", "room1_socket_1").unwrap().is_on()
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut house = house();
+        let mut engine = RuleEngine::new();
+        engine
+            .add_rule(
+                Rule::new(
+                    "overheat",
+                    Condition::above("room1_thermo_1", Metric::Temperature, 25.0),
+                    Action::TurnOff("room1_socket_1".to_owned()),
+                )
+                .with_hysteresis(2.0),
+            )
+            .unwrap();
+
+        assert!(engine.evaluate(&mut house).is_empty());
+
+        set_temperature(&mut house, 25.5);
+        let firings = engine.evaluate(&mut house);
+        assert_eq!(1, firings.len());
+        assert_eq!(Ok(()), firings[0].result);
+        assert!(!is_on(&house));
+
+        // Не взводится, пока температура не опустится до 23 °C
+        house
+            .socket_mut("room1", "room1_socket_1")
+            .unwrap()
+            .turn_on();
+        set_temperature(&mut house, 24.0);
+        assert!(engine.evaluate(&mut house).is_empty());
+        set_temperature(&mut house, 26.0);
+        assert!(engine.evaluate(&mut house).is_empty());
+        assert!(is_on(&house));
+
+        set_temperature(&mut house, 23.0);
+        assert!(engine.evaluate(&mut house).is_empty());
+        set_temperature(&mut house, 26.0);
+        assert_eq!(1, engine.evaluate(&mut house).len());
+        assert!(!is_on(&house));
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let clock = Arc::new(ManualClock::default());
+        let mut house = house();
+        let mut engine = RuleEngine::new().with_clock(clock.clone());
+        engine
+            .add_rule(
+                Rule::new(
+                    "overheat",
+                    Condition::above("room1/room1_thermo_1", Metric::Temperature, 25.0),
+                    Action::TurnOff("room1/room1_socket_1".to_owned()),
+                )
+                .with_cooldown(Duration::from_secs(600)),
+            )
+            .unwrap();
+
+        set_temperature(&mut house, 26.0);
+        assert_eq!(1, engine.evaluate(&mut house).len());
+
+        set_temperature(&mut house, 20.0);
+        engine.evaluate(&mut house);
+        set_temperature(&mut house, 26.0);
+        assert!(engine.evaluate(&mut house).is_empty());
+
+        clock.advance(Duration::from_secs(600));
+        assert_eq!(1, engine.evaluate(&mut house).len());
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let mut house = house();
+        let mut engine = RuleEngine::new();
+        engine
+            .add_rule(
+                Rule::new(
+                    "overheat",
+                    Condition::above("room1_thermo_1", Metric::Temperature, 25.0),
+                    Action::TurnOff("room1_socket_1".to_owned()),
+                )
+                .with_action(Action::Lock("room1_socket_1".to_owned()))
+                .with_action(Action::Lock("door".to_owned())),
+            )
+            .unwrap();
+        assert_eq!(
+            Err(RuleError::DuplicateRule("overheat".to_owned())),
+            engine.add_rule(Rule::new(
+                "overheat",
+                Condition::triggered("door"),
+                Action::Unlock("door".to_owned())
+            ))
+        );
+
+        assert!(engine.dry_run(&house).is_empty());
+        set_temperature(&mut house, 30.0);
+
+        let firings: Vec<String> = engine
+            .dry_run(&house)
+            .iter()
+            .map(Firing::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "overheat: turn off room1_socket_1",
+                "overheat: lock room1_socket_1 (device \"room1_socket_1\" can't lock)",
+                "overheat: lock door",
+            ],
+            firings
+        );
+        assert!(is_on(&house));
+
+        // Пробный прогон не разряжает правило
+        assert_eq!(3, engine.evaluate(&mut house).len());
+        assert!(!is_on(&house));
+        assert!(house
+            .device_by_path("room1/door")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<SmartLock>()
+            .unwrap()
+            .is_locked());
+    }
+
+    #[test]
+    fn test_fires_on_any_change() {
+        let mut house = house();
+        let is_locked = |house: &SmartHouse| {
+            house
+                .device_by_path("room1/door")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<SmartLock>()
+                .unwrap()
+                .is_locked()
+        };
+        house
+            .add_rule(Rule::new(
+                "leaving",
+                Condition::above("room1_socket_1", Metric::Power, 1000.0),
+                Action::Lock("door".to_owned()),
+            ))
+            .unwrap();
+
+        // Правило срабатывает от любого изменения девайса, не только от `update_device`
+        house.switch_socket("room1_socket_1", false).unwrap();
+        assert!(!is_locked(&house));
+        house.switch_socket("room1_socket_1", true).unwrap();
+        assert!(is_locked(&house));
+
+        // Отмена действия правила не запускает его снова
+        house.undo().unwrap();
+        assert!(!is_locked(&house));
+        assert!(is_on(&house));
+    }
+
+    #[test]
+    fn test_add_rules() {
+        let mut house = house();
+        let rule = |name: &str| {
+            Rule::new(
+                name,
+                Condition::triggered("door"),
+                Action::TurnOff("room1_socket_1".to_owned()),
+            )
+        };
+
+        assert_eq!(
+            Err(RuleError::DuplicateRule("a".to_owned())),
+            house.add_rules([rule("a"), rule("b"), rule("a")])
+        );
+        assert_eq!(0, house.rules().rules().count());
+        assert!(!house.journal().can_undo());
+
+        house.add_rules([rule("a"), rule("b")]).unwrap();
+        assert_eq!(2, house.rules().rules().count());
+        house.undo().unwrap();
+        assert_eq!(0, house.rules().rules().count());
+
+        let mut negative = rule("c");
+        negative.hysteresis = -1.0;
+        assert_eq!(
+            Err(RuleError::InvalidHysteresis {
+                rule: "c".to_owned(),
+                hysteresis: -1.0
+            }),
+            house.add_rule(negative)
+        );
+    }
+}
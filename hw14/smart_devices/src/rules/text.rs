@@ -0,0 +1,522 @@
+//! Текстовый формат правил, по правилу на строку:
+//!
+//! ```text
+//! # комментарий
+//! rule overheat: when room1_thermo_1 temperature > 25 °C then turn off room1_socket_1 hysteresis 1 °C cooldown 10m
+//! rule hall: when hall/motion triggered then turn on hall/lamp, unlock "front door"
+//! ```
+//!
+//! Величины: `temperature`, `humidity`, `power`, `energy`, `brightness`, `position`.
+//! Действия: `turn on`, `turn off`, `lock`, `unlock`, `open`, `close`.
+//! Имена с пробелами и знаками `,:<>#` берутся в двойные кавычки, внутри кавычек `\"` и `\\`.
+
+use super::{Action, Condition, Metric, Rule, RuleError};
+use crate::{
+    device::temperature::{Temperature, TemperatureUnit},
+    quoting,
+};
+use std::{fmt, str::FromStr, time::Duration};
+
+type Result<T> = std::result::Result<T, RuleError>;
+
+/// Разбираем правила, пустые строки и комментарии `#` пропускаются
+pub fn parse_rules(text: &str) -> Result<Vec<Rule>> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let tokens = match tokenize(line, index + 1) {
+                Ok(tokens) if tokens.is_empty() => return None,
+                Ok(tokens) => tokens,
+                Err(error) => return Some(Err(error)),
+            };
+            Some(Parser::new(tokens, index + 1).rule())
+        })
+        .collect()
+}
+
+/// Записываем правила в текстовом формате, по правилу на строку
+pub fn format_rules<'a, I: IntoIterator<Item = &'a Rule>>(rules: I) -> String {
+    rules.into_iter().map(|rule| format!("{rule}\n")).collect()
+}
+
+/// Действие в текстовом формате, например `"turn off room1_socket_1"`
+pub fn format_action(action: &Action) -> String {
+    format!("{} {}", action.verb(), quote(action.device()))
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rules = parse_rules(s)?;
+        match rules.len() {
+            1 => Ok(rules.remove(0)),
+            count => Err(RuleError::Parse {
+                line: 1,
+                message: format!("expected one rule, found {count}"),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rule {}: when ", quote(&self.name))?;
+        let metric = match &self.when {
+            Condition::Above {
+                device,
+                metric,
+                value,
+            } => {
+                write!(f, "{} {metric} > {value} {}", quote(device), metric.unit())?;
+                Some(metric)
+            }
+            Condition::Below {
+                device,
+                metric,
+                value,
+            } => {
+                write!(f, "{} {metric} < {value} {}", quote(device), metric.unit())?;
+                Some(metric)
+            }
+            Condition::Triggered { device } => {
+                write!(f, "{} triggered", quote(device))?;
+                None
+            }
+        };
+
+        let actions: Vec<String> = self.then.iter().map(format_action).collect();
+        write!(f, " then {}", actions.join(", "))?;
+
+        if self.hysteresis != 0.0 {
+            write!(f, " hysteresis {}", self.hysteresis)?;
+            if let Some(metric) = metric {
+                write!(f, " {}", metric.unit())?;
+            }
+        }
+        if !self.cooldown.is_zero() {
+            write!(f, " cooldown {}", humantime::format_duration(self.cooldown))?;
+        }
+        Ok(())
+    }
+}
+
+/// Имя в кавычках, если без них оно не прочитается обратно
+fn quote(name: &str) -> String {
+    quoting::quote(name, SEPARATORS)
+}
+
+/// Символы, которые сами по себе являются словами
+const SEPARATORS: &str = ",:<>";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+fn tokenize(line: &str, line_number: usize) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.chars().peekable();
+
+    let flush = |current: &mut String, tokens: &mut Vec<Token>| {
+        if !current.is_empty() {
+            tokens.push(Token {
+                text: std::mem::take(current),
+                quoted: false,
+            });
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            '"' => {
+                flush(&mut current, &mut tokens);
+                let Some((text, _)) = quoting::unquote(&mut chars) else {
+                    return Err(RuleError::Parse {
+                        line: line_number,
+                        message: "unterminated quoted name".to_owned(),
+                    });
+                };
+                tokens.push(Token { text, quoted: true });
+            }
+            c if c.is_whitespace() => flush(&mut current, &mut tokens),
+            c if SEPARATORS.contains(c) => {
+                flush(&mut current, &mut tokens);
+                tokens.push(Token {
+                    text: c.to_string(),
+                    quoted: false,
+                });
+            }
+            c => current.push(c),
+        }
+    }
+    flush(&mut current, &mut tokens);
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>, line: usize) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            line,
+        }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T> {
+        Err(RuleError::Parse {
+            line: self.line,
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of rule".to_owned()),
+        }
+    }
+
+    /// Следующее слово - ключевое слово `keyword`
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| !t.quoted && t.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<()> {
+        let token = self.next()?;
+        if token.quoted || !token.text.eq_ignore_ascii_case(keyword) {
+            return self.error(format!("expected \"{keyword}\", found \"{}\"", token.text));
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let token = self.next()?;
+        if !token.quoted && SEPARATORS.contains(token.text.as_str()) {
+            return self.error(format!("expected name, found \"{}\"", token.text));
+        }
+        Ok(token.text)
+    }
+
+    fn rule(mut self) -> Result<Rule> {
+        self.expect("rule")?;
+        let name = self.name()?;
+        self.expect(":")?;
+        self.expect("when")?;
+        let (when, metric) = self.condition()?;
+        self.expect("then")?;
+
+        let mut then = vec![self.action()?];
+        while self.is_keyword(",") {
+            self.next()?;
+            then.push(self.action()?);
+        }
+
+        let mut rule = Rule {
+            name,
+            when,
+            then,
+            hysteresis: 0.0,
+            cooldown: Duration::ZERO,
+        };
+        while let Some(token) = self.peek().cloned() {
+            if self.is_keyword("hysteresis") {
+                self.next()?;
+                rule.hysteresis = self.difference(metric)?.abs();
+            } else if self.is_keyword("cooldown") {
+                self.next()?;
+                rule.cooldown = self.duration()?;
+            } else {
+                return self.error(format!("unexpected \"{}\"", token.text));
+            }
+        }
+
+        Ok(rule)
+    }
+
+    fn condition(&mut self) -> Result<(Condition, Option<Metric>)> {
+        let device = self.name()?;
+        if self.is_keyword("triggered") {
+            self.next()?;
+            return Ok((Condition::Triggered { device }, None));
+        }
+
+        let token = self.next()?;
+        let metric = match token.text.to_ascii_lowercase().as_str() {
+            "temperature" => Metric::Temperature,
+            "humidity" => Metric::Humidity,
+            "power" => Metric::Power,
+            "energy" => Metric::Energy,
+            "brightness" => Metric::Brightness,
+            "position" => Metric::Position,
+            _ => return self.error(format!("unknown condition \"{}\"", token.text)),
+        };
+
+        let above = match self.next()?.text.as_str() {
+            ">" => true,
+            "<" => false,
+            other => return self.error(format!("expected \">\" or \"<\", found \"{other}\"")),
+        };
+
+        let (value, unit) = self.quantity(metric, "then")?;
+        let value = match unit {
+            Some(unit) => Temperature::new(value, unit).as_celsius(),
+            None => value,
+        };
+
+        let when = if above {
+            Condition::Above {
+                device,
+                metric,
+                value,
+            }
+        } else {
+            Condition::Below {
+                device,
+                metric,
+                value,
+            }
+        };
+        Ok((when, Some(metric)))
+    }
+
+    /// Число с необязательной единицей величины, слитно (`25°C`) или через пробел.
+    /// Для температуры возвращаем и ее единицы.
+    fn quantity(
+        &mut self,
+        metric: Metric,
+        terminator: &str,
+    ) -> Result<(f64, Option<TemperatureUnit>)> {
+        let token = self.next()?;
+        let split = token
+            .text
+            .find(|c: char| !(c.is_ascii_digit() || "+-.".contains(c)))
+            .unwrap_or(token.text.len());
+        let (number, mut unit) = token.text.split_at(split);
+        let value: f64 = match number.parse() {
+            Ok(value) => value,
+            Err(_) => return self.error(format!("expected number, found \"{}\"", token.text)),
+        };
+
+        let next_is_unit = self
+            .peek()
+            .is_some_and(|t| !t.quoted && !SEPARATORS.contains(t.text.as_str()))
+            && !self.is_keyword(terminator)
+            && !self.is_keyword("hysteresis")
+            && !self.is_keyword("cooldown");
+        let unit_token;
+        if unit.is_empty() && next_is_unit {
+            unit_token = self.next()?.text;
+            unit = &unit_token;
+        }
+
+        if unit.is_empty() {
+            return Ok((value, None));
+        }
+        if metric == Metric::Temperature {
+            return match unit.trim_start_matches('°').parse() {
+                Ok(unit) => Ok((value, Some(unit))),
+                Err(error) => self.error(format!("{error}")),
+            };
+        }
+        if !unit.eq_ignore_ascii_case(metric.unit()) {
+            return self.error(format!(
+                "expected {metric} in {}, found \"{unit}\"",
+                metric.unit()
+            ));
+        }
+        Ok((value, None))
+    }
+
+    /// Разница величин для гистерезиса, для температуры переводим в °C
+    fn difference(&mut self, metric: Option<Metric>) -> Result<f64> {
+        let (value, unit) = self.quantity(metric.unwrap_or(Metric::Position), "cooldown")?;
+        Ok(match unit {
+            Some(TemperatureUnit::Fahrenheit) => value * 5.0 / 9.0,
+            _ => value,
+        })
+    }
+
+    /// Длительность до конца строки или следующего параметра, например `1h 30m`
+    fn duration(&mut self) -> Result<Duration> {
+        let mut words = Vec::new();
+        while self.peek().is_some() && !self.is_keyword("hysteresis") {
+            words.push(self.next()?.text);
+        }
+
+        match humantime::parse_duration(&words.join(" ")) {
+            Ok(duration) => Ok(duration),
+            Err(error) => self.error(format!("invalid cooldown: {error}")),
+        }
+    }
+
+    fn action(&mut self) -> Result<Action> {
+        let token = self.next()?;
+        let verb = token.text.to_ascii_lowercase();
+        let action: fn(String) -> Action = match verb.as_str() {
+            "turn" => {
+                let token = self.next()?;
+                match token.text.to_ascii_lowercase().as_str() {
+                    "on" => Action::TurnOn,
+                    "off" => Action::TurnOff,
+                    _ => {
+                        return self.error(format!(
+                            "expected \"on\" or \"off\", found \"{}\"",
+                            token.text
+                        ))
+                    }
+                }
+            }
+            "lock" => Action::Lock,
+            "unlock" => Action::Unlock,
+            "open" => Action::Open,
+            "close" => Action::Close,
+            _ => return self.error(format!("unknown action \"{}\"", token.text)),
+        };
+
+        Ok(action(self.name()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"
+# отопление
+rule overheat: when room1_thermo_1 temperature > 77°F then turn off room1_socket_1 hysteresis 1.8 F cooldown 1h 30m
+rule "hall light": when hall/motion triggered then turn on "hall/lamp 1", unlock door
+rule dark: when bedroom/blinds position < 10 % then turn on bedroom/lamp
+"#;
+
+        let rules = parse_rules(text).unwrap();
+        assert_eq!(
+            Rule::new(
+                "overheat",
+                Condition::above("room1_thermo_1", Metric::Temperature, 25.0),
+                Action::TurnOff("room1_socket_1".to_owned())
+            )
+            .with_hysteresis(1.0)
+            .with_cooldown(Duration::from_secs(5400)),
+            rules[0]
+        );
+        assert_eq!(
+            vec![
+                Action::TurnOn("hall/lamp 1".to_owned()),
+                Action::Unlock("door".to_owned())
+            ],
+            rules[1].then
+        );
+
+        let formatted = format_rules(&rules);
+        assert_eq!(
+            r#"rule overheat: when room1_thermo_1 temperature > 25 °C then turn off room1_socket_1 hysteresis 1 °C cooldown 1h 30m
+rule "hall light": when hall/motion triggered then turn on "hall/lamp 1", unlock door
+rule dark: when bedroom/blinds position < 10 % then turn on bedroom/lamp
+"#,
+            formatted
+        );
+        assert_eq!(rules, parse_rules(&formatted).unwrap());
+    }
+
+    #[test]
+    fn test_quoted_names() {
+        let rule = Rule::new(
+            "tv \"night\"",
+            Condition::triggered("living/12\" tv"),
+            Action::TurnOff("living/12\" tv".to_owned()),
+        )
+        .with_action(Action::TurnOn(r"c:\lamp #1".to_owned()))
+        .with_action(Action::Open("a, b: <c>".to_owned()));
+
+        let formatted = rule.to_string();
+        assert_eq!(
+            r#"rule "tv \"night\"": when "living/12\" tv" triggered then turn off "living/12\" tv", turn on "c:\\lamp #1", open "a, b: <c>""#,
+            formatted
+        );
+        assert_eq!(rule, formatted.parse().unwrap());
+    }
+
+    #[test]
+    fn test_metrics_and_actions() {
+        let metrics = [
+            (Metric::Temperature, "°C"),
+            (Metric::Humidity, "%"),
+            (Metric::Power, "W"),
+            (Metric::Energy, "kWh"),
+            (Metric::Brightness, "%"),
+            (Metric::Position, "%"),
+        ];
+        let actions = [
+            Action::TurnOn("d".to_owned()),
+            Action::TurnOff("d".to_owned()),
+            Action::Lock("d".to_owned()),
+            Action::Unlock("d".to_owned()),
+            Action::Open("d".to_owned()),
+            Action::Close("d".to_owned()),
+        ];
+
+        for ((metric, unit), action) in metrics.into_iter().zip(actions) {
+            let verb = action.verb();
+            for rule in [
+                Rule::new("a", Condition::above("s", metric, 12.5), action.clone())
+                    .with_hysteresis(0.5),
+                Rule::new("b", Condition::below("s", metric, 3.0), action.clone())
+                    .with_cooldown(Duration::from_secs(90)),
+            ] {
+                let formatted = rule.to_string();
+                assert!(formatted.contains(&format!("s {metric} ")), "{formatted}");
+                assert!(formatted.contains(&format!(" {unit} ")), "{formatted}");
+                assert!(formatted.contains(&format!("then {verb} d")), "{formatted}");
+                assert_eq!(rule, formatted.parse().unwrap(), "{formatted}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| parse_rules(text).unwrap_err().to_string();
+
+        assert_eq!(
+            "line 2: unknown condition \"pressure\"",
+            error("\nrule a: when t pressure > 1 then turn on s")
+        );
+        assert_eq!(
+            "line 1: expected power in W, found \"kWh\"",
+            error("rule a: when s power > 1 kWh then turn off s")
+        );
+        assert_eq!(
+            "line 1: unexpected end of rule",
+            error("rule a: when t temperature > 25 then")
+        );
+        assert_eq!(
+            "line 1: unterminated quoted name",
+            error("rule a: when \"t temperature > 25")
+        );
+        assert!(
+            "rule a: when s triggered then open b\nrule b: when s triggered then close b"
+                .parse::<Rule>()
+                .is_err()
+        );
+    }
+}
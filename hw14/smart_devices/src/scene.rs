@@ -0,0 +1,708 @@
+use crate::{
+    device::{
+        blinds::blinds_position, light::Color, lock::LockJammed, Device, DeviceKind, DeviceState,
+        SmartBlinds, SmartLight, SmartLock, SmartSocket,
+    },
+    journal::Operation,
+    power, SmartHouse, SmartHouseError,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Ошибка сцен.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SceneError {
+    /// Сцена с таким именем уже есть.
+    #[error("scene \"{0}\" already exists")]
+    DuplicateScene(String),
+
+    /// Сцены с таким именем нет.
+    #[error("scene \"{0}\" not found")]
+    SceneNotFound(String),
+
+    /// Девайс не найден ни по пути, ни по уникальному имени.
+    #[error("device \"{0}\" not found")]
+    DeviceNotFound(String),
+
+    /// Девайс упомянут в сцене дважды.
+    #[error("device \"{0}\" is already in the scene")]
+    DuplicateDevice(String),
+
+    /// Девайс другого вида, чем в сцене.
+    #[error("device \"{device}\" is a {found:?}, scene expects a {expected:?}")]
+    KindMismatch {
+        device: String,
+        expected: DeviceKind,
+        found: DeviceKind,
+    },
+
+    /// Состоянием девайса нельзя управлять: термометр, датчик.
+    #[error("device \"{0}\" can't be controlled by a scene")]
+    NotControllable(String),
+
+    /// Замок заклинило.
+    #[error(transparent)]
+    LockJammed(#[from] LockJammed),
+
+    /// Бюджет мощности выключил розетку, которую включает эта же сцена.
+    #[error("power budget turned off \"{0}\" of the same scene")]
+    ShedInScene(String),
+
+    /// Ошибка дома.
+    #[error(transparent)]
+    House(#[from] SmartHouseError),
+}
+
+type Result<T> = std::result::Result<T, SceneError>;
+
+/// Состояние, в которое сцена приводит девайс
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TargetState {
+    Socket {
+        is_on: bool,
+    },
+    Light {
+        is_on: bool,
+        brightness: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<Color>,
+    },
+    Lock {
+        is_locked: bool,
+    },
+    Blinds {
+        position: u8,
+    },
+}
+
+impl TargetState {
+    /// Управляемая часть состояния девайса, `None` для термометров и датчиков
+    pub fn of(state: &DeviceState) -> Option<Self> {
+        match *state {
+            DeviceState::Socket { is_on, .. } => Some(TargetState::Socket { is_on }),
+            DeviceState::Light {
+                is_on,
+                brightness,
+                color,
+            } => Some(TargetState::Light {
+                is_on,
+                brightness,
+                color,
+            }),
+            DeviceState::Lock { is_locked, .. } => Some(TargetState::Lock { is_locked }),
+            DeviceState::Blinds { position } => Some(TargetState::Blinds { position }),
+            DeviceState::Thermometer { .. } | DeviceState::Sensor { .. } => None,
+        }
+    }
+
+    pub fn kind(&self) -> DeviceKind {
+        match self {
+            TargetState::Socket { .. } => DeviceKind::Socket,
+            TargetState::Light { .. } => DeviceKind::Light,
+            TargetState::Lock { .. } => DeviceKind::Lock,
+            TargetState::Blinds { .. } => DeviceKind::Blinds,
+        }
+    }
+
+    /// Проверяем, что состояние можно применить к девайсу, и узнаем, изменится ли он
+    fn check(&self, device: &dyn Device, path: &str) -> Result<bool> {
+        if device.kind() != self.kind() {
+            return Err(SceneError::KindMismatch {
+                device: path.to_owned(),
+                expected: self.kind(),
+                found: device.kind(),
+            });
+        }
+
+        if let (TargetState::Lock { is_locked }, Some(lock)) =
+            (self, device.as_any().downcast_ref::<SmartLock>())
+        {
+            if lock.is_jammed() && lock.is_locked() != *is_locked {
+                return Err(LockJammed(lock.name().to_owned()).into());
+            }
+        }
+
+        Ok(TargetState::of(&device.state()).as_ref() != Some(self))
+    }
+
+    /// Сколько розетка будет потреблять в этом состоянии, Вт; `None` - не розетка
+    fn draw(&self, device: &dyn Device) -> Option<f64> {
+        match (self, device.state()) {
+            (TargetState::Socket { is_on }, DeviceState::Socket { current_power, .. }) => {
+                Some(if *is_on { current_power } else { 0.0 })
+            }
+            _ => None,
+        }
+    }
+
+    /// Применяем проверенное состояние
+    fn apply(&self, device: &mut dyn Device) -> Result<()> {
+        let device = device.as_any_mut();
+        match *self {
+            TargetState::Socket { is_on } => {
+                if let Some(socket) = device.downcast_mut::<SmartSocket>() {
+                    if is_on {
+                        socket.turn_on()
+                    } else {
+                        socket.turn_off()
+                    }
+                }
+            }
+            TargetState::Light {
+                is_on,
+                brightness,
+                color,
+            } => {
+                if let Some(light) = device.downcast_mut::<SmartLight>() {
+                    if is_on {
+                        light.turn_on()
+                    } else {
+                        light.turn_off()
+                    }
+                    light.set_brightness(brightness);
+                    light.set_color(color);
+                }
+            }
+            TargetState::Lock { is_locked } => {
+                if let Some(lock) = device.downcast_mut::<SmartLock>() {
+                    if lock.is_locked() != is_locked {
+                        if is_locked {
+                            lock.lock()?
+                        } else {
+                            lock.unlock()?
+                        }
+                    }
+                }
+            }
+            TargetState::Blinds { position } => {
+                if let Some(blinds) = device.downcast_mut::<SmartBlinds>() {
+                    blinds.set_position(position)
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TargetState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on_off = |is_on: bool| if is_on { "on" } else { "off" };
+        match self {
+            TargetState::Socket { is_on } => write!(f, "{}", on_off(*is_on)),
+            TargetState::Light {
+                is_on,
+                brightness,
+                color,
+            } => {
+                write!(f, "{}, {brightness} %", on_off(*is_on))?;
+                if let Some(color) = color {
+                    write!(f, ", {color}")?;
+                }
+                Ok(())
+            }
+            TargetState::Lock { is_locked } => {
+                write!(f, "{}", if *is_locked { "locked" } else { "unlocked" })
+            }
+            TargetState::Blinds { position } => write!(f, "{}", blinds_position(*position)),
+        }
+    }
+}
+
+/// Целевое состояние одного девайса сцены
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneTarget {
+    /// Путь девайса или имя, уникальное в доме
+    pub device: String,
+    #[serde(flatten)]
+    pub state: TargetState,
+}
+
+/// Именованный набор целевых состояний девайсов, например "Night mode"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub name: String,
+    pub targets: Vec<SceneTarget>,
+}
+
+impl Scene {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Добавляем девайс с целевым состоянием
+    pub fn with_target(mut self, device: &str, state: TargetState) -> Self {
+        self.targets.push(SceneTarget {
+            device: device.to_owned(),
+            state,
+        });
+        self
+    }
+}
+
+impl fmt::Display for Scene {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        for (i, target) in self.targets.iter().enumerate() {
+            let separator = if i == 0 { " " } else { "; " };
+            write!(f, "{separator}{} {}", target.device, target.state)?;
+        }
+        Ok(())
+    }
+}
+
+/// Что случилось с девайсом при применении сцены
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetStatus {
+    Changed,
+    /// Девайс уже был в нужном состоянии
+    Unchanged,
+    /// Девайс в порядке, но сцена не применена из-за других девайсов
+    NotApplied,
+    Failed(SceneError),
+}
+
+impl fmt::Display for TargetStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TargetStatus::Changed => write!(f, "changed"),
+            TargetStatus::Unchanged => write!(f, "unchanged"),
+            TargetStatus::NotApplied => write!(f, "not applied"),
+            TargetStatus::Failed(error) => write!(f, "failed: {error}"),
+        }
+    }
+}
+
+/// Результат применения сцены: либо применены все девайсы, либо ни один
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneReport {
+    pub scene: String,
+    pub applied: bool,
+    pub devices: Vec<(String, TargetStatus)>,
+}
+
+impl fmt::Display for SceneReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "scene \"{}\" {}",
+            self.scene,
+            if self.applied {
+                "applied"
+            } else {
+                "not applied"
+            }
+        )?;
+        for (device, status) in &self.devices {
+            write!(f, "\n{device}: {status}")?;
+        }
+        Ok(())
+    }
+}
+
+impl SmartHouse {
+    /// Сцены в порядке добавления
+    pub fn scenes(&self) -> impl Iterator<Item = &Scene> {
+        self.scenes.iter()
+    }
+
+    pub fn scene(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|s| s.name == name)
+    }
+
+    /// Добавляем сцену, имена сцен уникальны, девайсы в сцене - тоже.
+    /// Сами девайсы проверяются при применении: их могут добавить позже.
+    pub fn add_scene(&mut self, scene: Scene) -> Result<()> {
+        if self.scene(&scene.name).is_some() {
+            return Err(SceneError::DuplicateScene(scene.name));
+        }
+        for (i, target) in scene.targets.iter().enumerate() {
+            if scene.targets[..i].iter().any(|t| t.device == target.device) {
+                return Err(SceneError::DuplicateDevice(target.device.clone()));
+            }
+        }
+
+        self.journal.record(Operation::AddScene {
+            scene: scene.clone(),
+        });
+        self.scenes.push(scene);
+        Ok(())
+    }
+
+    pub fn delete_scene(&mut self, name: &str) -> Result<Scene> {
+        let position = self
+            .scenes
+            .iter()
+            .position(|s| s.name == name)
+            .ok_or_else(|| SceneError::SceneNotFound(name.to_owned()))?;
+
+        let scene = self.scenes.remove(position);
+        self.journal.record(Operation::DeleteScene {
+            position,
+            scene: scene.clone(),
+        });
+        Ok(scene)
+    }
+
+    /// Запоминаем текущее состояние девайсов как новую сцену
+    pub fn capture_scene(&mut self, name: &str, devices: &[&str]) -> Result<&Scene> {
+        let mut scene = Scene::new(name);
+        for device in devices {
+            let path = self
+                .resolve_device(device)
+                .ok_or_else(|| SceneError::DeviceNotFound((*device).to_owned()))?;
+            let state = self
+                .device_by_path(&path)
+                .and_then(|d| TargetState::of(&d.state()))
+                .ok_or_else(|| SceneError::NotControllable(path.clone()))?;
+            scene = scene.with_target(&path, state);
+        }
+
+        self.add_scene(scene)?;
+        Ok(self.scenes.last().expect("scene was just added"))
+    }
+
+    /// Применяем сцену целиком: если хоть один девайс не подходит,
+    /// не меняется ни один, а в отчете видно, какие девайсы помешали.
+    /// Бюджеты мощности проверяются для всех розеток сцены вместе.
+    pub fn apply_scene(&mut self, name: &str) -> Result<SceneReport> {
+        let scene = self
+            .scene(name)
+            .cloned()
+            .ok_or_else(|| SceneError::SceneNotFound(name.to_owned()))?;
+
+        let mut checks: Vec<Result<(String, bool)>> = scene
+            .targets
+            .iter()
+            .map(|target| {
+                self.resolve_device(&target.device)
+                    .ok_or_else(|| SceneError::DeviceNotFound(target.device.clone()))
+                    .and_then(|path| {
+                        let device = self.device_by_path(&path).expect("path was resolved");
+                        let changes = target.state.check(device, &path)?;
+                        Ok((path, changes))
+                    })
+            })
+            .collect();
+
+        if checks.iter().all(Result::is_ok) {
+            // Путь, мощность до и после для каждой розетки сцены
+            let draws: Vec<(String, f64, f64)> = checks
+                .iter()
+                .zip(&scene.targets)
+                .filter_map(|(check, target)| {
+                    let (path, _) = check.as_ref().ok()?;
+                    let device = self.device_by_path(path)?;
+                    let new = target.state.draw(device)?;
+                    Some((path.clone(), power::draw(&device.state()), new))
+                })
+                .collect();
+            let changes: Vec<(String, f64)> = draws
+                .iter()
+                .map(|(path, _, new)| (path.clone(), *new))
+                .collect();
+
+            // Бюджет превышает вся сцена, в отчете виноваты включаемые ею розетки
+            if let Err(error) = self.check_power_together(&changes) {
+                for check in &mut checks {
+                    let rising = matches!(check, Ok((path, _))
+                        if draws.iter().any(|(p, old, new)| p == path && new > old));
+                    if rising {
+                        *check = Err(error.clone().into());
+                    }
+                }
+            }
+        }
+
+        let names = scene.targets.iter().map(|t| t.device.clone());
+        if !checks.iter().all(Result::is_ok) {
+            return Ok(SceneReport {
+                scene: scene.name,
+                applied: false,
+                devices: names
+                    .zip(checks)
+                    .map(|(device, check)| match check {
+                        Ok(_) => (device, TargetStatus::NotApplied),
+                        Err(error) => (device, TargetStatus::Failed(error)),
+                    })
+                    .collect(),
+            });
+        }
+
+        let checks: Vec<(String, bool)> = checks.into_iter().map(Result::unwrap).collect();
+        let name = format!("apply scene \"{}\"", scene.name);
+        let applied = self.atomic(name, |house| {
+            for (i, ((path, changes), target)) in checks.iter().zip(&scene.targets).enumerate() {
+                if *changes {
+                    house
+                        .change_device(path, |device| target.state.apply(device))
+                        .map_err(SceneError::from)
+                        .and_then(|applied| applied)
+                        .map_err(|error| (i, error))?;
+                }
+            }
+
+            // Политика `Shed` могла выключить розетку, которую сцена уже включила
+            for (i, ((path, _), target)) in checks.iter().zip(&scene.targets).enumerate() {
+                let device = house.device_by_path(path).expect("path was resolved");
+                if target.state.check(device, path) != Ok(false) {
+                    return Err((i, SceneError::ShedInScene(path.clone())));
+                }
+            }
+            Ok(())
+        });
+
+        let succeeded = applied.is_ok();
+        let devices = match applied {
+            Ok(()) => names
+                .zip(checks)
+                .map(|(device, (_, changes))| {
+                    let status = if changes {
+                        TargetStatus::Changed
+                    } else {
+                        TargetStatus::Unchanged
+                    };
+                    (device, status)
+                })
+                .collect(),
+            Err((failed, error)) => {
+                let mut error = Some(error);
+                names
+                    .enumerate()
+                    .map(|(i, device)| match error.take_if(|_| i == failed) {
+                        Some(error) => (device, TargetStatus::Failed(error)),
+                        None => (device, TargetStatus::NotApplied),
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(SceneReport {
+            scene: scene.name,
+            applied: succeeded,
+            devices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn house() -> SmartHouse {
+        SmartHouse::new(
+            "my smart house",
+            HashMap::from([
+                (
+                    "hall",
+                    vec![
+                        Box::new(SmartLight::new("lamp", "ceiling", true, 80)) as Box<dyn Device>,
+                        Box::new(SmartLock::new("front", "front door", false)),
+                        Box::new(crate::device::SmartThermometer::new("thermo", "wall", 20.0)),
+                    ],
+                ),
+                (
+                    "kitchen",
+                    vec![Box::new(SmartSocket::new("kettle", "2kW", true, 2000.0))
+                        as Box<dyn Device>],
+                ),
+            ]),
+        )
+    }
+
+    fn status(report: &SceneReport) -> Vec<String> {
+        report
+            .devices
+            .iter()
+            .map(|(device, status)| format!("{device}: {status}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_capture_and_apply() {
+        let mut house = house();
+        assert_eq!(
+            Err(SceneError::NotControllable("hall/thermo".to_owned())),
+            house.capture_scene("day", &["thermo"]).map(|_| ())
+        );
+        let day = house
+            .capture_scene("day", &["lamp", "kitchen/kettle"])
+            .unwrap();
+        assert_eq!(
+            "day: hall/lamp on, 80 %; kitchen/kettle on",
+            day.to_string()
+        );
+
+        house
+            .add_scene(
+                Scene::new("night")
+                    .with_target(
+                        "lamp",
+                        TargetState::Light {
+                            is_on: false,
+                            brightness: 10,
+                            color: None,
+                        },
+                    )
+                    .with_target("front", TargetState::Lock { is_locked: true })
+                    .with_target("kettle", TargetState::Socket { is_on: true }),
+            )
+            .unwrap();
+
+        let report = house.apply_scene("night").unwrap();
+        assert!(report.applied);
+        assert_eq!(
+            vec!["lamp: changed", "front: changed", "kettle: unchanged"],
+            status(&report)
+        );
+        assert!(!house
+            .device_by_path("hall/lamp")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<SmartLight>()
+            .unwrap()
+            .is_on());
+
+        let report = house.apply_scene("day").unwrap();
+        assert_eq!(
+            "scene \"day\" applied\nhall/lamp: changed\nkitchen/kettle: unchanged",
+            report.to_string()
+        );
+        assert_eq!(
+            Err(SceneError::SceneNotFound("away".to_owned())),
+            house.apply_scene("away")
+        );
+    }
+
+    #[test]
+    fn test_all_or_nothing() {
+        let mut house = house();
+        house
+            .add_scene(
+                Scene::new("away")
+                    .with_target("kettle", TargetState::Socket { is_on: false })
+                    .with_target("front", TargetState::Lock { is_locked: true })
+                    .with_target("thermo", TargetState::Socket { is_on: false })
+                    .with_target("garage/door", TargetState::Lock { is_locked: true }),
+            )
+            .unwrap();
+        house
+            .device_by_path_mut("hall/front")
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<SmartLock>()
+            .unwrap()
+            .jam();
+
+        let report = house.apply_scene("away").unwrap();
+        assert!(!report.applied);
+        assert_eq!(
+            vec![
+                "kettle: not applied",
+                "front: failed: lock \"front\" is jammed",
+                "thermo: failed: device \"hall/thermo\" is a Thermometer, scene expects a Socket",
+                "garage/door: failed: device \"garage/door\" not found",
+            ],
+            status(&report)
+        );
+        assert!(house.socket("kitchen", "kettle").unwrap().is_on());
+
+        assert_eq!(
+            Err(SceneError::DuplicateDevice("kettle".to_owned())),
+            house.add_scene(
+                Scene::new("twice")
+                    .with_target("kettle", TargetState::Socket { is_on: false })
+                    .with_target("kettle", TargetState::Socket { is_on: true })
+            )
+        );
+    }
+
+    #[test]
+    fn test_power_budget() {
+        use crate::power::{BudgetPolicy, PowerBudget};
+
+        let mut house = house();
+        for (socket, power, on) in [
+            ("oven", 1500.0, false),
+            ("toaster", 1000.0, false),
+            ("fridge", 600.0, true),
+        ] {
+            house
+                .add_device("kitchen", Box::new(SmartSocket::new(socket, "", on, power)))
+                .unwrap();
+        }
+        house
+            .set_power_budget(
+                Some("kitchen"),
+                Some(PowerBudget::new(4000.0, BudgetPolicy::Refuse)),
+            )
+            .unwrap();
+        house
+            .add_scene(
+                Scene::new("baking")
+                    .with_target("oven", TargetState::Socket { is_on: true })
+                    .with_target("toaster", TargetState::Socket { is_on: true }),
+            )
+            .unwrap();
+        let operations = house.journal().operations().count();
+
+        // По отдельности розетки влезают в бюджет, вместе - нет
+        let report = house.apply_scene("baking").unwrap();
+        assert!(!report.applied);
+        let exceeded = "failed: power budget of room \"kitchen\" exceeded: 5100 W of 4000 W";
+        assert_eq!(
+            vec![format!("oven: {exceeded}"), format!("toaster: {exceeded}")],
+            status(&report)
+        );
+        assert!(!house.socket("kitchen", "oven").unwrap().is_on());
+        assert_eq!(operations, house.journal().operations().count());
+
+        // Бюджет выключил бы духовку, которую включает сама сцена
+        house
+            .set_power_budget(
+                Some("kitchen"),
+                Some(PowerBudget::new(4600.0, BudgetPolicy::Shed)),
+            )
+            .unwrap();
+        house.set_load_priority("kitchen/kettle", Some(10)).unwrap();
+        house.set_load_priority("kitchen/oven", Some(-1)).unwrap();
+        let operations = house.journal().operations().count();
+        let report = house.apply_scene("baking").unwrap();
+        assert!(!report.applied);
+        assert_eq!(
+            vec![
+                "oven: failed: power budget turned off \"kitchen/oven\" of the same scene",
+                "toaster: not applied"
+            ],
+            status(&report)
+        );
+        for (socket, on) in [
+            ("kettle", true),
+            ("oven", false),
+            ("toaster", false),
+            ("fridge", true),
+        ] {
+            assert_eq!(
+                on,
+                house.socket("kitchen", socket).unwrap().is_on(),
+                "{socket}"
+            );
+        }
+        assert_eq!(operations, house.journal().operations().count());
+
+        // Холодильник выключается раньше духовки, и сцена применяется
+        house.set_load_priority("kitchen/fridge", Some(-2)).unwrap();
+        let report = house.apply_scene("baking").unwrap();
+        assert!(report.applied);
+        assert!(!house.socket("kitchen", "fridge").unwrap().is_on());
+        house.undo().unwrap();
+        assert!(house.socket("kitchen", "fridge").unwrap().is_on());
+        assert!(!house.socket("kitchen", "oven").unwrap().is_on());
+    }
+}
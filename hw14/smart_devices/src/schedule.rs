@@ -0,0 +1,588 @@
+pub mod cron;
+
+use crate::{
+    clock::{system_clock, Clock},
+    device::{Device, SmartSocket},
+    journal::Operation,
+    SmartHouse, SmartHouseError,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use cron::Cron;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+/// Сколько действий одного задания выполняем за раз, если часы ушли далеко вперед
+const MAX_CATCH_UP: usize = 1024;
+
+/// Ошибка расписаний.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ScheduleError {
+    /// Задание с таким именем уже есть.
+    #[error("job \"{0}\" already exists")]
+    DuplicateJob(String),
+
+    /// Задания с таким именем нет.
+    #[error("job \"{0}\" not found")]
+    JobNotFound(String),
+
+    /// Не удалось разобрать выражение расписания.
+    #[error("invalid schedule \"{expression}\": {message}")]
+    InvalidCron { expression: String, message: String },
+
+    /// Девайс не найден ни по пути, ни по уникальному имени.
+    #[error("device \"{0}\" not found")]
+    DeviceNotFound(String),
+
+    /// Ошибка дома.
+    #[error(transparent)]
+    House(#[from] SmartHouseError),
+
+    /// Расписания управляют только розетками.
+    #[error("device \"{0}\" is not a socket")]
+    NotASocket(String),
+}
+
+type Result<T> = std::result::Result<T, ScheduleError>;
+
+/// Что сделать с розеткой
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Switch {
+    On,
+    Off,
+}
+
+impl Switch {
+    pub fn opposite(self) -> Self {
+        match self {
+            Switch::On => Switch::Off,
+            Switch::Off => Switch::On,
+        }
+    }
+}
+
+impl fmt::Display for Switch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Switch::On => write!(f, "on"),
+            Switch::Off => write!(f, "off"),
+        }
+    }
+}
+
+fn utc() -> Tz {
+    Tz::UTC
+}
+
+/// Когда выполняется задание
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum When {
+    /// По расписанию в часовом поясе `time_zone`
+    Repeat {
+        cron: Cron,
+        #[serde(default = "utc")]
+        time_zone: Tz,
+    },
+    /// Однократно
+    Once {
+        #[serde(with = "humantime_serde")]
+        at: SystemTime,
+    },
+}
+
+/// Задание расписания: включить или выключить розетку
+/// (путь или имя, уникальное в доме), а через `duration` вернуть обратно
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub name: String,
+    pub device: String,
+    pub switch: Switch,
+    pub when: When,
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub duration: Option<Duration>,
+}
+
+impl ScheduledJob {
+    /// Повторяющееся задание по выражению `cron` (см. [`cron`]) в часовом поясе UTC
+    pub fn repeat(name: &str, device: &str, switch: Switch, cron: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.to_owned(),
+            device: device.to_owned(),
+            switch,
+            when: When::Repeat {
+                cron: cron.parse()?,
+                time_zone: Tz::UTC,
+            },
+            duration: None,
+        })
+    }
+
+    /// Однократный таймер
+    pub fn once(name: &str, device: &str, switch: Switch, at: SystemTime) -> Self {
+        Self {
+            name: name.to_owned(),
+            device: device.to_owned(),
+            switch,
+            when: When::Once { at },
+            duration: None,
+        }
+    }
+
+    /// Часовой пояс повторяющегося задания, однократному он не нужен
+    pub fn with_time_zone(mut self, time_zone: Tz) -> Self {
+        if let When::Repeat { time_zone: tz, .. } = &mut self.when {
+            *tz = time_zone;
+        }
+        self
+    }
+
+    /// Через `duration` после срабатывания вернуть розетку в прежнее состояние
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Времена срабатывания строго после `after`
+    fn occurrences_after(&self, after: SystemTime) -> Box<dyn Iterator<Item = SystemTime> + '_> {
+        match &self.when {
+            When::Repeat { cron, time_zone } => {
+                let after = DateTime::<Utc>::from(after).with_timezone(time_zone);
+                Box::new(
+                    std::iter::successors(cron.next_after(&after), |t| cron.next_after(t))
+                        .map(SystemTime::from),
+                )
+            }
+            When::Once { at } => Box::new((*at > after).then_some(*at).into_iter()),
+        }
+    }
+
+    /// Действия задания в интервале `(from, to]`, не больше `limit`
+    fn actions_between(
+        &self,
+        from: SystemTime,
+        to: SystemTime,
+        limit: usize,
+    ) -> Vec<ScheduledAction> {
+        let action = |at, switch| ScheduledAction {
+            at,
+            job: self.name.clone(),
+            device: self.device.clone(),
+            switch,
+        };
+
+        // Обратное действие может попасть в интервал, даже если само срабатывание раньше
+        let lead = self.duration.unwrap_or_default();
+        let start = from.checked_sub(lead).unwrap_or(SystemTime::UNIX_EPOCH);
+
+        let mut actions = Vec::new();
+        for at in self.occurrences_after(start) {
+            if at > to || actions.len() >= limit {
+                break;
+            }
+            if at > from {
+                actions.push(action(at, self.switch));
+            }
+            if let Some(back) = self.duration.map(|d| at + d) {
+                if from < back && back <= to {
+                    actions.push(action(back, self.switch.opposite()));
+                }
+            }
+        }
+
+        actions.sort_by_key(|a| a.at);
+        actions.truncate(limit);
+        actions
+    }
+
+    /// Однократное задание выполнено целиком
+    fn is_finished(&self, now: SystemTime) -> bool {
+        match self.when {
+            When::Once { at } => at + self.duration.unwrap_or_default() <= now,
+            When::Repeat { .. } => false,
+        }
+    }
+}
+
+/// Запланированное действие
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledAction {
+    #[serde(with = "humantime_serde")]
+    pub at: SystemTime,
+    pub job: String,
+    pub device: String,
+    pub switch: Switch,
+}
+
+impl fmt::Display for ScheduledAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} turn {} {} ({})",
+            humantime::format_rfc3339_seconds(self.at),
+            self.switch,
+            self.device,
+            self.job
+        )
+    }
+}
+
+/// Выполненное действие и его результат
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledRun {
+    pub action: ScheduledAction,
+    pub result: Result<()>,
+}
+
+impl ScheduledAction {
+    fn apply(&self, house: &mut SmartHouse) -> Result<()> {
+        let switch = |device: &mut dyn Device| {
+            let socket = device
+                .as_any_mut()
+                .downcast_mut::<SmartSocket>()
+                .ok_or_else(|| ScheduleError::NotASocket(self.device.clone()))?;
+            match self.switch {
+                Switch::On => socket.turn_on(),
+                Switch::Off => socket.turn_off(),
+            }
+            Ok(())
+        };
+
+        let path = house
+            .resolve_device(&self.device)
+            .ok_or_else(|| ScheduleError::DeviceNotFound(self.device.clone()))?;
+        house.change_device(&path, switch)?
+    }
+}
+
+/// Задания расписания и время, до которого они уже выполнены
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    jobs: Vec<ScheduledJob>,
+    checked_at: SystemTime,
+    clock: Arc<dyn Clock>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        let clock = system_clock();
+        Self {
+            jobs: Vec::new(),
+            checked_at: clock.now(),
+            clock,
+        }
+    }
+
+    /// Подменяем часы, выполняться будут действия после их текущего времени
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.checked_at = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Задания в порядке добавления
+    pub fn jobs(&self) -> impl Iterator<Item = &ScheduledJob> {
+        self.jobs.iter()
+    }
+
+    /// Ссылки заданий на розетки, чтобы перенести их при переименовании
+    pub(crate) fn device_references_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.jobs.iter_mut().map(|job| &mut job.device)
+    }
+
+    pub fn job(&self, name: &str) -> Option<&ScheduledJob> {
+        self.jobs.iter().find(|j| j.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Добавляем задание, имена заданий уникальны
+    pub fn add_job(&mut self, job: ScheduledJob) -> Result<()> {
+        self.insert_job(self.jobs.len(), job)
+    }
+
+    /// Ставим задание на место `position` (или в конец)
+    pub(crate) fn insert_job(&mut self, position: usize, job: ScheduledJob) -> Result<()> {
+        if self.job(&job.name).is_some() {
+            return Err(ScheduleError::DuplicateJob(job.name));
+        }
+
+        self.jobs.insert(position.min(self.jobs.len()), job);
+        Ok(())
+    }
+
+    /// Однократный таймер через `delay` от текущего времени
+    pub fn add_timer(
+        &mut self,
+        name: &str,
+        device: &str,
+        switch: Switch,
+        delay: Duration,
+    ) -> Result<()> {
+        self.add_job(self.timer(name, device, switch, delay))
+    }
+
+    fn timer(&self, name: &str, device: &str, switch: Switch, delay: Duration) -> ScheduledJob {
+        ScheduledJob::once(name, device, switch, self.clock.now() + delay)
+    }
+
+    pub fn remove_job(&mut self, name: &str) -> Result<ScheduledJob> {
+        self.take_job(name).map(|(_, job)| job)
+    }
+
+    /// Удаляем задание и возвращаем его вместе с его местом
+    fn take_job(&mut self, name: &str) -> Result<(usize, ScheduledJob)> {
+        let position = self
+            .jobs
+            .iter()
+            .position(|j| j.name == name)
+            .ok_or_else(|| ScheduleError::JobNotFound(name.to_owned()))?;
+
+        Ok((position, self.jobs.remove(position)))
+    }
+
+    /// Ближайшие `count` действий всех заданий по времени
+    pub fn upcoming(&self, count: usize) -> Vec<ScheduledAction> {
+        let now = self.clock.now();
+        let horizon = now + Duration::from_secs(100 * 366 * 24 * 3600);
+        let mut actions: Vec<ScheduledAction> = self
+            .jobs
+            .iter()
+            .flat_map(|job| job.actions_between(now, horizon, count))
+            .collect();
+
+        actions.sort_by_key(|a| a.at);
+        actions.truncate(count);
+        actions
+    }
+
+    /// Выполняем действия, время которых наступило с прошлой проверки,
+    /// по порядку времени. Выполненные однократные задания удаляются.
+    pub fn run_due(&mut self, house: &mut SmartHouse) -> Vec<ScheduledRun> {
+        let now = self.clock.now();
+        if now <= self.checked_at {
+            return Vec::new();
+        }
+
+        let mut actions: Vec<ScheduledAction> = self
+            .jobs
+            .iter()
+            .flat_map(|job| job.actions_between(self.checked_at, now, MAX_CATCH_UP))
+            .collect();
+        actions.sort_by_key(|a| a.at);
+
+        self.checked_at = now;
+        self.jobs.retain(|job| !job.is_finished(now));
+
+        actions
+            .into_iter()
+            .map(|action| ScheduledRun {
+                result: action.apply(house),
+                action,
+            })
+            .collect()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SmartHouse {
+    /// Добавляем задание в расписание дома, имена заданий уникальны
+    pub fn add_job(&mut self, job: ScheduledJob) -> Result<()> {
+        self.schedule.add_job(job.clone())?;
+        self.journal.record(Operation::AddJob { job });
+        Ok(())
+    }
+
+    /// Однократный таймер через `delay` от текущего времени
+    pub fn add_timer(
+        &mut self,
+        name: &str,
+        device: &str,
+        switch: Switch,
+        delay: Duration,
+    ) -> Result<()> {
+        self.add_job(self.schedule.timer(name, device, switch, delay))
+    }
+
+    pub fn remove_job(&mut self, name: &str) -> Result<ScheduledJob> {
+        let (position, job) = self.schedule.take_job(name)?;
+        self.journal.record(Operation::RemoveJob {
+            position,
+            job: job.clone(),
+        });
+        Ok(job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::ManualClock, device::Device};
+    use std::collections::HashMap;
+
+    /// Пятница, 5 января 2024, 06:00 UTC
+    fn friday_morning() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_434_400)
+    }
+
+    fn house() -> SmartHouse {
+        SmartHouse::new(
+            "my smart house",
+            HashMap::from([(
+                "room1",
+                vec![
+                    Box::new(SmartSocket::new("kettle", "kitchen", false, 2000.0))
+                        as Box<dyn Device>,
+                    Box::new(SmartSocket::new("pump", "aquarium", false, 10.0)),
+                ],
+            )]),
+        )
+    }
+
+    fn is_on(house: &SmartHouse, socket: &str) -> bool {
+        house.socket("room1", socket).unwrap().is_on()
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let clock = Arc::new(ManualClock::new(friday_morning()));
+        let mut scheduler = Scheduler::new().with_clock(clock);
+        scheduler
+            .add_job(
+                ScheduledJob::repeat("morning", "kettle", Switch::On, "weekdays 07:00").unwrap(),
+            )
+            .unwrap();
+        scheduler
+            .add_job(
+                ScheduledJob::repeat("night", "kettle", Switch::Off, "weekdays 23:00").unwrap(),
+            )
+            .unwrap();
+        scheduler
+            .add_job(
+                ScheduledJob::repeat("pump", "room1/pump", Switch::On, "every 2h")
+                    .unwrap()
+                    .with_duration(Duration::from_secs(15 * 60)),
+            )
+            .unwrap();
+        scheduler
+            .add_timer("boost", "kettle", Switch::On, Duration::from_secs(90))
+            .unwrap();
+
+        let upcoming: Vec<String> = scheduler
+            .upcoming(6)
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "2024-01-05T06:01:30Z turn on kettle (boost)",
+                "2024-01-05T06:15:00Z turn off room1/pump (pump)",
+                "2024-01-05T07:00:00Z turn on kettle (morning)",
+                "2024-01-05T08:00:00Z turn on room1/pump (pump)",
+                "2024-01-05T08:15:00Z turn off room1/pump (pump)",
+                "2024-01-05T10:00:00Z turn on room1/pump (pump)",
+            ],
+            upcoming
+        );
+
+        // После пятничного вечера - сразу понедельник
+        let night = scheduler.job("night").unwrap();
+        let weekend = night.actions_between(
+            friday_morning() + Duration::from_secs(18 * 3600),
+            friday_morning() + Duration::from_secs(4 * 24 * 3600),
+            10,
+        );
+        assert_eq!(
+            vec!["2024-01-08T23:00:00Z turn off kettle (night)"],
+            weekend.iter().map(|a| a.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_time_zone() {
+        let job = ScheduledJob::repeat("morning", "kettle", Switch::On, "daily 07:00")
+            .unwrap()
+            .with_time_zone(chrono_tz::Europe::Moscow);
+
+        let actions = job.actions_between(
+            friday_morning(),
+            friday_morning() + Duration::from_secs(24 * 3600),
+            10,
+        );
+        assert_eq!(
+            "2024-01-06T04:00:00Z turn on kettle (morning)",
+            actions[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_run_due() {
+        let clock = Arc::new(ManualClock::new(friday_morning()));
+        let mut house = house();
+        let mut scheduler = Scheduler::new().with_clock(clock.clone());
+        scheduler
+            .add_job(
+                ScheduledJob::repeat("pump", "pump", Switch::On, "every 2h")
+                    .unwrap()
+                    .with_duration(Duration::from_secs(15 * 60)),
+            )
+            .unwrap();
+        scheduler
+            .add_timer("boost", "kettle", Switch::On, Duration::from_secs(60))
+            .unwrap();
+        scheduler
+            .add_job(ScheduledJob::once(
+                "broken",
+                "nope",
+                Switch::On,
+                friday_morning() + Duration::from_secs(60),
+            ))
+            .unwrap();
+
+        assert!(scheduler.run_due(&mut house).is_empty());
+
+        clock.advance(Duration::from_secs(2 * 3600 + 5 * 60));
+        let runs = scheduler.run_due(&mut house);
+        assert_eq!(
+            vec![
+                (Switch::On, "kettle", Ok(())),
+                (
+                    Switch::On,
+                    "nope",
+                    Err(ScheduleError::DeviceNotFound("nope".to_owned()))
+                ),
+                (Switch::Off, "pump", Ok(())),
+                (Switch::On, "pump", Ok(())),
+            ],
+            runs.iter()
+                .map(|r| (r.action.switch, r.action.device.as_str(), r.result.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert!(is_on(&house, "kettle"));
+        assert!(is_on(&house, "pump"));
+        assert!(scheduler.job("boost").is_none());
+
+        clock.advance(Duration::from_secs(10 * 60));
+        let runs = scheduler.run_due(&mut house);
+        assert_eq!(1, runs.len());
+        assert!(!is_on(&house, "pump"));
+        assert!(scheduler.run_due(&mut house).is_empty());
+    }
+}
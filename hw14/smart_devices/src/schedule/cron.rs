@@ -0,0 +1,324 @@
+//! Выражения расписания: классический cron из пяти полей
+//! (`минуты часы дни_месяца месяцы дни_недели`, например `"0 7 * * 1-5"`)
+//! и короткие формы:
+//!
+//! - `daily 07:00`, `weekdays 07:00`, `weekends 09:30`, `mon,wed,fri 18:00`, `mon-thu 08:15`;
+//! - `every 15m`, `every 2h` - каждые N минут (часов) от начала часа (суток);
+//! - `hourly` - в начале каждого часа.
+
+use super::ScheduleError;
+use chrono::{DateTime, Datelike, Days, LocalResult, NaiveDate, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Сколько дней вперед ищем ближайшее время: хватает, чтобы дождаться 29 февраля
+const SEARCH_DAYS: u64 = 8 * 366;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Разобранное выражение расписания, каждое поле - битовая маска допустимых значений
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    text: String,
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    /// 0 - воскресенье
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// Ближайшее время срабатывания строго после `after`, в том же часовом поясе.
+    /// Время, которого нет из-за перевода часов, пропускается, а повторяющееся
+    /// берется первое.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let local = after.naive_local();
+        let time_zone = after.timezone();
+
+        for offset in 0..SEARCH_DAYS {
+            let date = local.date().checked_add_days(Days::new(offset))?;
+            if !self.matches_day(date) {
+                continue;
+            }
+
+            for hour in (0..24).filter(|h| bit(self.hours.into(), *h)) {
+                for minute in (0..60).filter(|m| bit(self.minutes, *m)) {
+                    let Some(time) = NaiveTime::from_hms_opt(hour, minute, 0) else {
+                        continue;
+                    };
+                    if offset == 0 && (hour, minute) < (local.hour(), local.minute()) {
+                        continue;
+                    }
+
+                    let candidate = match time_zone.from_local_datetime(&date.and_time(time)) {
+                        LocalResult::Single(t) | LocalResult::Ambiguous(t, _) => t,
+                        LocalResult::None => continue,
+                    };
+                    if candidate > *after {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if !bit(self.months.into(), date.month()) {
+            return false;
+        }
+
+        let day = bit(self.days.into(), date.day());
+        let weekday = bit(self.weekdays.into(), date.weekday().num_days_from_sunday());
+        // Как в cron: если заданы и дни месяца, и дни недели, подходит любой из них
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+impl FromStr for Cron {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |message: String| ScheduleError::InvalidCron {
+            expression: s.to_owned(),
+            message,
+        };
+
+        let text = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        let expanded = expand(&text.to_ascii_lowercase()).map_err(error)?;
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(error(format!("expected 5 fields, found {}", fields.len())));
+        };
+
+        let weekdays_mask = field(weekdays, 0, 7, &WEEKDAYS, 0).map_err(error)?;
+        Ok(Self {
+            text,
+            minutes: field(minutes, 0, 59, &[], 0).map_err(error)?,
+            hours: field(hours, 0, 23, &[], 0).map_err(error)? as u32,
+            days: field(days, 1, 31, &[], 0).map_err(error)? as u32,
+            months: field(months, 1, 12, &MONTHS, 1).map_err(error)? as u16,
+            any_day: days == "*",
+            // 7 - тоже воскресенье
+            weekdays: ((weekdays_mask | weekdays_mask >> 7) & 0x7f) as u8,
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+/// Короткую форму переводим в пять полей cron
+fn expand(text: &str) -> Result<String, String> {
+    let words: Vec<&str> = text.split(' ').collect();
+    match words[..] {
+        ["hourly"] => Ok("0 * * * *".to_owned()),
+        ["every", period] => {
+            let period = humantime::parse_duration(period).map_err(|e| e.to_string())?;
+            let secs = period.as_secs();
+            match secs {
+                _ if secs > 0 && secs.is_multiple_of(3600) && secs / 3600 < 24 => {
+                    Ok(format!("0 */{} * * *", secs / 3600))
+                }
+                _ if secs > 0 && secs.is_multiple_of(60) && secs / 60 < 60 => {
+                    Ok(format!("*/{} * * * *", secs / 60))
+                }
+                _ => Err(
+                    "period must be whole minutes below an hour or whole hours below a day"
+                        .to_owned(),
+                ),
+            }
+        }
+        [days, time] if time.contains(':') => {
+            let time = NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("invalid time \"{time}\""))?;
+            let weekdays = match days {
+                "daily" => "*",
+                "weekdays" => "1-5",
+                "weekends" => "0,6",
+                days => days,
+            };
+            Ok(format!("{} {} * * {weekdays}", time.minute(), time.hour()))
+        }
+        _ => Ok(text.to_owned()),
+    }
+}
+
+/// Разбираем поле cron в битовую маску
+fn field(field: &str, min: u32, max: u32, names: &[&str], first_name: u32) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let value = match names.iter().position(|name| *name == s) {
+            Some(position) => position as u32 + first_name,
+            None => s.parse().map_err(|_| format!("invalid value \"{s}\""))?,
+        };
+        if value < min || value > max {
+            return Err(format!("value {value} out of range {min}-{max}"));
+        }
+        Ok(value)
+    };
+
+    let mut mask = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step \"{step}\"")),
+            },
+            None => (item, 1),
+        };
+
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (value(from)?, value(to)?),
+                // `5/15` - с 5 до конца с шагом 15
+                None if item.contains('/') => (value(range)?, max),
+                None => {
+                    let value = value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if from > to {
+            return Err(format!("invalid range \"{range}\""));
+        }
+
+        for value in (from..=to).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+impl TryFrom<String> for Cron {
+    type Error = ScheduleError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> Self {
+        cron.text
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use chrono_tz::Europe::Moscow;
+
+    fn next(cron: &str, after: &str) -> String {
+        let after = DateTime::parse_from_rfc3339(after)
+            .unwrap()
+            .with_timezone(&Utc);
+        cron.parse::<Cron>()
+            .unwrap()
+            .next_after(&after)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 2024-01-05 - пятница
+        assert_eq!(
+            "2024-01-08T07:00:00+00:00",
+            next("weekdays 07:00", "2024-01-05T07:00:00Z")
+        );
+        assert_eq!(
+            "2024-01-05T07:00:00+00:00",
+            next("0 7 * * mon-fri", "2024-01-05T06:59:30Z")
+        );
+        assert_eq!(
+            "2024-01-05T10:00:00+00:00",
+            next("every 2h", "2024-01-05T08:00:00Z")
+        );
+        assert_eq!(
+            "2024-01-05T08:15:00+00:00",
+            next("*/15 * * * *", "2024-01-05T08:00:00Z")
+        );
+        assert_eq!(
+            "2028-02-29T00:00:00+00:00",
+            next("0 0 29 feb *", "2024-03-01T00:00:00Z")
+        );
+        // День месяца или день недели
+        assert_eq!(
+            "2024-01-07T00:00:00+00:00",
+            next("0 0 13 * sun", "2024-01-05T00:00:00Z")
+        );
+        assert_eq!("", next("0 0 31 feb *", "2024-01-05T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_time_zone() {
+        let cron: Cron = "daily 23:00".parse().unwrap();
+        let after = Moscow.with_ymd_and_hms(2024, 1, 5, 12, 0, 0).unwrap();
+        assert_eq!(
+            "2024-01-05T20:00:00+00:00",
+            cron.next_after(&after)
+                .unwrap()
+                .with_timezone(&Utc)
+                .to_rfc3339()
+        );
+
+        // 2:30 31 марта 2024 в Берлине нет, следующее время - 1 апреля
+        let cron: Cron = "30 2 * * *".parse().unwrap();
+        let after = chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(2024, 3, 30, 12, 0, 0)
+            .unwrap();
+        assert_eq!(
+            "2024-04-01T02:30:00+02:00",
+            cron.next_after(&after).unwrap().to_rfc3339()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |s: &str| s.parse::<Cron>().unwrap_err().to_string();
+        assert_eq!(
+            "invalid schedule \"0 7 * *\": expected 5 fields, found 4",
+            error("0 7 * *")
+        );
+        assert_eq!(
+            "invalid schedule \"0 25 * * *\": value 25 out of range 0-23",
+            error("0 25 * * *")
+        );
+        assert_eq!(
+            "invalid schedule \"every 90m\": period must be whole minutes below an hour or whole hours below a day",
+            error("every 90m")
+        );
+        assert_eq!(
+            "invalid schedule \"weekdays 7am\": expected 5 fields, found 2",
+            error("weekdays 7am")
+        );
+        assert_eq!(
+            "Weekdays 07:00",
+            "Weekdays   07:00".parse::<Cron>().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,695 @@
+//! Симуляция дома в виртуальном времени для проверки автоматизаций и отчетов.
+//!
+//! Температура комнат стремится к уличной, обогреватели на розетках ее поднимают,
+//! нагрузка розеток меняется по профилям, термометры показывают температуру с шумом.
+//! Время идет по `ManualClock`, поэтому сутки считаются за доли секунды,
+//! а прогон с одним и тем же `seed` повторяется в точности.
+
+use crate::{
+    alarms::Alert,
+    clock::{Clock, ManualClock},
+    device::{temperature::Temperature, Device, SmartSocket, SmartThermometer},
+    location,
+    rules::Firing,
+    schedule::ScheduledRun,
+    SmartHouse,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    f64::consts::PI,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// Шаг симуляции по умолчанию
+pub const DEFAULT_STEP: Duration = Duration::from_secs(60);
+
+const DAY_SECS: f64 = 24.0 * 3600.0;
+
+/// Секунды от начала суток по UTC
+fn seconds_of_day(at: SystemTime) -> f64 {
+    let since_epoch = at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    since_epoch % DAY_SECS
+}
+
+/// Уличная температура: суточная синусоида вокруг `mean`, °C
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutdoorTemperature {
+    pub mean: f64,
+    /// Отклонение от средней в самый холодный и самый теплый час
+    pub amplitude: f64,
+    /// Самый холодный час суток по UTC, самый теплый - через 12 часов
+    pub coldest_hour: f64,
+}
+
+impl OutdoorTemperature {
+    /// Температура без суточных колебаний
+    pub fn constant(temperature: f64) -> Self {
+        Self::daily(temperature, 0.0)
+    }
+
+    /// Колебания на `amplitude` вокруг `mean`, холоднее всего в 5 утра
+    pub fn daily(mean: f64, amplitude: f64) -> Self {
+        Self {
+            mean,
+            amplitude,
+            coldest_hour: 5.0,
+        }
+    }
+
+    pub fn at(&self, at: SystemTime) -> f64 {
+        let phase = (seconds_of_day(at) - self.coldest_hour * 3600.0) / DAY_SECS;
+        self.mean - self.amplitude * (2.0 * PI * phase).cos()
+    }
+}
+
+impl Default for OutdoorTemperature {
+    fn default() -> Self {
+        Self::daily(10.0, 5.0)
+    }
+}
+
+/// Тепловая модель комнаты
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoomModel {
+    /// Настоящая температура воздуха, °C
+    pub temperature: f64,
+    /// За это время разница с уличной температурой уменьшается в e раз
+    pub time_constant: Duration,
+    /// Сколько энергии нагревает комнату на градус, Дж/°C
+    pub heat_capacity: f64,
+}
+
+impl RoomModel {
+    /// Комната средней теплоизоляции с температурой `temperature`
+    pub fn new(temperature: f64) -> Self {
+        Self {
+            temperature,
+            time_constant: Duration::from_secs(6 * 3600),
+            heat_capacity: 2_000_000.0,
+        }
+    }
+
+    pub fn with_time_constant(mut self, time_constant: Duration) -> Self {
+        self.time_constant = time_constant;
+        self
+    }
+
+    pub fn with_heat_capacity(mut self, heat_capacity: f64) -> Self {
+        self.heat_capacity = heat_capacity;
+        self
+    }
+
+    /// Температура, к которой комната придет при постоянных улице и обогреве, Вт
+    pub fn equilibrium(&self, outdoor: f64, heating: f64) -> f64 {
+        outdoor + heating * self.time_constant.as_secs_f64() / self.heat_capacity
+    }
+
+    /// Проживаем `duration`: решение уравнения теплообмена точное,
+    /// поэтому большой шаг не раскачивает температуру
+    fn advance(&mut self, outdoor: f64, heating: f64, duration: Duration) {
+        let equilibrium = self.equilibrium(outdoor, heating);
+        let tau = self.time_constant.as_secs_f64();
+        let decay = if tau > 0.0 {
+            (-duration.as_secs_f64() / tau).exp()
+        } else {
+            0.0
+        };
+        self.temperature = equilibrium + (self.temperature - equilibrium) * decay;
+    }
+}
+
+/// Как меняется мощность нагрузки розетки во времени, Вт
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadProfile {
+    Constant(f64),
+    /// Нагрузка `power` первую долю `duty` каждого периода, остальное время - 0.
+    /// Так работает, например, компрессор холодильника.
+    Cycle {
+        power: f64,
+        period: Duration,
+        duty: f64,
+    },
+    /// Мощность по часам суток UTC: `(час, мощность)` действует до следующего часа
+    /// списка, до первого часа действует последний
+    Daily(Vec<(u32, f64)>),
+}
+
+impl LoadProfile {
+    pub fn at(&self, at: SystemTime) -> f64 {
+        match self {
+            LoadProfile::Constant(power) => *power,
+            LoadProfile::Cycle {
+                power,
+                period,
+                duty,
+            } => {
+                let period = period.as_secs_f64();
+                if period <= 0.0 {
+                    return *power;
+                }
+                let since_epoch = at
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                if since_epoch % period < period * duty {
+                    *power
+                } else {
+                    0.0
+                }
+            }
+            LoadProfile::Daily(hours) => {
+                let hour = seconds_of_day(at) / 3600.0;
+                let mut hours: Vec<&(u32, f64)> = hours.iter().collect();
+                hours.sort_by_key(|(hour, _)| *hour);
+                hours
+                    .iter()
+                    .rev()
+                    .find(|(start, _)| f64::from(*start) <= hour)
+                    .or(hours.last())
+                    .map(|(_, power)| *power)
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Что произошло с автоматизацией дома за один шаг
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tick {
+    pub at: SystemTime,
+    pub runs: Vec<ScheduledRun>,
+    pub firings: Vec<Firing>,
+    pub alerts: Vec<Alert>,
+}
+
+impl Tick {
+    /// На шаге не сработало ни одно расписание, правило или тревога
+    pub fn is_quiet(&self) -> bool {
+        self.runs.is_empty() && self.firings.is_empty() && self.alerts.is_empty()
+    }
+}
+
+/// Дом, живущий в виртуальном времени.
+///
+/// ```
+/// # use smart_devices::{SmartHouse, device::{SmartSocket, SmartThermometer}};
+/// # use smart_devices::simulation::{OutdoorTemperature, Simulation};
+/// # use std::time::{Duration, SystemTime};
+/// let mut house = SmartHouse::new_empty("house");
+/// house.add_room("kitchen").unwrap();
+/// house.add_device("kitchen", Box::new(SmartThermometer::new("thermo", "", 20.0))).unwrap();
+/// house.add_device("kitchen", Box::new(SmartSocket::new("heater", "", true, 1500.0))).unwrap();
+///
+/// let mut simulation = Simulation::new(house, SystemTime::UNIX_EPOCH, 42)
+///     .with_outdoor(OutdoorTemperature::constant(0.0))
+///     .with_heater("kitchen/heater");
+/// simulation.run_for(Duration::from_secs(24 * 3600));
+/// let thermo = simulation.house().thermometer("kitchen", "thermo").unwrap();
+/// assert!(thermo.current_temperature() > 10.0);
+/// ```
+pub struct Simulation {
+    house: SmartHouse,
+    clock: Arc<ManualClock>,
+    rng: StdRng,
+    step: Duration,
+    outdoor: OutdoorTemperature,
+    /// Модели расположений с термометрами или обогревателями
+    rooms: BTreeMap<String, RoomModel>,
+    /// Розетки, вся мощность которых уходит в тепло их комнаты
+    heaters: BTreeSet<String>,
+    profiles: BTreeMap<String, LoadProfile>,
+    /// Разброс нагрузки относительно профиля, доля
+    load_jitter: f64,
+    /// Стандартное отклонение показаний термометров, °C
+    sensor_noise: f64,
+}
+
+impl Simulation {
+    /// Симуляция с момента `start`. Часы симуляции ставятся розеткам, термометрам,
+    /// датчикам, правилам, расписаниям и тревогам дома; девайсы, добавленные в дом позже,
+    /// живут по своим часам.
+    /// Комнаты с термометрами начинают со средней температуры их термометров.
+    pub fn new(mut house: SmartHouse, start: SystemTime, seed: u64) -> Self {
+        let clock = Arc::new(ManualClock::new(start));
+        house.set_clock(clock.clone());
+
+        let mut readings: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+        for (room, device) in thermometers(&house) {
+            readings
+                .entry(room)
+                .or_default()
+                .push(device.current_temperature());
+        }
+        let rooms = readings
+            .into_iter()
+            .map(|(room, temperatures)| {
+                let mean = temperatures.iter().sum::<f64>() / temperatures.len() as f64;
+                (room, RoomModel::new(mean))
+            })
+            .collect();
+
+        Self {
+            house,
+            clock,
+            rng: StdRng::seed_from_u64(seed),
+            step: DEFAULT_STEP,
+            outdoor: OutdoorTemperature::default(),
+            rooms,
+            heaters: BTreeSet::new(),
+            profiles: BTreeMap::new(),
+            load_jitter: 0.0,
+            sensor_noise: 0.0,
+        }
+    }
+
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn with_outdoor(mut self, outdoor: OutdoorTemperature) -> Self {
+        self.outdoor = outdoor;
+        self
+    }
+
+    /// Задаем модель расположения, например утепленной спальни
+    pub fn with_room(mut self, room: &str, model: RoomModel) -> Self {
+        self.rooms.insert(location::normalize_path(room), model);
+        self
+    }
+
+    /// Розетка `socket` (путь) греет свое расположение всей своей мощностью.
+    /// Расположение без модели начинает с уличной температуры.
+    pub fn with_heater(mut self, socket: &str) -> Self {
+        let socket = location::normalize_path(socket);
+        if let Some((room, _)) = location::split_last(&socket) {
+            let outdoor = self.outdoor.at(self.now());
+            self.rooms
+                .entry(room.to_owned())
+                .or_insert_with(|| RoomModel::new(outdoor));
+        }
+        self.heaters.insert(socket);
+        self
+    }
+
+    /// Мощность нагрузки розетки `socket` (путь) меняется по профилю
+    pub fn with_load_profile(mut self, socket: &str, profile: LoadProfile) -> Self {
+        self.profiles
+            .insert(location::normalize_path(socket), profile);
+        self
+    }
+
+    /// Нагрузка отклоняется от профиля случайно, `jitter` - стандартное отклонение в долях
+    pub fn with_load_jitter(mut self, jitter: f64) -> Self {
+        self.load_jitter = jitter;
+        self
+    }
+
+    /// Термометры ошибаются случайно, `noise` - стандартное отклонение, °C
+    pub fn with_sensor_noise(mut self, noise: f64) -> Self {
+        self.sensor_noise = noise;
+        self
+    }
+
+    pub fn house(&self) -> &SmartHouse {
+        &self.house
+    }
+
+    pub fn house_mut(&mut self) -> &mut SmartHouse {
+        &mut self.house
+    }
+
+    pub fn into_house(self) -> SmartHouse {
+        self.house
+    }
+
+    /// Часы симуляции, по ним можно создавать новые девайсы
+    pub fn clock(&self) -> Arc<ManualClock> {
+        self.clock.clone()
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// Модель расположения с настоящей температурой
+    pub fn room(&self, room: &str) -> Option<&RoomModel> {
+        self.rooms.get(&location::normalize_path(room))
+    }
+
+    pub fn outdoor_temperature(&self) -> f64 {
+        self.outdoor.at(self.now())
+    }
+
+    /// Один шаг: нагрузка розеток встает по профилям, комнаты остывают и греются,
+    /// время идет, термометры снимают показания, затем выполняются расписания и правила
+    /// и проверяются тревоги
+    pub fn step(&mut self) -> Tick {
+        let from = self.now();
+        self.apply_profiles(from);
+        let outdoor = self.outdoor.at(from + self.step / 2);
+        let heating = self.heating();
+        for (room, model) in &mut self.rooms {
+            let heat = heating.get(room).copied().unwrap_or_default();
+            model.advance(outdoor, heat, self.step);
+        }
+
+        self.clock.advance(self.step);
+        let now = self.now();
+        self.read_sensors();
+
+        // Правила и тревоги проверяем один раз после всех заданий, чтобы вернуть их в шаге
+        let runs = self.house.without_reactions(SmartHouse::run_schedule);
+        let (firings, alerts) = self.house.react();
+        Tick {
+            at: now,
+            runs,
+            firings,
+            alerts,
+        }
+    }
+
+    /// Шагаем, пока не пройдет `duration`, и возвращаем шаги, на которых
+    /// срабатывала автоматизация или тревоги
+    pub fn run_for(&mut self, duration: Duration) -> Vec<Tick> {
+        let until = self.now() + duration;
+        self.run_until(until)
+    }
+
+    pub fn run_until(&mut self, until: SystemTime) -> Vec<Tick> {
+        let mut ticks = Vec::new();
+        while self.now() < until && !self.step.is_zero() {
+            let tick = self.step();
+            if !tick.is_quiet() {
+                ticks.push(tick);
+            }
+        }
+        ticks
+    }
+
+    /// Мощность обогревателей по расположениям, Вт
+    fn heating(&self) -> BTreeMap<String, f64> {
+        let mut heating = BTreeMap::new();
+        for heater in &self.heaters {
+            let Some((room, _)) = location::split_last(heater) else {
+                continue;
+            };
+            let Some(socket) = self
+                .house
+                .device_by_path(heater)
+                .and_then(|d| d.as_any().downcast_ref::<SmartSocket>())
+            else {
+                continue;
+            };
+            *heating.entry(room.to_owned()).or_default() += socket.current_power();
+        }
+        heating
+    }
+
+    /// Профили меняют нагрузку напрямую, как настоящие приборы:
+    /// бюджеты мощности при этом не проверяются
+    fn apply_profiles(&mut self, now: SystemTime) {
+        for (path, profile) in &self.profiles {
+            let jitter = 1.0 + gaussian(&mut self.rng, self.load_jitter);
+            let power = (profile.at(now) * jitter).max(0.0);
+            let Some(socket) = self
+                .house
+                .device_by_path_mut(path)
+                .and_then(|d| d.as_any_mut().downcast_mut::<SmartSocket>())
+            else {
+                continue;
+            };
+            socket.set_load_power(power);
+        }
+    }
+
+    /// Показания термометров - настоящая температура комнаты плюс шум.
+    /// Новые показания публикуются в шину дома как изменения состояния.
+    fn read_sensors(&mut self) {
+        let paths: Vec<(String, String)> = thermometers(&self.house)
+            .map(|(room, device)| (room, device.name().to_owned()))
+            .collect();
+        for (room, name) in paths {
+            let Some(model) = self.rooms.get(&room) else {
+                continue;
+            };
+            let reading = model.temperature + gaussian(&mut self.rng, self.sensor_noise);
+            let Some(thermometer) = self.house.thermometer_mut(&room, &name) else {
+                continue;
+            };
+            let old = thermometer.state();
+            thermometer.set_current_temperature(Temperature::celsius(reading));
+
+            if let Some(device) = self.house.device(&room, &name) {
+                self.house
+                    .events()
+                    .publish_state_change(Some(&room), device, old);
+            }
+        }
+    }
+}
+
+/// Термометры дома вместе с их расположениями
+fn thermometers(house: &SmartHouse) -> impl Iterator<Item = (String, &SmartThermometer)> {
+    house.walk().into_iter().flat_map(|(room, location)| {
+        location.devices.iter().filter_map(move |device| {
+            device
+                .as_any()
+                .downcast_ref::<SmartThermometer>()
+                .map(|thermometer| (room.clone(), thermometer))
+        })
+    })
+}
+
+/// Нормальное распределение с нулевым средним по Боксу-Мюллеру
+fn gaussian(rng: &mut StdRng, std_dev: f64) -> f64 {
+    if std_dev == 0.0 {
+        return 0.0;
+    }
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    std_dev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::{sensor::SensorKind, SmartSensor},
+        rules::{Action, Condition, Metric, Rule},
+        schedule::Switch,
+    };
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("simulated house");
+        house.add_room("kitchen").unwrap();
+        house
+            .add_device(
+                "kitchen",
+                Box::new(SmartThermometer::new("thermo", "", 20.0)),
+            )
+            .unwrap();
+        house
+            .add_device(
+                "kitchen",
+                Box::new(SmartSocket::new("heater", "", false, 1500.0)),
+            )
+            .unwrap();
+        house
+            .add_device(
+                "kitchen",
+                Box::new(SmartSocket::new("fridge", "", true, 0.0)),
+            )
+            .unwrap();
+        house
+    }
+
+    fn temperature(simulation: &Simulation) -> f64 {
+        simulation
+            .house()
+            .thermometer("kitchen", "thermo")
+            .unwrap()
+            .current_temperature()
+    }
+
+    #[test]
+    fn test_room_physics() {
+        let mut simulation = Simulation::new(house(), SystemTime::UNIX_EPOCH, 1)
+            .with_outdoor(OutdoorTemperature::constant(0.0))
+            .with_heater("kitchen/heater");
+        assert_eq!(
+            Some(20.0),
+            simulation.room("kitchen").map(|r| r.temperature)
+        );
+
+        // За постоянную времени разница с улицей уменьшается в e раз
+        assert!(simulation.run_for(6 * HOUR).is_empty());
+        assert!((temperature(&simulation) - 20.0 / std::f64::consts::E).abs() < 1e-9);
+
+        // Обогреватель ведет комнату к равновесию
+        simulation
+            .house_mut()
+            .socket_mut("kitchen", "heater")
+            .unwrap()
+            .turn_on();
+        simulation.run_for(5 * 24 * HOUR);
+        assert!((temperature(&simulation) - 16.2).abs() < 0.1);
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + 126 * HOUR,
+            simulation
+                .house()
+                .thermometer("kitchen", "thermo")
+                .unwrap()
+                .history()
+                .iter()
+                .last()
+                .unwrap()
+                .at
+        );
+
+        let outdoor = OutdoorTemperature::daily(10.0, 5.0);
+        assert_eq!(5.0, outdoor.at(SystemTime::UNIX_EPOCH + 5 * HOUR));
+        assert_eq!(15.0, outdoor.at(SystemTime::UNIX_EPOCH + 17 * HOUR));
+    }
+
+    #[test]
+    fn test_thermostat() {
+        let mut house = house();
+        house
+            .add_rule(
+                Rule::new(
+                    "heat",
+                    Condition::below("thermo", Metric::Temperature, 19.0),
+                    Action::TurnOn("heater".to_owned()),
+                )
+                .with_hysteresis(0.5),
+            )
+            .unwrap();
+        house
+            .add_rule(
+                Rule::new(
+                    "stop heating",
+                    Condition::above("thermo", Metric::Temperature, 21.0),
+                    Action::TurnOff("heater".to_owned()),
+                )
+                .with_hysteresis(0.5),
+            )
+            .unwrap();
+        let mut simulation = Simulation::new(house, SystemTime::UNIX_EPOCH, 7)
+            .with_outdoor(OutdoorTemperature::daily(10.0, 5.0))
+            .with_heater("kitchen/heater")
+            .with_sensor_noise(0.1);
+
+        let ticks = simulation.run_for(24 * HOUR);
+        assert!(ticks.len() > 4);
+        for (i, tick) in ticks.iter().enumerate() {
+            let expected = if i % 2 == 0 { "heat" } else { "stop heating" };
+            assert_eq!(1, tick.firings.len());
+            assert_eq!(expected, tick.firings[0].rule);
+            assert!(tick.firings[0].result.is_ok());
+        }
+
+        let stats = simulation
+            .house()
+            .thermometer("kitchen", "thermo")
+            .unwrap()
+            .stats_over(12 * HOUR)
+            .unwrap();
+        assert_eq!(721, stats.count);
+        assert!(stats.min.as_celsius() > 18.0 && stats.max.as_celsius() < 22.0);
+        assert!(
+            simulation
+                .house()
+                .socket("kitchen", "heater")
+                .unwrap()
+                .energy_kwh()
+                > 10.0
+        );
+    }
+
+    #[test]
+    fn test_load_profiles() {
+        let cycle = LoadProfile::Cycle {
+            power: 150.0,
+            period: HOUR,
+            duty: 0.25,
+        };
+        assert_eq!(150.0, cycle.at(SystemTime::UNIX_EPOCH + HOUR / 10));
+        assert_eq!(0.0, cycle.at(SystemTime::UNIX_EPOCH + HOUR / 2));
+        let daily = LoadProfile::Daily(vec![(18, 2000.0), (8, 100.0)]);
+        assert_eq!(2000.0, daily.at(SystemTime::UNIX_EPOCH + 3 * HOUR));
+        assert_eq!(100.0, daily.at(SystemTime::UNIX_EPOCH + 12 * HOUR));
+        assert_eq!(2000.0, daily.at(SystemTime::UNIX_EPOCH + 20 * HOUR));
+
+        let mut simulation = Simulation::new(house(), SystemTime::UNIX_EPOCH, 3)
+            .with_load_profile("kitchen/fridge", cycle);
+        // Таймер отсчитывается по часам симуляции
+        simulation
+            .house_mut()
+            .add_timer("heat", "heater", Switch::On, 2 * HOUR)
+            .unwrap();
+        let ticks = simulation.run_for(24 * HOUR);
+        assert_eq!(1, ticks.len());
+        assert_eq!(SystemTime::UNIX_EPOCH + 2 * HOUR, ticks[0].at);
+        assert!(ticks[0].runs[0].result.is_ok());
+
+        let house = simulation.into_house();
+        let fridge = house.socket("kitchen", "fridge").unwrap();
+        assert!((fridge.energy_kwh() - 0.9).abs() < 1e-9);
+        assert!((house.socket("kitchen", "heater").unwrap().energy_kwh() - 33.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_seed() {
+        let readings = |seed| {
+            let mut simulation = Simulation::new(house(), SystemTime::UNIX_EPOCH, seed)
+                .with_sensor_noise(0.5)
+                .with_load_profile("kitchen/fridge", LoadProfile::Constant(100.0))
+                .with_load_jitter(0.1);
+            simulation.run_for(HOUR);
+            let house = simulation.into_house();
+            let thermo = house.thermometer("kitchen", "thermo").unwrap();
+            let temperatures: Vec<Temperature> =
+                thermo.history().iter().map(|r| r.temperature).collect();
+            (
+                temperatures,
+                house.socket("kitchen", "fridge").unwrap().load_power(),
+            )
+        };
+
+        assert_eq!(readings(42), readings(42));
+        assert_ne!(readings(42), readings(43));
+    }
+
+    #[test]
+    fn test_sensor_clock() {
+        let mut house = house();
+        house
+            .add_device(
+                "kitchen",
+                Box::new(SmartSensor::new("door", "", SensorKind::Contact)),
+            )
+            .unwrap();
+        let mut simulation = Simulation::new(house, SystemTime::UNIX_EPOCH, 42);
+        simulation.step();
+        let now = simulation.now();
+
+        // Срабатывание отмечается временем симуляции, а не настоящим
+        let sensor = simulation
+            .house_mut()
+            .device_mut("kitchen", "door")
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<SmartSensor>()
+            .unwrap();
+        sensor.trigger();
+        assert_eq!(Some(now), sensor.last_triggered());
+    }
+}
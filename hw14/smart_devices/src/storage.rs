@@ -0,0 +1,774 @@
+use crate::{
+    alarms::{Alarm, AlarmError},
+    device::{temperature::TemperatureUnit, Device, DeviceState, SmartThermometer},
+    location::{self, Location},
+    power::PowerBudgets,
+    rules::{Rule, RuleError},
+    scene::{Scene, SceneError},
+    schedule::{ScheduleError, ScheduledJob},
+    DeviceNamePolicy, SmartHouse, SmartHouseError,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fs, io, path::Path};
+use thiserror::Error;
+
+/// Текущая версия формата файла дома.
+/// Увеличиваем при изменении схемы, а старые версии приводим к новой в `migrate`.
+/// Версия 2 добавила вложенные расположения (`rooms` внутри `rooms`).
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Формат файла дома
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Определяем формат по расширению файла
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Ошибка сохранения или загрузки дома.
+#[derive(Error, Debug)]
+pub enum StorageError {
+    /// Внутренняя ошибка IO.
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    /// Ошибка разбора или записи JSON.
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// Ошибка разбора TOML.
+    #[error("TOML parse error: {0}")]
+    TomlDe(#[from] toml::de::Error),
+
+    /// Ошибка записи TOML.
+    #[error("TOML write error: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+
+    /// Расширение файла не соответствует ни одному формату.
+    #[error("unknown file format: {0}")]
+    UnknownFormat(String),
+
+    /// Файл записан более новой версией формата.
+    #[error("unsupported format version {0}")]
+    UnsupportedVersion(u32),
+
+    /// Содержимое файла не складывается в корректный дом.
+    #[error("invalid house: {0}")]
+    House(#[from] SmartHouseError),
+
+    /// Правила автоматизации не складываются в корректный набор.
+    #[error("invalid rules: {0}")]
+    Rules(#[from] RuleError),
+
+    /// Задания расписания не складываются в корректный набор.
+    #[error("invalid schedule: {0}")]
+    Schedule(#[from] ScheduleError),
+
+    /// Сцены не складываются в корректный набор.
+    #[error("invalid scenes: {0}")]
+    Scenes(#[from] SceneError),
+
+    /// Тревоги не складываются в корректный набор.
+    #[error("invalid alarms: {0}")]
+    Alarms(#[from] AlarmError),
+}
+
+type Result<T> = std::result::Result<T, StorageError>;
+
+#[derive(Serialize, Deserialize)]
+struct HouseRecord {
+    // Файлы без версии записаны до ее появления, т.е. в первой версии
+    #[serde(default = "first_version")]
+    version: u32,
+    name: String,
+    #[serde(default)]
+    device_names: DeviceNamePolicy,
+    #[serde(default)]
+    temperature_unit: TemperatureUnit,
+    #[serde(default)]
+    rooms: Vec<RoomRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<Rule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    schedule: Vec<ScheduledJob>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    scenes: Vec<Scene>,
+    #[serde(default, skip_serializing_if = "PowerBudgets::is_empty")]
+    power: PowerBudgets,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alarms: Vec<Alarm>,
+}
+
+/// Расположение в файле дома вместе с поддеревом.
+/// Журнал изменений хранит в таком виде удаленные комнаты.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomRecord {
+    pub name: String,
+    #[serde(default)]
+    pub devices: Vec<DeviceRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rooms: Vec<RoomRecord>,
+}
+
+/// Девайс в файле дома: имя, описание, теги и состояние
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceRecord {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Единицы, в которых термометр показывает температуру
+    #[serde(default, skip_serializing_if = "is_celsius")]
+    pub unit: TemperatureUnit,
+    #[serde(flatten)]
+    pub state: DeviceState,
+}
+
+fn is_celsius(unit: &TemperatureUnit) -> bool {
+    *unit == TemperatureUnit::Celsius
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl RoomRecord {
+    pub(crate) fn from_location(location: &Location) -> Self {
+        Self {
+            name: location.name.clone(),
+            devices: location
+                .devices
+                .iter()
+                .map(|device| DeviceRecord::of(device.as_ref(), location.tags.get(device.name())))
+                .collect(),
+            rooms: location.children.iter().map(Self::from_location).collect(),
+        }
+    }
+
+    /// Добавляем расположение со всем поддеревом в конец `parent`.
+    /// Имя с `/` иначе молча превратилось бы во вложенные расположения.
+    pub(crate) fn add_to(
+        self,
+        house: &mut SmartHouse,
+        parent: &str,
+    ) -> std::result::Result<(), SmartHouseError> {
+        if self.name.trim().is_empty() || self.name.contains(location::PATH_SEPARATOR) {
+            return Err(SmartHouseError::InvalidName(self.name));
+        }
+        let path = location::join_path(parent, &self.name);
+        house.add_room(&path)?;
+        for device in self.devices {
+            device.add_to(house, &path)?;
+        }
+        for room in self.rooms {
+            room.add_to(house, &path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DeviceRecord {
+    pub(crate) fn of(device: &dyn Device, tags: Option<&BTreeSet<String>>) -> Self {
+        Self {
+            name: device.name().to_owned(),
+            description: device.description().to_owned(),
+            tags: tags.cloned().unwrap_or_default(),
+            unit: device
+                .as_any()
+                .downcast_ref::<SmartThermometer>()
+                .map(SmartThermometer::display_unit)
+                .unwrap_or_default(),
+            state: device.state(),
+        }
+    }
+
+    /// Добавляем девайс с тегами в конец комнаты
+    pub(crate) fn add_to(
+        self,
+        house: &mut SmartHouse,
+        room: &str,
+    ) -> std::result::Result<(), SmartHouseError> {
+        let mut device = self.state.into_device(&self.name, &self.description);
+        if let Some(thermometer) = device.as_any_mut().downcast_mut::<SmartThermometer>() {
+            thermometer.set_display_unit(self.unit);
+        }
+        house.add_device(room, device)?;
+        for tag in &self.tags {
+            house.tag_device(room, &self.name, tag)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl HouseRecord {
+    fn from_house(house: &SmartHouse) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            name: house.name().to_owned(),
+            device_names: house.device_name_policy(),
+            temperature_unit: house.temperature_unit(),
+            rooms: house
+                .locations
+                .iter()
+                .map(RoomRecord::from_location)
+                .collect(),
+            rules: house.rules().rules().cloned().collect(),
+            schedule: house.schedule().jobs().cloned().collect(),
+            scenes: house.scenes().cloned().collect(),
+            power: house.power_budgets().clone(),
+            alarms: house.alarms().alarms().cloned().collect(),
+        }
+    }
+
+    /// Приводим запись старой версии к текущей схеме.
+    /// Плоский дом версии 1 - частный случай дерева версии 2.
+    fn migrate(self) -> Result<Self> {
+        if self.version > FORMAT_VERSION {
+            return Err(StorageError::UnsupportedVersion(self.version));
+        }
+
+        Ok(Self {
+            version: FORMAT_VERSION,
+            ..self
+        })
+    }
+
+    fn into_house(self) -> Result<SmartHouse> {
+        let mut house = SmartHouse::new_empty(&self.name);
+        house.set_device_name_policy(self.device_names)?;
+        house.set_temperature_unit(self.temperature_unit);
+        for room in self.rooms {
+            room.add_to(&mut house, "")?;
+        }
+        for rule in self.rules {
+            house.add_rule(rule)?;
+        }
+        for job in self.schedule {
+            house.add_job(job)?;
+        }
+        for scene in self.scenes {
+            house.add_scene(scene)?;
+        }
+        // Бюджеты и приоритеты пропавших расположений и розеток ничего не значат
+        let mut power = self.power;
+        power.retain_known(&house);
+        house.power = power;
+        for alarm in self.alarms {
+            house.add_alarm(alarm)?;
+        }
+        // Загрузка - не изменение, отменять ее нечего
+        house.journal_mut().clear();
+
+        Ok(house)
+    }
+}
+
+impl SmartHouse {
+    /// Загружаем дом из файла, формат определяется по расширению
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let format = format_of(path)?;
+        let content = fs::read_to_string(path)?;
+        Self::decode(&content, format)
+    }
+
+    /// Сохраняем дом в файл, формат определяется по расширению
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let format = format_of(path)?;
+        fs::write(path, self.encode(format)?)?;
+        Ok(())
+    }
+
+    /// Сериализуем дом в строку заданного формата
+    pub fn encode(&self, format: Format) -> Result<String> {
+        let record = HouseRecord::from_house(self);
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(&record)?,
+            Format::Toml => toml::to_string(&record)?,
+        })
+    }
+
+    /// Восстанавливаем дом из строки заданного формата
+    pub fn decode(content: &str, format: Format) -> Result<Self> {
+        let record: HouseRecord = match format {
+            Format::Json => serde_json::from_str(content)?,
+            Format::Toml => toml::from_str(content)?,
+        };
+
+        record.migrate()?.into_house()
+    }
+}
+
+fn format_of(path: &Path) -> Result<Format> {
+    Format::from_path(path).ok_or_else(|| StorageError::UnknownFormat(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        device::{SmartSocket, SmartThermometer},
+        power::{BudgetPolicy, PowerBudget},
+    };
+
+    fn test_house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("my smart house");
+        house.add_room("room2").unwrap();
+        house.add_room("room1").unwrap();
+        house
+            .add_device(
+                "room2",
+                Box::new(SmartSocket::new(
+                    "socket",
+                    "socket description",
+                    true,
+                    220.5,
+                )),
+            )
+            .unwrap();
+        house
+            .add_device(
+                "room2",
+                Box::new(SmartThermometer::new("thermo", "thermo description", 19.2)),
+            )
+            .unwrap();
+
+        house
+    }
+
+    /// Счетчик включенной розетки идет по системным часам, поэтому энергия
+    /// сравнивается с допуском
+    fn assert_same_state(expected: DeviceState, actual: DeviceState) {
+        let without_energy = |state| match state {
+            DeviceState::Socket {
+                energy_kwh,
+                is_on,
+                current_power,
+                voltage,
+            } => (
+                DeviceState::Socket {
+                    is_on,
+                    current_power,
+                    voltage,
+                    energy_kwh: 0.0,
+                },
+                energy_kwh,
+            ),
+            state => (state, 0.0),
+        };
+
+        let (expected, expected_energy) = without_energy(expected);
+        let (actual, actual_energy) = without_energy(actual);
+        assert_eq!(expected, actual);
+        assert!((expected_energy - actual_energy).abs() < 1e-3);
+    }
+
+    fn assert_same_house(expected: &SmartHouse, actual: &SmartHouse) {
+        assert_eq!(expected.name(), actual.name());
+        assert_eq!(expected.device_name_policy(), actual.device_name_policy());
+        assert_eq!(expected.temperature_unit(), actual.temperature_unit());
+        assert_eq!(
+            expected.locations().collect::<Vec<_>>(),
+            actual.locations().collect::<Vec<_>>()
+        );
+        for room in expected.locations() {
+            let expected_devices: Vec<_> = expected.room_devices(&room).collect();
+            let actual_devices: Vec<_> = actual.room_devices(&room).collect();
+            assert_eq!(expected_devices.len(), actual_devices.len());
+            for (e, a) in expected_devices.iter().zip(actual_devices.iter()) {
+                assert_eq!(e.name(), a.name());
+                assert_eq!(e.description(), a.description());
+                assert_same_state(e.state(), a.state());
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let house = test_house();
+        let json = house.encode(Format::Json).unwrap();
+        let loaded = SmartHouse::decode(&json, Format::Json).unwrap();
+
+        assert_same_house(&house, &loaded);
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let house = test_house();
+        let toml = house.encode(Format::Toml).unwrap();
+        let loaded = SmartHouse::decode(&toml, Format::Toml).unwrap();
+
+        assert_same_house(&house, &loaded);
+    }
+
+    #[test]
+    fn test_nested_round_trip() {
+        let mut house = test_house();
+        house.add_room("floor2/kitchen").unwrap();
+        house
+            .add_device(
+                "floor2",
+                Box::new(SmartThermometer::new("hall", "stairs", 21.0)),
+            )
+            .unwrap();
+        house
+            .add_device(
+                "floor2/kitchen",
+                Box::new(SmartSocket::new("socket1", "kettle", false, 0.0)),
+            )
+            .unwrap();
+
+        for format in [Format::Json, Format::Toml] {
+            let loaded = SmartHouse::decode(&house.encode(format).unwrap(), format).unwrap();
+            assert_same_house(&house, &loaded);
+        }
+        assert!(house
+            .encode(Format::Json)
+            .unwrap()
+            .contains(r#""name": "kitchen""#));
+    }
+
+    #[test]
+    fn test_all_device_kinds_round_trip() {
+        use crate::device::{
+            light::Color, sensor::SensorKind, SmartBlinds, SmartLight, SmartLock, SmartSensor,
+        };
+
+        let mut house = test_house();
+        let mut light = SmartLight::new("light", "rgb strip", true, 60);
+        light.set_color(Some(Color::rgb(0, 128, 255)));
+        let mut lock = SmartLock::new("lock", "front door", true);
+        lock.jam();
+        let mut sensor = SmartSensor::new("sensor", "door", SensorKind::Contact);
+        sensor.trigger();
+        for device in [
+            Box::new(light) as Box<dyn crate::device::Device>,
+            Box::new(lock),
+            Box::new(sensor),
+            Box::new(SmartBlinds::new("blinds", "window", 30)),
+        ] {
+            house.add_device("room1", device).unwrap();
+        }
+        let mut outdoor = SmartThermometer::new("outdoor", "", 10.0);
+        outdoor.set_display_unit(TemperatureUnit::Fahrenheit);
+        house.add_device("room1", Box::new(outdoor)).unwrap();
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let loaded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_same_house(&house, &loaded);
+            assert_eq!(
+                TemperatureUnit::Fahrenheit,
+                loaded
+                    .thermometer("room1", "outdoor")
+                    .unwrap()
+                    .display_unit()
+            );
+        }
+        assert!(house
+            .encode(Format::Json)
+            .unwrap()
+            .contains(r##""color": "#0080ff""##));
+    }
+
+    #[test]
+    fn test_decode_without_version() {
+        let house = SmartHouse::decode(
+            r#"
+name = "old house"
+
+[[rooms]]
+name = "room1"
+
+[[rooms.devices]]
+kind = "socket"
+name = "socket"
+is_on = false
+current_power = 0
+"#,
+            Format::Toml,
+        )
+        .unwrap();
+
+        assert_eq!("old house", house.name());
+        assert!(!house.socket("room1", "socket").unwrap().is_on());
+    }
+
+    #[test]
+    fn test_decode_unsupported_version() {
+        let result = SmartHouse::decode(
+            &format!(r#"{{"version": {}, "name": "house"}}"#, FORMAT_VERSION + 1),
+            Format::Json,
+        );
+
+        assert!(matches!(
+            result,
+            Err(StorageError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let house = test_house();
+        // Свое имя на процесс, чтобы параллельные прогоны не делили файлы
+        let dir = std::env::temp_dir();
+        let name = format!("smart_house_test_save_load_{}", std::process::id());
+
+        for extension in ["json", "toml"] {
+            let path = dir.join(format!("{name}.{extension}"));
+            house.save(&path).unwrap();
+            let loaded = SmartHouse::load(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_same_house(&house, &loaded);
+        }
+
+        assert!(matches!(
+            house.save(dir.join(format!("{name}.yaml"))),
+            Err(StorageError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_device_name_policy() {
+        let mut house = test_house();
+        house
+            .set_device_name_policy(DeviceNamePolicy::HouseWide)
+            .unwrap();
+        let json = house.encode(Format::Json).unwrap();
+        assert!(json.contains(r#""device_names": "house_wide""#));
+        assert_same_house(&house, &SmartHouse::decode(&json, Format::Json).unwrap());
+
+        let result = SmartHouse::decode(
+            r#"{"name": "house", "device_names": "house_wide", "rooms": [
+                {"name": "room1", "devices": [{"kind": "thermometer", "name": "t", "current_temperature": 20}]},
+                {"name": "room2", "devices": [{"kind": "thermometer", "name": "t", "current_temperature": 20}]}
+            ]}"#,
+            Format::Json,
+        );
+        assert!(matches!(
+            result,
+            Err(StorageError::House(SmartHouseError::DuplicateDevice { room, .. })) if room == "room1"
+        ));
+    }
+
+    #[test]
+    fn test_decode_duplicate_room() {
+        let result = SmartHouse::decode(
+            r#"{"name": "house", "rooms": [{"name": "room1"}, {"name": "room1"}]}"#,
+            Format::Json,
+        );
+
+        assert!(matches!(
+            result,
+            Err(StorageError::House(SmartHouseError::DuplicateRoom(room))) if room == "room1"
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_room_name() {
+        for name in ["a/b", " "] {
+            let result = SmartHouse::decode(
+                &format!(r#"{{"name": "house", "rooms": [{{"name": "{name}"}}]}}"#),
+                Format::Json,
+            );
+
+            assert!(matches!(
+                result,
+                Err(StorageError::House(SmartHouseError::InvalidName(room))) if room == name
+            ));
+        }
+    }
+
+    #[test]
+    fn test_rules_round_trip() {
+        let mut house = test_house();
+        let rules = crate::rules::text::parse_rules(
+            "rule overheat: when room1_thermo_1 temperature > 25 °C then turn off room1_socket_1 hysteresis 1 °C cooldown 10m\n\
+             rule cold: when room1_thermo_1 temperature < 18 °C then turn on room1_socket_1",
+        )
+        .unwrap();
+        for rule in rules.clone() {
+            house.add_rule(rule).unwrap();
+        }
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let decoded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_eq!(rules, decoded.rules().rules().cloned().collect::<Vec<_>>());
+        }
+
+        let json = house.encode(Format::Json).unwrap();
+        assert!(json.contains(r#""cooldown": "10m""#));
+        assert!(json.contains(r#""turn_off": "room1_socket_1""#));
+    }
+
+    #[test]
+    fn test_schedule_round_trip() {
+        use crate::schedule::Switch;
+
+        let mut house = test_house();
+        let jobs = vec![
+            ScheduledJob::repeat("morning", "room1_socket_1", Switch::On, "weekdays 07:00")
+                .unwrap()
+                .with_time_zone(chrono_tz::Europe::Moscow),
+            ScheduledJob::repeat("pump", "room1/room1_socket_1", Switch::On, "0 */2 * * *")
+                .unwrap()
+                .with_duration(std::time::Duration::from_secs(15 * 60)),
+        ];
+        for job in jobs.clone() {
+            house.add_job(job).unwrap();
+        }
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let decoded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_eq!(jobs, decoded.schedule().jobs().cloned().collect::<Vec<_>>());
+        }
+
+        let json = house.encode(Format::Json).unwrap();
+        assert!(json.contains(r#""cron": "weekdays 07:00""#));
+        assert!(json.contains(r#""time_zone": "Europe/Moscow""#));
+        assert!(json.contains(r#""duration": "15m""#));
+
+        let result = SmartHouse::decode(
+            r#"{"name": "house", "schedule": [{"name": "j", "device": "s", "switch": "on",
+                "when": {"repeat": {"cron": "0 25 * * *"}}}]}"#,
+            Format::Json,
+        );
+        assert!(matches!(result, Err(StorageError::Json(_))));
+    }
+
+    #[test]
+    fn test_scenes_round_trip() {
+        use crate::scene::TargetState;
+
+        let mut house = test_house();
+        house.capture_scene("day", &["socket"]).unwrap();
+        house
+            .add_scene(
+                Scene::new("night")
+                    .with_target("room2/socket", TargetState::Socket { is_on: false }),
+            )
+            .unwrap();
+        let scenes: Vec<Scene> = house.scenes().cloned().collect();
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let decoded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_eq!(scenes, decoded.scenes().cloned().collect::<Vec<_>>());
+        }
+
+        let json = house.encode(Format::Json).unwrap();
+        assert!(json.contains(r#""device": "room2/socket""#));
+        assert!(json.contains(r#""kind": "socket""#));
+    }
+
+    #[test]
+    fn test_tags_round_trip() {
+        let mut house = test_house();
+        house.tag_device("room2", "socket", "heating").unwrap();
+        house.tag_device("room2", "socket", "critical").unwrap();
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let decoded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_eq!(
+                vec!["critical", "heating"],
+                decoded
+                    .device_tags("room2", "socket")
+                    .unwrap()
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let result = SmartHouse::decode(
+            r#"{"name": "house", "rooms": [{"name": "r", "devices": [{"name": "s",
+                "tags": ["bad tag"], "kind": "socket", "is_on": true, "current_power": 1}]}]}"#,
+            Format::Json,
+        );
+        assert!(matches!(
+            result,
+            Err(StorageError::House(SmartHouseError::InvalidTag(tag))) if tag == "bad tag"
+        ));
+    }
+
+    #[test]
+    fn test_power_round_trip() {
+        let mut house = test_house();
+        house
+            .set_power_budget(
+                Some("room2"),
+                Some(PowerBudget::new(1000.0, BudgetPolicy::Shed)),
+            )
+            .unwrap();
+        house
+            .set_power_budget(None, Some(PowerBudget::new(3500.0, BudgetPolicy::Warn)))
+            .unwrap();
+        house.set_load_priority("room2/socket", Some(3)).unwrap();
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let decoded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_eq!(house.power_budgets(), decoded.power_budgets());
+        }
+
+        // Бюджеты и приоритеты несуществующих путей при загрузке отбрасываются
+        let mut value: serde_json::Value =
+            serde_json::from_str(&house.encode(Format::Json).unwrap()).unwrap();
+        value["power"]["rooms"]["attic"] = value["power"]["rooms"]["room2"].clone();
+        value["power"]["priorities"]["room2/lamp"] = 1.into();
+        let decoded = SmartHouse::decode(&value.to_string(), Format::Json).unwrap();
+        assert_eq!(house.power_budgets(), decoded.power_budgets());
+
+        let json = test_house().encode(Format::Json).unwrap();
+        assert!(!json.contains(r#""power""#));
+    }
+
+    #[test]
+    fn test_alarms_round_trip() {
+        use crate::alarms::{Alarm, Severity, Threshold};
+        use std::time::Duration;
+
+        let mut house = test_house();
+        let alarms = vec![
+            Alarm::new("too cold", "thermo", Threshold::TemperatureBelow(16.0))
+                .with_hysteresis(1.0),
+            Alarm::new(
+                "left on",
+                "room2/socket",
+                Threshold::OnLongerThan(Duration::from_secs(2 * 3600)),
+            )
+            .with_severity(Severity::Critical),
+        ];
+        for alarm in alarms.clone() {
+            house.add_alarm(alarm).unwrap();
+        }
+
+        for format in [Format::Json, Format::Toml] {
+            let encoded = house.encode(format).unwrap();
+            let decoded = SmartHouse::decode(&encoded, format).unwrap();
+            assert_eq!(
+                alarms,
+                decoded.alarms().alarms().cloned().collect::<Vec<_>>()
+            );
+        }
+
+        let json = house.encode(Format::Json).unwrap();
+        assert!(json.contains(r#""on_longer_than": "2h""#));
+    }
+}
@@ -0,0 +1,392 @@
+//! Теги девайсов: произвольные метки вроде `heating` или `critical`,
+//! по которым девайсы ищутся во всех комнатах сразу.
+
+use crate::{
+    device::{Device, DeviceKind},
+    journal::Operation,
+    location::{self, Location},
+    query::DeviceQuery,
+    rules::{Action, RuleError},
+    SmartHouse, SmartHouseError,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+type Result<T> = std::result::Result<T, SmartHouseError>;
+
+/// Тег - непустая строка из букв, цифр, `-` и `_`
+pub fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty()
+        || !tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(SmartHouseError::InvalidTag(tag.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Отбор девайсов по тегам: есть все `tags`, нет ни одного из `excluded`
+/// и, если задан, вид девайса `kind`. Пустой запрос подходит любому девайсу.
+///
+/// В текстовом виде теги перечисляются через запятую, исключенные - с `!`:
+/// `"heating,!critical"`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagQuery {
+    pub tags: BTreeSet<String>,
+    pub excluded: BTreeSet<String>,
+    pub kind: Option<DeviceKind>,
+}
+
+impl TagQuery {
+    pub fn tagged(tag: &str) -> Self {
+        Self::default().and(tag)
+    }
+
+    pub fn and(mut self, tag: &str) -> Self {
+        self.tags.insert(tag.to_owned());
+        self
+    }
+
+    pub fn without(mut self, tag: &str) -> Self {
+        self.excluded.insert(tag.to_owned());
+        self
+    }
+
+    pub fn of_kind(mut self, kind: DeviceKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Отбор тот же, что у `DeviceQuery`: запрос по тегам - его частный случай
+    /// без расположения, поэтому расположение девайса не важно
+    pub fn matches(&self, device: &dyn Device, tags: Option<&BTreeSet<String>>) -> bool {
+        DeviceQuery::from(self).matches("", device, tags)
+    }
+
+    /// Девайсы расположения, подходящие запросу
+    pub(crate) fn filter<'a>(
+        &self,
+        location: &'a Location,
+    ) -> impl Iterator<Item = &'a dyn Device> + 'a {
+        let query = DeviceQuery::from(self);
+        location
+            .devices
+            .iter()
+            .map(|d| d.as_ref())
+            .filter(move |d| query.matches("", *d, location.tags.get(d.name())))
+    }
+}
+
+impl FromStr for TagQuery {
+    type Err = SmartHouseError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut query = Self::default();
+        for tag in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match tag.strip_prefix('!') {
+                Some(tag) => {
+                    validate_tag(tag)?;
+                    query = query.without(tag);
+                }
+                None => {
+                    validate_tag(tag)?;
+                    query = query.and(tag);
+                }
+            }
+        }
+
+        Ok(query)
+    }
+}
+
+impl fmt::Display for TagQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tags = self.tags.iter().map(String::to_owned);
+        let excluded = self.excluded.iter().map(|t| format!("!{t}"));
+        write!(f, "{}", tags.chain(excluded).collect::<Vec<_>>().join(","))
+    }
+}
+
+/// Результат массовой операции над одним девайсом
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulkResult {
+    pub action: Action,
+    pub result: std::result::Result<(), RuleError>,
+}
+
+impl fmt::Display for BulkResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.action.verb(), self.action.device())?;
+        if let Err(error) = &self.result {
+            write!(f, " ({error})")?;
+        }
+        Ok(())
+    }
+}
+
+impl SmartHouse {
+    /// Вешаем тег на девайс, `false` - тег уже был
+    pub fn tag_device(&mut self, room: &str, device: &str, tag: &str) -> Result<bool> {
+        validate_tag(tag)?;
+        self.room_devices_mut(room, device)?;
+
+        let location = self.room_mut(room)?;
+        let added = location
+            .tags
+            .entry(device.to_owned())
+            .or_default()
+            .insert(tag.to_owned());
+        if added {
+            self.journal.record(Operation::TagDevice {
+                room: location::normalize_path(room),
+                device: device.to_owned(),
+                tag: tag.to_owned(),
+            });
+        }
+        Ok(added)
+    }
+
+    /// Снимаем тег с девайса, `false` - тега не было
+    pub fn untag_device(&mut self, room: &str, device: &str, tag: &str) -> Result<bool> {
+        self.room_devices_mut(room, device)?;
+
+        let location = self.room_mut(room)?;
+        let Some(tags) = location.tags.get_mut(device) else {
+            return Ok(false);
+        };
+        let removed = tags.remove(tag);
+        if tags.is_empty() {
+            location.tags.remove(device);
+        }
+        if removed {
+            self.journal.record(Operation::UntagDevice {
+                room: location::normalize_path(room),
+                device: device.to_owned(),
+                tag: tag.to_owned(),
+            });
+        }
+        Ok(removed)
+    }
+
+    /// Теги девайса по алфавиту
+    pub fn device_tags(&self, room: &str, device: &str) -> Result<impl Iterator<Item = &str>> {
+        let location = self.room(room)?;
+        if self.device(room, device).is_none() {
+            return Err(SmartHouseError::DeviceNotFound {
+                room: room.to_owned(),
+                device: device.to_owned(),
+            });
+        }
+
+        Ok(location
+            .tags
+            .get(device)
+            .into_iter()
+            .flatten()
+            .map(String::as_str))
+    }
+
+    /// Все теги дома с числом девайсов, на которых они висят
+    pub fn tags(&self) -> BTreeMap<String, usize> {
+        let mut tags = BTreeMap::new();
+        for (_, location) in self.walk() {
+            for tag in location.tags.values().flatten() {
+                *tags.entry(tag.clone()).or_default() += 1;
+            }
+        }
+        tags
+    }
+
+    /// Пути девайсов, подходящих запросу, во всех комнатах дома
+    pub fn find_tagged(&self, query: &TagQuery) -> Vec<String> {
+        self.find_devices(&query.into())
+            .iter()
+            .map(|found| found.path())
+            .collect()
+    }
+
+    /// Выполняем действие над всеми подходящими девайсами, например
+    /// `house.apply_tagged(&TagQuery::tagged("heating"), Action::TurnOff)`.
+    /// Ошибка одного девайса не мешает остальным, в журнал операция попадает целиком.
+    pub fn apply_tagged<F>(&mut self, query: &TagQuery, action: F) -> Vec<BulkResult>
+    where
+        F: Fn(String) -> Action,
+    {
+        let paths = self.find_tagged(query);
+        self.journaled(format!("apply to tagged \"{query}\""), |house| {
+            paths
+                .into_iter()
+                .map(|path| {
+                    let action = action(path);
+                    let result = action.check(house).and_then(|()| action.apply(house));
+                    BulkResult { action, result }
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{SmartLight, SmartLock, SmartSocket};
+
+    fn house() -> SmartHouse {
+        let mut house = SmartHouse::new_empty("tagged house");
+        house.add_room("kitchen").unwrap();
+        house.add_room("floor2/bedroom").unwrap();
+        let devices: [(&str, Box<dyn Device>, &[&str]); 5] = [
+            (
+                "kitchen",
+                Box::new(SmartSocket::new("heater", "", true, 1500.0)),
+                &["heating"],
+            ),
+            (
+                "kitchen",
+                Box::new(SmartSocket::new("fridge", "", true, 150.0)),
+                &["critical"],
+            ),
+            (
+                "floor2/bedroom",
+                Box::new(SmartSocket::new("floor", "", true, 800.0)),
+                &["heating", "critical"],
+            ),
+            (
+                "floor2/bedroom",
+                Box::new(SmartLight::new("lamp", "", true, 50)),
+                &["heating"],
+            ),
+            (
+                "floor2/bedroom",
+                Box::new(SmartLock::new("door", "", false)),
+                &[],
+            ),
+        ];
+        for (room, device, tags) in devices {
+            let name = device.name().to_owned();
+            house.add_device(room, device).unwrap();
+            for tag in tags {
+                house.tag_device(room, &name, tag).unwrap();
+            }
+        }
+        house
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut house = house();
+        assert_eq!(
+            BTreeMap::from([("critical".to_owned(), 2), ("heating".to_owned(), 3)]),
+            house.tags()
+        );
+        assert_eq!(
+            Err(SmartHouseError::InvalidTag("kitchen appliances".to_owned())),
+            house.tag_device("kitchen", "fridge", "kitchen appliances")
+        );
+        assert_eq!(Ok(false), house.tag_device("kitchen", "heater", "heating"));
+        assert_eq!(
+            Ok(false),
+            house.untag_device("kitchen", "heater", "critical")
+        );
+
+        // Теги переезжают и переименовываются вместе с девайсом
+        house
+            .move_device("kitchen", "floor2/bedroom", "fridge")
+            .unwrap();
+        house
+            .rename_device("floor2/bedroom", "fridge", "minibar")
+            .unwrap();
+        house.rename_room("floor2", "upstairs").unwrap();
+        assert_eq!(
+            vec!["critical"],
+            house
+                .device_tags("upstairs/bedroom", "minibar")
+                .unwrap()
+                .collect::<Vec<_>>()
+        );
+
+        house.delete_device("upstairs/bedroom", "minibar").unwrap();
+        assert_eq!(Some(&1), house.tags().get("critical"));
+    }
+
+    #[test]
+    fn test_query() {
+        let house = house();
+        assert_eq!(
+            vec![
+                "kitchen/heater",
+                "floor2/bedroom/floor",
+                "floor2/bedroom/lamp"
+            ],
+            house.find_tagged(&TagQuery::tagged("heating"))
+        );
+        assert_eq!(
+            vec!["kitchen/heater", "floor2/bedroom/lamp"],
+            house.find_tagged(&"heating, !critical".parse().unwrap())
+        );
+        assert_eq!(
+            vec!["floor2/bedroom/floor"],
+            house.find_tagged(
+                &TagQuery::tagged("heating")
+                    .and("critical")
+                    .of_kind(DeviceKind::Socket)
+            )
+        );
+        assert_eq!(5, house.find_tagged(&TagQuery::default()).len());
+        assert_eq!(
+            "heating,!critical",
+            "!critical,heating".parse::<TagQuery>().unwrap().to_string()
+        );
+        assert!("heating,!".parse::<TagQuery>().is_err());
+    }
+
+    #[test]
+    fn test_apply_tagged() {
+        let mut house = house();
+        let results = house.apply_tagged(
+            &TagQuery::tagged("heating").of_kind(DeviceKind::Socket),
+            Action::TurnOff,
+        );
+        assert_eq!(
+            vec!["turn off kitchen/heater", "turn off floor2/bedroom/floor"],
+            results.iter().map(|r| r.to_string()).collect::<Vec<_>>()
+        );
+        assert!(!house.socket("kitchen", "heater").unwrap().is_on());
+        assert!(house.socket("kitchen", "fridge").unwrap().is_on());
+
+        let results = house.apply_tagged(&TagQuery::tagged("heating"), Action::Close);
+        assert!(results.iter().all(|r| r.result.is_err()));
+    }
+
+    #[test]
+    fn test_tagged_report() {
+        use crate::report::ReportOptions;
+
+        let house = house();
+        let options = ReportOptions::strict().tagged("critical".parse().unwrap());
+        let outcome = house.create_report_with(&house, &options).unwrap();
+        assert_eq!(2, outcome.report.matches("Name: ").count());
+        assert!(outcome.report.contains("Name: fridge"));
+        assert!(outcome.report.contains("Name: floor"));
+
+        let report = house
+            .report_with(&house, &options.within("floor2"))
+            .unwrap();
+        assert_eq!(1, report.rooms.len());
+        assert_eq!("floor2/bedroom", report.rooms[0].name);
+        assert_eq!(1, report.rooms[0].devices.len());
+
+        let report = house
+            .report_with(
+                &house,
+                &ReportOptions::strict().tagged(TagQuery::tagged("none")),
+            )
+            .unwrap();
+        assert!(report.rooms.is_empty());
+    }
+}
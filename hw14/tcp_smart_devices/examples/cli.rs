@@ -1,16 +1,39 @@
-use tcp_smart_devices::TcpSmartSocketClient;
+use tcp_smart_devices::{house::TcpSmartHouseClient, TcpSmartSocketClient};
 
 const ADDR: &str = "127.0.0.1:55331";
+/// Адрес дома из примера `house_server`, со сценами работаем через него
+const HOUSE_ADDR: &str = "127.0.0.1:55332";
+
+const USAGE: &str = "use 'on', 'off', 'info', 'scenes', 'scene-apply NAME', \
+'scene-capture NAME DEVICE...' or 'scene-delete NAME'";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Читаем аргументы командной строки.
     let mut cli_args = std::env::args().skip(1);
     let Some(action) = cli_args.next() else {
-        return Err(String::from("No action provided, use 'on' or 'off' or 'info'").into());
+        return Err(format!("No action provided, {USAGE}").into());
     };
 
     println!("Performing action: '{action}'...");
 
+    if action.starts_with("scene") {
+        let mut client = TcpSmartHouseClient::new(HOUSE_ADDR)?;
+        let name = cli_args.next().unwrap_or_default();
+        let response = match action.as_str() {
+            "scenes" => client.scenes()?,
+            "scene-apply" => client.apply_scene(&name)?,
+            "scene-capture" => {
+                let devices: Vec<String> = cli_args.collect();
+                let devices: Vec<&str> = devices.iter().map(String::as_str).collect();
+                client.capture_scene(&name, &devices)?
+            }
+            "scene-delete" => client.delete_scene(&name)?,
+            _ => format!("Unknown action, {USAGE}"),
+        };
+        println!("{response}");
+        return Ok(());
+    }
+
     // Соединяемся с умной розеткой по tcp через клиента
     let mut client = TcpSmartSocketClient::new(ADDR)?;
 
@@ -28,7 +51,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("{}", response)
         }
         _ => {
-            println!("Unknown action, use 'on' or 'off' or 'info'")
+            println!("Unknown action, {USAGE}")
         }
     }
 
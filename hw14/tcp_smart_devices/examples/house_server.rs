@@ -0,0 +1,43 @@
+use smart_devices::{
+    device::{Device, SmartLight, SmartLock, SmartSocket},
+    SmartHouse,
+};
+use std::{collections::HashMap, path::PathBuf};
+use tcp_smart_devices::house::TcpSmartHouse;
+
+const ADDR: &str = "127.0.0.1:55332";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Дом берем из файла, если он передан и существует, иначе - демонстрационный
+    let storage_path = std::env::args().nth(1).map(PathBuf::from);
+    let house = match &storage_path {
+        Some(path) if path.exists() => SmartHouse::load(path)?,
+        _ => SmartHouse::new(
+            "Tcp smart house",
+            HashMap::from([
+                (
+                    "hall",
+                    vec![
+                        Box::new(SmartLight::new("lamp", "ceiling lamp", true, 80))
+                            as Box<dyn Device>,
+                        Box::new(SmartLock::new("front", "front door", false)),
+                    ],
+                ),
+                (
+                    "kitchen",
+                    vec![
+                        Box::new(SmartSocket::new("kettle", "electric kettle", false, 2000.0))
+                            as Box<dyn Device>,
+                    ],
+                ),
+            ]),
+        ),
+    };
+
+    let mut tcp_smart_house = TcpSmartHouse::new(house);
+    if let Some(path) = storage_path {
+        tcp_smart_house = tcp_smart_house.with_storage(path);
+    }
+
+    tcp_smart_house.serve(ADDR)
+}
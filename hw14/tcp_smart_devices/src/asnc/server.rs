@@ -1,11 +1,15 @@
 use crate::{decode_request, encode_response, Command, Request, Response};
-use smart_devices::device::SmartSocket;
+use smart_devices::{
+    device::{Device, SmartSocket},
+    events::EventBus,
+};
 use std::sync::Arc;
 use stp::asnc::server::{StpConnection, StpServer};
 use tokio::sync::RwLock;
 
 pub struct AsyncTcpSmartSocket {
     inner: Arc<RwLock<SmartSocket>>,
+    events: Option<EventBus>,
 }
 
 impl AsyncTcpSmartSocket {
@@ -14,8 +18,15 @@ impl AsyncTcpSmartSocket {
 
         Self {
             inner: Arc::new(RwLock::new(socket)),
+            events: None,
         }
     }
+
+    /// Публикуем включение и выключение розетки в шину
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
 }
 
 pub trait Server {
@@ -31,12 +42,13 @@ impl Server for AsyncTcpSmartSocket {
         &self,
         addr: &str,
     ) -> impl std::future::Future<Output = Result<(), Box<dyn std::error::Error>>> + Send {
-        serve(self.inner.clone(), addr)
+        serve(self.inner.clone(), self.events.clone(), addr)
     }
 }
 
 async fn serve(
     socket: Arc<RwLock<SmartSocket>>,
+    events: Option<EventBus>,
     addr: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let server = StpServer::bind(addr.to_owned()).await?;
@@ -53,20 +65,37 @@ async fn serve(
             continue;
         };
 
-        tokio::spawn(handle_connection(socket.clone(), connection));
+        tokio::spawn(handle_connection(
+            socket.clone(),
+            events.clone(),
+            connection,
+        ));
+    }
+}
+
+/// Включаем или выключаем розетку и публикуем изменение в шину
+async fn switch(socket: &RwLock<SmartSocket>, events: Option<&EventBus>, on: bool) -> String {
+    let mut socket = socket.write().await;
+    let old = socket.state();
+    if on {
+        socket.turn_on();
+    } else {
+        socket.turn_off();
+    }
+    if let Some(events) = events {
+        events.publish_state_change(None, &*socket, old);
     }
+    format!("{}", socket)
 }
 
-async fn handle_request(socket: Arc<RwLock<SmartSocket>>, request: Request) -> Response {
+async fn handle_request(
+    socket: Arc<RwLock<SmartSocket>>,
+    events: Option<&EventBus>,
+    request: Request,
+) -> Response {
     Response(match request.0 {
-        Command::SmartSocketOn => {
-            socket.clone().write().await.turn_on();
-            format!("{}", socket.clone().read().await)
-        }
-        Command::SmartSocketOff => {
-            socket.clone().write().await.turn_off();
-            format!("{}", socket.clone().read().await)
-        }
+        Command::SmartSocketOn => switch(&socket, events, true).await,
+        Command::SmartSocketOff => switch(&socket, events, false).await,
         Command::SmartSocketInfo => {
             format!("{}", socket.clone().read().await)
         }
@@ -80,13 +109,20 @@ async fn handle_request(socket: Arc<RwLock<SmartSocket>>, request: Request) -> R
     })
 }
 
-async fn handle_connection(socket: Arc<RwLock<SmartSocket>>, mut connection: StpConnection) {
+async fn handle_connection(
+    socket: Arc<RwLock<SmartSocket>>,
+    events: Option<EventBus>,
+    mut connection: StpConnection,
+) {
     loop {
         let socket = socket.clone();
+        let events = events.clone();
         let process_result = connection
             .process_request_async(|req| async move {
                 match decode_request(&req) {
-                    Some(request) => encode_response(handle_request(socket.clone(), request).await),
+                    Some(request) => encode_response(
+                        handle_request(socket.clone(), events.as_ref(), request).await,
+                    ),
                     None => "unknown command".to_owned(),
                 }
             })
@@ -114,6 +150,7 @@ mod tests {
 
         let result = handle_request(
             tcp_smart_socket.inner.clone(),
+            None,
             Request(Command::SmartSocketOn),
         )
         .await;
@@ -122,7 +159,7 @@ mod tests {
             Response(
                 r#"Name: tcp_smart_socket
 Description: this is smart socket works by tcp protocol
-Current state: on, 220 Volts"#
+Current state: on, 220 W, 230 V, 0.96 A, 0.000 kWh"#
                     .to_owned()
             ),
             result
@@ -140,6 +177,7 @@ Current state: on, 220 Volts"#
 
         let result = handle_request(
             tcp_smart_socket.inner.clone(),
+            None,
             Request(Command::SmartSocketOff),
         )
         .await;
@@ -148,7 +186,7 @@ Current state: on, 220 Volts"#
             Response(
                 r#"Name: tcp_smart_socket
 Description: this is smart socket works by tcp protocol
-Current state: off, 230 Volts"#
+Current state: off, 0 W, 230 V, 0.00 A, 0.000 kWh"#
                     .to_owned()
             ),
             result
@@ -166,6 +204,7 @@ Current state: off, 230 Volts"#
 
         let result = handle_request(
             tcp_smart_socket.inner.clone(),
+            None,
             Request(Command::SmartSocketInfo),
         )
         .await;
@@ -174,7 +213,7 @@ Current state: off, 230 Volts"#
             Response(
                 r#"Name: tcp_smart_socket
 Description: this is smart socket works by tcp protocol
-Current state: on, 233.3 Volts"#
+Current state: on, 233.3 W, 230 V, 1.01 A, 0.000 kWh"#
                     .to_owned()
             ),
             result
@@ -192,6 +231,7 @@ Current state: on, 233.3 Volts"#
 
         let result = handle_request(
             tcp_smart_socket.inner.clone(),
+            None,
             Request(Command::SmartSocketState),
         )
         .await;
@@ -200,12 +240,14 @@ Current state: on, 233.3 Volts"#
 
         _ = handle_request(
             tcp_smart_socket.inner.clone(),
+            None,
             Request(Command::SmartSocketOff),
         )
         .await;
 
         let result = handle_request(
             tcp_smart_socket.inner.clone(),
+            None,
             Request(Command::SmartSocketState),
         )
         .await;
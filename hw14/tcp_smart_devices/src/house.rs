@@ -0,0 +1,346 @@
+use crate::Response;
+use smart_devices::SmartHouse;
+use std::{net::ToSocketAddrs, path::PathBuf};
+use stp::error::{ConnectError, RequestError};
+use stp::{client::StpClient, server::StpServer};
+
+/// Команды дому. В запросе команда идет первой строкой, аргументы - следующими,
+/// так что в именах сцен и девайсов можно использовать пробелы.
+#[derive(Debug, PartialEq)]
+pub enum HouseCommand {
+    SceneList,
+    SceneApply(String),
+    SceneCapture {
+        name: String,
+        devices: Vec<String>,
+    },
+    SceneDelete(String),
+    /// Включаем или выключаем розетку по полному пути с учетом бюджетов мощности
+    Switch {
+        device: String,
+        on: bool,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct HouseRequest(pub HouseCommand);
+
+pub fn encode_house_request(request: HouseRequest) -> String {
+    let lines = match request.0 {
+        HouseCommand::SceneList => vec!["scenes".to_owned()],
+        HouseCommand::SceneApply(name) => vec!["scene apply".to_owned(), name],
+        HouseCommand::SceneCapture { name, devices } => {
+            let mut lines = vec!["scene capture".to_owned(), name];
+            lines.extend(devices);
+            lines
+        }
+        HouseCommand::SceneDelete(name) => vec!["scene delete".to_owned(), name],
+        HouseCommand::Switch { device, on } => {
+            let command = if on { "socket on" } else { "socket off" };
+            vec![command.to_owned(), device]
+        }
+    };
+
+    lines.join("\n")
+}
+
+pub fn decode_house_request(request: &str) -> Option<HouseRequest> {
+    let mut lines = request.lines();
+    let command = lines.next()?;
+    let mut args: Vec<String> = lines.map(str::to_owned).collect();
+
+    let command = match (command, args.len()) {
+        ("scenes", 0) => HouseCommand::SceneList,
+        ("scene apply", 1) => HouseCommand::SceneApply(args.remove(0)),
+        ("scene capture", 2..) => HouseCommand::SceneCapture {
+            name: args.remove(0),
+            devices: args,
+        },
+        ("scene delete", 1) => HouseCommand::SceneDelete(args.remove(0)),
+        ("socket on" | "socket off", 1) => HouseCommand::Switch {
+            device: args.remove(0),
+            on: command == "socket on",
+        },
+        _ => return None,
+    };
+
+    Some(HouseRequest(command))
+}
+
+/// Умный дом, которым управляют по tcp
+pub struct TcpSmartHouse {
+    house: SmartHouse,
+    /// Файл, в который сохраняем дом после изменений
+    storage_path: Option<PathBuf>,
+}
+
+impl TcpSmartHouse {
+    pub fn new(house: SmartHouse) -> Self {
+        Self {
+            house,
+            storage_path: None,
+        }
+    }
+
+    pub fn with_storage(mut self, path: PathBuf) -> Self {
+        self.storage_path = Some(path);
+        self
+    }
+
+    pub fn house(&self) -> &SmartHouse {
+        &self.house
+    }
+
+    /// Обрабатываем запрос
+    pub fn handle(&mut self, request: HouseRequest) -> Response {
+        let (response, changed) = match request.0 {
+            HouseCommand::SceneList => {
+                let scenes: Vec<String> = self.house.scenes().map(|s| s.to_string()).collect();
+                if scenes.is_empty() {
+                    ("no scenes".to_owned(), false)
+                } else {
+                    (scenes.join("\n"), false)
+                }
+            }
+            HouseCommand::SceneApply(name) => match self.house.apply_scene(&name) {
+                Ok(report) => (report.to_string(), report.applied),
+                Err(error) => (format!("error: {error}"), false),
+            },
+            HouseCommand::SceneCapture { name, devices } => {
+                let devices: Vec<&str> = devices.iter().map(String::as_str).collect();
+                match self.house.capture_scene(&name, &devices) {
+                    Ok(scene) => (format!("scene captured\n{scene}"), true),
+                    Err(error) => (format!("error: {error}"), false),
+                }
+            }
+            HouseCommand::SceneDelete(name) => match self.house.delete_scene(&name) {
+                Ok(scene) => (format!("scene \"{}\" deleted", scene.name), true),
+                Err(error) => (format!("error: {error}"), false),
+            },
+            HouseCommand::Switch { device, on } => match self.house.switch_socket(&device, on) {
+                Ok(overloads) => {
+                    let state = if on { "on" } else { "off" };
+                    let mut lines = vec![format!("socket \"{device}\" turned {state}")];
+                    lines.extend(overloads.iter().map(|o| format!("warning: {o}")));
+                    (lines.join("\n"), true)
+                }
+                Err(error) => (format!("error: {error}"), false),
+            },
+        };
+
+        if changed {
+            self.save();
+        }
+        Response(response)
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.storage_path else {
+            return;
+        };
+        if let Err(error) = self.house.save(path) {
+            eprintln!("Failed to save house to {}: {error}", path.display());
+        }
+    }
+
+    /// Запускаем сервер на tcp адресе
+    pub fn serve(&mut self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let server = StpServer::bind(addr.to_owned())?;
+
+        println!(
+            "Tcp smart house \"{}\" works at {}",
+            self.house.name(),
+            addr
+        );
+
+        loop {
+            let Ok(mut connection) = server.accept() else {
+                continue;
+            };
+
+            connection.process_request(|req| match decode_house_request(&req) {
+                Some(request) => self.handle(request).0,
+                None => "unknown command".to_owned(),
+            })?;
+        }
+    }
+}
+
+pub struct TcpSmartHouseClient {
+    stp: StpClient,
+}
+
+impl TcpSmartHouseClient {
+    /// Подключаемся к серверу.
+    pub fn new<Addr: ToSocketAddrs>(addr: Addr) -> Result<Self, ConnectError> {
+        let stp = StpClient::connect(addr)?;
+        Ok(Self { stp })
+    }
+
+    fn send(&mut self, command: HouseCommand) -> Result<String, RequestError> {
+        self.stp
+            .send_request(encode_house_request(HouseRequest(command)))
+    }
+
+    /// Список сцен, по одной в строке
+    pub fn scenes(&mut self) -> Result<String, RequestError> {
+        self.send(HouseCommand::SceneList)
+    }
+
+    /// Применяем сцену, в ответе - результат по каждому девайсу
+    pub fn apply_scene(&mut self, name: &str) -> Result<String, RequestError> {
+        self.send(HouseCommand::SceneApply(name.to_owned()))
+    }
+
+    /// Запоминаем текущее состояние девайсов как сцену
+    pub fn capture_scene(&mut self, name: &str, devices: &[&str]) -> Result<String, RequestError> {
+        self.send(HouseCommand::SceneCapture {
+            name: name.to_owned(),
+            devices: devices.iter().map(|d| (*d).to_owned()).collect(),
+        })
+    }
+
+    pub fn delete_scene(&mut self, name: &str) -> Result<String, RequestError> {
+        self.send(HouseCommand::SceneDelete(name.to_owned()))
+    }
+
+    /// Включаем или выключаем розетку, превышения бюджетов - строками `warning: ...`
+    pub fn switch_socket(&mut self, device: &str, on: bool) -> Result<String, RequestError> {
+        self.send(HouseCommand::Switch {
+            device: device.to_owned(),
+            on,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smart_devices::{
+        device::{Device, SmartLight, SmartSocket},
+        power::{BudgetPolicy, PowerBudget},
+    };
+    use std::collections::HashMap;
+
+    fn house() -> TcpSmartHouse {
+        TcpSmartHouse::new(SmartHouse::new(
+            "tcp house",
+            HashMap::from([(
+                "living room",
+                vec![
+                    Box::new(SmartSocket::new("tv", "tv socket", true, 120.0)) as Box<dyn Device>,
+                    Box::new(SmartLight::new("lamp", "floor lamp", true, 100)),
+                ],
+            )]),
+        ))
+    }
+
+    fn send(house: &mut TcpSmartHouse, command: HouseCommand) -> String {
+        let request = decode_house_request(&encode_house_request(HouseRequest(command)));
+        house.handle(request.expect("request round trip")).0
+    }
+
+    #[test]
+    fn serve_scenes() {
+        let mut house = house();
+        assert_eq!("no scenes", send(&mut house, HouseCommand::SceneList));
+
+        let response = send(
+            &mut house,
+            HouseCommand::SceneCapture {
+                name: "movie night".to_owned(),
+                devices: vec!["tv".to_owned(), "living room/lamp".to_owned()],
+            },
+        );
+        assert_eq!(
+            "scene captured\nmovie night: living room/tv on; living room/lamp on, 100 %",
+            response
+        );
+
+        let _ = house.house.update_device("living room", "tv", |d| {
+            d.as_any_mut()
+                .downcast_mut::<SmartSocket>()
+                .unwrap()
+                .turn_off()
+        });
+        assert_eq!(
+            "scene \"movie night\" applied\nliving room/tv: changed\nliving room/lamp: unchanged",
+            send(
+                &mut house,
+                HouseCommand::SceneApply("movie night".to_owned())
+            )
+        );
+        assert!(house.house().socket("living room", "tv").unwrap().is_on());
+
+        assert_eq!(
+            "scene \"movie night\" deleted",
+            send(
+                &mut house,
+                HouseCommand::SceneDelete("movie night".to_owned())
+            )
+        );
+        assert_eq!(
+            "error: scene \"movie night\" not found",
+            send(
+                &mut house,
+                HouseCommand::SceneApply("movie night".to_owned())
+            )
+        );
+    }
+
+    #[test]
+    fn serve_switch() {
+        let mut house = house();
+        house
+            .house
+            .set_power_budget(
+                Some("living room"),
+                Some(PowerBudget::new(200.0, BudgetPolicy::Refuse)),
+            )
+            .unwrap();
+        house
+            .house
+            .add_device(
+                "living room",
+                Box::new(SmartSocket::new("heater", "", false, 1000.0)),
+            )
+            .unwrap();
+        let switch = |device: &str, on| HouseCommand::Switch {
+            device: device.to_owned(),
+            on,
+        };
+
+        assert_eq!(
+            "error: power budget of room \"living room\" exceeded: 1120 W of 200 W",
+            send(&mut house, switch("living room/heater", true))
+        );
+        assert_eq!(
+            "socket \"tv\" turned off",
+            send(&mut house, switch("tv", false))
+        );
+
+        house
+            .house
+            .set_power_budget(
+                Some("living room"),
+                Some(PowerBudget::new(200.0, BudgetPolicy::Warn)),
+            )
+            .unwrap();
+        assert_eq!(
+            "socket \"living room/heater\" turned on\nwarning: room \"living room\" draws 1000 W of 200 W",
+            send(&mut house, switch("living room/heater", true))
+        );
+        assert_eq!(
+            "error: device \"living room/lamp\" is not a socket",
+            send(&mut house, switch("living room/lamp", true))
+        );
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert_eq!(None, decode_house_request("scene apply"));
+        assert_eq!(None, decode_house_request("scene capture\nonly name"));
+        assert_eq!(None, decode_house_request("socket on"));
+        assert_eq!(None, decode_house_request("dance"));
+    }
+}
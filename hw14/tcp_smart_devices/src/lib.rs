@@ -1,9 +1,13 @@
-use smart_devices::device::SmartSocket;
+use smart_devices::{
+    device::{Device, SmartSocket},
+    events::EventBus,
+};
 use std::net::ToSocketAddrs;
 use stp::error::{ConnectError, RequestError};
 use stp::{client::StpClient, server::StpServer};
 
 pub mod asnc;
+pub mod house;
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
@@ -48,12 +52,32 @@ pub fn decode_response(response: &str) -> Response {
 
 pub struct TcpSmartSocket {
     socket: SmartSocket,
+    events: Option<EventBus>,
 }
 
 impl TcpSmartSocket {
     pub fn new(name: &str, description: &str, is_on: bool, current_power: f64) -> Self {
         Self {
             socket: SmartSocket::new(name, description, is_on, current_power),
+            events: None,
+        }
+    }
+
+    /// Публикуем включение и выключение розетки в шину
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn switch(&mut self, on: bool) {
+        let old = self.socket.state();
+        if on {
+            self.socket.turn_on();
+        } else {
+            self.socket.turn_off();
+        }
+        if let Some(events) = &self.events {
+            events.publish_state_change(None, &self.socket, old);
         }
     }
 }
@@ -70,11 +94,11 @@ impl Server for TcpSmartSocket {
     fn handle(&mut self, request: Request) -> Response {
         Response(match request.0 {
             Command::SmartSocketOn => {
-                self.socket.turn_on();
+                self.switch(true);
                 format!("{}", self.socket)
             }
             Command::SmartSocketOff => {
-                self.socket.turn_off();
+                self.switch(false);
                 format!("{}", self.socket)
             }
             Command::SmartSocketInfo => {
@@ -173,7 +197,7 @@ mod tests {
             Response(
                 r#"Name: tcp_smart_socket
 Description: this is smart socket works by tcp protocol
-Current state: on, 220 Volts"#
+Current state: on, 220 W, 230 V, 0.96 A, 0.000 kWh"#
                     .to_owned()
             ),
             result
@@ -195,7 +219,7 @@ Current state: on, 220 Volts"#
             Response(
                 r#"Name: tcp_smart_socket
 Description: this is smart socket works by tcp protocol
-Current state: off, 230 Volts"#
+Current state: off, 0 W, 230 V, 0.00 A, 0.000 kWh"#
                     .to_owned()
             ),
             result
@@ -217,7 +241,7 @@ Current state: off, 230 Volts"#
             Response(
                 r#"Name: tcp_smart_socket
 Description: this is smart socket works by tcp protocol
-Current state: on, 233.3 Volts"#
+Current state: on, 233.3 W, 230 V, 1.01 A, 0.000 kWh"#
                     .to_owned()
             ),
             result
@@ -240,4 +264,25 @@ Current state: on, 233.3 Volts"#
         let result = tcp_smart_socket.handle(Request(Command::SmartSocketState));
         assert_eq!(Response("off".to_owned()), result);
     }
+
+    #[test]
+    fn serve_publishes_events() {
+        use smart_devices::events::{Event, EventFilter};
+
+        let events = EventBus::new();
+        let subscription = events.subscribe_queue(EventFilter::all().device("tcp_smart_socket"));
+        let mut tcp_smart_socket =
+            TcpSmartSocket::new("tcp_smart_socket", "", false, 100.0).with_events(events);
+
+        let _ = tcp_smart_socket.handle(Request(Command::SmartSocketOn));
+        let _ = tcp_smart_socket.handle(Request(Command::SmartSocketOn));
+        let _ = tcp_smart_socket.handle(Request(Command::SmartSocketInfo));
+
+        let events = subscription.drain();
+        assert_eq!(1, events.len());
+        assert!(matches!(
+            &events[0],
+            Event::DeviceStateChanged { room: None, new, .. } if new.kind() == smart_devices::device::DeviceKind::Socket
+        ));
+    }
 }
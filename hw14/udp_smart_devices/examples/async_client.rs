@@ -1,20 +1,49 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+
+use smart_devices::{
+    device::SmartThermometer,
+    simulation::{OutdoorTemperature, Simulation},
+    SmartHouse,
+};
 
 use udp_smart_devices::asnc::UdpSmartThermometerClient;
 
 const RECIEVER_ADDR: &str = "127.0.0.1:55331";
 const BIND_ADDR: &str = "127.0.0.1:55330";
 
+/// Комната с термометром, остывающая к суточной уличной температуре
+fn simulated_room() -> Simulation {
+    let mut house = SmartHouse::new_empty("simulated house");
+    house.add_room("room").expect("new room");
+    house
+        .add_device("room", Box::new(SmartThermometer::new("thermo", "", 20.2)))
+        .expect("new device");
+
+    Simulation::new(house, SystemTime::now(), 42)
+        .with_step(Duration::from_secs(10 * 60))
+        .with_outdoor(OutdoorTemperature::daily(10.4, 6.0))
+        .with_sensor_noise(0.2)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = UdpSmartThermometerClient::new(BIND_ADDR, RECIEVER_ADDR).await?;
 
-    let mut temperature = 10.4;
-    for i in 0..300 {
-        temperature += 0.2 * i as f64;
+    // Показания берем из симуляции комнаты: каждую секунду проходит 10 минут
+    let mut simulation = simulated_room();
+    for _ in 0..300 {
+        simulation.step();
+        let temperature = simulation
+            .house()
+            .thermometer("room", "thermo")
+            .map(|thermo| thermo.current_temperature())
+            .unwrap_or_default();
         match client.send_temperature(temperature).await {
-            Ok(_) => println!("successfully send temperature {}", temperature),
-            Err(e) => println!("failure send temperature {}, cause of {}", temperature, e),
+            Ok(_) => println!("successfully send temperature {:.2}", temperature),
+            Err(e) => println!(
+                "failure send temperature {:.2}, cause of {}",
+                temperature, e
+            ),
         };
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
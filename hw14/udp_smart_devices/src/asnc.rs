@@ -1,4 +1,7 @@
-use smart_devices::device::SmartThermometer;
+use smart_devices::{
+    device::{Device, SmartThermometer},
+    events::EventBus,
+};
 use std::future::Future;
 use std::{
     sync::{
@@ -18,6 +21,7 @@ type AMutex<T> = Arc<Mutex<T>>;
 pub struct StreamingSmartThermometer {
     thermometer: AMutex<SmartThermometer>,
     finished: Arc<AtomicBool>,
+    events: Option<EventBus>,
 }
 
 impl StreamingSmartThermometer {
@@ -29,9 +33,16 @@ impl StreamingSmartThermometer {
                 current_temperature,
             ))),
             finished: Arc::new(AtomicBool::new(false)),
+            events: None,
         }
     }
 
+    /// Публикуем изменения температуры в шину
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     pub async fn current_temperature(&self) -> f64 {
         self.thermometer.lock().await.current_temperature()
     }
@@ -60,6 +71,7 @@ impl Server for StreamingSmartThermometer {
     ) -> std::io::Result<()> {
         let finished = self.finished.clone();
         let thermometer = self.thermometer.clone();
+        let events = self.events.clone();
         tokio::spawn(async move {
             loop {
                 if finished.load(Ordering::SeqCst) {
@@ -80,7 +92,16 @@ impl Server for StreamingSmartThermometer {
                     Ok(_) => {}
                 }
                 let val = f64::from_be_bytes(buf);
-                thermometer.lock().await.set_temperature(val);
+                let (name, old, new) = {
+                    let mut thermometer = thermometer.lock().await;
+                    let old = thermometer.state();
+                    thermometer.set_temperature(val);
+                    (thermometer.name().to_owned(), old, thermometer.state())
+                };
+                // Публикуем без блокировки: обработчик может читать термометр
+                if let Some(events) = &events {
+                    events.publish_change(None, &name, old, new);
+                }
             }
         });
 
@@ -1,4 +1,7 @@
-use smart_devices::device::SmartThermometer;
+use smart_devices::{
+    device::{history::ReadingStats, Device, SmartThermometer},
+    events::EventBus,
+};
 use std::{
     net::{ToSocketAddrs, UdpSocket},
     sync::{
@@ -16,6 +19,7 @@ type AMutex<T> = Arc<Mutex<T>>;
 pub struct StreamingSmartThermometer {
     thermometer: AMutex<SmartThermometer>,
     finished: Arc<AtomicBool>,
+    events: Option<EventBus>,
 }
 
 impl StreamingSmartThermometer {
@@ -27,12 +31,24 @@ impl StreamingSmartThermometer {
                 current_temperature,
             ))),
             finished: Arc::new(AtomicBool::new(false)),
+            events: None,
         }
     }
 
+    /// Публикуем изменения температуры в шину
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     pub fn current_temperature(&self) -> f64 {
         self.thermometer.lock().unwrap().current_temperature()
     }
+
+    /// Статистика принятых показаний за последние `window`
+    pub fn stats_over(&self, window: Duration) -> Option<ReadingStats> {
+        self.thermometer.lock().unwrap().stats_over(window)
+    }
 }
 
 pub trait Streaming {
@@ -55,6 +71,7 @@ impl Server for StreamingSmartThermometer {
 
         let finished = self.finished.clone();
         let thermometer = self.thermometer.clone();
+        let events = self.events.clone();
         thread::spawn(move || loop {
             if finished.load(Ordering::SeqCst) {
                 return;
@@ -72,7 +89,16 @@ impl Server for StreamingSmartThermometer {
                 Ok(_) => {}
             }
             let val = f64::from_be_bytes(buf);
-            thermometer.lock().unwrap().set_temperature(val);
+            let (name, old, new) = {
+                let mut thermometer = thermometer.lock().unwrap();
+                let old = thermometer.state();
+                thermometer.set_temperature(val);
+                (thermometer.name().to_owned(), old, thermometer.state())
+            };
+            // Публикуем без блокировки: обработчик может читать термометр
+            if let Some(events) = &events {
+                events.publish_change(None, &name, old, new);
+            }
         });
 
         Ok(())
@@ -108,10 +134,19 @@ impl UdpSmartThermometer {
         ))
     }
 
+    /// Публикуем изменения температуры в шину
+    pub fn with_events(self, events: EventBus) -> Self {
+        Self(self.0.with_events(events))
+    }
+
     pub fn current_temperature(&self) -> f64 {
         self.0.current_temperature()
     }
 
+    pub fn stats_over(&self, window: Duration) -> Option<ReadingStats> {
+        self.0.stats_over(window)
+    }
+
     pub fn run<A: ToSocketAddrs>(
         &self,
         address: A,
@@ -150,6 +185,7 @@ impl<A: ToSocketAddrs> UdpSmartThermometerClient<A> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use smart_devices::events::{Event, EventFilter};
     use std::sync::mpsc::{self, Receiver};
 
     struct TestStreaming {
@@ -172,7 +208,10 @@ mod tests {
         let (tx, rx) = mpsc::channel();
         let streaming = TestStreaming { receiver: rx };
 
-        let thermo = StreamingSmartThermometer::new("test name", "test description", 32.0);
+        let events = EventBus::new();
+        let subscription = events.subscribe_queue(EventFilter::all());
+        let thermo = StreamingSmartThermometer::new("test name", "test description", 32.0)
+            .with_events(events);
         let result = thermo.run(streaming, Duration::from_secs(1));
         assert!(result.is_ok());
 
@@ -191,5 +230,38 @@ mod tests {
         thread::sleep(Duration::from_millis(100));
 
         assert_eq!(11.5, thermo.current_temperature());
+
+        let stats = thermo.stats_over(Duration::from_secs(60)).unwrap();
+        assert_eq!(3, stats.count);
+        assert_eq!(11.5, stats.min.as_celsius());
+        assert_eq!(32.0, stats.max.as_celsius());
+
+        let events = subscription.drain();
+        assert_eq!(2, events.len());
+        assert!(events.iter().all(
+            |e| matches!(e, Event::DeviceStateChanged { device, .. } if device == "test name")
+        ));
+    }
+
+    #[test]
+    fn test_handler_reads_thermometer() {
+        let (tx, rx) = mpsc::channel();
+        let streaming = TestStreaming { receiver: rx };
+
+        let events = EventBus::new();
+        let thermo =
+            StreamingSmartThermometer::new("test name", "", 32.0).with_events(events.clone());
+        // Обработчик вызывается в потоке приема и читает тот же термометр
+        let thermometer = thermo.thermometer.clone();
+        let (seen_tx, seen_rx) = mpsc::channel();
+        let seen_tx = Mutex::new(seen_tx);
+        events.subscribe(EventFilter::all(), move |_| {
+            let temperature = thermometer.lock().unwrap().current_temperature();
+            seen_tx.lock().unwrap().send(temperature).unwrap();
+        });
+        thermo.run(streaming, Duration::from_secs(1)).unwrap();
+
+        tx.send(20.5f64.to_be_bytes()).unwrap();
+        assert_eq!(Ok(20.5), seen_rx.recv_timeout(Duration::from_secs(1)));
     }
 }
@@ -35,9 +35,26 @@ get_rooms() {
     do_get
 }
 
+rename_room() {
+    url="rooms/rename"
+    data="{\"name\":\"$1\", \"new_name\":\"$2\"}"
+
+    do_post
+}
+
 add_room_device() {
+    local device_json
+    case "$3" in
+        thermometer)
+            device_json="{\"kind\":\"thermometer\", \"name\":\"$2\", \"description\":\"$2 description\", \"current_temperature\":${4:-20}, \"unit\":\"${5:-celsius}\"}"
+            ;;
+        *)
+            device_json="{\"kind\":\"socket\", \"name\":\"$2\", \"description\":\"$2 description\", \"is_on\":false, \"current_power\":${4:-220}}"
+            ;;
+    esac
+
     url="room/devices/add"
-    data="{\"room\":\"$1\", \"device\":\"$2\"}"
+    data="{\"room\":\"$1\", \"device\":${device_json}}"
 
     do_post
 }
@@ -49,6 +66,27 @@ delete_room_device() {
     do_post
 }
 
+move_room_device() {
+    url="room/devices/move"
+    data="{\"from\":\"$1\", \"to\":\"$2\", \"device\":\"$3\"}"
+
+    do_post
+}
+
+rename_room_device() {
+    url="room/devices/rename"
+    data="{\"room\":\"$1\", \"device\":\"$2\", \"new_name\":\"$3\"}"
+
+    do_post
+}
+
+set_device_name_policy() {
+    url="house/device_names"
+    data="{\"policy\":\"$1\"}"
+
+    do_post
+}
+
 get_room_devices() {
     url="room/devices"
     data="{\"room\":\"$1\"}"
@@ -75,6 +113,252 @@ get_report() {
     do_get
 }
 
+get_house_report() {
+    curl -X GET --location "http://localhost:8080/house/report?format=${1:-json}${2:+&unit=$2}${3:+&tags=$3}"
+}
+
+set_temperature_unit() {
+    url="house/temperature_unit"
+    data="{\"unit\":\"$1\"}"
+
+    do_post
+}
+
+get_rules() {
+    url="house/rules"
+    do_get
+}
+
+add_rule() {
+    local text="${1//\\/\\\\}"
+    url="house/rules/add"
+    data="{\"text\":\"${text//\"/\\\"}\"}"
+
+    do_post
+}
+
+delete_rule() {
+    url="house/rules/delete"
+    data="{\"name\":\"$1\"}"
+
+    do_post
+}
+
+dry_run_rules() {
+    url="house/rules/dry_run"
+    do_get
+}
+
+get_schedule() {
+    curl -X GET --location "http://localhost:8080/house/schedule${1:+?upcoming=$1}" | jq .
+}
+
+add_job() {
+    local duration_json=""
+    if [ -n "$6" ]; then
+        duration_json=", \"duration\":\"$6\""
+    fi
+
+    url="house/schedule/add"
+    data="{\"name\":\"$1\", \"device\":\"$2\", \"switch\":\"$3\", \"when\":{\"repeat\":{\"cron\":\"$4\", \"time_zone\":\"${5:-UTC}\"}}${duration_json}}"
+
+    do_post
+}
+
+delete_job() {
+    url="house/schedule/delete"
+    data="{\"name\":\"$1\"}"
+
+    do_post
+}
+
+get_scenes() {
+    url="house/scenes"
+    do_get
+}
+
+# capture_scene NAME DEVICE...
+capture_scene() {
+    local name="$1"
+    shift
+    local devices=""
+    for device in "$@"; do
+        devices="${devices:+$devices, }\"$device\""
+    done
+
+    url="house/scenes/capture"
+    data="{\"name\":\"$name\", \"devices\":[${devices}]}"
+
+    do_post
+}
+
+delete_scene() {
+    url="house/scenes/delete"
+    data="{\"name\":\"$1\"}"
+
+    do_post
+}
+
+apply_scene() {
+    url="house/scenes/apply"
+    data="{\"name\":\"$1\"}"
+
+    do_post
+}
+
+get_tags() {
+    url="house/tags"
+    do_get
+}
+
+add_device_tag() {
+    url="room/devices/tags/add"
+    data="{\"room\":\"$1\", \"device\":\"$2\", \"tag\":\"$3\"}"
+
+    do_post
+}
+
+delete_device_tag() {
+    url="room/devices/tags/delete"
+    data="{\"room\":\"$1\", \"device\":\"$2\", \"tag\":\"$3\"}"
+
+    do_post
+}
+
+# get_tagged TAGS [KIND], например get_tagged 'heating,!critical' socket
+get_tagged() {
+    curl -X GET --location "http://localhost:8080/house/tagged?tags=$1${2:+&kind=$2}" | jq .
+}
+
+# search_devices QUERY, например search_devices 'kind=socket&on=true&name=heat*'
+search_devices() {
+    curl -X GET --location "http://localhost:8080/house/devices/search?$1" | jq .
+}
+
+# apply_tagged ACTION TAGS [KIND], например apply_tagged turn_off heating socket
+apply_tagged() {
+    local kind_json=""
+    if [ -n "$3" ]; then
+        kind_json=", \"kind\":\"$3\""
+    fi
+
+    url="house/tagged/apply"
+    data="{\"action\":\"$1\", \"tags\":\"$2\"${kind_json}}"
+
+    do_post
+}
+
+# get_events [AFTER] [WAIT], например get_events 12 30 - ждать новые события до 30 секунд
+get_events() {
+    curl -X GET --location "http://localhost:8080/house/events?after=${1:-0}&wait=${2:-0}" | jq .
+}
+
+get_power() {
+    url="house/power"
+    do_get
+}
+
+# set_power_budget [ROOM] [LIMIT] [POLICY], например set_power_budget kitchen 3000 shed;
+# без ROOM - бюджет всего дома, без LIMIT - снять бюджет
+set_power_budget() {
+    local room_json="null"
+    if [ -n "$1" ]; then
+        room_json="\"$1\""
+    fi
+
+    url="house/power/budget"
+    data="{\"room\":${room_json}, \"limit\":${2:-null}, \"policy\":\"${3:-refuse}\"}"
+
+    do_post
+}
+
+# set_load_priority DEVICE [PRIORITY], например set_load_priority kitchen/kettle 5
+set_load_priority() {
+    url="house/power/priority"
+    data="{\"device\":\"$1\", \"priority\":${2:-null}}"
+
+    do_post
+}
+
+# switch_socket DEVICE on|off, например switch_socket kitchen/kettle on
+switch_socket() {
+    local on="false"
+    if [ "$2" = "on" ]; then
+        on="true"
+    fi
+
+    url="house/devices/switch"
+    data="{\"device\":\"$1\", \"on\":${on}}"
+
+    do_post
+}
+
+get_alarms() {
+    url="house/alarms"
+    do_get
+}
+
+# add_alarm NAME DEVICE THRESHOLD VALUE [SEVERITY], например
+# add_alarm 'too hot' kitchen/thermo temperature_above 30 critical
+# add_alarm 'iron left on' iron on_longer_than '"30m"'
+add_alarm() {
+    url="house/alarms/add"
+    data="{\"name\":\"$1\", \"device\":\"$2\", \"threshold\":{\"$3\":$4}, \"severity\":\"${5:-warning}\"}"
+
+    do_post
+}
+
+delete_alarm() {
+    url="house/alarms/delete"
+    data="{\"name\":\"$1\"}"
+
+    do_post
+}
+
+# get_alerts [AFTER] - тревоги после номера AFTER
+get_alerts() {
+    curl -X GET --location "http://localhost:8080/house/alerts?after=${1:-0}" | jq .
+}
+
+# diff_house FILE - разница с домом из файла: JSON-файл дома или текстовое описание
+diff_house() {
+    url="house/diff"
+    if [[ "$1" == *.json ]]; then
+        data="$(cat "$1")"
+    else
+        data="$(jq -Rs '{text: .}' < "$1")"
+    fi
+
+    do_post
+}
+
+# apply_diff FILE - приводим дом к дому из файла, конфликты перечислит ответ
+apply_diff() {
+    url="house/diff/apply"
+    data="$(diff_house "$1" | jq '{changes}')"
+
+    do_post
+}
+
+get_journal() {
+    url="house/journal"
+    do_get
+}
+
+undo() {
+    url="house/undo"
+    data="{}"
+
+    do_post
+}
+
+redo() {
+    url="house/redo"
+    data="{}"
+
+    do_post
+}
+
 demo() {
     echo "== adding Room 1 =="
     add_room "Room 1" ; echo 
@@ -95,10 +379,10 @@ demo() {
     add_room_device "Room 2" "Socket 4" ; echo 
 
     echo "== adding Thermometer 1 to Room 2 =="
-    add_room_device "Room 2" "Thermometer 1" ; echo
+    add_room_device "Room 2" "Thermometer 1" thermometer 25 ; echo
 
     echo "== adding Thermometer 2 to Room 2 =="
-    add_room_device "Room 2" "Thermometer 2" ; echo
+    add_room_device "Room 2" "Thermometer 2" thermometer ; echo
 
     echo "== getting rooms =="
     get_rooms ; echo
@@ -123,8 +407,11 @@ case "$1" in
     get_rooms)
         get_rooms
         ;;
+    rename_room)
+        rename_room "$2" "$3"
+        ;;
     add_room_device)
-        add_room_device "$2" "$3"
+        add_room_device "$2" "$3" "$4" "$5" "$6"
         ;;
     delete_room_device)
         delete_room_device "$2" "$3"
@@ -132,9 +419,117 @@ case "$1" in
     get_room_devices)
         get_room_devices "$2"
         ;;
+    move_room_device)
+        move_room_device "$2" "$3" "$4"
+        ;;
+    rename_room_device)
+        rename_room_device "$2" "$3" "$4"
+        ;;
+    set_device_name_policy)
+        set_device_name_policy "$2"
+        ;;
     get_report)
         get_report "$2" "$3" "$4" "$5" "$6" "$7" "$8"
         ;;
+    get_house_report)
+        get_house_report "$2" "$3" "$4"
+        ;;
+    set_temperature_unit)
+        set_temperature_unit "$2"
+        ;;
+    get_rules)
+        get_rules
+        ;;
+    add_rule)
+        add_rule "$2"
+        ;;
+    delete_rule)
+        delete_rule "$2"
+        ;;
+    dry_run_rules)
+        dry_run_rules
+        ;;
+    get_schedule)
+        get_schedule "$2"
+        ;;
+    add_job)
+        add_job "$2" "$3" "$4" "$5" "$6" "$7"
+        ;;
+    delete_job)
+        delete_job "$2"
+        ;;
+    get_scenes)
+        get_scenes
+        ;;
+    capture_scene)
+        capture_scene "${@:2}"
+        ;;
+    delete_scene)
+        delete_scene "$2"
+        ;;
+    apply_scene)
+        apply_scene "$2"
+        ;;
+    get_tags)
+        get_tags
+        ;;
+    add_device_tag)
+        add_device_tag "$2" "$3" "$4"
+        ;;
+    delete_device_tag)
+        delete_device_tag "$2" "$3" "$4"
+        ;;
+    get_tagged)
+        get_tagged "$2" "$3"
+        ;;
+    apply_tagged)
+        apply_tagged "$2" "$3" "$4"
+        ;;
+    get_events)
+        get_events "$2" "$3"
+        ;;
+    search_devices)
+        search_devices "$2"
+        ;;
+    get_power)
+        get_power
+        ;;
+    set_power_budget)
+        set_power_budget "$2" "$3" "$4"
+        ;;
+    set_load_priority)
+        set_load_priority "$2" "$3"
+        ;;
+    switch_socket)
+        switch_socket "$2" "$3"
+        ;;
+    get_alarms)
+        get_alarms
+        ;;
+    add_alarm)
+        add_alarm "$2" "$3" "$4" "$5" "$6"
+        ;;
+    delete_alarm)
+        delete_alarm "$2"
+        ;;
+    get_alerts)
+        get_alerts "$2"
+        ;;
+    diff_house)
+        diff_house "$2"
+        ;;
+    apply_diff)
+        apply_diff "$2"
+        ;;
+    get_journal)
+        get_journal
+        ;;
+    undo)
+        undo
+        ;;
+    redo)
+        redo
+        ;;
     demo)
         demo
         ;;
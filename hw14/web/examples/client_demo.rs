@@ -40,9 +40,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     print!("adding Socket 1 to Room 1: ");
     let resp = client
         .post(format!("{}{}", base_url, "/room/devices/add"))
-        .json(&dto::RoomDeviceRequest {
-            device: "Socket 1".to_owned(),
+        .json(&dto::AddRoomDeviceRequest {
             room: "Room 1".to_owned(),
+            device: dto::DeviceModel::Socket(dto::SocketModel {
+                name: "Socket 1".to_owned(),
+                description: "Socket 1 description".to_owned(),
+                is_on: false,
+                current_power: 220.2,
+            }),
         })
         .send()
         .await?;
@@ -57,9 +62,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     print!("adding Socket 2 to Room 1: ");
     let resp = client
         .post(format!("{}{}", base_url, "/room/devices/add"))
-        .json(&dto::RoomDeviceRequest {
-            device: "Socket 2".to_owned(),
+        .json(&dto::AddRoomDeviceRequest {
             room: "Room 1".to_owned(),
+            device: dto::DeviceModel::Socket(dto::SocketModel {
+                name: "Socket 2".to_owned(),
+                description: "Socket 2 description".to_owned(),
+                is_on: false,
+                current_power: 220.2,
+            }),
         })
         .send()
         .await?;
@@ -74,9 +84,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     print!("adding Socket 3 to Room 2: ");
     let resp = client
         .post(format!("{}{}", base_url, "/room/devices/add"))
-        .json(&dto::RoomDeviceRequest {
-            device: "Socket 3".to_owned(),
+        .json(&dto::AddRoomDeviceRequest {
             room: "Room 2".to_owned(),
+            device: dto::DeviceModel::Socket(dto::SocketModel {
+                name: "Socket 3".to_owned(),
+                description: "Socket 3 description".to_owned(),
+                is_on: false,
+                current_power: 220.2,
+            }),
         })
         .send()
         .await?;
@@ -91,9 +106,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     print!("adding Socket 4 to Room 2: ");
     let resp = client
         .post(format!("{}{}", base_url, "/room/devices/add"))
-        .json(&dto::RoomDeviceRequest {
-            device: "Socket 4".to_owned(),
+        .json(&dto::AddRoomDeviceRequest {
             room: "Room 2".to_owned(),
+            device: dto::DeviceModel::Socket(dto::SocketModel {
+                name: "Socket 4".to_owned(),
+                description: "Socket 4 description".to_owned(),
+                is_on: false,
+                current_power: 220.2,
+            }),
         })
         .send()
         .await?;
@@ -108,9 +128,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     print!("adding Thermometer 1 to Room 2: ");
     let resp = client
         .post(format!("{}{}", base_url, "/room/devices/add"))
-        .json(&dto::RoomDeviceRequest {
-            device: "Thermometer 1".to_owned(),
+        .json(&dto::AddRoomDeviceRequest {
             room: "Room 2".to_owned(),
+            device: dto::DeviceModel::Thermometer(dto::ThermometerModel {
+                name: "Thermometer 1".to_owned(),
+                description: "Thermometer 1 description".to_owned(),
+                current_temperature: 25.0,
+                unit: Default::default(),
+                humidity: None,
+            }),
         })
         .send()
         .await?;
@@ -125,9 +151,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     print!("adding Thermometer 2 to Room 2: ");
     let resp = client
         .post(format!("{}{}", base_url, "/room/devices/add"))
-        .json(&dto::RoomDeviceRequest {
-            device: "Thermometer 2".to_owned(),
+        .json(&dto::AddRoomDeviceRequest {
             room: "Room 2".to_owned(),
+            device: dto::DeviceModel::Thermometer(dto::ThermometerModel {
+                name: "Thermometer 2".to_owned(),
+                description: "Thermometer 2 description".to_owned(),
+                current_temperature: 25.0,
+                unit: Default::default(),
+                humidity: None,
+            }),
         })
         .send()
         .await?;
@@ -208,6 +240,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 name: "Thermometer 1".to_owned(),
                 description: "Thermometer 1 description".to_owned(),
                 current_temperature: 25.0,
+                unit: Default::default(),
+                humidity: None,
             },
         })
         .send()
@@ -0,0 +1,53 @@
+use smart_devices::alarms::{Alert, AlertSink};
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Сколько последних тревог помнит веб-слой
+pub const ALERT_LOG_CAPACITY: usize = 1000;
+
+/// Последние тревоги дома с номерами, по которым клиент забирает только новые.
+/// Подключается к дому как получатель тревог.
+#[derive(Default)]
+pub struct AlertLog {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    last_id: u64,
+    recent: VecDeque<(u64, Alert)>,
+}
+
+impl AlertLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Номер последней тревоги, 0 - тревог еще не было
+    pub fn last_id(&self) -> u64 {
+        self.inner.lock().unwrap().last_id
+    }
+
+    /// Тревоги после `after`
+    pub fn since(&self, after: u64) -> Vec<(u64, Alert)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .recent
+            .iter()
+            .filter(|(id, _)| *id > after)
+            .cloned()
+            .collect()
+    }
+}
+
+impl AlertSink for AlertLog {
+    fn send(&self, alert: &Alert) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.recent.len() == ALERT_LOG_CAPACITY {
+            inner.recent.pop_front();
+        }
+        inner.last_id += 1;
+        let id = inner.last_id;
+        inner.recent.push_back((id, alert.clone()));
+    }
+}
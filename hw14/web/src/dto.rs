@@ -1,6 +1,23 @@
-use std::str;
+use std::{collections::BTreeMap, str};
 
 use serde::{Deserialize, Serialize};
+use smart_devices::{
+    alarms::{Alarm, Alert},
+    device::{
+        temperature::{Temperature, TemperatureUnit},
+        Device, DeviceKind, DeviceState, SmartSocket, SmartThermometer,
+    },
+    diff::HouseDiff,
+    events::Event,
+    journal::Operation,
+    power::{BudgetPolicy, Overload, PowerBudget},
+    query::DeviceMatch,
+    rules::{Action, Firing, Rule},
+    scene::{Scene, SceneReport, TargetStatus},
+    schedule::{ScheduledAction, ScheduledJob},
+    tags::BulkResult,
+    DeviceNamePolicy,
+};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RoomRequest {
@@ -20,12 +37,38 @@ pub struct RoomsListResponse {
     pub rooms: Vec<String>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RenameRoomRequest {
+    pub name: String,
+    pub new_name: String,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RoomDeviceRequest {
     pub room: String,
     pub device: String,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveRoomDeviceRequest {
+    pub from: String,
+    pub to: String,
+    pub device: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RenameRoomDeviceRequest {
+    pub room: String,
+    pub device: String,
+    pub new_name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AddRoomDeviceRequest {
+    pub room: String,
+    pub device: DeviceModel,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RoomDeviceResponse {
     pub house_name: String,
@@ -57,7 +100,47 @@ pub struct SocketModel {
 pub struct ThermometerModel {
     pub name: String,
     pub description: String,
+    /// Температура в единицах `unit`
     pub current_temperature: f64,
+    /// По умолчанию градусы Цельсия
+    #[serde(default)]
+    pub unit: TemperatureUnit,
+    /// Относительная влажность, %
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<f64>,
+}
+
+impl From<&ThermometerModel> for SmartThermometer {
+    fn from(model: &ThermometerModel) -> Self {
+        let mut thermometer = SmartThermometer::new(
+            &model.name,
+            &model.description,
+            Temperature::new(model.current_temperature, model.unit).as_celsius(),
+        );
+        thermometer.set_humidity(model.humidity);
+        thermometer
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeviceModel {
+    Socket(SocketModel),
+    Thermometer(ThermometerModel),
+}
+
+impl From<DeviceModel> for Box<dyn Device> {
+    fn from(model: DeviceModel) -> Self {
+        match model {
+            DeviceModel::Socket(socket) => Box::new(SmartSocket::new(
+                &socket.name,
+                &socket.description,
+                socket.is_on,
+                socket.current_power,
+            )),
+            DeviceModel::Thermometer(thermometer) => Box::new(SmartThermometer::from(&thermometer)),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -73,3 +156,424 @@ pub enum ReportResponse {
     #[serde(rename = "error")]
     Error(String),
 }
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HouseReportQuery {
+    /// text, json, markdown, html или csv; по умолчанию json
+    pub format: Option<String>,
+    /// c, f или k; по умолчанию единицы, выбранные для дома
+    pub unit: Option<String>,
+    /// Только девайсы с тегами, например `heating,!critical`
+    pub tags: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemperatureUnitModel {
+    pub unit: TemperatureUnit,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceNamePolicyModel {
+    /// per_room или house_wide
+    pub policy: DeviceNamePolicy,
+}
+
+/// Правило в текстовом формате (`{"text": "rule ..."}`) или в JSON
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AddRuleRequest {
+    Text { text: String },
+    Rule(Rule),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RuleRequest {
+    pub name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RulesListResponse {
+    pub rules: Vec<Rule>,
+    /// Те же правила в текстовом формате
+    pub text: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FiringModel {
+    pub rule: String,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<&Firing> for FiringModel {
+    fn from(firing: &Firing) -> Self {
+        Self {
+            rule: firing.rule.clone(),
+            action: smart_devices::rules::text::format_action(&firing.action),
+            error: firing.result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DryRunResponse {
+    pub firings: Vec<FiringModel>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduleQuery {
+    /// Сколько ближайших действий показать, по умолчанию 10
+    pub upcoming: Option<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduleResponse {
+    pub jobs: Vec<ScheduledJob>,
+    pub upcoming: Vec<ScheduledAction>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JobRequest {
+    pub name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneRequest {
+    pub name: String,
+}
+
+/// Сцена из текущего состояния девайсов: пути или уникальные имена
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CaptureSceneRequest {
+    pub name: String,
+    pub devices: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScenesListResponse {
+    pub scenes: Vec<Scene>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneDeviceModel {
+    pub device: String,
+    /// changed, unchanged, not_applied или failed
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneReportResponse {
+    pub scene: String,
+    pub applied: bool,
+    pub devices: Vec<SceneDeviceModel>,
+}
+
+impl From<&SceneReport> for SceneReportResponse {
+    fn from(report: &SceneReport) -> Self {
+        Self {
+            scene: report.scene.clone(),
+            applied: report.applied,
+            devices: report
+                .devices
+                .iter()
+                .map(|(device, status)| {
+                    let (status, error) = match status {
+                        TargetStatus::Changed => ("changed", None),
+                        TargetStatus::Unchanged => ("unchanged", None),
+                        TargetStatus::NotApplied => ("not_applied", None),
+                        TargetStatus::Failed(error) => ("failed", Some(error.to_string())),
+                    };
+                    SceneDeviceModel {
+                        device: device.clone(),
+                        status: status.to_owned(),
+                        error,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceTagRequest {
+    pub room: String,
+    pub device: String,
+    pub tag: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceTagsResponse {
+    pub room: String,
+    pub device: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TagsResponse {
+    /// Тег и число девайсов с ним
+    pub tags: BTreeMap<String, usize>,
+}
+
+/// Отбор девайсов по тегам: `tags=heating,!critical&kind=socket`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TaggedQuery {
+    #[serde(default)]
+    pub tags: String,
+    pub kind: Option<DeviceKind>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TaggedDevicesResponse {
+    pub devices: Vec<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    TurnOn,
+    TurnOff,
+    Lock,
+    Unlock,
+    Open,
+    Close,
+}
+
+impl BulkAction {
+    pub fn action(self) -> fn(String) -> Action {
+        match self {
+            BulkAction::TurnOn => Action::TurnOn,
+            BulkAction::TurnOff => Action::TurnOff,
+            BulkAction::Lock => Action::Lock,
+            BulkAction::Unlock => Action::Unlock,
+            BulkAction::Open => Action::Open,
+            BulkAction::Close => Action::Close,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BulkRequest {
+    #[serde(flatten)]
+    pub query: TaggedQuery,
+    pub action: BulkAction,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BulkResultModel {
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<&BulkResult> for BulkResultModel {
+    fn from(result: &BulkResult) -> Self {
+        Self {
+            device: result.action.device().to_owned(),
+            error: result.result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BulkResponse {
+    pub results: Vec<BulkResultModel>,
+}
+
+/// `after` - номер последнего полученного события, `wait` - сколько секунд ждать новых
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EventsQuery {
+    pub after: Option<u64>,
+    pub wait: Option<u64>,
+    pub room: Option<String>,
+    pub device: Option<String>,
+    pub kind: Option<DeviceKind>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EventModel {
+    pub id: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EventsResponse {
+    pub events: Vec<EventModel>,
+    /// С этого номера продолжать следующий запрос
+    pub last_id: u64,
+}
+
+/// Поиск девайсов: `kind=socket&room=floor2&name=heat*&on=true&tags=heating,!critical`.
+/// Температура задается в единицах `unit`, по умолчанию - в единицах дома.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceSearchQuery {
+    pub kind: Option<DeviceKind>,
+    pub room: Option<String>,
+    pub name: Option<String>,
+    pub on: Option<bool>,
+    pub min_temperature: Option<f64>,
+    pub max_temperature: Option<f64>,
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub tags: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FoundDeviceModel {
+    pub room: String,
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    #[serde(flatten)]
+    pub state: DeviceState,
+}
+
+impl From<&DeviceMatch<'_>> for FoundDeviceModel {
+    fn from(found: &DeviceMatch<'_>) -> Self {
+        Self {
+            room: found.room.clone(),
+            name: found.device.name().to_owned(),
+            description: found.device.description().to_owned(),
+            tags: found.tags().map(str::to_owned).collect(),
+            state: found.device.state(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceSearchResponse {
+    pub devices: Vec<FoundDeviceModel>,
+}
+
+/// Бюджет мощности расположения вместе с текущей нагрузкой, Вт
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoomPowerModel {
+    pub room: String,
+    pub load: f64,
+    #[serde(flatten)]
+    pub budget: PowerBudget,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PowerResponse {
+    pub load: f64,
+    pub budget: Option<PowerBudget>,
+    pub rooms: Vec<RoomPowerModel>,
+    /// Приоритеты розеток, у остальных - 0
+    pub priorities: BTreeMap<String, i32>,
+}
+
+/// Без `room` - бюджет всего дома, без `limit` - снять бюджет
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PowerBudgetRequest {
+    pub room: Option<String>,
+    pub limit: Option<f64>,
+    #[serde(default)]
+    pub policy: BudgetPolicy,
+}
+
+/// Без `priority` - вернуть приоритет по умолчанию
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LoadPriorityRequest {
+    pub device: String,
+    pub priority: Option<i32>,
+}
+
+/// `device` - полный путь розетки
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SwitchRequest {
+    pub device: String,
+    pub on: bool,
+}
+
+/// Превышенные бюджеты, которые не помешали включению
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SwitchResponse {
+    pub overloads: Vec<Overload>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlarmRequest {
+    pub name: String,
+}
+
+/// Пороги тревог и поднятые, еще не снятые тревоги
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlarmsResponse {
+    pub alarms: Vec<Alarm>,
+    pub active: Vec<Alert>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlertsQuery {
+    pub after: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlertModel {
+    pub id: u64,
+    pub message: String,
+    #[serde(flatten)]
+    pub alert: Alert,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlertsResponse {
+    pub alerts: Vec<AlertModel>,
+    /// С этого номера продолжать следующий запрос
+    pub last_id: u64,
+}
+
+/// Операция журнала вместе с ее описанием
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalEntryModel {
+    pub description: String,
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+impl From<&Operation> for JournalEntryModel {
+    fn from(operation: &Operation) -> Self {
+        Self {
+            description: operation.to_string(),
+            operation: operation.clone(),
+        }
+    }
+}
+
+/// `undone` - отмененные операции, первой идет та, которую повторит redo
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalResponse {
+    pub done: Vec<JournalEntryModel>,
+    pub undone: Vec<JournalEntryModel>,
+    pub dropped: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UndoResponse {
+    pub operation: Option<JournalEntryModel>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Дом для сравнения: текстовое описание (`{"text": "room ..."}`) или файл дома в JSON
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DiffRequest {
+    Text { text: String },
+    House(serde_json::Value),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DiffResponse {
+    /// Изменения построчно, как их печатает `HouseDiff`
+    pub text: String,
+    #[serde(flatten)]
+    pub diff: HouseDiff,
+}
@@ -0,0 +1,49 @@
+use smart_devices::events::{Event, EventBus, EventFilter, Subscription};
+use std::collections::VecDeque;
+
+/// Сколько последних событий помнит веб-слой
+pub const EVENT_LOG_CAPACITY: usize = 1000;
+
+/// Последние события дома с номерами, по которым клиент забирает только новые
+pub struct EventLog {
+    subscription: Subscription,
+    next_id: u64,
+    recent: VecDeque<(u64, Event)>,
+}
+
+impl EventLog {
+    pub fn new(events: &EventBus) -> Self {
+        Self {
+            subscription: events.subscribe_queue(EventFilter::all()),
+            next_id: 1,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Переносим в журнал события, накопившиеся в подписке
+    fn sync(&mut self) {
+        for event in self.subscription.drain() {
+            if self.recent.len() == EVENT_LOG_CAPACITY {
+                self.recent.pop_front();
+            }
+            self.recent.push_back((self.next_id, event));
+            self.next_id += 1;
+        }
+    }
+
+    /// Номер последнего события, 0 - событий еще не было
+    pub fn last_id(&mut self) -> u64 {
+        self.sync();
+        self.next_id - 1
+    }
+
+    /// События после `after`, подходящие фильтру
+    pub fn since(&mut self, after: u64, filter: &EventFilter) -> Vec<(u64, Event)> {
+        self.sync();
+        self.recent
+            .iter()
+            .filter(|(id, event)| *id > after && filter.matches(event))
+            .cloned()
+            .collect()
+    }
+}
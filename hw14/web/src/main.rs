@@ -5,30 +5,93 @@ use actix_web::{
 };
 use serde_json::json;
 use smart_devices::{
-    device::{info::BorrowingDeviceInfoProvider, SmartSocket, SmartThermometer},
-    SmartHouse,
+    alarms::{Alarm, AlarmError, LogSink},
+    device::{
+        info::BorrowingDeviceInfoProvider,
+        temperature::{Temperature, TemperatureUnit},
+        SmartSocket, SmartThermometer,
+    },
+    diff::HouseDiff,
+    dsl,
+    events::{EventBus, EventFilter},
+    journal::JournalError,
+    power::PowerBudget,
+    query::DeviceQuery,
+    report::{render::ReportFormat, ReportOptions},
+    rules::{text, RuleError},
+    scene::{Scene, SceneError},
+    schedule::{ScheduleError, ScheduledJob},
+    storage::Format,
+    tags::TagQuery,
+    SmartHouse, SmartHouseError,
 };
 use std::{
     error::Error,
-    sync::{Arc, RwLock},
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+    thread,
+    time::Duration,
 };
 
+pub mod alert_log;
 pub mod dto;
+pub mod event_log;
+
+use alert_log::AlertLog;
+use event_log::EventLog;
 
 type ArwLock<T> = Arc<RwLock<T>>;
 
 pub struct AppState {
     pub smart_house: ArwLock<SmartHouse>,
+    /// Файл, в котором хранится дом между перезапусками
+    pub storage_path: Option<PathBuf>,
+    /// Шина событий дома
+    pub events: EventBus,
+    pub event_log: Mutex<EventLog>,
+    /// Тревоги дома, сюда их присылает сам дом
+    pub alerts: Arc<AlertLog>,
 }
 
 type AppData = Data<AppState>;
 
+/// Как часто проверяем, не пора ли выполнить задания расписания, правила,
+/// у которых прошла пауза, и не поднять ли тревоги
+const SCHEDULE_TICK: Duration = Duration::from_secs(15);
+
+/// Дольше этого запрос событий не ждет новых
+const MAX_EVENTS_WAIT: Duration = Duration::from_secs(60);
+
+/// Ближайших действий расписания в ответе по умолчанию
+const DEFAULT_UPCOMING: usize = 10;
+
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let house = SmartHouse::new_empty("my smart house");
+    // Путь к файлу дома (.json или .toml) можно передать первым аргументом
+    let storage_path = std::env::args().nth(1).map(PathBuf::from);
+    let mut house = match &storage_path {
+        Some(path) if path.exists() => SmartHouse::load(path)?,
+        _ => SmartHouse::new_empty("my smart house"),
+    };
+    let alerts = Arc::new(AlertLog::new());
+    house.add_alert_sink(alerts.clone());
+    house.add_alert_sink(Arc::new(LogSink));
 
+    let events = house.events().clone();
     let data = Data::new(AppState {
         smart_house: Arc::new(RwLock::new(house)),
+        storage_path,
+        event_log: Mutex::new(EventLog::new(&events)),
+        events,
+        alerts,
+    });
+
+    let scheduler_data = Data::clone(&data);
+    thread::spawn(move || loop {
+        thread::sleep(SCHEDULE_TICK);
+        run_schedule(&scheduler_data);
+        evaluate_rules(&scheduler_data);
+        scheduler_data.smart_house.write().unwrap().check_alarms();
     });
 
     HttpServer::new(move || {
@@ -42,10 +105,50 @@ async fn main() -> Result<(), Box<dyn Error>> {
             .service(add_room)
             .service(delete_room)
             .service(get_rooms)
+            .service(rename_room)
             .service(add_room_device)
             .service(delete_room_device)
             .service(get_room_devices)
+            .service(move_room_device)
+            .service(rename_room_device)
+            .service(get_device_name_policy)
+            .service(set_device_name_policy)
+            .service(get_temperature_unit)
+            .service(set_temperature_unit)
+            .service(get_rules)
+            .service(add_rules)
+            .service(delete_rule)
+            .service(dry_run_rules)
+            .service(get_schedule)
+            .service(add_job)
+            .service(delete_job)
+            .service(get_scenes)
+            .service(add_scene)
+            .service(capture_scene)
+            .service(delete_scene)
+            .service(apply_scene)
+            .service(get_tags)
+            .service(add_device_tag)
+            .service(delete_device_tag)
+            .service(get_tagged)
+            .service(apply_tagged)
+            .service(search_devices)
+            .service(get_power)
+            .service(set_power_budget)
+            .service(set_load_priority)
+            .service(switch_socket)
+            .service(get_alarms)
+            .service(add_alarm)
+            .service(delete_alarm)
+            .service(get_alerts)
+            .service(diff_house)
+            .service(apply_diff)
+            .service(get_events)
+            .service(get_journal)
+            .service(undo)
+            .service(redo)
             .service(get_report)
+            .service(get_house_report)
             .default_service(web::to(default_response))
     })
     .bind("0.0.0.0:8080")?
@@ -55,6 +158,194 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Сохраняем дом после изменения, если задан файл
+fn save_house(data: &AppData) {
+    let Some(path) = &data.storage_path else {
+        return;
+    };
+
+    if let Err(err) = data.smart_house.read().unwrap().save(path) {
+        eprintln!("Can't save house to {}: {}", path.display(), err);
+    }
+}
+
+/// Ответ с описанием ошибки изменения дома
+fn error_response(error: SmartHouseError) -> HttpResponse {
+    let body = dto::ErrorResponse {
+        error: error.to_string(),
+    };
+
+    match error {
+        SmartHouseError::RoomNotFound(_) | SmartHouseError::DeviceNotFound { .. } => {
+            HttpResponse::NotFound().json(body)
+        }
+        SmartHouseError::DuplicateRoom(_) | SmartHouseError::DuplicateDevice { .. } => {
+            HttpResponse::Conflict().json(body)
+        }
+        SmartHouseError::ReportError { .. } => HttpResponse::UnprocessableEntity().json(body),
+        SmartHouseError::InvalidName(_)
+        | SmartHouseError::InvalidTag(_)
+        | SmartHouseError::NotASocket(_)
+        | SmartHouseError::InvalidPowerLimit(_) => HttpResponse::BadRequest().json(body),
+        SmartHouseError::PowerBudgetExceeded { .. } => HttpResponse::Conflict().json(body),
+    }
+}
+
+/// Выполняем наступившие действия расписаний
+fn run_schedule(data: &AppData) {
+    let runs = data.smart_house.write().unwrap().run_schedule();
+    for run in &runs {
+        match &run.result {
+            Ok(()) => println!("Schedule: {}", run.action),
+            Err(err) => eprintln!("Schedule: {}: {}", run.action, err),
+        }
+    }
+    if !runs.is_empty() {
+        save_house(data);
+    }
+}
+
+/// Правила сами проверяются при изменениях девайсов, а здесь срабатывают те,
+/// которые тогда пропустила пауза между срабатываниями
+fn evaluate_rules(data: &AppData) {
+    let firings = data.smart_house.write().unwrap().evaluate_rules();
+    for firing in &firings {
+        println!("Rule: {firing}");
+    }
+    if !firings.is_empty() {
+        save_house(data);
+    }
+}
+
+/// Ответ с описанием ошибки расписаний
+fn schedule_error_response(error: ScheduleError) -> HttpResponse {
+    let body = dto::ErrorResponse {
+        error: error.to_string(),
+    };
+
+    match error {
+        ScheduleError::House(error) => error_response(error),
+        ScheduleError::JobNotFound(_) | ScheduleError::DeviceNotFound(_) => {
+            HttpResponse::NotFound().json(body)
+        }
+        ScheduleError::DuplicateJob(_) => HttpResponse::Conflict().json(body),
+        ScheduleError::InvalidCron { .. } | ScheduleError::NotASocket(_) => {
+            HttpResponse::BadRequest().json(body)
+        }
+    }
+}
+
+fn schedule_response(house: &SmartHouse, upcoming: usize) -> HttpResponse {
+    HttpResponse::Ok().json(dto::ScheduleResponse {
+        jobs: house.schedule().jobs().cloned().collect(),
+        upcoming: house.schedule().upcoming(upcoming),
+    })
+}
+
+/// Ответ с описанием ошибки правил автоматизации
+fn rule_error_response(error: RuleError) -> HttpResponse {
+    let body = dto::ErrorResponse {
+        error: error.to_string(),
+    };
+
+    match error {
+        RuleError::House(error) => error_response(error),
+        RuleError::RuleNotFound(_) | RuleError::DeviceNotFound(_) => {
+            HttpResponse::NotFound().json(body)
+        }
+        RuleError::DuplicateRule(_) => HttpResponse::Conflict().json(body),
+        RuleError::Parse { .. }
+        | RuleError::UnsupportedAction { .. }
+        | RuleError::InvalidHysteresis { .. } => HttpResponse::BadRequest().json(body),
+        RuleError::LockJammed(_) => HttpResponse::UnprocessableEntity().json(body),
+    }
+}
+
+fn rules_response(house: &SmartHouse) -> HttpResponse {
+    HttpResponse::Ok().json(dto::RulesListResponse {
+        rules: house.rules().rules().cloned().collect(),
+        text: text::format_rules(house.rules().rules()),
+    })
+}
+
+/// Ответ с описанием ошибки сцен
+fn scene_error_response(error: SceneError) -> HttpResponse {
+    let body = dto::ErrorResponse {
+        error: error.to_string(),
+    };
+
+    match error {
+        SceneError::House(error) => error_response(error),
+        SceneError::SceneNotFound(_) | SceneError::DeviceNotFound(_) => {
+            HttpResponse::NotFound().json(body)
+        }
+        SceneError::DuplicateScene(_) => HttpResponse::Conflict().json(body),
+        SceneError::DuplicateDevice(_)
+        | SceneError::KindMismatch { .. }
+        | SceneError::NotControllable(_) => HttpResponse::BadRequest().json(body),
+        SceneError::LockJammed(_) | SceneError::ShedInScene(_) => {
+            HttpResponse::UnprocessableEntity().json(body)
+        }
+    }
+}
+
+fn journal_error_response(error: JournalError) -> HttpResponse {
+    match error {
+        JournalError::House(error) => error_response(error),
+        JournalError::Scene(error) => scene_error_response(error),
+        JournalError::Rule(error) => rule_error_response(error),
+        JournalError::Schedule(error) => schedule_error_response(error),
+        JournalError::Alarm(error) => alarm_error_response(error),
+        JournalError::DeviceMismatch(_) => HttpResponse::Conflict().json(dto::ErrorResponse {
+            error: error.to_string(),
+        }),
+    }
+}
+
+/// Ответ с описанием ошибки тревог
+fn alarm_error_response(error: AlarmError) -> HttpResponse {
+    let body = dto::ErrorResponse {
+        error: error.to_string(),
+    };
+
+    match error {
+        AlarmError::AlarmNotFound(_) => HttpResponse::NotFound().json(body),
+        AlarmError::DuplicateAlarm(_) => HttpResponse::Conflict().json(body),
+        AlarmError::InvalidHysteresis { .. } => HttpResponse::BadRequest().json(body),
+    }
+}
+
+fn alarms_response(house: &SmartHouse) -> HttpResponse {
+    HttpResponse::Ok().json(dto::AlarmsResponse {
+        alarms: house.alarms().alarms().cloned().collect(),
+        active: house.alarms().active().cloned().collect(),
+    })
+}
+
+fn scenes_response(house: &SmartHouse) -> HttpResponse {
+    HttpResponse::Ok().json(dto::ScenesListResponse {
+        scenes: house.scenes().cloned().collect(),
+    })
+}
+
+/// Запрос по тегам из параметров, ошибка - уже готовый ответ
+fn tag_query(query: &dto::TaggedQuery) -> Result<TagQuery, HttpResponse> {
+    let mut tag_query: TagQuery = query.tags.parse().map_err(error_response)?;
+    tag_query.kind = query.kind;
+    Ok(tag_query)
+}
+
+fn device_tags_response(house: &SmartHouse, room: &str, device: &str) -> HttpResponse {
+    match house.device_tags(room, device) {
+        Ok(tags) => HttpResponse::Ok().json(dto::DeviceTagsResponse {
+            room: room.to_owned(),
+            device: device.to_owned(),
+            tags: tags.map(str::to_owned).collect(),
+        }),
+        Err(error) => error_response(error),
+    }
+}
+
 async fn default_response(data: AppData) -> HttpResponse {
     HttpResponse::Ok().json(
         json! ( {"message": format!("Welcome to {}!", data.smart_house.read().unwrap().name())} ),
@@ -63,10 +354,15 @@ async fn default_response(data: AppData) -> HttpResponse {
 
 #[actix_web::post("/rooms/add")]
 async fn add_room(room_request: web::Json<dto::RoomRequest>, data: AppData) -> HttpResponse {
-    data.smart_house
+    let result = data
+        .smart_house
         .write()
         .unwrap()
         .add_room(&room_request.name);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
     HttpResponse::Ok().json(dto::RoomResponse {
         house_name: data.smart_house.read().unwrap().name().to_owned(),
         room_name: room_request.name.clone(),
@@ -81,10 +377,15 @@ async fn add_room(room_request: web::Json<dto::RoomRequest>, data: AppData) -> H
 
 #[actix_web::post("/rooms/delete")]
 async fn delete_room(room_request: web::Json<dto::RoomRequest>, data: AppData) -> HttpResponse {
-    data.smart_house
+    let result = data
+        .smart_house
         .write()
         .unwrap()
         .delete_room(&room_request.name);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
     HttpResponse::Ok().json(dto::RoomsListResponse {
         house_name: data.smart_house.read().unwrap().name().to_owned(),
         rooms: data.smart_house.read().unwrap().rooms().collect(),
@@ -99,15 +400,41 @@ async fn get_rooms(data: AppData) -> HttpResponse {
     })
 }
 
+#[actix_web::post("/rooms/rename")]
+async fn rename_room(
+    rename_request: web::Json<dto::RenameRoomRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .rename_room(&rename_request.name, &rename_request.new_name);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+    HttpResponse::Ok().json(dto::RoomsListResponse {
+        house_name: data.smart_house.read().unwrap().name().to_owned(),
+        rooms: data.smart_house.read().unwrap().rooms().collect(),
+    })
+}
+
 #[actix_web::post("/room/devices/add")]
 async fn add_room_device(
-    device_request: web::Json<dto::RoomDeviceRequest>,
+    device_request: web::Json<dto::AddRoomDeviceRequest>,
     data: AppData,
 ) -> HttpResponse {
-    data.smart_house
+    let device_request = device_request.into_inner();
+    let result = data
+        .smart_house
         .write()
         .unwrap()
-        .add_device(&device_request.room, &device_request.device);
+        .add_device(&device_request.room, device_request.device.into());
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
 
     HttpResponse::Ok().json(dto::RoomDeviceResponse {
         house_name: data.smart_house.read().unwrap().name().to_owned(),
@@ -126,10 +453,15 @@ async fn delete_room_device(
     device_request: web::Json<dto::RoomDeviceRequest>,
     data: AppData,
 ) -> HttpResponse {
-    data.smart_house
+    let result = data
+        .smart_house
         .write()
         .unwrap()
         .delete_device(&device_request.room, &device_request.device);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
 
     HttpResponse::Ok().json(dto::RoomDeviceResponse {
         house_name: data.smart_house.read().unwrap().name().to_owned(),
@@ -148,6 +480,15 @@ async fn get_room_devices(
     devices_request: web::Json<dto::RoomDevicesListRequest>,
     data: AppData,
 ) -> HttpResponse {
+    if !data
+        .smart_house
+        .read()
+        .unwrap()
+        .has_room(&devices_request.room)
+    {
+        return error_response(SmartHouseError::RoomNotFound(devices_request.room.clone()));
+    }
+
     HttpResponse::Ok().json(dto::RoomDevicesListResponse {
         house_name: data.smart_house.read().unwrap().name().to_owned(),
         room_name: devices_request.room.clone(),
@@ -160,6 +501,641 @@ async fn get_room_devices(
     })
 }
 
+#[actix_web::post("/room/devices/move")]
+async fn move_room_device(
+    move_request: web::Json<dto::MoveRoomDeviceRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().move_device(
+        &move_request.from,
+        &move_request.to,
+        &move_request.device,
+    );
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    HttpResponse::Ok().json(dto::RoomDeviceResponse {
+        house_name: data.smart_house.read().unwrap().name().to_owned(),
+        room_name: move_request.to.clone(),
+        devices: data
+            .smart_house
+            .read()
+            .unwrap()
+            .devices(&move_request.to)
+            .collect(),
+    })
+}
+
+#[actix_web::post("/room/devices/rename")]
+async fn rename_room_device(
+    rename_request: web::Json<dto::RenameRoomDeviceRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().rename_device(
+        &rename_request.room,
+        &rename_request.device,
+        &rename_request.new_name,
+    );
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    HttpResponse::Ok().json(dto::RoomDeviceResponse {
+        house_name: data.smart_house.read().unwrap().name().to_owned(),
+        room_name: rename_request.room.clone(),
+        devices: data
+            .smart_house
+            .read()
+            .unwrap()
+            .devices(&rename_request.room)
+            .collect(),
+    })
+}
+
+#[actix_web::get("/house/device_names")]
+async fn get_device_name_policy(data: AppData) -> HttpResponse {
+    HttpResponse::Ok().json(dto::DeviceNamePolicyModel {
+        policy: data.smart_house.read().unwrap().device_name_policy(),
+    })
+}
+
+#[actix_web::post("/house/device_names")]
+async fn set_device_name_policy(
+    policy_request: web::Json<dto::DeviceNamePolicyModel>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .set_device_name_policy(policy_request.policy);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    HttpResponse::Ok().json(policy_request.into_inner())
+}
+
+#[actix_web::get("/house/temperature_unit")]
+async fn get_temperature_unit(data: AppData) -> HttpResponse {
+    HttpResponse::Ok().json(dto::TemperatureUnitModel {
+        unit: data.smart_house.read().unwrap().temperature_unit(),
+    })
+}
+
+#[actix_web::post("/house/temperature_unit")]
+async fn set_temperature_unit(
+    unit_request: web::Json<dto::TemperatureUnitModel>,
+    data: AppData,
+) -> HttpResponse {
+    data.smart_house
+        .write()
+        .unwrap()
+        .set_temperature_unit(unit_request.unit);
+    save_house(&data);
+
+    HttpResponse::Ok().json(unit_request.into_inner())
+}
+
+#[actix_web::get("/house/rules")]
+async fn get_rules(data: AppData) -> HttpResponse {
+    rules_response(&data.smart_house.read().unwrap())
+}
+
+/// Добавляем одно правило в JSON или несколько в текстовом формате.
+/// Если хоть одно не подходит, не добавляется ни одно.
+#[actix_web::post("/house/rules/add")]
+async fn add_rules(rules_request: web::Json<dto::AddRuleRequest>, data: AppData) -> HttpResponse {
+    let rules = match rules_request.into_inner() {
+        dto::AddRuleRequest::Text { text } => match text::parse_rules(&text) {
+            Ok(rules) => rules,
+            Err(error) => return rule_error_response(error),
+        },
+        dto::AddRuleRequest::Rule(rule) => vec![rule],
+    };
+
+    if let Err(error) = data.smart_house.write().unwrap().add_rules(rules) {
+        return rule_error_response(error);
+    }
+    save_house(&data);
+
+    rules_response(&data.smart_house.read().unwrap())
+}
+
+#[actix_web::post("/house/rules/delete")]
+async fn delete_rule(rule_request: web::Json<dto::RuleRequest>, data: AppData) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .remove_rule(&rule_request.name);
+    if let Err(error) = result {
+        return rule_error_response(error);
+    }
+    save_house(&data);
+
+    rules_response(&data.smart_house.read().unwrap())
+}
+
+/// Какие правила сработали бы сейчас
+#[actix_web::get("/house/rules/dry_run")]
+async fn dry_run_rules(data: AppData) -> HttpResponse {
+    HttpResponse::Ok().json(dto::DryRunResponse {
+        firings: data
+            .smart_house
+            .read()
+            .unwrap()
+            .dry_run_rules()
+            .iter()
+            .map(dto::FiringModel::from)
+            .collect(),
+    })
+}
+
+#[actix_web::get("/house/schedule")]
+async fn get_schedule(query: web::Query<dto::ScheduleQuery>, data: AppData) -> HttpResponse {
+    schedule_response(
+        &data.smart_house.read().unwrap(),
+        query.upcoming.unwrap_or(DEFAULT_UPCOMING),
+    )
+}
+
+#[actix_web::post("/house/schedule/add")]
+async fn add_job(job: web::Json<ScheduledJob>, data: AppData) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().add_job(job.into_inner());
+    if let Err(error) = result {
+        return schedule_error_response(error);
+    }
+    save_house(&data);
+
+    schedule_response(&data.smart_house.read().unwrap(), DEFAULT_UPCOMING)
+}
+
+#[actix_web::post("/house/schedule/delete")]
+async fn delete_job(job_request: web::Json<dto::JobRequest>, data: AppData) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .remove_job(&job_request.name);
+    if let Err(error) = result {
+        return schedule_error_response(error);
+    }
+    save_house(&data);
+
+    schedule_response(&data.smart_house.read().unwrap(), DEFAULT_UPCOMING)
+}
+
+#[actix_web::get("/house/scenes")]
+async fn get_scenes(data: AppData) -> HttpResponse {
+    scenes_response(&data.smart_house.read().unwrap())
+}
+
+#[actix_web::post("/house/scenes/add")]
+async fn add_scene(scene: web::Json<Scene>, data: AppData) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .add_scene(scene.into_inner());
+    if let Err(error) = result {
+        return scene_error_response(error);
+    }
+    save_house(&data);
+
+    scenes_response(&data.smart_house.read().unwrap())
+}
+
+/// Новая сцена из текущего состояния девайсов
+#[actix_web::post("/house/scenes/capture")]
+async fn capture_scene(
+    capture_request: web::Json<dto::CaptureSceneRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let devices: Vec<&str> = capture_request.devices.iter().map(String::as_str).collect();
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .capture_scene(&capture_request.name, &devices)
+        .map(|_| ());
+    if let Err(error) = result {
+        return scene_error_response(error);
+    }
+    save_house(&data);
+
+    scenes_response(&data.smart_house.read().unwrap())
+}
+
+#[actix_web::post("/house/scenes/delete")]
+async fn delete_scene(scene_request: web::Json<dto::SceneRequest>, data: AppData) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .delete_scene(&scene_request.name);
+    if let Err(error) = result {
+        return scene_error_response(error);
+    }
+    save_house(&data);
+
+    scenes_response(&data.smart_house.read().unwrap())
+}
+
+/// Применяем сцену целиком, если не применилась - 422 с отчетом по девайсам
+#[actix_web::post("/house/scenes/apply")]
+async fn apply_scene(scene_request: web::Json<dto::SceneRequest>, data: AppData) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .apply_scene(&scene_request.name);
+    let report = match result {
+        Ok(report) => report,
+        Err(error) => return scene_error_response(error),
+    };
+    if !report.applied {
+        return HttpResponse::UnprocessableEntity().json(dto::SceneReportResponse::from(&report));
+    }
+    save_house(&data);
+
+    HttpResponse::Ok().json(dto::SceneReportResponse::from(&report))
+}
+
+#[actix_web::get("/house/tags")]
+async fn get_tags(data: AppData) -> HttpResponse {
+    HttpResponse::Ok().json(dto::TagsResponse {
+        tags: data.smart_house.read().unwrap().tags(),
+    })
+}
+
+#[actix_web::post("/room/devices/tags/add")]
+async fn add_device_tag(
+    tag_request: web::Json<dto::DeviceTagRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().tag_device(
+        &tag_request.room,
+        &tag_request.device,
+        &tag_request.tag,
+    );
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    device_tags_response(
+        &data.smart_house.read().unwrap(),
+        &tag_request.room,
+        &tag_request.device,
+    )
+}
+
+#[actix_web::post("/room/devices/tags/delete")]
+async fn delete_device_tag(
+    tag_request: web::Json<dto::DeviceTagRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().untag_device(
+        &tag_request.room,
+        &tag_request.device,
+        &tag_request.tag,
+    );
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    device_tags_response(
+        &data.smart_house.read().unwrap(),
+        &tag_request.room,
+        &tag_request.device,
+    )
+}
+
+/// Пути девайсов с тегами во всех комнатах
+#[actix_web::get("/house/tagged")]
+async fn get_tagged(query: web::Query<dto::TaggedQuery>, data: AppData) -> HttpResponse {
+    let query = match tag_query(&query) {
+        Ok(query) => query,
+        Err(response) => return response,
+    };
+
+    HttpResponse::Ok().json(dto::TaggedDevicesResponse {
+        devices: data.smart_house.read().unwrap().find_tagged(&query),
+    })
+}
+
+#[actix_web::get("/house/devices/search")]
+async fn search_devices(query: web::Query<dto::DeviceSearchQuery>, data: AppData) -> HttpResponse {
+    let tags: TagQuery = match query.tags.parse() {
+        Ok(tags) => tags,
+        Err(error) => return error_response(error),
+    };
+    let unit: Option<TemperatureUnit> = match query.unit.as_deref().map(str::parse).transpose() {
+        Ok(unit) => unit,
+        Err(error) => {
+            return HttpResponse::BadRequest().json(dto::ErrorResponse {
+                error: error.to_string(),
+            })
+        }
+    };
+
+    let house = data.smart_house.read().unwrap();
+    let unit = unit.unwrap_or(house.temperature_unit());
+    let mut device_query = DeviceQuery::from(&tags);
+    device_query.kind = query.kind;
+    device_query.room = query.room.clone();
+    device_query.name = query.name.clone();
+    device_query.is_on = query.on;
+    device_query.min_temperature = query.min_temperature.map(|t| Temperature::new(t, unit));
+    device_query.max_temperature = query.max_temperature.map(|t| Temperature::new(t, unit));
+
+    HttpResponse::Ok().json(dto::DeviceSearchResponse {
+        devices: house
+            .find_devices(&device_query)
+            .iter()
+            .map(dto::FoundDeviceModel::from)
+            .collect(),
+    })
+}
+
+/// Массовое действие над девайсами с тегами, например выключить все обогреватели
+#[actix_web::post("/house/tagged/apply")]
+async fn apply_tagged(bulk_request: web::Json<dto::BulkRequest>, data: AppData) -> HttpResponse {
+    let query = match tag_query(&bulk_request.query) {
+        Ok(query) => query,
+        Err(response) => return response,
+    };
+
+    let results = data
+        .smart_house
+        .write()
+        .unwrap()
+        .apply_tagged(&query, bulk_request.action.action());
+    if results.iter().any(|r| r.result.is_ok()) {
+        save_house(&data);
+    }
+
+    HttpResponse::Ok().json(dto::BulkResponse {
+        results: results.iter().map(dto::BulkResultModel::from).collect(),
+    })
+}
+
+fn power_response(house: &SmartHouse) -> HttpResponse {
+    let budgets = house.power_budgets();
+    let load = |room| house.power_load(room).unwrap_or_default();
+    HttpResponse::Ok().json(dto::PowerResponse {
+        load: load(None),
+        budget: budgets.house(),
+        rooms: budgets
+            .rooms()
+            .map(|(room, budget)| dto::RoomPowerModel {
+                room: room.to_owned(),
+                load: load(Some(room)),
+                budget,
+            })
+            .collect(),
+        priorities: budgets
+            .priorities()
+            .map(|(device, priority)| (device.to_owned(), priority))
+            .collect(),
+    })
+}
+
+#[actix_web::get("/house/power")]
+async fn get_power(data: AppData) -> HttpResponse {
+    power_response(&data.smart_house.read().unwrap())
+}
+
+#[actix_web::post("/house/power/budget")]
+async fn set_power_budget(
+    budget_request: web::Json<dto::PowerBudgetRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let budget = budget_request
+        .limit
+        .map(|limit| PowerBudget::new(limit, budget_request.policy));
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .set_power_budget(budget_request.room.as_deref(), budget);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    power_response(&data.smart_house.read().unwrap())
+}
+
+#[actix_web::post("/house/power/priority")]
+async fn set_load_priority(
+    priority_request: web::Json<dto::LoadPriorityRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .set_load_priority(&priority_request.device, priority_request.priority);
+    if let Err(error) = result {
+        return error_response(error);
+    }
+    save_house(&data);
+
+    power_response(&data.smart_house.read().unwrap())
+}
+
+/// Включаем или выключаем розетку с учетом бюджетов мощности,
+/// отказ по бюджету - 409
+#[actix_web::post("/house/devices/switch")]
+async fn switch_socket(
+    switch_request: web::Json<dto::SwitchRequest>,
+    data: AppData,
+) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .switch_socket(&switch_request.device, switch_request.on);
+    let overloads = match result {
+        Ok(overloads) => overloads,
+        Err(error) => return error_response(error),
+    };
+    save_house(&data);
+
+    HttpResponse::Ok().json(dto::SwitchResponse { overloads })
+}
+
+#[actix_web::get("/house/alarms")]
+async fn get_alarms(data: AppData) -> HttpResponse {
+    alarms_response(&data.smart_house.read().unwrap())
+}
+
+/// Добавляем порог тревоги и сразу его проверяем
+#[actix_web::post("/house/alarms/add")]
+async fn add_alarm(alarm: web::Json<Alarm>, data: AppData) -> HttpResponse {
+    {
+        let mut house = data.smart_house.write().unwrap();
+        if let Err(error) = house.add_alarm(alarm.into_inner()) {
+            return alarm_error_response(error);
+        }
+        house.check_alarms();
+    }
+    save_house(&data);
+
+    alarms_response(&data.smart_house.read().unwrap())
+}
+
+#[actix_web::post("/house/alarms/delete")]
+async fn delete_alarm(alarm_request: web::Json<dto::AlarmRequest>, data: AppData) -> HttpResponse {
+    let result = data
+        .smart_house
+        .write()
+        .unwrap()
+        .remove_alarm(&alarm_request.name);
+    if let Err(error) = result {
+        return alarm_error_response(error);
+    }
+    save_house(&data);
+
+    alarms_response(&data.smart_house.read().unwrap())
+}
+
+/// Тревоги после `after`, поднятые и снятые
+#[actix_web::get("/house/alerts")]
+async fn get_alerts(query: web::Query<dto::AlertsQuery>, data: AppData) -> HttpResponse {
+    let alerts = data.alerts.since(query.after.unwrap_or(0));
+    HttpResponse::Ok().json(dto::AlertsResponse {
+        last_id: alerts
+            .last()
+            .map_or_else(|| data.alerts.last_id(), |(id, _)| *id),
+        alerts: alerts
+            .into_iter()
+            .map(|(id, alert)| dto::AlertModel {
+                id,
+                message: alert.to_string(),
+                alert,
+            })
+            .collect(),
+    })
+}
+
+/// Изменения, которые превращают текущий дом в присланный
+#[actix_web::post("/house/diff")]
+async fn diff_house(diff_request: web::Json<dto::DiffRequest>, data: AppData) -> HttpResponse {
+    let other = match diff_request.into_inner() {
+        dto::DiffRequest::Text { text } => dsl::parse_house(&text).map_err(|e| e.to_string()),
+        dto::DiffRequest::House(house) => {
+            SmartHouse::decode(&house.to_string(), Format::Json).map_err(|e| e.to_string())
+        }
+    };
+    let other = match other {
+        Ok(other) => other,
+        Err(error) => return HttpResponse::BadRequest().json(dto::ErrorResponse { error }),
+    };
+
+    let diff = data.smart_house.read().unwrap().diff(&other);
+    HttpResponse::Ok().json(dto::DiffResponse {
+        text: diff.to_string(),
+        diff,
+    })
+}
+
+/// Применяем разницу к дому. Конфликтующие изменения пропускаются
+/// и перечисляются в ответе.
+#[actix_web::post("/house/diff/apply")]
+async fn apply_diff(diff: web::Json<HouseDiff>, data: AppData) -> HttpResponse {
+    let report = data.smart_house.write().unwrap().apply_diff(&diff);
+    if !report.applied.is_empty() {
+        save_house(&data);
+    }
+
+    HttpResponse::Ok().json(report)
+}
+
+#[actix_web::get("/house/journal")]
+async fn get_journal(data: AppData) -> HttpResponse {
+    let house = data.smart_house.read().unwrap();
+    let journal = house.journal();
+    HttpResponse::Ok().json(dto::JournalResponse {
+        done: journal
+            .operations()
+            .map(dto::JournalEntryModel::from)
+            .collect(),
+        undone: journal.undone().map(dto::JournalEntryModel::from).collect(),
+        dropped: journal.dropped(),
+    })
+}
+
+/// Отменяем последнее изменение дома, `operation` в ответе пустое, если отменять нечего
+#[actix_web::post("/house/undo")]
+async fn undo(data: AppData) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().undo();
+    let operation = match result {
+        Ok(operation) => operation,
+        Err(error) => return journal_error_response(error),
+    };
+    if operation.is_some() {
+        save_house(&data);
+    }
+
+    HttpResponse::Ok().json(dto::UndoResponse {
+        operation: operation.as_ref().map(dto::JournalEntryModel::from),
+    })
+}
+
+#[actix_web::post("/house/redo")]
+async fn redo(data: AppData) -> HttpResponse {
+    let result = data.smart_house.write().unwrap().redo();
+    let operation = match result {
+        Ok(operation) => operation,
+        Err(error) => return journal_error_response(error),
+    };
+    if operation.is_some() {
+        save_house(&data);
+    }
+
+    HttpResponse::Ok().json(dto::UndoResponse {
+        operation: operation.as_ref().map(dto::JournalEntryModel::from),
+    })
+}
+
+/// События после `after`. Если их нет, ждем новые до `wait` секунд,
+/// так что клиент может не опрашивать дом, а держать долгий запрос.
+#[actix_web::get("/house/events")]
+async fn get_events(query: web::Query<dto::EventsQuery>, data: AppData) -> HttpResponse {
+    let mut filter = EventFilter::all();
+    filter.room = query.room.clone();
+    filter.device = query.device.clone();
+    filter.kind = query.kind;
+
+    // Подписываемся до чтения журнала, чтобы не пропустить событие между ними
+    let mut subscription = data.events.subscribe_queue(filter.clone());
+    let after = query.after.unwrap_or(0);
+    let mut events = data.event_log.lock().unwrap().since(after, &filter);
+
+    let wait = Duration::from_secs(query.wait.unwrap_or(0)).min(MAX_EVENTS_WAIT);
+    if events.is_empty() && !wait.is_zero() {
+        let _ = actix_web::rt::time::timeout(wait, subscription.recv_async()).await;
+        events = data.event_log.lock().unwrap().since(after, &filter);
+    }
+
+    let last_id = data.event_log.lock().unwrap().last_id();
+    HttpResponse::Ok().json(dto::EventsResponse {
+        events: events
+            .into_iter()
+            .map(|(id, event)| dto::EventModel { id, event })
+            .collect(),
+        last_id,
+    })
+}
+
 #[actix_web::get("/report")]
 async fn get_report(report_request: web::Json<dto::ReportRequest>, data: AppData) -> HttpResponse {
     let socket = SmartSocket::new(
@@ -169,11 +1145,8 @@ async fn get_report(report_request: web::Json<dto::ReportRequest>, data: AppData
         report_request.socket.current_power,
     );
 
-    let thermometer = SmartThermometer::new(
-        &report_request.thermometer.name,
-        &report_request.thermometer.description,
-        report_request.thermometer.current_temperature,
-    );
+    let mut thermometer = SmartThermometer::from(&report_request.thermometer);
+    thermometer.set_display_unit(data.smart_house.read().unwrap().temperature_unit());
 
     let info_provider = BorrowingDeviceInfoProvider::new(&socket, &thermometer);
     let report_result = data
@@ -187,3 +1160,54 @@ async fn get_report(report_request: web::Json<dto::ReportRequest>, data: AppData
         Err(error) => HttpResponse::Ok().json(dto::ReportResponse::Error(error.to_string())),
     }
 }
+
+#[actix_web::get("/house/report")]
+async fn get_house_report(query: web::Query<dto::HouseReportQuery>, data: AppData) -> HttpResponse {
+    let format: ReportFormat = match query.format.as_deref().unwrap_or("json").parse() {
+        Ok(format) => format,
+        Err(error) => {
+            return HttpResponse::BadRequest().json(dto::ReportResponse::Error(error.to_string()))
+        }
+    };
+
+    let unit: Option<TemperatureUnit> = match query.unit.as_deref().map(str::parse).transpose() {
+        Ok(unit) => unit,
+        Err(error) => {
+            return HttpResponse::BadRequest().json(dto::ReportResponse::Error(error.to_string()))
+        }
+    };
+
+    let mut options = ReportOptions::strict();
+    if let Some(tags) = &query.tags {
+        match tags.parse() {
+            Ok(tags) => options = options.tagged(tags),
+            Err(error) => {
+                return HttpResponse::BadRequest()
+                    .json(dto::ReportResponse::Error(error.to_string()))
+            }
+        }
+    }
+
+    let house = data.smart_house.read().unwrap();
+    let mut report = match house.report_with(&*house, &options) {
+        Ok(report) => report,
+        Err(error) => {
+            return HttpResponse::Ok().json(dto::ReportResponse::Error(error.to_string()))
+        }
+    };
+    if let Some(unit) = unit {
+        report.temperature_unit = unit;
+    }
+
+    let content_type = match format {
+        ReportFormat::Text => "text/plain; charset=utf-8",
+        ReportFormat::Json => "application/json",
+        ReportFormat::Markdown => "text/markdown; charset=utf-8",
+        ReportFormat::Html => "text/html; charset=utf-8",
+        ReportFormat::Csv => "text/csv; charset=utf-8",
+    };
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .body(format.renderer().render(&report))
+}